@@ -5,6 +5,12 @@ declare const _default: typeof import('./index')
 export default _default
 
 export declare class BackendRuntime {
+  /**
+   * Verifies a hashcash stamp and records it so the same stamp is rejected
+   * as `replayed` for the rest of its validity window. Accepted stamps are
+   * kept in `runtime_gates`, or in process memory before the runtime starts.
+   */
+  verifyChallengeResponseOnce(response: string, bits: number, resource: string, validityMs?: number | undefined | null): Promise<RuntimeChallengeVerifyResult>
   acquireCoordinationLease(key: string, owner: string, ttlMs: number): Promise<CoordinationLeaseGrant | null>
   releaseCoordinationLease(key: string, owner: string, fencingToken: bigint | number): Promise<boolean>
   renewCoordinationLease(key: string, owner: string, fencingToken: bigint | number, ttlMs: number): Promise<boolean>
@@ -12,14 +18,42 @@ export declare class BackendRuntime {
    * Merge pending doc updates with y-octo and persist the merged snapshot.
    *
    * Do not use this for snapshots that will be sent back to yjs clients until
-   * the y-octo/yjs round-trip compatibility issue is resolved.
+   * the y-octo/yjs round-trip compatibility issue is resolved. With
+   * `verify_round_trip`, every merge is decoded again and compared with the
+   * updates applied one by one; a divergent merge is recorded in
+   * `doc_compaction_diagnostics` and not persisted, leaving the updates
+   * pending.
    *
    * The caller owns quota reconciliation and must pass a fresh
    * history_max_age_seconds value. The compactor intentionally does not read
    * effective_workspace_quota_states; if a future caller cannot provide a
    * fresh quota state, fail and retry after Node reconciles it.
    */
-  compactPendingDocUpdates(workspaceId: string, docId: string, batchLimit: number, historyMinIntervalMs: number, historyMaxAgeSeconds: number, owner: string, leaseTtlMs: number): Promise<RuntimeDocCompactionResult>
+  compactPendingDocUpdates(workspaceId: string, docId: string, batchLimit: number, historyMinIntervalMs: number, historyMaxAgeSeconds: number, owner: string, leaseTtlMs: number, verifyRoundTrip?: boolean | undefined | null): Promise<RuntimeDocCompactionResult>
+  /**
+   * Compact the docs of one workspace that have pending updates.
+   *
+   * Docs are ranked by pending update count and by the age of their oldest
+   * update, and up to `doc_limit` of them are compacted, `concurrency` at a
+   * time. Each doc takes the same per-doc lease as
+   * `compact_pending_doc_updates`, and docs whose lease is held elsewhere are
+   * skipped. The backlog fields describe what is still pending afterwards.
+   *
+   * The same y-octo, quota and `verify_round_trip` notes as
+   * `compact_pending_doc_updates` apply.
+   */
+  compactPendingWorkspaceDocs(workspaceId: string, docLimit: number, concurrency: number, batchLimit: number, historyMinIntervalMs: number, historyMaxAgeSeconds: number, owner: string, leaseTtlMs: number, verifyRoundTrip?: boolean | undefined | null): Promise<RuntimeDocCompactionScheduleResult>
+  /**
+   * Returns the full doc recorded in history at `timestamp_ms`, whether the
+   * row is stored as a keyframe or as a delta.
+   */
+  getDocHistory(workspaceId: string, docId: string, timestampMs: number): Promise<Buffer | null>
+  /**
+   * Converts full-blob history rows written before delta encoding, one batch
+   * of docs at a time. Pass the returned `next_cursor` back in until it is
+   * `None`.
+   */
+  migrateDocHistoryDeltas(cursor: string | undefined | null, docLimit: number): Promise<RuntimeDocHistoryDeltaMigrationResult>
   upsertDocSnapshot(workspaceId: string, docId: string, blob: Buffer, timestampMs: number, editorId?: string | undefined | null): Promise<boolean>
   createDocHistory(input: RuntimeDocHistoryInput): Promise<boolean>
   deleteDocStorage(workspaceId: string, docId: string): Promise<void>
@@ -27,6 +61,7 @@ export declare class BackendRuntime {
   cleanupExpiredRuntimeGates(limit: number): Promise<number>
   cleanupExpiredUserSessions(limit: number): Promise<number>
   cleanupExpiredSnapshotHistories(limit: number): Promise<number>
+  cleanupExpiredDocCompactionDiagnostics(limit: number): Promise<number>
   isInviteAbuseUserQuarantinedOrBanned(userId: string): Promise<boolean>
   isInviteAbuseWorkspaceQuarantined(workspaceId: string): Promise<boolean>
   claimInviteAbuseAction(actionId: string, workerId: string): Promise<boolean>
@@ -54,6 +89,9 @@ export declare class BackendRuntime {
   revokeWorkspaceInviteLink(workspaceId: string): Promise<boolean>
   createByokLocalLease(activeKey: string, leaseId: string, payload: any, ttlMs: number): Promise<RuntimeByokLocalLeaseRecord>
   getByokLocalLease(leaseId: string): Promise<RuntimeByokLocalLeaseRecord | null>
+  issueRefreshTokenFamily(userId: string, authSessionId: string, ttlMs: number, familyTtlMs: number): Promise<RuntimeRefreshTokenFamilyRecord>
+  rotateRefreshToken(token: string, ttlMs: number): Promise<RuntimeRefreshTokenRotateResult>
+  revokeRefreshTokenFamily(familyId: string): Promise<boolean>
   cleanupExpiredRuntimeStates(limit: number): Promise<number>
   refreshWorkspaceAdminStatsDirty(batchLimit: number, owner: string, leaseTtlMs: number): Promise<RuntimeWorkspaceStatsRefreshResult>
   recalibrateWorkspaceAdminStats(lastSid: number, batchLimit: number, owner: string, leaseTtlMs: number): Promise<RuntimeWorkspaceStatsRecalibrationResult>
@@ -64,20 +102,81 @@ export declare class BackendRuntime {
   stop(): Promise<void>
   health(): Promise<BackendRuntimeHealth>
   runMigrations(): Promise<void>
+  /**
+   * Reports applied and pending runtime migrations without changing the
+   * database.
+   */
+  planMigrations(): Promise<RuntimeMigrationPlan>
+  /**
+   * Rolls back every applied migration newer than `target_version`. Pass `0`
+   * to drop all runtime tables.
+   */
+  revertMigrations(targetVersion: number): Promise<RuntimeMigrationPlan>
 }
 
 export declare class LlmStreamHandle {
   abort(): void
 }
 
+/**
+ * Local S3-compatible server for development and tests. Point a storage at
+ * it by passing `storageConfig(bucket)` as a `storages.*` entry.
+ */
+export declare class ObjectStorageMockServer {
+  /** Starts serving on a random localhost port. */
+  static start(): ObjectStorageMockServer
+  get endpoint(): string
+  /** JSON storage provider config for the `aws-s3` provider. */
+  storageConfig(bucket: string): string
+  /** Stops accepting connections and drops every stored object. */
+  stop(): void
+}
+
+/** Pull-based object body returned by `StorageRuntime.openObjectStream`. */
+export declare class StorageObjectStream {
+  get metadata(): RuntimeObjectMetadata
+  get contentRange(): RuntimeObjectContentRange | null
+  /** Resolves to the next chunk, or `null` once the body is exhausted. */
+  read(): Promise<Buffer | null>
+  /** Releases the underlying file or connection before the body is drained. */
+  close(): Promise<void>
+}
+
 export declare class StorageRuntime {
   planUnreferencedWorkspaceBlobs(workspaceId: string, gracePeriodDays: number, limit: number): Promise<RuntimeBlobCleanupPlanResult>
   executeBlobCleanupCandidates(runId: string, gracePeriodDays: number, limit: number): Promise<RuntimeBlobCleanupExecuteResult>
+  /**
+   * Copies a workspace's blobs from the `from_scope` backend to the
+   * `to_scope` backend, `limit` objects per call, verifying each copy by
+   * reading it back. With `delete_source` the source object is removed once
+   * its copy verified. Call until `nextCursor` is `null`.
+   */
+  migrateWorkspaceBlobs(fromScope: string, toScope: string, workspaceId: string, limit: number, deleteSource?: boolean | undefined | null): Promise<RuntimeBlobMigrationResult>
   cleanupExpiredPendingBlobs(cutoffMs: number, limit: number): Promise<RuntimeBlobCleanupResult>
   releaseDeletedBlobs(workspaceId: string, limit: number): Promise<RuntimeBlobCleanupResult>
   backfillMissingBlobMetadata(workspaceId: string | undefined | null, limit: number): Promise<RuntimeBlobMetadataBackfillResult>
   rebuildDocBlobRefs(workspaceId: string, docId: string): Promise<RuntimeDocBlobRefsResult>
   rebuildWorkspaceDocBlobRefs(workspaceId: string, limit: number): Promise<RuntimeDocBlobRefsResult>
+  /**
+   * Re-wraps data keys still sealed by a retired key and encrypts objects
+   * written before encryption was enabled, `limit` objects per call. Progress
+   * is checkpointed per active key, so call until `nextCursor` is `null`.
+   */
+  rotateStorageEncryption(scope: string, limit: number): Promise<RuntimeStorageEncryptionRotationResult>
+  /** Deleted versions of `key` still within retention, oldest first. */
+  listObjectVersions(scope: string, key: string): Promise<Array<RuntimeObjectTombstone>>
+  /**
+   * Restores the latest deleted version of `key`, or the one deleted at
+   * `deletedAtMs`. Resolves to `null` when no such tombstone exists; fails
+   * if `key` currently holds an object.
+   */
+  restoreObject(scope: string, key: string, deletedAtMs?: number | undefined | null): Promise<RuntimeObjectMetadata | null>
+  /**
+   * Permanently deletes tombstones older than the scope's retention window,
+   * scanning `limit` tombstones per call. Call until `nextCursor` is `null`;
+   * the following call starts a new pass.
+   */
+  purgeExpiredTombstones(scope: string, limit: number): Promise<RuntimeTombstonePurgeResult>
   constructor()
   start(): Promise<void>
   configure(configJson: string): void
@@ -87,7 +186,16 @@ export declare class StorageRuntime {
   providerCapabilities(scope: string): Promise<StorageProviderCapabilities>
   putObject(scope: string, key: string, body: Buffer, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<RuntimeObjectMetadata>
   headObject(scope: string, key: string): Promise<RuntimeObjectMetadata | null>
-  getObject(scope: string, key: string): Promise<RuntimeObjectGetResult | null>
+  /**
+   * `range` takes an HTTP `Range` header value such as `bytes=0-1023` or
+   * `bytes=-500`; the result then carries the served `contentRange`.
+   */
+  getObject(scope: string, key: string, range?: string | undefined | null): Promise<RuntimeObjectGetResult | null>
+  /**
+   * Streaming variant of `getObject` for serving large bodies without
+   * buffering them; pull chunks with `read()` until it returns `null`.
+   */
+  openObjectStream(scope: string, key: string, range?: string | undefined | null): Promise<StorageObjectStream | null>
   listObjects(scope: string, prefix?: string | undefined | null): Promise<Array<RuntimeObjectListEntry>>
   deleteObject(scope: string, key: string): Promise<void>
   presignPut(scope: string, key: string, metadata?: RuntimeObjectStoragePutOptions | undefined | null): Promise<RuntimePresignedObjectRequest | null>
@@ -171,6 +279,12 @@ export interface AssertSafeUrlRequest {
   url: string
 }
 
+/**
+ * Public JWKS document for the given signing keys, for services that only
+ * verify tokens.
+ */
+export declare function authSessionAccessTokenJwks(keys: Array<AuthSessionSigningKey>): string
+
 export declare function authSessionAccessTokenKeyId(token: string): string | null
 
 export interface AuthSessionAccessTokenVerification {
@@ -185,11 +299,32 @@ export interface AuthSessionRefreshToken {
   secretHash: string
 }
 
+export interface AuthSessionSigningKey {
+  kid: string
+  /** `EdDSA` (Ed25519) or `ES256` (P-256). */
+  alg: string
+  /** Unencrypted PKCS#8 PEM. */
+  privateKey: string
+}
+
 export interface BackendRuntimeHealth {
   started: boolean
   databaseConnected: boolean
 }
 
+/**
+ * Builds a forward update that restores a doc to a history version.
+ *
+ * # Arguments
+ * * `current_bin` - The live doc binary
+ * * `history_bin` - The doc binary stored for the history point
+ *
+ * # Returns
+ * A Buffer containing only the delta (changes) as a y-octo update binary,
+ * ready to be pushed like any other doc update
+ */
+export declare function buildDocRestoreUpdate(currentBin: Buffer, historyBin: Buffer): Buffer
+
 export declare function buildPublicRootDoc(rootDocBin: Buffer, docMetas: Array<PublicDocMetaInput>): Buffer
 
 export interface BuiltInPromptRenderContract {
@@ -285,8 +420,14 @@ export interface ContentPolicyMatch {
   reason: string
   value?: string
   span?: ContentPolicyMatchSpan
+  /**
+   * Public suffix plus one label of the matched host, for `url_or_domain`
+   * and `email` matches under a known TLD.
+   */
+  registrableDomain?: string
 }
 
+/** Range of the match in `original`, in UTF-16 code units. */
 export interface ContentPolicyMatchSpan {
   start: number
   end: number
@@ -294,7 +435,23 @@ export interface ContentPolicyMatchSpan {
 
 export interface ContentPolicyScanInput {
   value: string
+  /**
+   * Checks to run, all of them when omitted: `url_or_domain`, `email`,
+   * `phone_number`, `crypto_wallet`, `invisible_characters` and `deny_term`.
+   */
   checks?: Array<string>
+  /** Terms for the `deny_term` check, compared on their confusable skeleton. */
+  denyTerms?: Array<string>
+  /**
+   * Domains, including their subdomains, that `url_or_domain` does not
+   * report unless they were spelled with confusable characters.
+   */
+  allowDomains?: Array<string>
+  /**
+   * Domains, including their subdomains, that `url_or_domain` reports as
+   * `contains_denied_domain`, even when allowed.
+   */
+  denyDomains?: Array<string>
 }
 
 export interface ContentPolicyScanResult {
@@ -332,6 +489,19 @@ export declare function createLicenseCustomerPortal(request: LicenseKeyRequest):
 
 export declare function deactivateLicense(request: LicenseKeyRequest): Promise<CommandResponse>
 
+/**
+ * Compares two versions of a doc block by block, for highlighting what
+ * changed between two history points. Runs off the main thread.
+ *
+ * # Arguments
+ * * `old_bin` - The earlier doc binary
+ * * `new_bin` - The later doc binary
+ * * `doc_id` - The document ID shared by both versions
+ */
+export declare function diffDocVersions(oldBin: Buffer, newBin: Buffer, docId: string): Promise<NativeDocDiff>
+
+export declare function evaluatePermissionBatchV1(input: any): any
+
 export declare function evaluatePermissionV1(input: any): any
 
 export declare function fetchRemoteAttachment(request: RemoteAttachmentFetchRequest): Promise<RemoteAttachmentFetchResponse>
@@ -627,6 +797,16 @@ export interface ModelRegistryVariantContract {
   behaviorFlags?: Array<string>
 }
 
+export interface NativeBlockDiff {
+  blockId: string
+  flavour: string
+  /** One of `added`, `removed` or `modified`. */
+  kind: string
+  /** Inline diff of the block text; empty when the text is unchanged. */
+  text: Array<NativeTextDiffSegment>
+  changedProperties: Array<NativePropertyChange>
+}
+
 export interface NativeBlockInfo {
   blockId: string
   flavour: string
@@ -645,6 +825,17 @@ export interface NativeCrawlResult {
   summary: string
 }
 
+export interface NativeDocDiff {
+  /**
+   * Changed blocks in the order of the new version, followed by removed
+   * blocks in the order of the old version.
+   */
+  blocks: Array<NativeBlockDiff>
+  added: number
+  removed: number
+  modified: number
+}
+
 export interface NativeMarkdownResult {
   title: string
   markdown: string
@@ -657,6 +848,18 @@ export interface NativePageDocContent {
   summary: string
 }
 
+export interface NativePropertyChange {
+  key: string
+  oldValue?: string
+  newValue?: string
+}
+
+export interface NativeTextDiffSegment {
+  /** One of `equal`, `insert` or `delete`. */
+  op: string
+  text: string
+}
+
 export interface NativeWorkspaceDocContent {
   name: string
   avatarKey: string
@@ -664,6 +867,39 @@ export interface NativeWorkspaceDocContent {
 
 export declare function parseAuthSessionRefreshToken(token: string): ParsedAuthSessionRefreshToken | null
 
+export interface OfflineLicenseInput {
+  signedPayload: Buffer
+  /** The workspace the license must be issued for. */
+  workspaceId: string
+  /** Defaults to the public key embedded at build time. */
+  publicKey?: string
+  /** Defaults to the license AES key embedded at build time. */
+  licenseAesKey?: string
+  gracePeriodDays?: number
+  /** Members currently occupying seats, used to report overuse. */
+  seatsUsed?: number
+  /**
+   * The latest `checkedAt` persisted by the caller. Verification never runs
+   * at an earlier time, so winding the clock back cannot revive a license.
+   */
+  lastCheckedAt?: string
+  /** The `entitlement.plans` config; may override `selfhost_team` quotas. */
+  plans?: Record<string, unknown>
+  now: string
+}
+
+export interface OfflineLicenseStatus {
+  /** One of `active`, `grace`, `expired` or `invalid`. */
+  status: string
+  valid: boolean
+  entitlement: ResolvedEntitlement
+  checkedAt: string
+  graceEndsAt?: string
+  seatLimit?: number
+  seatsUsed?: number
+  seatOveruse: number
+}
+
 export interface ParsedAuthSessionRefreshToken {
   id: string
   secretHash: string
@@ -869,6 +1105,8 @@ export interface ResolvedEntitlement {
   flags: Record<string, boolean>
   errorCode?: string
   errorMessage?: string
+  /** End of the grace period of an expired license file, when one applies. */
+  graceUntil?: string
 }
 
 export interface ResolvedQuota {
@@ -889,6 +1127,13 @@ export interface ResolveEntitlementInput {
   signedPayload?: Buffer
   publicKey?: string
   licenseAesKey?: string
+  /** The `entitlement.plans` config. An invalid catalog fails the call. */
+  plans?: Record<string, unknown>
+  /**
+   * Keeps an expired license file valid, with status `grace`, for this many
+   * days after it expires.
+   */
+  licenseGracePeriodDays?: number
   now: string
 }
 
@@ -941,12 +1186,30 @@ export interface RuntimeBlobMetadataBackfillResult {
   workspaceIds: Array<string>
 }
 
+export interface RuntimeBlobMigrationResult {
+  scanned: number
+  copied: number
+  skippedExisting: number
+  skippedMissing: number
+  deletedSource: number
+  failed: number
+  nextCursor?: string
+}
+
 export interface RuntimeByokLocalLeaseRecord {
   leaseId: string
   payload: any
   expiresAtMs: number
 }
 
+export interface RuntimeChallengeVerifyResult {
+  ok: boolean
+  /** One of `invalid`, `expired`, `future` or `replayed` when `ok` is false. */
+  reason?: string
+  /** When the accepted stamp stops being valid. */
+  expiresAtMs?: number
+}
+
 export interface RuntimeDocBlobRefsResult {
   scannedDocs: number
   parsedDocs: number
@@ -963,6 +1226,34 @@ export interface RuntimeDocCompactionResult {
   docId: string
   updatesMerged: number
   historyCreated: boolean
+  diverged: boolean
+}
+
+export interface RuntimeDocCompactionFailure {
+  docId: string
+  error: string
+}
+
+export interface RuntimeDocCompactionScheduleResult {
+  workspaceId: string
+  scannedDocs: number
+  compactedDocs: number
+  skippedDocs: number
+  failedDocs: number
+  /** Why each of the `failed_docs` failed. */
+  failures: Array<RuntimeDocCompactionFailure>
+  divergedDocs: number
+  updatesMerged: number
+  historiesCreated: number
+  backlogDocs: number
+  backlogUpdates: number
+  oldestUpdateAgeMs?: number
+}
+
+export interface RuntimeDocHistoryDeltaMigrationResult {
+  scannedDocs: number
+  convertedRows: number
+  nextCursor?: string
 }
 
 export interface RuntimeDocHistoryInput {
@@ -1032,6 +1323,20 @@ export interface RuntimeMailDeliveryQuotaRecipientInput {
   userId?: string
 }
 
+export interface RuntimeMigrationPlan {
+  currentVersion: number
+  pending: number
+  migrations: Array<RuntimeMigrationStatus>
+}
+
+export interface RuntimeMigrationStatus {
+  version: number
+  description: string
+  /** One of `applied`, `pending`, `failed` or `checksum_mismatch`. */
+  state: string
+  reversible: boolean
+}
+
 export interface RuntimeMultipartUploadInit {
   uploadId: string
   expiresAtMs: number
@@ -1042,9 +1347,19 @@ export interface RuntimeMultipartUploadPart {
   etag: string
 }
 
+export interface RuntimeObjectContentRange {
+  start: number
+  /** Inclusive, like the `Content-Range` header. */
+  end: number
+  total: number
+  /** Ready-to-send `Content-Range` header value. */
+  header: string
+}
+
 export interface RuntimeObjectGetResult {
   body: Buffer
   metadata: RuntimeObjectMetadata
+  contentRange?: RuntimeObjectContentRange
 }
 
 export interface RuntimeObjectListEntry {
@@ -1066,6 +1381,14 @@ export interface RuntimeObjectStoragePutOptions {
   checksumCrc32?: string
 }
 
+export interface RuntimeObjectTombstone {
+  key: string
+  deletedAtMs: number
+  /** `None` once soft delete is no longer configured for the scope. */
+  expiresAtMs?: number
+  contentLength: number
+}
+
 export interface RuntimePresignedObjectRequest {
   url: string
   headersJson: string
@@ -1085,6 +1408,43 @@ export interface RuntimeQuotaTargetDomainInput {
   count: number
 }
 
+export interface RuntimeRefreshTokenFamilyRecord {
+  token: string
+  familyId: string
+  generation: number
+  userId: string
+  authSessionId: string
+  expiresAtMs: number
+  familyExpiresAtMs: number
+}
+
+export interface RuntimeRefreshTokenRotateResult {
+  ok: boolean
+  /** One of `invalid`, `expired`, `revoked` or `reused` when `ok` is false. */
+  reason?: string
+  familyId?: string
+  userId?: string
+  authSessionId?: string
+  refreshToken?: RuntimeRefreshTokenFamilyRecord
+}
+
+export interface RuntimeStorageEncryptionRotationResult {
+  scanned: number
+  rewrapped: number
+  encrypted: number
+  skipped: number
+  failed: number
+  nextCursor?: string
+}
+
+export interface RuntimeTombstonePurgeResult {
+  scanned: number
+  purged: number
+  retained: number
+  failed: number
+  nextCursor?: string
+}
+
 export interface RuntimeVerificationTokenRecord {
   tokenType: number
   token: string
@@ -1186,6 +1546,8 @@ export declare function scanContentPolicyV1(input: ContentPolicyScanInput): Cont
 
 export declare function signAuthSessionAccessToken(userId: string, authSessionId: string, keyId: string, secret: Buffer, issuedAt: number, expiresAt: number): string
 
+export declare function signAuthSessionAccessTokenWithKey(userId: string, authSessionId: string, key: AuthSessionSigningKey, issuedAt: number, expiresAt: number): string
+
 export interface StorageProviderCapabilities {
   put: boolean
   get: boolean
@@ -1282,4 +1644,16 @@ export declare function validateDocUpdate(update: Buffer): Promise<boolean>
 
 export declare function verifyAuthSessionAccessToken(token: string, expectedKeyId: string, secret: Buffer, now: number): AuthSessionAccessTokenVerification
 
+/**
+ * Verifies an `EdDSA` or `ES256` token against a JWKS document, picking the
+ * key by `kid`. Symmetric tokens are always rejected here.
+ */
+export declare function verifyAuthSessionAccessTokenWithJwks(token: string, jwks: string, now: number): AuthSessionAccessTokenVerification
+
+/**
+ * Verifies an imported license file entirely offline, applying the grace
+ * period after expiry and reporting seat overuse.
+ */
+export declare function verifyOfflineLicenseV1(input: OfflineLicenseInput): OfflineLicenseStatus
+
 export declare function verifyChallengeResponse(response: string, bits: number, resource: string): Promise<boolean>
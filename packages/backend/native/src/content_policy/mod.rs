@@ -0,0 +1,854 @@
+mod public_suffix;
+
+use napi_derive::napi;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+use unicode_skeleton::UnicodeSkeleton;
+use url::Url;
+
+use self::public_suffix::registrable_domain;
+
+const VERSION: u32 = 1;
+const PHONE_MIN_DIGITS: usize = 7;
+const PHONE_MAX_DIGITS: usize = 15;
+/// Without a leading `+`, shorter digit runs are too often dates or amounts.
+const PHONE_MIN_LOCAL_DIGITS: usize = 10;
+const BECH32_CHARSET: &str = "qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+#[napi(object)]
+pub struct ContentPolicyScanInput {
+  pub value: String,
+  /// Checks to run, all of them when omitted: `url_or_domain`, `email`,
+  /// `phone_number`, `crypto_wallet`, `invisible_characters` and `deny_term`.
+  pub checks: Option<Vec<String>>,
+  /// Terms for the `deny_term` check, compared on their confusable skeleton.
+  pub deny_terms: Option<Vec<String>>,
+  /// Domains, including their subdomains, that `url_or_domain` does not
+  /// report unless they were spelled with confusable characters.
+  pub allow_domains: Option<Vec<String>>,
+  /// Domains, including their subdomains, that `url_or_domain` reports as
+  /// `contains_denied_domain`, even when allowed.
+  pub deny_domains: Option<Vec<String>>,
+}
+
+/// Range of the match in `original`, in UTF-16 code units.
+#[napi(object)]
+pub struct ContentPolicyMatchSpan {
+  pub start: u32,
+  pub end: u32,
+}
+
+#[napi(object)]
+pub struct ContentPolicyMatch {
+  #[napi(js_name = "type")]
+  pub match_type: String,
+  pub reason: String,
+  pub value: Option<String>,
+  pub span: Option<ContentPolicyMatchSpan>,
+  /// Public suffix plus one label of the matched host, for `url_or_domain`
+  /// and `email` matches under a known TLD.
+  pub registrable_domain: Option<String>,
+}
+
+#[napi(object)]
+pub struct ContentPolicyScanResult {
+  pub version: u32,
+  pub original: String,
+  pub normalized: String,
+  pub skeleton: String,
+  pub matched: bool,
+  pub matches: Vec<ContentPolicyMatch>,
+  pub flags: Vec<String>,
+}
+
+#[napi]
+pub fn scan_content_policy_v1(input: ContentPolicyScanInput) -> ContentPolicyScanResult {
+  let original = input.value;
+  let normalized = normalize_content(&original);
+  let mapped = MappedSkeleton::new(&original);
+  let mut flags = Vec::new();
+  if normalized != original {
+    flags.push("unicode_normalized".to_string());
+  }
+  if mapped.skeleton != normalized {
+    flags.push("confusable_skeleton_changed".to_string());
+  }
+
+  let checks = input.checks.as_deref();
+  let mut matches = Vec::new();
+  if should_run_check(checks, "url_or_domain") {
+    let allow_domains = domain_list(input.allow_domains);
+    let deny_domains = domain_list(input.deny_domains);
+    matches.extend(
+      scan_url_or_domain(&mapped.skeleton)
+        .into_iter()
+        .filter_map(|(start, end)| mapped.domain_match_result(start, end, &allow_domains, &deny_domains)),
+    );
+  }
+  for (check, reason, scan) in [
+    (
+      "email",
+      "contains_email_address",
+      scan_email as fn(&str) -> Vec<(usize, usize)>,
+    ),
+    ("phone_number", "contains_phone_number", scan_phone_number),
+    ("crypto_wallet", "contains_crypto_wallet_address", scan_crypto_wallet),
+  ] {
+    if should_run_check(checks, check) {
+      matches.extend(scan(&mapped.skeleton).into_iter().map(|(start, end)| {
+        let mut matched = mapped.match_result(check, reason, start, end);
+        if check == "email" {
+          matched.registrable_domain = mapped.skeleton[start..end]
+            .rsplit_once('@')
+            .and_then(|(_, domain)| registrable_domain(domain));
+        }
+        matched
+      }));
+    }
+  }
+  if should_run_check(checks, "invisible_characters") {
+    matches.extend(scan_invisible_characters(&original));
+  }
+  if should_run_check(checks, "deny_term") {
+    let terms = input.deny_terms.unwrap_or_default();
+    matches.extend(
+      scan_deny_terms(&mapped.skeleton, &terms)
+        .into_iter()
+        .map(|(start, end)| mapped.match_result("deny_term", "contains_denied_term", start, end)),
+    );
+  }
+  let skeleton = mapped.skeleton;
+
+  ContentPolicyScanResult {
+    version: VERSION,
+    original,
+    normalized,
+    skeleton,
+    matched: !matches.is_empty(),
+    matches,
+    flags,
+  }
+}
+
+fn should_run_check(checks: Option<&[String]>, check: &str) -> bool {
+  checks
+    .map(|checks| checks.iter().any(|configured| configured == check))
+    .unwrap_or(true)
+}
+
+fn normalize_content(value: &str) -> String {
+  value.nfkc().collect::<String>()
+}
+
+fn skeleton_content(value: &str) -> String {
+  let mut output = String::with_capacity(value.len());
+  for ch in value.chars() {
+    push_skeleton_char(&mut output, ch);
+  }
+  output
+}
+
+/// Skeleton of the NFKC-normalized input that remembers which original
+/// characters produced each skeleton character, so matches found on the
+/// skeleton can be reported against the string the user typed.
+struct MappedSkeleton<'a> {
+  original: &'a str,
+  skeleton: String,
+  /// `(skeleton byte offset, original byte range)` per skeleton char.
+  origins: Vec<(usize, usize, usize)>,
+}
+
+impl<'a> MappedSkeleton<'a> {
+  fn new(original: &'a str) -> Self {
+    let mut skeleton = String::with_capacity(original.len());
+    let mut origins = Vec::new();
+    for (start, end) in normalization_clusters(original) {
+      let piece = skeleton_content(&normalize_content(&original[start..end]));
+      for ch in piece.chars() {
+        origins.push((skeleton.len(), start, end));
+        skeleton.push(ch);
+      }
+    }
+    Self {
+      original,
+      skeleton,
+      origins,
+    }
+  }
+
+  fn original_range(&self, start: usize, end: usize) -> Option<(usize, usize)> {
+    let first = self.origins.partition_point(|(offset, _, _)| *offset < start);
+    let last = self.origins.partition_point(|(offset, _, _)| *offset < end);
+    (first < last).then(|| (self.origins[first].1, self.origins[last - 1].2))
+  }
+
+  fn match_result(&self, match_type: &str, reason: &str, start: usize, end: usize) -> ContentPolicyMatch {
+    ContentPolicyMatch {
+      match_type: match_type.to_string(),
+      reason: reason.to_string(),
+      value: Some(self.skeleton[start..end].to_string()),
+      span: self
+        .original_range(start, end)
+        .map(|(original_start, original_end)| utf16_span(self.original, original_start, original_end)),
+      registrable_domain: None,
+    }
+  }
+
+  /// Deny lists win over allow lists. An allowed domain is only skipped when
+  /// the original text spells it without confusable substitutions, so a
+  /// Cyrillic `аffine.pro` is still reported when `affine.pro` is allowed.
+  fn domain_match_result(
+    &self,
+    start: usize,
+    end: usize,
+    allow_domains: &[String],
+    deny_domains: &[String],
+  ) -> Option<ContentPolicyMatch> {
+    let host = match_host(&self.skeleton[start..end]);
+    let reason = match host.as_deref() {
+      Some(host) if covers_domain(deny_domains, host) => "contains_denied_domain",
+      Some(host) if covers_domain(allow_domains, host) => {
+        let verbatim = self
+          .original_range(start, end)
+          .is_some_and(|(original_start, original_end)| {
+            normalize_content(&self.original[original_start..original_end]).to_lowercase() == self.skeleton[start..end]
+          });
+        if verbatim {
+          return None;
+        }
+        "contains_allowed_domain_lookalike"
+      }
+      _ => "contains_url_or_domain",
+    };
+    let mut matched = self.match_result("url_or_domain", reason, start, end);
+    matched.registrable_domain = host.as_deref().and_then(registrable_domain);
+    Some(matched)
+  }
+}
+
+/// Splits `value` into runs that NFKC never composes across: a starter plus
+/// the combining marks or conjoining Hangul jamo that follow it.
+fn normalization_clusters(value: &str) -> Vec<(usize, usize)> {
+  let mut clusters = Vec::<(usize, usize)>::new();
+  for (offset, ch) in value.char_indices() {
+    let end = offset + ch.len_utf8();
+    let continues = is_combining_mark(ch) || matches!(ch, '\u{1160}'..='\u{11ff}');
+    match clusters.last_mut() {
+      Some(cluster) if continues => cluster.1 = end,
+      _ => clusters.push((offset, end)),
+    }
+  }
+  clusters
+}
+
+fn utf16_span(value: &str, start: usize, end: usize) -> ContentPolicyMatchSpan {
+  let start_units = value[..start].encode_utf16().count();
+  ContentPolicyMatchSpan {
+    start: start_units as u32,
+    end: (start_units + value[start..end].encode_utf16().count()) as u32,
+  }
+}
+
+fn push_skeleton_char(output: &mut String, ch: char) {
+  if is_default_ignorable(ch) {
+    return;
+  }
+  if let Some(replacement) = url_punctuation_ascii(ch).or_else(|| enclosed_ascii(ch)) {
+    output.push_str(replacement);
+    return;
+  }
+  if ch.is_ascii() {
+    output.extend(ch.to_lowercase());
+    return;
+  }
+
+  let source = ch.to_string();
+  let skeleton = source.skeleton_chars().collect::<String>();
+  if skeleton == source {
+    output.extend(ch.to_lowercase());
+    return;
+  }
+
+  for skeleton_ch in skeleton.chars() {
+    push_skeleton_char(output, skeleton_ch);
+  }
+}
+
+fn is_default_ignorable(ch: char) -> bool {
+  matches!(
+    ch,
+    '\u{00ad}'
+      | '\u{034f}'
+      | '\u{061c}'
+      | '\u{180e}'
+      | '\u{200b}'..='\u{200f}'
+      | '\u{202a}'..='\u{202e}'
+      | '\u{2060}'..='\u{206f}'
+      | '\u{fe00}'..='\u{fe0f}'
+      | '\u{feff}'
+      | '\u{e0100}'..='\u{e01ef}'
+  )
+}
+
+fn enclosed_ascii(ch: char) -> Option<&'static str> {
+  let code = ch as u32;
+  if (0x1f150..=0x1f169).contains(&code) {
+    return ASCII_LOWERCASE.get((code - 0x1f150) as usize).copied();
+  }
+  if ch == '⓿' || ch == '🄌' {
+    return Some("0");
+  }
+  for (start, digits) in [
+    (0x2776, ASCII_DIGITS_1_TO_9),
+    (0x2780, ASCII_DIGITS_1_TO_9),
+    (0x278a, ASCII_DIGITS_1_TO_9),
+  ] {
+    if (start..=start + 8).contains(&code) {
+      return digits.get((code - start) as usize).copied();
+    }
+  }
+  None
+}
+
+const ASCII_LOWERCASE: [&str; 26] = [
+  "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s", "t", "u", "v", "w",
+  "x", "y", "z",
+];
+
+const ASCII_DIGITS_1_TO_9: [&str; 9] = ["1", "2", "3", "4", "5", "6", "7", "8", "9"];
+
+fn url_punctuation_ascii(ch: char) -> Option<&'static str> {
+  match ch {
+    // URL punctuation variants that are not always useful in generic skeletons.
+    '。' | '．' | '｡' | '․' | '‧' | '∙' | '●' | '•' => Some("."),
+    '⁄' | '∕' | '╱' | '／' => Some("/"),
+    '：' | '꞉' | 'ː' | '։' => Some(":"),
+    '‐' | '‑' | '‒' | '–' | '—' | '―' | '−' | '﹘' | '－' => Some("-"),
+    '_' | '＿' => Some("_"),
+    _ => None,
+  }
+}
+
+fn scan_url_or_domain(value: &str) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  let chars = value.char_indices().collect::<Vec<_>>();
+  let mut index = 0;
+  while index < chars.len() {
+    let start = chars[index].0;
+    let remaining = &value[start..];
+    if remaining.starts_with("http://") || remaining.starts_with("https://") || remaining.starts_with("www.") {
+      let end = consume_non_space(value, start);
+      matches.push((start, end));
+      index = char_index_after(&chars, end);
+      continue;
+    }
+
+    if is_domain_boundary_before(value, start) {
+      let end = consume_domain(value, start);
+      if end > start && is_domain_candidate(&value[start..end]) && is_domain_boundary_after(value, end) {
+        matches.push((start, end));
+        index = char_index_after(&chars, end);
+        continue;
+      }
+    }
+    index += 1;
+  }
+  matches
+}
+
+fn match_host(value: &str) -> Option<String> {
+  if value.starts_with("http://") || value.starts_with("https://") {
+    return Url::parse(value).ok()?.host_str().map(ToString::to_string);
+  }
+  value
+    .split(['/', '?', '#', ':'])
+    .next()
+    .filter(|host| !host.is_empty())
+    .map(ToString::to_string)
+}
+
+fn domain_list(domains: Option<Vec<String>>) -> Vec<String> {
+  domains
+    .unwrap_or_default()
+    .iter()
+    .map(|domain| {
+      skeleton_content(&normalize_content(domain))
+        .trim()
+        .trim_start_matches("*.")
+        .trim_matches('.')
+        .to_string()
+    })
+    .filter(|domain| !domain.is_empty())
+    .collect()
+}
+
+fn covers_domain(domains: &[String], host: &str) -> bool {
+  domains.iter().any(|domain| {
+    host
+      .strip_suffix(domain.as_str())
+      .is_some_and(|prefix| prefix.is_empty() || prefix.ends_with('.'))
+  })
+}
+
+fn consume_non_space(value: &str, start: usize) -> usize {
+  value[start..]
+    .char_indices()
+    .find_map(|(offset, ch)| ch.is_whitespace().then_some(start + offset))
+    .unwrap_or(value.len())
+}
+
+fn consume_domain(value: &str, start: usize) -> usize {
+  value[start..]
+    .char_indices()
+    .find_map(|(offset, ch)| (!is_domain_char(ch)).then_some(start + offset))
+    .unwrap_or(value.len())
+}
+
+/// Well-formed labels under a TLD from the public suffix list, and not a
+/// bare public suffix such as `co.uk`.
+fn is_domain_candidate(candidate: &str) -> bool {
+  let labels = candidate.split('.').collect::<Vec<_>>();
+  if labels.len() < 2 || registrable_domain(candidate).is_none() {
+    return false;
+  }
+  labels.iter().all(|label| {
+    !label.is_empty()
+      && label.len() <= 63
+      && !label.starts_with('-')
+      && !label.ends_with('-')
+      && label.chars().all(|ch| ch.is_ascii_alphanumeric() || ch == '-')
+  })
+}
+
+fn is_domain_char(ch: char) -> bool {
+  ch.is_ascii_alphanumeric() || ch == '-' || ch == '.'
+}
+
+fn is_domain_boundary_before(value: &str, start: usize) -> bool {
+  if start == 0 {
+    return true;
+  }
+  let Some(previous) = value[..start].chars().next_back() else {
+    return true;
+  };
+  !(previous.is_ascii_alphanumeric() || previous == '-' || previous == '_' || previous == '@')
+}
+
+fn is_domain_boundary_after(value: &str, end: usize) -> bool {
+  if end >= value.len() {
+    return true;
+  }
+  let Some(next) = value[end..].chars().next() else {
+    return true;
+  };
+  !(next.is_ascii_alphanumeric() || next == '-' || next == '_' || next == '.')
+}
+
+fn char_index_after(chars: &[(usize, char)], byte_index: usize) -> usize {
+  chars
+    .iter()
+    .position(|(offset, _)| *offset >= byte_index)
+    .unwrap_or(chars.len())
+}
+
+fn scan_email(value: &str) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  let mut search_from = 0;
+  while let Some(at) = value[search_from..].find('@').map(|offset| search_from + offset) {
+    search_from = at + 1;
+    let start = value[..at]
+      .char_indices()
+      .rev()
+      .take_while(|(_, ch)| is_email_local_char(*ch))
+      .last()
+      .map_or(at, |(offset, _)| offset);
+    let end = consume_domain(value, at + 1);
+    let domain = value[at + 1..end].trim_end_matches('.');
+    let end = at + 1 + domain.len();
+    if start < at
+      && !value[start..at].starts_with('.')
+      && is_domain_candidate(domain)
+      && is_domain_boundary_after(value, end)
+      && matches.last().is_none_or(|(_, previous_end)| *previous_end <= start)
+    {
+      matches.push((start, end));
+      search_from = end;
+    }
+  }
+  matches
+}
+
+fn is_email_local_char(ch: char) -> bool {
+  ch.is_ascii_alphanumeric() || matches!(ch, '.' | '_' | '%' | '+' | '-')
+}
+
+fn scan_phone_number(value: &str) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  let mut offset = 0;
+  while let Some(ch) = value[offset..].chars().next() {
+    if matches!(ch, '+' | '(' | '0'..='9') && is_token_boundary_before(value, offset) {
+      let end = consume_phone(value, offset);
+      let digits = value[offset..end].chars().filter(char::is_ascii_digit).count();
+      let min_digits = if ch == '+' {
+        PHONE_MIN_DIGITS
+      } else {
+        PHONE_MIN_LOCAL_DIGITS
+      };
+      if (min_digits..=PHONE_MAX_DIGITS).contains(&digits) && is_token_boundary_after(value, end) {
+        matches.push((offset, end));
+        offset = end;
+        continue;
+      }
+    }
+    offset += ch.len_utf8();
+  }
+  matches
+}
+
+/// Digits with at most two separators between groups; ends after the last
+/// digit.
+fn consume_phone(value: &str, start: usize) -> usize {
+  let mut end = start;
+  let mut separators = 0;
+  for (offset, ch) in value[start..].char_indices() {
+    if ch.is_ascii_digit() {
+      end = start + offset + 1;
+      separators = 0;
+    } else if (ch == '+' && offset == 0) || matches!(ch, ' ' | '-' | '.' | '(' | ')') {
+      separators += 1;
+      if separators > 2 {
+        break;
+      }
+    } else {
+      break;
+    }
+  }
+  end
+}
+
+fn scan_crypto_wallet(value: &str) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  let mut token_start = None;
+  for (offset, ch) in value.char_indices().chain([(value.len(), ' ')]) {
+    if ch.is_ascii_alphanumeric() {
+      token_start.get_or_insert(offset);
+      continue;
+    }
+    if let Some(start) = token_start.take()
+      && is_crypto_wallet(&value[start..offset])
+    {
+      matches.push((start, offset));
+    }
+  }
+  matches
+}
+
+/// Tokens are lowercase here, so base58 addresses are matched by shape
+/// rather than by their case-sensitive alphabet.
+fn is_crypto_wallet(token: &str) -> bool {
+  let has_digit = token.chars().any(|ch| ch.is_ascii_digit());
+  let has_letter = token.chars().any(|ch| ch.is_ascii_alphabetic());
+  if let Some(hex) = token.strip_prefix("0x") {
+    return hex.len() == 40 && hex.chars().all(|ch| ch.is_ascii_hexdigit());
+  }
+  if let Some(data) = token.strip_prefix("bc1") {
+    return (39..=59).contains(&data.len()) && data.chars().all(|ch| BECH32_CHARSET.contains(ch));
+  }
+  let is_base58 = !token.contains('0');
+  match token.chars().next() {
+    Some('1' | '3') => (26..=35).contains(&token.len()) && is_base58 && has_letter,
+    Some('t') => token.len() == 34 && is_base58 && has_digit,
+    _ => false,
+  }
+}
+
+/// Runs on the original string because the skeleton drops these characters.
+fn scan_invisible_characters(value: &str) -> Vec<ContentPolicyMatch> {
+  let chars = value.char_indices().collect::<Vec<_>>();
+  let mut matches = Vec::new();
+  let mut index = 0;
+  while index < chars.len() {
+    let Some(mut reason) = invisible_character_reason(&chars, index) else {
+      index += 1;
+      continue;
+    };
+    let start = chars[index].0;
+    let mut end_index = index;
+    while end_index + 1 < chars.len()
+      && let Some(next_reason) = invisible_character_reason(&chars, end_index + 1)
+    {
+      if next_reason == "contains_bidi_control" {
+        reason = next_reason;
+      }
+      end_index += 1;
+    }
+    let (last_offset, last) = chars[end_index];
+    let end = last_offset + last.len_utf8();
+    matches.push(ContentPolicyMatch {
+      match_type: "invisible_characters".to_string(),
+      reason: reason.to_string(),
+      value: Some(
+        value[start..end]
+          .chars()
+          .map(|ch| format!("U+{:04X}", ch as u32))
+          .collect::<Vec<_>>()
+          .join(" "),
+      ),
+      span: Some(utf16_span(value, start, end)),
+      registrable_domain: None,
+    });
+    index = end_index + 1;
+  }
+  matches
+}
+
+/// Bidi embeddings, overrides and isolates are always suspicious. Joiners
+/// have legitimate uses in emoji and complex scripts, so they only count
+/// between ASCII letters or digits.
+fn invisible_character_reason(chars: &[(usize, char)], index: usize) -> Option<&'static str> {
+  let ch = chars[index].1;
+  match ch {
+    '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => Some("contains_bidi_control"),
+    '\u{200c}' | '\u{200d}' => {
+      let ascii_at = |index: Option<usize>| {
+        index
+          .and_then(|index| chars.get(index))
+          .is_some_and(|(_, ch)| ch.is_ascii_alphanumeric())
+      };
+      (ascii_at(index.checked_sub(1)) && ascii_at(Some(index + 1))).then_some("contains_invisible_character")
+    }
+    '\u{00ad}' | '\u{180e}' | '\u{200b}' | '\u{2060}'..='\u{2064}' | '\u{feff}' | '\u{e0000}'..='\u{e007f}' => {
+      Some("contains_invisible_character")
+    }
+    _ => None,
+  }
+}
+
+fn scan_deny_terms(value: &str, terms: &[String]) -> Vec<(usize, usize)> {
+  let mut matches = Vec::new();
+  for term in terms {
+    let term = skeleton_content(&normalize_content(term));
+    let term = term.trim();
+    if term.is_empty() {
+      continue;
+    }
+    let starts_word = term.starts_with(|ch: char| ch.is_alphanumeric());
+    let ends_word = term.ends_with(|ch: char| ch.is_alphanumeric());
+    for (start, _) in value.match_indices(term) {
+      let end = start + term.len();
+      let before = value[..start].chars().next_back();
+      let after = value[end..].chars().next();
+      if (!starts_word || before.is_none_or(|ch| !ch.is_alphanumeric()))
+        && (!ends_word || after.is_none_or(|ch| !ch.is_alphanumeric()))
+      {
+        matches.push((start, end));
+      }
+    }
+  }
+  matches.sort_unstable();
+  matches
+}
+
+fn is_token_boundary_before(value: &str, start: usize) -> bool {
+  value[..start]
+    .chars()
+    .next_back()
+    .is_none_or(|previous| !(previous.is_ascii_alphanumeric() || matches!(previous, '+' | '_')))
+}
+
+fn is_token_boundary_after(value: &str, end: usize) -> bool {
+  value[end..]
+    .chars()
+    .next()
+    .is_none_or(|next| !(next.is_ascii_alphanumeric() || next == '_'))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn scan(value: &str) -> ContentPolicyScanResult {
+    scan_content_policy_v1(ContentPolicyScanInput {
+      value: value.to_string(),
+      checks: Some(vec!["url_or_domain".to_string()]),
+      deny_terms: None,
+      allow_domains: None,
+      deny_domains: None,
+    })
+  }
+
+  #[test]
+  fn scans_url_or_domain_cases() {
+    struct Case {
+      input: &'static str,
+      matched: bool,
+      skeleton: &'static str,
+      value: Option<&'static str>,
+    }
+
+    for case in [
+      Case {
+        input: "https://ｅxample．com",
+        matched: true,
+        skeleton: "https://example.com",
+        value: Some("https://example.com"),
+      },
+      Case {
+        input: "join 🅠⓿❶.com",
+        matched: true,
+        skeleton: "join q01.com",
+        value: Some("q01.com"),
+      },
+      Case {
+        input: "exa\u{200b}mple.com",
+        matched: true,
+        skeleton: "example.com",
+        value: Some("example.com"),
+      },
+      Case {
+        input: "hello workspace",
+        matched: false,
+        skeleton: "hello workspace",
+        value: None,
+      },
+      Case {
+        input: "user@example.com",
+        matched: false,
+        skeleton: "user@example.com",
+        value: None,
+      },
+    ] {
+      let result = scan(case.input);
+      assert_eq!(result.matched, case.matched, "{}", case.input);
+      assert_eq!(result.skeleton, case.skeleton, "{}", case.input);
+      assert_eq!(
+        result.matches.first().and_then(|matched| matched.value.as_deref()),
+        case.value,
+        "{}",
+        case.input
+      );
+    }
+  }
+
+  fn scan_checks(value: &str, checks: &[&str], deny_terms: &[&str]) -> Vec<(String, String, Option<(u32, u32)>)> {
+    scan_content_policy_v1(ContentPolicyScanInput {
+      value: value.to_string(),
+      checks: Some(checks.iter().map(ToString::to_string).collect()),
+      deny_terms: Some(deny_terms.iter().map(ToString::to_string).collect()),
+      allow_domains: None,
+      deny_domains: None,
+    })
+    .matches
+    .into_iter()
+    .map(|matched| {
+      (
+        matched.match_type,
+        matched.value.unwrap_or_default(),
+        matched.span.map(|span| (span.start, span.end)),
+      )
+    })
+    .collect()
+  }
+
+  fn found(match_type: &str, value: &str, span: (u32, u32)) -> (String, String, Option<(u32, u32)>) {
+    (match_type.to_string(), value.to_string(), Some(span))
+  }
+
+  #[test]
+  fn maps_skeleton_matches_back_to_original_spans() {
+    assert_eq!(
+      scan_checks("visit https://ｅxample．com", &["url_or_domain"], &[]),
+      [found("url_or_domain", "https://example.com", (6, 25))]
+    );
+    assert_eq!(
+      scan_checks("🅠⓿❶.com", &["url_or_domain"], &[]),
+      [found("url_or_domain", "q01.com", (0, 8))]
+    );
+    assert_eq!(
+      scan_checks(
+        "Get FREE ＭＯＮＥＹ now, not freemoneyx",
+        &["deny_term"],
+        &["Free Money"]
+      ),
+      [found("deny_term", "free money", (4, 14))]
+    );
+  }
+
+  #[test]
+  fn scans_contact_and_wallet_checks() {
+    let wallet = format!("0x{}", "a1".repeat(20));
+    let input = format!("mail ｕser@example.com or +1 (555) 123-4567, tip {wallet}");
+    assert_eq!(
+      scan_checks(&input, &["email", "phone_number", "crypto_wallet"], &[]),
+      [
+        found("email", "user@example.com", (5, 21)),
+        found("phone_number", "+1 (555) 123-4567", (25, 42)),
+        found("crypto_wallet", &wallet, (48, 90)),
+      ]
+    );
+    assert!(scan_checks("due 2024-01-01, order 12345", &["phone_number"], &[]).is_empty());
+    assert_eq!(
+      scan_checks("bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq", &["crypto_wallet"], &[]).len(),
+      1
+    );
+    assert!(scan_checks("unbelievably extraordinary", &["crypto_wallet"], &[]).is_empty());
+  }
+
+  #[test]
+  fn flags_bidi_controls_and_hidden_joiners_but_not_emoji_sequences() {
+    assert_eq!(
+      scan_checks("invoice\u{202e}fdp.exe", &["invisible_characters"], &[]),
+      [found("invisible_characters", "U+202E", (7, 8))]
+    );
+    assert_eq!(
+      scan_checks("pay\u{200b}\u{2066}pal", &["invisible_characters"], &[])
+        .into_iter()
+        .map(|(_, value, span)| (value, span))
+        .collect::<Vec<_>>(),
+      [("U+200B U+2066".to_string(), Some((3, 5)))]
+    );
+    assert!(scan_checks("👩\u{200d}💻 team", &["invisible_characters"], &[]).is_empty());
+    assert_eq!(scan_checks("a\u{200d}b", &["invisible_characters"], &[]).len(), 1);
+  }
+
+  #[test]
+  fn url_or_domain_uses_public_suffixes_and_domain_lists() {
+    let scan_domains = |value: &str, allow: &[&str], deny: &[&str]| {
+      scan_content_policy_v1(ContentPolicyScanInput {
+        value: value.to_string(),
+        checks: Some(vec!["url_or_domain".to_string()]),
+        deny_terms: None,
+        allow_domains: Some(allow.iter().map(ToString::to_string).collect()),
+        deny_domains: Some(deny.iter().map(ToString::to_string).collect()),
+      })
+      .matches
+      .into_iter()
+      .map(|matched| (matched.reason, matched.registrable_domain.unwrap_or_default()))
+      .collect::<Vec<_>>()
+    };
+    let reported = |reason: &str, domain: &str| (reason.to_string(), domain.to_string());
+
+    assert!(scan_domains("see readme.txt or co.uk", &[], &[]).is_empty());
+    assert_eq!(
+      scan_domains("docs.example.co.uk xn--e1afmkfd.xn--p1ai", &[], &[]),
+      [
+        reported("contains_url_or_domain", "example.co.uk"),
+        reported("contains_url_or_domain", "xn--e1afmkfd.xn--p1ai"),
+      ]
+    );
+    assert_eq!(
+      scan_domains(
+        "https://app.Affine.pro/docs, \u{0430}ffine.pro and notaffine.pro",
+        &["affine.pro"],
+        &[]
+      ),
+      [
+        reported("contains_allowed_domain_lookalike", "affine.pro"),
+        reported("contains_url_or_domain", "notaffine.pro"),
+      ]
+    );
+    assert_eq!(
+      scan_domains("login.evil.com", &["evil.com"], &["evil.com"]),
+      [reported("contains_denied_domain", "evil.com")]
+    );
+  }
+}
@@ -0,0 +1,114 @@
+//! Registrable-domain lookup backed by the embedded Public Suffix List
+//! (<https://publicsuffix.org/list/>). The private section is included, so
+//! sites hosted under suffixes such as `github.io` count as separate domains.
+
+use std::{collections::HashSet, sync::LazyLock};
+
+use url::Host;
+
+static PUBLIC_SUFFIX_LIST: &str = include_str!("public_suffix_list.dat");
+static PUBLIC_SUFFIX_RULES: LazyLock<PublicSuffixRules> =
+  LazyLock::new(|| PublicSuffixRules::parse(PUBLIC_SUFFIX_LIST));
+
+#[derive(Default)]
+struct PublicSuffixRules {
+  rules: HashSet<String>,
+  /// Parents of `*.` rules.
+  wildcards: HashSet<String>,
+  /// `!` rules without the marker.
+  exceptions: HashSet<String>,
+}
+
+impl PublicSuffixRules {
+  fn parse(source: &str) -> Self {
+    let mut rules = Self::default();
+    for line in source.lines() {
+      let Some(rule) = line.split_whitespace().next() else {
+        continue;
+      };
+      if rule.starts_with("//") {
+        continue;
+      }
+      let (set, rule) = if let Some(rule) = rule.strip_prefix('!') {
+        (&mut rules.exceptions, rule)
+      } else if let Some(rule) = rule.strip_prefix("*.") {
+        (&mut rules.wildcards, rule)
+      } else {
+        (&mut rules.rules, rule)
+      };
+      if let Some(rule) = ascii_domain(rule) {
+        set.insert(rule);
+      }
+    }
+    rules
+  }
+
+  fn is_listed_tld(&self, tld: &str) -> bool {
+    self.rules.contains(tld) || self.wildcards.contains(tld)
+  }
+
+  /// Number of trailing labels forming the public suffix. The first match
+  /// from the left is the longest one; exceptions win over wildcards of the
+  /// same length.
+  fn public_suffix_len(&self, labels: &[&str]) -> usize {
+    for start in 0..labels.len() {
+      let suffix = labels[start..].join(".");
+      if self.exceptions.contains(&suffix) {
+        return labels.len() - start - 1;
+      }
+      if self.rules.contains(&suffix)
+        || (start + 1 < labels.len() && self.wildcards.contains(&labels[start + 1..].join(".")))
+      {
+        return labels.len() - start;
+      }
+    }
+    1
+  }
+}
+
+/// Rules are stored in Unicode; matched hosts come out of the ASCII skeleton,
+/// so IDN rules are converted to their punycode form.
+fn ascii_domain(domain: &str) -> Option<String> {
+  if domain.is_ascii() {
+    return Some(domain.to_ascii_lowercase());
+  }
+  match Host::parse(domain) {
+    Ok(Host::Domain(domain)) => Some(domain),
+    _ => None,
+  }
+}
+
+/// The public suffix plus one label, e.g. `example.co.uk` for
+/// `www.example.co.uk`. `None` for hosts under a TLD the list does not know
+/// and for hosts that are themselves public suffixes.
+pub(super) fn registrable_domain(host: &str) -> Option<String> {
+  let host = host.trim_end_matches('.').to_ascii_lowercase();
+  let labels = host.split('.').collect::<Vec<_>>();
+  if labels.iter().any(|label| label.is_empty()) || !PUBLIC_SUFFIX_RULES.is_listed_tld(labels.last()?) {
+    return None;
+  }
+  let suffix_len = PUBLIC_SUFFIX_RULES.public_suffix_len(&labels);
+  (labels.len() > suffix_len).then(|| labels[labels.len() - suffix_len - 1..].join("."))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn extracts_registrable_domains_with_wildcards_exceptions_and_idn() {
+    for (host, expected) in [
+      ("www.example.com", Some("example.com")),
+      ("a.b.example.co.uk", Some("example.co.uk")),
+      ("project.github.io", Some("project.github.io")),
+      ("foo.bar.ck", Some("foo.bar.ck")),
+      ("www.ck", Some("www.ck")),
+      ("xn--e1afmkfd.xn--p1ai", Some("xn--e1afmkfd.xn--p1ai")),
+      ("co.uk", None),
+      ("readme.txt", None),
+      ("example..com", None),
+    ] {
+      assert_eq!(registrable_domain(host).as_deref(), expected, "{host}");
+    }
+  }
+}
@@ -0,0 +1,488 @@
+use std::collections::{BTreeSet, HashMap};
+
+use affine_common::napi_utils::map_napi_err;
+use affine_doc_loader::{self as doc_loader, BlockInfo};
+use napi::{Env, Task, bindgen_prelude::*};
+use napi_derive::napi;
+
+/// Token pairs compared when diffing one block's text. Larger edits are
+/// reported as a whole-text replacement instead.
+const MAX_TEXT_DIFF_CELLS: usize = 4_000_000;
+
+/// Token pairs compared across all blocks of one doc diff. Once spent, the
+/// remaining changed blocks are reported as whole-text replacements.
+const MAX_DOC_DIFF_CELLS: usize = 16_000_000;
+
+#[napi(object)]
+pub struct NativeTextDiffSegment {
+  /// One of `equal`, `insert` or `delete`.
+  pub op: String,
+  pub text: String,
+}
+
+#[napi(object)]
+pub struct NativePropertyChange {
+  pub key: String,
+  pub old_value: Option<String>,
+  pub new_value: Option<String>,
+}
+
+#[napi(object)]
+pub struct NativeBlockDiff {
+  pub block_id: String,
+  pub flavour: String,
+  /// One of `added`, `removed` or `modified`.
+  pub kind: String,
+  /// Inline diff of the block text; empty when the text is unchanged.
+  pub text: Vec<NativeTextDiffSegment>,
+  pub changed_properties: Vec<NativePropertyChange>,
+}
+
+#[napi(object)]
+pub struct NativeDocDiff {
+  /// Changed blocks in the order of the new version, followed by removed
+  /// blocks in the order of the old version.
+  pub blocks: Vec<NativeBlockDiff>,
+  pub added: u32,
+  pub removed: u32,
+  pub modified: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DiffOp {
+  Equal,
+  Insert,
+  Delete,
+}
+
+impl DiffOp {
+  fn as_str(self) -> &'static str {
+    match self {
+      Self::Equal => "equal",
+      Self::Insert => "insert",
+      Self::Delete => "delete",
+    }
+  }
+}
+
+fn block_text(block: &BlockInfo) -> String {
+  block.content.as_deref().unwrap_or_default().join("\n")
+}
+
+fn is_word_char(c: char) -> bool {
+  // CJK text has no word separators, so each ideograph is its own token.
+  c.is_alphanumeric() && (c as u32) < 0x2E80
+}
+
+/// Splits text into runs of word characters and single other characters, so
+/// inline diffs align on whole words.
+fn tokenize(text: &str) -> Vec<&str> {
+  let mut tokens = Vec::new();
+  let mut word_start = None;
+  for (index, c) in text.char_indices() {
+    if is_word_char(c) {
+      word_start.get_or_insert(index);
+      continue;
+    }
+    if let Some(start) = word_start.take() {
+      tokens.push(&text[start..index]);
+    }
+    tokens.push(&text[index..index + c.len_utf8()]);
+  }
+  if let Some(start) = word_start {
+    tokens.push(&text[start..]);
+  }
+  tokens
+}
+
+fn push_op(ops: &mut Vec<(DiffOp, String)>, op: DiffOp, text: &str) {
+  if text.is_empty() {
+    return;
+  }
+  match ops.last_mut() {
+    Some((last_op, last_text)) if *last_op == op => last_text.push_str(text),
+    _ => ops.push((op, text.to_string())),
+  }
+}
+
+/// Word-level diff of `old` into `new`, with adjacent runs of the same kind
+/// merged.
+pub(crate) fn diff_text_ops(old: &str, new: &str) -> Vec<(DiffOp, String)> {
+  diff_text_ops_within(old, new, &mut { MAX_TEXT_DIFF_CELLS })
+}
+
+/// Like [`diff_text_ops`], but charges the compared token pairs to `budget`
+/// and falls back to a whole-text replacement when it would be exceeded.
+fn diff_text_ops_within(old: &str, new: &str, budget: &mut usize) -> Vec<(DiffOp, String)> {
+  let old_tokens = tokenize(old);
+  let new_tokens = tokenize(new);
+  let prefix = old_tokens
+    .iter()
+    .zip(&new_tokens)
+    .take_while(|(old, new)| old == new)
+    .count();
+  let suffix = old_tokens[prefix..]
+    .iter()
+    .rev()
+    .zip(new_tokens[prefix..].iter().rev())
+    .take_while(|(old, new)| old == new)
+    .count();
+  let old_middle = &old_tokens[prefix..old_tokens.len() - suffix];
+  let new_middle = &new_tokens[prefix..new_tokens.len() - suffix];
+
+  let mut ops = Vec::new();
+  push_op(&mut ops, DiffOp::Equal, &old_tokens[..prefix].concat());
+  let cells = old_middle.len().saturating_mul(new_middle.len());
+  if cells > MAX_TEXT_DIFF_CELLS.min(*budget) {
+    push_op(&mut ops, DiffOp::Delete, &old_middle.concat());
+    push_op(&mut ops, DiffOp::Insert, &new_middle.concat());
+  } else {
+    *budget -= cells;
+    for (op, token) in lcs_ops(old_middle, new_middle) {
+      push_op(&mut ops, op, token);
+    }
+  }
+  push_op(
+    &mut ops,
+    DiffOp::Equal,
+    &old_tokens[old_tokens.len() - suffix..].concat(),
+  );
+  ops
+}
+
+fn diff_text(old: &str, new: &str, budget: &mut usize) -> Vec<NativeTextDiffSegment> {
+  diff_text_ops_within(old, new, budget)
+    .into_iter()
+    .map(|(op, text)| NativeTextDiffSegment {
+      op: op.as_str().to_string(),
+      text,
+    })
+    .collect()
+}
+
+fn lcs_ops<'a>(old: &[&'a str], new: &[&'a str]) -> Vec<(DiffOp, &'a str)> {
+  let width = new.len() + 1;
+  // lengths[i * width + j] is the LCS length of old[i..] and new[j..].
+  let mut lengths = vec![0_u32; (old.len() + 1) * width];
+  for i in (0..old.len()).rev() {
+    for j in (0..new.len()).rev() {
+      lengths[i * width + j] = if old[i] == new[j] {
+        lengths[(i + 1) * width + j + 1] + 1
+      } else {
+        lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+      };
+    }
+  }
+
+  let mut ops = Vec::with_capacity(old.len() + new.len());
+  let (mut i, mut j) = (0, 0);
+  while i < old.len() && j < new.len() {
+    if old[i] == new[j] {
+      ops.push((DiffOp::Equal, old[i]));
+      i += 1;
+      j += 1;
+    } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+      ops.push((DiffOp::Delete, old[i]));
+      i += 1;
+    } else {
+      ops.push((DiffOp::Insert, new[j]));
+      j += 1;
+    }
+  }
+  ops.extend(old[i..].iter().map(|token| (DiffOp::Delete, *token)));
+  ops.extend(new[j..].iter().map(|token| (DiffOp::Insert, *token)));
+  ops
+}
+
+fn list_property(values: &Option<Vec<String>>) -> Option<String> {
+  values.as_ref().map(|values| values.join(","))
+}
+
+/// Flattens a block into comparable properties. `additional` holds a JSON
+/// object for most flavours, so its keys are compared one by one.
+fn block_properties(block: &BlockInfo) -> HashMap<String, String> {
+  let mut properties = HashMap::new();
+  let mut insert = |key: &str, value: Option<String>| {
+    if let Some(value) = value {
+      properties.insert(key.to_string(), value);
+    }
+  };
+  insert("flavour", Some(block.flavour.clone()));
+  insert("parentBlockId", block.parent_block_id.clone());
+  insert("blob", list_property(&block.blob));
+  insert("refDocId", list_property(&block.ref_doc_id));
+  insert("refInfo", list_property(&block.ref_info));
+
+  match block
+    .additional
+    .as_deref()
+    .map(serde_json::from_str::<serde_json::Value>)
+  {
+    Some(Ok(serde_json::Value::Object(additional))) => {
+      for (key, value) in additional {
+        let value = match value {
+          serde_json::Value::String(value) => value,
+          value => value.to_string(),
+        };
+        insert(&format!("additional.{key}"), Some(value));
+      }
+    }
+    _ => insert("additional", block.additional.clone()),
+  }
+  properties
+}
+
+fn diff_properties(old: &BlockInfo, new: &BlockInfo) -> Vec<NativePropertyChange> {
+  let old = block_properties(old);
+  let new = block_properties(new);
+  old
+    .keys()
+    .chain(new.keys())
+    .collect::<BTreeSet<_>>()
+    .into_iter()
+    .filter(|key| old.get(*key) != new.get(*key))
+    .map(|key| NativePropertyChange {
+      key: key.clone(),
+      old_value: old.get(key).cloned(),
+      new_value: new.get(key).cloned(),
+    })
+    .collect()
+}
+
+fn whole_block(block: &BlockInfo, kind: &str, op: DiffOp) -> NativeBlockDiff {
+  let text = Some(block_text(block))
+    .filter(|text| !text.is_empty())
+    .map(|text| NativeTextDiffSegment {
+      op: op.as_str().to_string(),
+      text,
+    })
+    .into_iter()
+    .collect();
+  NativeBlockDiff {
+    block_id: block.block_id.clone(),
+    flavour: block.flavour.clone(),
+    kind: kind.to_string(),
+    text,
+    changed_properties: Vec::new(),
+  }
+}
+
+fn diff_blocks(old_blocks: &[BlockInfo], new_blocks: &[BlockInfo]) -> NativeDocDiff {
+  let old_by_id = old_blocks
+    .iter()
+    .map(|block| (block.block_id.as_str(), block))
+    .collect::<HashMap<_, _>>();
+  let new_ids = new_blocks
+    .iter()
+    .map(|block| block.block_id.as_str())
+    .collect::<BTreeSet<_>>();
+
+  let mut diff = NativeDocDiff {
+    blocks: Vec::new(),
+    added: 0,
+    removed: 0,
+    modified: 0,
+  };
+  let mut budget = MAX_DOC_DIFF_CELLS;
+  for new_block in new_blocks {
+    let Some(old_block) = old_by_id.get(new_block.block_id.as_str()) else {
+      diff.blocks.push(whole_block(new_block, "added", DiffOp::Insert));
+      diff.added += 1;
+      continue;
+    };
+
+    let (old_text, new_text) = (block_text(old_block), block_text(new_block));
+    let text = if old_text == new_text {
+      Vec::new()
+    } else {
+      diff_text(&old_text, &new_text, &mut budget)
+    };
+    let changed_properties = diff_properties(old_block, new_block);
+    if text.is_empty() && changed_properties.is_empty() {
+      continue;
+    }
+    diff.blocks.push(NativeBlockDiff {
+      block_id: new_block.block_id.clone(),
+      flavour: new_block.flavour.clone(),
+      kind: "modified".to_string(),
+      text,
+      changed_properties,
+    });
+    diff.modified += 1;
+  }
+  for old_block in old_blocks {
+    if !new_ids.contains(old_block.block_id.as_str()) {
+      diff.blocks.push(whole_block(old_block, "removed", DiffOp::Delete));
+      diff.removed += 1;
+    }
+  }
+  diff
+}
+
+fn diff_doc_binaries(old_bin: Vec<u8>, new_bin: Vec<u8>, doc_id: String) -> Result<NativeDocDiff> {
+  let old = map_napi_err(
+    doc_loader::parse_doc_from_binary(old_bin, doc_id.clone()),
+    Status::GenericFailure,
+  )?;
+  let new = map_napi_err(
+    doc_loader::parse_doc_from_binary(new_bin, doc_id),
+    Status::GenericFailure,
+  )?;
+  Ok(diff_blocks(&old.blocks, &new.blocks))
+}
+
+pub struct AsyncDiffDocVersions {
+  old_bin: Vec<u8>,
+  new_bin: Vec<u8>,
+  doc_id: String,
+}
+
+#[napi]
+impl Task for AsyncDiffDocVersions {
+  type Output = NativeDocDiff;
+  type JsValue = NativeDocDiff;
+
+  fn compute(&mut self) -> Result<Self::Output> {
+    diff_doc_binaries(
+      std::mem::take(&mut self.old_bin),
+      std::mem::take(&mut self.new_bin),
+      std::mem::take(&mut self.doc_id),
+    )
+  }
+
+  fn resolve(&mut self, _: Env, output: Self::Output) -> Result<Self::JsValue> {
+    Ok(output)
+  }
+}
+
+/// Compares two versions of a doc block by block, for highlighting what
+/// changed between two history points. Runs off the main thread.
+///
+/// # Arguments
+/// * `old_bin` - The earlier doc binary
+/// * `new_bin` - The later doc binary
+/// * `doc_id` - The document ID shared by both versions
+#[napi]
+pub fn diff_doc_versions(old_bin: Buffer, new_bin: Buffer, doc_id: String) -> AsyncTask<AsyncDiffDocVersions> {
+  AsyncTask::new(AsyncDiffDocVersions {
+    old_bin: old_bin.into(),
+    new_bin: new_bin.into(),
+    doc_id,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use y_octo::Doc;
+
+  use super::*;
+
+  fn segments(diff: &[NativeTextDiffSegment]) -> Vec<(&str, &str)> {
+    diff
+      .iter()
+      .map(|segment| (segment.op.as_str(), segment.text.as_str()))
+      .collect()
+  }
+
+  #[test]
+  fn text_diff_aligns_on_words() {
+    let diff_text = |old, new| diff_text(old, new, &mut { MAX_TEXT_DIFF_CELLS });
+    assert_eq!(
+      segments(&diff_text("the quick fox", "the slow brown fox")),
+      vec![
+        ("equal", "the "),
+        ("delete", "quick"),
+        ("insert", "slow brown"),
+        ("equal", " fox")
+      ]
+    );
+    assert_eq!(segments(&diff_text("", "new")), vec![("insert", "new")]);
+    assert_eq!(
+      segments(&diff_text("你好世界", "你们好世界")),
+      vec![("equal", "你"), ("insert", "们"), ("equal", "好世界")]
+    );
+  }
+
+  #[test]
+  fn doc_versions_report_modified_and_added_blocks() {
+    let doc_id = "doc-diff-test";
+    let old_bin = doc_loader::build_full_doc("Diff", "first paragraph\n\nsecond paragraph", doc_id).unwrap();
+    let delta = doc_loader::update_doc(
+      &old_bin,
+      "first paragraph edited\n\nsecond paragraph\n\nthird paragraph",
+      doc_id,
+    )
+    .unwrap();
+    let mut doc = Doc::default();
+    doc.apply_update_from_binary_v1(&old_bin).unwrap();
+    doc.apply_update_from_binary_v1(&delta).unwrap();
+    let new_bin = doc.encode_update_v1().unwrap();
+
+    let diff = diff_doc_binaries(old_bin, new_bin, doc_id.to_string()).unwrap();
+    assert!(diff.added + diff.modified > 0);
+    assert!(diff.blocks.iter().any(|block| {
+      block
+        .text
+        .iter()
+        .any(|segment| segment.op == "insert" && segment.text.contains("third"))
+    }));
+  }
+
+  #[test]
+  fn doc_versions_report_removed_blocks_and_property_changes() {
+    let doc_id = "doc-diff-test";
+    let bin = doc_loader::build_full_doc("Diff", "first paragraph\n\nsecond paragraph", doc_id).unwrap();
+    let parse = || {
+      doc_loader::parse_doc_from_binary(bin.clone(), doc_id.to_string())
+        .unwrap()
+        .blocks
+    };
+    let old = parse();
+    let mut new = parse();
+    let removed = new
+      .iter()
+      .position(|block| block_text(block) == "second paragraph")
+      .unwrap();
+    let removed_id = new.remove(removed).block_id;
+    let changed = new
+      .iter_mut()
+      .find(|block| block_text(block) == "first paragraph")
+      .unwrap();
+    changed.additional = Some(r#"{"type":"h1"}"#.to_string());
+    let changed_id = changed.block_id.clone();
+
+    let diff = diff_blocks(&old, &new);
+    assert_eq!((diff.added, diff.removed, diff.modified), (0, 1, 1));
+
+    let removed = diff.blocks.iter().find(|block| block.kind == "removed").unwrap();
+    assert_eq!(removed.block_id, removed_id);
+    assert_eq!(segments(&removed.text), vec![("delete", "second paragraph")]);
+
+    let modified = diff.blocks.iter().find(|block| block.kind == "modified").unwrap();
+    assert_eq!(modified.block_id, changed_id);
+    assert!(modified.text.is_empty());
+    assert!(
+      modified
+        .changed_properties
+        .iter()
+        .any(|change| { change.key == "additional.type" && change.new_value.as_deref() == Some("h1") })
+    );
+  }
+
+  #[test]
+  fn doc_diff_budget_falls_back_to_whole_text_replacement() {
+    let old = (0..100).map(|word| format!("a{word}")).collect::<Vec<_>>().join(" ");
+    let new = (0..100).map(|word| format!("b{word}")).collect::<Vec<_>>().join(" ");
+
+    let mut budget = usize::MAX;
+    assert!(diff_text(&old, &new, &mut budget).len() > 2);
+    assert!(budget < usize::MAX);
+
+    let mut budget = 10;
+    assert_eq!(
+      segments(&diff_text(&old, &new, &mut budget)),
+      vec![("delete", old.as_str()), ("insert", new.as_str())]
+    );
+    assert_eq!(budget, 10);
+  }
+}
@@ -0,0 +1,515 @@
+use std::collections::VecDeque;
+
+use affine_common::napi_utils::map_napi_err;
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use y_octo::{Any, Array, Doc, JwstCodecResult, Map, Text, TextAttributes, TextDeltaOp, TextInsert, Value};
+
+use crate::doc_diff::{DiffOp, diff_text_ops};
+
+/// Root types that hold page content. Everything a page shows lives under
+/// `blocks`, so restoring it restores the page.
+const RESTORED_ROOTS: &[&str] = &["blocks"];
+
+/// Stands in for an embed when diffing text, taking one unit like the embed.
+const EMBED_PLACEHOLDER: char = '\u{FFFC}';
+
+fn load_doc(bin: &[u8]) -> JwstCodecResult<Doc> {
+  let mut doc = Doc::default();
+  doc.apply_update_from_binary_v1(bin)?;
+  Ok(doc)
+}
+
+fn map_keys(map: &Map) -> Vec<String> {
+  map.keys().map(|key| key.to_string()).collect()
+}
+
+fn array_values(array: &Array) -> Vec<Value> {
+  (0..array.len()).filter_map(|index| array.get(index)).collect()
+}
+
+fn utf16_len(text: &str) -> u64 {
+  text.encode_utf16().count() as u64
+}
+
+/// Values that can be reconciled in place instead of being replaced.
+fn same_shape(current: &Value, target: &Value) -> bool {
+  match (current, target) {
+    (Value::Any(current), Value::Any(target)) => current == target,
+    (Value::Map(_), Value::Map(_)) | (Value::Array(_), Value::Array(_)) | (Value::Text(_), Value::Text(_)) => true,
+    _ => false,
+  }
+}
+
+/// Builds a new, unattached copy of `value` owned by `doc`.
+fn copy_value(doc: &Doc, value: &Value) -> JwstCodecResult<Value> {
+  Ok(match value {
+    Value::Map(source) => {
+      let mut map = doc.create_map()?;
+      for key in map_keys(source) {
+        if let Some(value) = source.get(&key) {
+          map.insert(key, copy_value(doc, &value)?)?;
+        }
+      }
+      Value::Map(map)
+    }
+    Value::Array(source) => {
+      let mut array = doc.create_array()?;
+      for value in array_values(source) {
+        array.push(copy_value(doc, &value)?)?;
+      }
+      Value::Array(array)
+    }
+    Value::Text(source) => {
+      let mut text = doc.create_text()?;
+      text.apply_delta(&source.to_delta())?;
+      Value::Text(text)
+    }
+    value => value.clone(),
+  })
+}
+
+fn reconcile_value(doc: &Doc, current: Value, target: &Value) -> JwstCodecResult<()> {
+  match (current, target) {
+    (Value::Map(mut current), Value::Map(target)) => reconcile_map(doc, &mut current, target),
+    (Value::Array(mut current), Value::Array(target)) => reconcile_array(doc, &mut current, target),
+    (Value::Text(mut current), Value::Text(target)) => reconcile_text(&mut current, target),
+    _ => Ok(()),
+  }
+}
+
+fn reconcile_map(doc: &Doc, current: &mut Map, target: &Map) -> JwstCodecResult<()> {
+  for key in map_keys(current) {
+    if target.get(&key).is_none() {
+      current.remove(&key);
+    }
+  }
+  for key in map_keys(target) {
+    let Some(target_value) = target.get(&key) else {
+      continue;
+    };
+    match current.get(&key) {
+      Some(current_value) if same_shape(&current_value, &target_value) => {
+        reconcile_value(doc, current_value, &target_value)?;
+      }
+      _ => {
+        current.insert(key, copy_value(doc, &target_value)?)?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// Keeps the matching head and tail of the array (reconciling nested types
+/// there) and replaces only the items in between, which covers the usual
+/// insert, remove and reorder of a few children.
+fn reconcile_array(doc: &Doc, current: &mut Array, target: &Array) -> JwstCodecResult<()> {
+  let current_values = array_values(current);
+  let target_values = array_values(target);
+  let prefix = current_values
+    .iter()
+    .zip(&target_values)
+    .take_while(|(current, target)| same_shape(current, target))
+    .count();
+  let suffix = current_values[prefix..]
+    .iter()
+    .rev()
+    .zip(target_values[prefix..].iter().rev())
+    .take_while(|(current, target)| same_shape(current, target))
+    .count();
+
+  for (current_value, target_value) in current_values[..prefix].iter().zip(&target_values).chain(
+    current_values[current_values.len() - suffix..]
+      .iter()
+      .zip(&target_values[target_values.len() - suffix..]),
+  ) {
+    reconcile_value(doc, current_value.clone(), target_value)?;
+  }
+
+  let removed = current_values.len() - prefix - suffix;
+  if removed > 0 {
+    current.remove(prefix as u64, removed as u64)?;
+  }
+  for (offset, target_value) in target_values[prefix..target_values.len() - suffix].iter().enumerate() {
+    current.insert((prefix + offset) as u64, copy_value(doc, target_value)?)?;
+  }
+  Ok(())
+}
+
+/// One insert of a text delta: a string or an embed, with its formatting.
+struct TextRun {
+  insert: TextInsert,
+  format: Option<TextAttributes>,
+}
+
+impl TextRun {
+  fn len(&self) -> u64 {
+    match &self.insert {
+      TextInsert::Text(text) => utf16_len(text),
+      TextInsert::Embed(_) => 1,
+    }
+  }
+
+  /// Splits off everything after the first `len` units. Embeds are one unit
+  /// long, so only strings are ever split.
+  fn split_off(&mut self, len: u64) -> Option<TextRun> {
+    let TextInsert::Text(text) = &mut self.insert else {
+      return None;
+    };
+    let mut units = 0;
+    let at = text
+      .char_indices()
+      .find(|(_, c)| {
+        let reached = units >= len;
+        units += c.len_utf16() as u64;
+        reached
+      })
+      .map_or(text.len(), |(index, _)| index);
+    (at < text.len()).then(|| TextRun {
+      insert: TextInsert::Text(text.split_off(at)),
+      format: self.format.clone(),
+    })
+  }
+}
+
+fn text_runs(text: &Text) -> VecDeque<TextRun> {
+  text
+    .to_delta()
+    .into_iter()
+    .filter_map(|op| match op {
+      TextDeltaOp::Insert { insert, format } => Some(TextRun { insert, format }),
+      _ => None,
+    })
+    .collect()
+}
+
+fn plain_text(runs: &VecDeque<TextRun>) -> String {
+  let mut plain = String::new();
+  for run in runs {
+    match &run.insert {
+      TextInsert::Text(text) => plain.push_str(text),
+      TextInsert::Embed(_) => plain.push(EMBED_PLACEHOLDER),
+    }
+  }
+  plain
+}
+
+/// Takes the runs covering the next `len` units.
+fn take_runs(runs: &mut VecDeque<TextRun>, mut len: u64) -> VecDeque<TextRun> {
+  let mut taken = VecDeque::new();
+  while len > 0
+    && let Some(mut run) = runs.pop_front()
+  {
+    if let Some(rest) = run.split_off(len) {
+      runs.push_front(rest);
+    }
+    len = len.saturating_sub(run.len());
+    taken.push_back(run);
+  }
+  taken
+}
+
+/// The attributes to apply over `current` to end up with `target`; removed
+/// attributes are cleared with `null`.
+fn format_change(current: Option<&TextAttributes>, target: Option<&TextAttributes>) -> Option<TextAttributes> {
+  let current = current.cloned().unwrap_or_default();
+  let target = target.cloned().unwrap_or_default();
+  if current == target {
+    return None;
+  }
+  let mut change = target
+    .iter()
+    .filter(|(key, value)| current.get(*key) != Some(*value))
+    .map(|(key, value)| (key.clone(), value.clone()))
+    .collect::<TextAttributes>();
+  for key in current.keys() {
+    if !target.contains_key(key) {
+      change.insert(key.clone(), Any::Null);
+    }
+  }
+  Some(change)
+}
+
+fn push_retain(delta: &mut Vec<TextDeltaOp>, len: u64, format: Option<TextAttributes>) {
+  if format.is_none()
+    && let Some(TextDeltaOp::Retain { retain, format: None }) = delta.last_mut()
+  {
+    *retain += len;
+    return;
+  }
+  delta.push(TextDeltaOp::Retain { retain: len, format });
+}
+
+/// Keeps runs whose text matched, re-applying any formatting that differs,
+/// and replaces embeds that changed.
+fn push_equal(delta: &mut Vec<TextDeltaOp>, mut current: VecDeque<TextRun>, mut target: VecDeque<TextRun>) {
+  while let (Some(mut current_run), Some(mut target_run)) = (current.pop_front(), target.pop_front()) {
+    let len = current_run.len().min(target_run.len());
+    if let Some(rest) = current_run.split_off(len) {
+      current.push_front(rest);
+    }
+    if let Some(rest) = target_run.split_off(len) {
+      target.push_front(rest);
+    }
+    let same_content = match (&current_run.insert, &target_run.insert) {
+      (TextInsert::Text(_), TextInsert::Text(_)) => true,
+      (TextInsert::Embed(current), TextInsert::Embed(target)) => current == target,
+      _ => false,
+    };
+    if same_content {
+      push_retain(
+        delta,
+        len,
+        format_change(current_run.format.as_ref(), target_run.format.as_ref()),
+      );
+    } else {
+      delta.push(TextDeltaOp::Delete { delete: len });
+      delta.push(TextDeltaOp::Insert {
+        insert: target_run.insert,
+        format: target_run.format,
+      });
+    }
+  }
+}
+
+/// Applies a word-level diff as a text delta, so unchanged runs keep their
+/// identity and get their formatting re-applied, and re-inserted runs come
+/// back with their attributes and embeds.
+fn reconcile_text(current: &mut Text, target: &Text) -> JwstCodecResult<()> {
+  let mut current_runs = text_runs(current);
+  let mut target_runs = text_runs(target);
+  let mut delta = Vec::new();
+  for (op, text) in diff_text_ops(&plain_text(&current_runs), &plain_text(&target_runs)) {
+    let len = utf16_len(&text);
+    match op {
+      DiffOp::Equal => push_equal(
+        &mut delta,
+        take_runs(&mut current_runs, len),
+        take_runs(&mut target_runs, len),
+      ),
+      DiffOp::Delete => {
+        take_runs(&mut current_runs, len);
+        delta.push(TextDeltaOp::Delete { delete: len });
+      }
+      DiffOp::Insert => {
+        for run in take_runs(&mut target_runs, len) {
+          delta.push(TextDeltaOp::Insert {
+            insert: run.insert,
+            format: run.format,
+          });
+        }
+      }
+    }
+  }
+
+  if let Some(TextDeltaOp::Retain { format: None, .. }) = delta.last() {
+    delta.pop();
+  }
+  if delta.is_empty() {
+    return Ok(());
+  }
+  current.apply_delta(&delta)
+}
+
+/// Computes an update that turns `current` into the content of `history`
+/// when applied, without replacing the snapshot. Edits are expressed as new
+/// CRDT operations on top of `current`, so they merge with concurrent
+/// collaborator updates like any other client's changes.
+pub(crate) fn restore_update(current: &[u8], history: &[u8]) -> JwstCodecResult<Vec<u8>> {
+  let doc = load_doc(current)?;
+  let history = load_doc(history)?;
+  let state_vector = doc.get_state_vector();
+
+  for root in RESTORED_ROOTS {
+    let target = history.get_or_create_map(root)?;
+    let mut current = doc.get_or_create_map(root)?;
+    reconcile_map(&doc, &mut current, &target)?;
+  }
+
+  doc.encode_state_as_update_v1(&state_vector)
+}
+
+/// Builds a forward update that restores a doc to a history version.
+///
+/// # Arguments
+/// * `current_bin` - The live doc binary
+/// * `history_bin` - The doc binary stored for the history point
+///
+/// # Returns
+/// A Buffer containing only the delta (changes) as a y-octo update binary,
+/// ready to be pushed like any other doc update
+#[napi]
+pub fn build_doc_restore_update(current_bin: Buffer, history_bin: Buffer) -> Result<Buffer> {
+  let result = map_napi_err(restore_update(&current_bin, &history_bin), Status::GenericFailure)?;
+  Ok(Buffer::from(result))
+}
+
+#[cfg(test)]
+mod tests {
+  use affine_doc_loader as doc_loader;
+
+  use super::*;
+
+  fn apply(bin: &[u8], update: &[u8]) -> Vec<u8> {
+    let mut doc = load_doc(bin).unwrap();
+    doc.apply_update_from_binary_v1(update).unwrap();
+    doc.encode_update_v1().unwrap()
+  }
+
+  fn markdown(bin: Vec<u8>, doc_id: &str) -> String {
+    doc_loader::parse_doc_to_markdown(bin, doc_id.to_string(), false, None)
+      .unwrap()
+      .markdown
+  }
+
+  #[test]
+  fn restore_update_brings_back_history_content() {
+    let doc_id = "doc-restore-test";
+    let history = doc_loader::build_full_doc("Restore", "first paragraph\n\nsecond paragraph", doc_id).unwrap();
+    let edit = doc_loader::update_doc(&history, "first paragraph changed\n\nthird paragraph", doc_id).unwrap();
+    let current = apply(&history, &edit);
+    assert_ne!(markdown(current.clone(), doc_id), markdown(history.clone(), doc_id));
+
+    let update = restore_update(&current, &history).unwrap();
+    let restored = apply(&current, &update);
+    assert_eq!(markdown(restored.clone(), doc_id), markdown(history.clone(), doc_id));
+
+    // Restoring again is a no-op once the content already matches.
+    let noop = restore_update(&restored, &history).unwrap();
+    assert_eq!(markdown(apply(&restored, &noop), doc_id), markdown(history, doc_id));
+  }
+
+  fn attrs(pairs: &[(&str, Any)]) -> Option<TextAttributes> {
+    Some(
+      pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.clone()))
+        .collect(),
+    )
+  }
+
+  fn insert(text: &str, format: Option<TextAttributes>) -> TextDeltaOp {
+    TextDeltaOp::Insert {
+      insert: TextInsert::Text(text.to_string()),
+      format,
+    }
+  }
+
+  fn paragraph_text(doc: &Doc, block_id: &str) -> Text {
+    let Some(Value::Map(block)) = doc.get_or_create_map("blocks").unwrap().get(block_id) else {
+      panic!("block {block_id} is missing");
+    };
+    let Some(Value::Text(text)) = block.get("prop:text") else {
+      panic!("block {block_id} has no text");
+    };
+    text
+  }
+
+  /// A doc with one paragraph holding bold, linked and embedded content.
+  fn formatted_doc() -> Vec<u8> {
+    let doc = Doc::default();
+    let mut blocks = doc.get_or_create_map("blocks").unwrap();
+    let mut block = doc.create_map().unwrap();
+    block.insert("sys:flavour".to_string(), "affine:paragraph").unwrap();
+    block
+      .insert("prop:text".to_string(), Value::Text(doc.create_text().unwrap()))
+      .unwrap();
+    blocks.insert("paragraph".to_string(), Value::Map(block)).unwrap();
+
+    let mention = Any::Object(
+      [("type".to_string(), Any::String("mention".to_string()))]
+        .into_iter()
+        .collect(),
+    );
+    paragraph_text(&doc, "paragraph")
+      .apply_delta(&[
+        insert("plain ", None),
+        insert("bold", attrs(&[("bold", Any::True)])),
+        insert(" and ", None),
+        insert(
+          "a link",
+          attrs(&[("link", Any::String("https://affine.pro".to_string()))]),
+        ),
+        TextDeltaOp::Insert {
+          insert: TextInsert::Embed(mention),
+          format: None,
+        },
+        insert(" tail", None),
+      ])
+      .unwrap();
+    doc.encode_update_v1().unwrap()
+  }
+
+  /// Edits the paragraph of `bin`: unbolds and rewords text, drops the link
+  /// and the embed.
+  fn edited_doc(bin: &[u8]) -> Vec<u8> {
+    let doc = load_doc(bin).unwrap();
+    let state_vector = doc.get_state_vector();
+    paragraph_text(&doc, "paragraph")
+      .apply_delta(&[
+        TextDeltaOp::Retain {
+          retain: 6,
+          format: None,
+        },
+        TextDeltaOp::Retain {
+          retain: 4,
+          format: attrs(&[("bold", Any::Null)]),
+        },
+        TextDeltaOp::Retain {
+          retain: 5,
+          format: None,
+        },
+        TextDeltaOp::Delete { delete: 7 },
+        insert("some text", attrs(&[("italic", Any::True)])),
+      ])
+      .unwrap();
+    apply(bin, &doc.encode_state_as_update_v1(&state_vector).unwrap())
+  }
+
+  #[test]
+  fn restore_update_brings_back_formatting_links_and_embeds() {
+    let history = formatted_doc();
+    let current = edited_doc(&history);
+    let history_delta = paragraph_text(&load_doc(&history).unwrap(), "paragraph").to_delta();
+    assert_ne!(
+      paragraph_text(&load_doc(&current).unwrap(), "paragraph").to_delta(),
+      history_delta
+    );
+
+    let restored = apply(&current, &restore_update(&current, &history).unwrap());
+    assert_eq!(
+      paragraph_text(&load_doc(&restored).unwrap(), "paragraph").to_delta(),
+      history_delta
+    );
+  }
+
+  #[test]
+  fn restore_update_merges_with_concurrent_collaborator_edits() {
+    let history = formatted_doc();
+    let current = edited_doc(&history);
+    let update = restore_update(&current, &history).unwrap();
+
+    // Another client edits the live doc after the restore update was built.
+    let collaborator = load_doc(&current).unwrap();
+    let state_vector = collaborator.get_state_vector();
+    let mut blocks = collaborator.get_or_create_map("blocks").unwrap();
+    let mut block = collaborator.create_map().unwrap();
+    block.insert("sys:flavour".to_string(), "affine:paragraph").unwrap();
+    blocks.insert("collaborator".to_string(), Value::Map(block)).unwrap();
+    let mut text = paragraph_text(&collaborator, "paragraph");
+    let end = text.len();
+    text.insert(end, " typed meanwhile").unwrap();
+    let concurrent = collaborator.encode_state_as_update_v1(&state_vector).unwrap();
+
+    let restore_first = apply(&apply(&current, &update), &concurrent);
+    let concurrent_first = apply(&apply(&current, &concurrent), &update);
+    for merged in [restore_first, concurrent_first] {
+      let doc = load_doc(&merged).unwrap();
+      assert!(doc.get_or_create_map("blocks").unwrap().get("collaborator").is_some());
+      let text = paragraph_text(&doc, "paragraph").to_string();
+      assert!(text.starts_with("plain bold and a link"));
+      assert!(text.ends_with(" typed meanwhile"));
+      assert!(!text.contains("some text"));
+    }
+  }
+}
@@ -27,6 +27,21 @@ const ONE_MB: i64 = 1024 * 1024;
 const ONE_GB: i64 = 1024 * ONE_MB;
 const ONE_DAY_SECONDS: i64 = 24 * 60 * 60;
 const MAX_SEAT_QUANTITY: i32 = 100_000;
+const MAX_PLAN_NAME_LEN: usize = 64;
+const DEFAULT_LICENSE_GRACE_PERIOD_DAYS: u32 = 14;
+const BUILTIN_PLANS: &[&str] = &[
+  "pro",
+  "lifetime_pro",
+  "ai",
+  "team",
+  "selfhost_team",
+  "selfhost_free",
+  "free",
+];
+
+/// Plans defined in app config, keyed by plan name. An entry named like a
+/// built-in plan replaces that plan's quotas.
+pub(crate) type PlanCatalog = HashMap<String, PlanDefinition>;
 
 #[napi(object)]
 pub struct ResolveEntitlementInput {
@@ -39,6 +54,12 @@ pub struct ResolveEntitlementInput {
   pub signed_payload: Option<Buffer>,
   pub public_key: Option<String>,
   pub license_aes_key: Option<String>,
+  /// The `entitlement.plans` config. An invalid catalog fails the call.
+  #[napi(ts_type = "Record<string, unknown>")]
+  pub plans: Option<Value>,
+  /// Keeps an expired license file valid, with status `grace`, for this many
+  /// days after it expires.
+  pub license_grace_period_days: Option<u32>,
   pub now: String,
 }
 
@@ -71,6 +92,43 @@ pub struct ResolvedEntitlement {
   pub flags: HashMap<String, bool>,
   pub error_code: Option<String>,
   pub error_message: Option<String>,
+  /// End of the grace period of an expired license file, when one applies.
+  pub grace_until: Option<String>,
+}
+
+#[napi(object)]
+pub struct OfflineLicenseInput {
+  pub signed_payload: Buffer,
+  /// The workspace the license must be issued for.
+  pub workspace_id: String,
+  /// Defaults to the public key embedded at build time.
+  pub public_key: Option<String>,
+  /// Defaults to the license AES key embedded at build time.
+  pub license_aes_key: Option<String>,
+  pub grace_period_days: Option<u32>,
+  /// Members currently occupying seats, used to report overuse.
+  pub seats_used: Option<u32>,
+  /// The latest `checkedAt` persisted by the caller. Verification never runs
+  /// at an earlier time, so winding the clock back cannot revive a license.
+  pub last_checked_at: Option<String>,
+  /// The `entitlement.plans` config; may override `selfhost_team` quotas.
+  #[napi(ts_type = "Record<string, unknown>")]
+  pub plans: Option<Value>,
+  pub now: String,
+}
+
+#[derive(Debug)]
+#[napi(object)]
+pub struct OfflineLicenseStatus {
+  /// One of `active`, `grace`, `expired` or `invalid`.
+  pub status: String,
+  pub valid: bool,
+  pub entitlement: ResolvedEntitlement,
+  pub checked_at: String,
+  pub grace_ends_at: Option<String>,
+  pub seat_limit: Option<i32>,
+  pub seats_used: Option<u32>,
+  pub seat_overuse: u32,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -101,8 +159,28 @@ struct LicenseData {
   end_at: String,
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct PlanDefinition {
+  pub(crate) blob_limit: i64,
+  /// Total storage, or the base allowance before per-seat storage when
+  /// `seatQuota` is set.
+  pub(crate) storage_quota: i64,
+  pub(crate) history_period: i64,
+  #[serde(default)]
+  pub(crate) member_limit: Option<i32>,
+  /// Makes the plan seat based: the member limit follows the purchased
+  /// quantity and each seat adds this much storage.
+  #[serde(default)]
+  pub(crate) seat_quota: Option<i64>,
+  #[serde(default)]
+  pub(crate) copilot_action_limit: Option<i32>,
+  #[serde(default)]
+  pub(crate) flags: HashMap<String, bool>,
+}
+
 struct PlanQuota {
-  name: &'static str,
+  name: String,
   blob_limit: i64,
   storage_quota: i64,
   history_period: i64,
@@ -110,18 +188,20 @@ struct PlanQuota {
   seat_quota: Option<i64>,
   copilot_action_limit: Option<i32>,
   unlimited_copilot: bool,
+  flags: HashMap<String, bool>,
 }
 
 #[napi]
 pub fn resolve_entitlement_v1(input: ResolveEntitlementInput) -> Result<ResolvedEntitlement> {
   validate_input(&input)?;
   let now = parse_time(&input.now)?;
+  let plans = parse_plan_catalog(input.plans.as_ref())?;
 
   if input.signed_payload.is_some() {
     if input.deployment_type != "selfhosted" || input.target_type != "workspace" {
       return invalid_arg("signedPayload is only supported for selfhosted workspace entitlements");
     }
-    return resolve_selfhost_license(input, now);
+    return resolve_selfhost_license(input, &plans, now);
   }
 
   let plan = input.plan.as_deref().unwrap_or_else(|| {
@@ -131,11 +211,71 @@ pub fn resolve_entitlement_v1(input: ResolveEntitlementInput) -> Result<Resolved
       "free"
     }
   });
-  if input.deployment_type == "selfhosted" && plan != "selfhost_free" {
+  if input.deployment_type == "selfhosted" && !is_unsigned_selfhost_plan(&plans, plan) {
     return invalid_arg("selfhosted commercial entitlements require signedPayload");
   }
   let quantity = parse_quantity(input.quantity.as_ref())?;
-  Ok(active(plan, quantity, None))
+  Ok(active(&plans, plan, quantity, None))
+}
+
+/// Verifies an imported license file entirely offline, applying the grace
+/// period after expiry and reporting seat overuse.
+#[napi]
+pub fn verify_offline_license_v1(input: OfflineLicenseInput) -> Result<OfflineLicenseStatus> {
+  let mut now = parse_time(&input.now)?;
+  if let Some(last_checked_at) = input.last_checked_at.as_deref() {
+    now = now.max(parse_time(last_checked_at)?);
+  }
+  let Some(public_key) = input.public_key.or(crate::AFFINE_PRO_PUBLIC_KEY.map(str::to_string)) else {
+    return invalid_arg("publicKey is required when no license public key is embedded");
+  };
+  let Some(license_aes_key) = input
+    .license_aes_key
+    .or(crate::AFFINE_PRO_LICENSE_AES_KEY.map(str::to_string))
+  else {
+    return invalid_arg("licenseAesKey is required when no license AES key is embedded");
+  };
+  let plans = parse_plan_catalog(input.plans.as_ref())?;
+
+  let entitlement = resolve_selfhost_license(
+    ResolveEntitlementInput {
+      deployment_type: "selfhosted".to_string(),
+      target_type: "workspace".to_string(),
+      target_id: Some(input.workspace_id),
+      plan: None,
+      quantity: None,
+      signed_payload: Some(input.signed_payload),
+      public_key: Some(public_key),
+      license_aes_key: Some(license_aes_key),
+      plans: None,
+      license_grace_period_days: Some(input.grace_period_days.unwrap_or(DEFAULT_LICENSE_GRACE_PERIOD_DAYS)),
+      now: now.to_rfc3339(),
+    },
+    &plans,
+    now,
+  )?;
+
+  let status = match entitlement.status.as_str() {
+    status @ ("active" | "grace" | "expired") => status,
+    _ => "invalid",
+  };
+
+  let seat_limit = entitlement.quota.seat_limit;
+  let seat_overuse = match (input.seats_used, seat_limit) {
+    (Some(used), Some(limit)) => used.saturating_sub(limit.max(0) as u32),
+    _ => 0,
+  };
+
+  Ok(OfflineLicenseStatus {
+    status: status.to_string(),
+    valid: entitlement.valid,
+    entitlement,
+    checked_at: now.to_rfc3339(),
+    grace_ends_at: entitlement.grace_until.clone(),
+    seat_limit,
+    seats_used: input.seats_used,
+    seat_overuse,
+  })
 }
 
 fn validate_input(input: &ResolveEntitlementInput) -> Result<()> {
@@ -149,6 +289,16 @@ fn validate_input(input: &ResolveEntitlementInput) -> Result<()> {
   Ok(())
 }
 
+fn parse_plan_catalog(plans: Option<&Value>) -> Result<PlanCatalog> {
+  let Some(plans) = plans else {
+    return Ok(PlanCatalog::new());
+  };
+  let plans = PlanCatalog::deserialize(plans)
+    .map_err(|err| NapiError::new(Status::InvalidArg, format!("invalid plans: {err}")))?;
+  validate_plan_catalog(&plans).map_err(|err| NapiError::new(Status::InvalidArg, format!("invalid plans: {err}")))?;
+  Ok(plans)
+}
+
 fn parse_quantity(quantity: Option<&Value>) -> Result<Option<i32>> {
   let Some(quantity) = quantity else {
     return Ok(None);
@@ -162,9 +312,13 @@ fn parse_quantity(quantity: Option<&Value>) -> Result<Option<i32>> {
   Ok(Some(quantity as i32))
 }
 
-fn resolve_selfhost_license(input: ResolveEntitlementInput, now: DateTime<Utc>) -> Result<ResolvedEntitlement> {
+fn resolve_selfhost_license(
+  input: ResolveEntitlementInput,
+  plans: &PlanCatalog,
+  now: DateTime<Utc>,
+) -> Result<ResolvedEntitlement> {
   let Some(payload) = input.signed_payload else {
-    return Ok(active("selfhost_free", None, None));
+    return Ok(active(plans, "selfhost_free", None, None));
   };
   let Some(public_key) = input.public_key else {
     return invalid_arg("publicKey is required for signed payload verification");
@@ -177,59 +331,81 @@ fn resolve_selfhost_license(input: ResolveEntitlementInput, now: DateTime<Utc>)
     .and_then(|decrypted| verify_license(&decrypted, &public_key))
   {
     Ok(payload) => payload,
-    Err((code, message)) => return Ok(invalid_license(code, message)),
+    Err((code, message)) => return Ok(invalid_license(plans, code, message)),
   };
 
   if let Err((code, message)) = validate_license_payload(&payload) {
-    return Ok(invalid_license(code, message));
+    return Ok(invalid_license(plans, code, message));
   }
 
   if payload.data.plan != "selfhostedteam" {
-    return Ok(invalid_license("invalid_payload", "license plan is not selfhostedteam"));
+    return Ok(invalid_license(
+      plans,
+      "invalid_payload",
+      "license plan is not selfhostedteam",
+    ));
   }
 
   if let Some(target_id) = input.target_id.as_deref()
     && target_id != payload.data.workspace_id.as_str()
   {
     return Ok(invalid_license(
+      plans,
       "workspace_mismatch",
       "workspace mismatched with license",
     ));
   }
 
   if payload.issued_at.is_empty() || payload.entity.is_empty() || payload.issuer.is_empty() {
-    return Ok(invalid_license("invalid_payload", "license payload is incomplete"));
+    return Ok(invalid_license(
+      plans,
+      "invalid_payload",
+      "license payload is incomplete",
+    ));
   }
 
   let file_expires_at = match parse_time(&payload.expires_at) {
     Ok(time) => time,
-    Err(_) => return Ok(invalid_license("invalid_payload", "invalid expiresAt")),
+    Err(_) => return Ok(invalid_license(plans, "invalid_payload", "invalid expiresAt")),
   };
   let license_expires_at = match parse_time(&payload.data.end_at) {
     Ok(time) => time,
-    Err(_) => return Ok(invalid_license("invalid_payload", "invalid endAt")),
+    Err(_) => return Ok(invalid_license(plans, "invalid_payload", "invalid endAt")),
   };
 
   let expires_at = file_expires_at.min(license_expires_at);
   if expires_at < now {
     let mut entitlement = expired(
+      plans,
       "selfhost_team",
       Some(payload.data.quantity),
       Some(expires_at.to_rfc3339()),
     );
-    entitlement.error_code = Some(
-      if license_expires_at < now && license_expires_at <= file_expires_at {
-        "expired_end_at"
-      } else {
-        "expired"
-      }
-      .to_string(),
-    );
+    let grace_until = input
+      .license_grace_period_days
+      .map(|days| expires_at + chrono::Duration::days(days.into()));
+    entitlement.grace_until = grace_until.map(|grace_until| grace_until.to_rfc3339());
+    if grace_until.is_some_and(|grace_until| now < grace_until) {
+      entitlement.valid = true;
+      entitlement.status = "grace".to_string();
+      entitlement.error_code = Some("grace_period".to_string());
+      entitlement.error_message = Some("license expired and is within its grace period".to_string());
+    } else {
+      entitlement.error_code = Some(
+        if license_expires_at < now && license_expires_at <= file_expires_at {
+          "expired_end_at"
+        } else {
+          "expired"
+        }
+        .to_string(),
+      );
+    }
     fill_license_metadata(&mut entitlement, &payload);
     return Ok(entitlement);
   }
 
   let mut entitlement = active(
+    plans,
     "selfhost_team",
     Some(payload.data.quantity),
     Some(expires_at.to_rfc3339()),
@@ -319,11 +495,11 @@ fn verify_license(decrypted: &(Vec<u8>, Vec<u8>), public_key: &str) -> LicenseRe
   serde_json::from_str::<LicensePayload>(&envelope.payload).map_err(|_| ("invalid_payload", "invalid license payload"))
 }
 
-fn active(plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> ResolvedEntitlement {
-  let quantity = quantity_for_plan(plan, quantity);
-  let catalog = plan_catalog(plan, quantity);
+fn active(plans: &PlanCatalog, plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> ResolvedEntitlement {
+  let quantity = quantity_for_plan(plans, plan, quantity);
+  let catalog = plan_catalog(plans, plan, quantity);
   ResolvedEntitlement {
-    plan: catalog.name.to_string(),
+    plan: catalog.name.clone(),
     valid: true,
     status: "active".to_string(),
     quantity,
@@ -338,14 +514,15 @@ fn active(plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> Reso
     flags: flags(&catalog),
     error_code: None,
     error_message: None,
+    grace_until: None,
   }
 }
 
-fn expired(plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> ResolvedEntitlement {
-  let quantity = quantity_for_plan(plan, quantity);
-  let catalog = plan_catalog(plan, quantity);
+fn expired(plans: &PlanCatalog, plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> ResolvedEntitlement {
+  let quantity = quantity_for_plan(plans, plan, quantity);
+  let catalog = plan_catalog(plans, plan, quantity);
   ResolvedEntitlement {
-    plan: catalog.name.to_string(),
+    plan: catalog.name.clone(),
     valid: false,
     status: "expired".to_string(),
     quantity,
@@ -360,13 +537,14 @@ fn expired(plan: &str, quantity: Option<i32>, expires_at: Option<String>) -> Res
     flags: flags(&catalog),
     error_code: Some("expired".to_string()),
     error_message: Some("license expired".to_string()),
+    grace_until: None,
   }
 }
 
-fn invalid_license(code: &'static str, message: &'static str) -> ResolvedEntitlement {
-  let catalog = plan_catalog("selfhost_free", None);
+fn invalid_license(plans: &PlanCatalog, code: &'static str, message: &'static str) -> ResolvedEntitlement {
+  let catalog = plan_catalog(plans, "selfhost_free", None);
   ResolvedEntitlement {
-    plan: catalog.name.to_string(),
+    plan: catalog.name.clone(),
     valid: false,
     status: "needs_reupload".to_string(),
     quantity: None,
@@ -381,22 +559,102 @@ fn invalid_license(code: &'static str, message: &'static str) -> ResolvedEntitle
     flags: flags(&catalog),
     error_code: Some(code.to_string()),
     error_message: Some(message.to_string()),
+    grace_until: None,
   }
 }
 
-fn quantity_for_plan(plan: &str, quantity: Option<i32>) -> Option<i32> {
-  if matches!(plan, "team" | "selfhost_team") {
-    quantity
-  } else {
-    None
+pub(crate) fn validate_plan_catalog(plans: &PlanCatalog) -> std::result::Result<(), String> {
+  for (name, plan) in plans {
+    if name.is_empty()
+      || name.len() > MAX_PLAN_NAME_LEN
+      || !name
+        .chars()
+        .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_' || ch == '-')
+    {
+      return Err(format!(
+        "plan name {name:?} must be 1-{MAX_PLAN_NAME_LEN} lowercase letters, digits, '_' or '-'"
+      ));
+    }
+    if plan.blob_limit <= 0 {
+      return Err(format!("plan {name} blobLimit must be positive"));
+    }
+    if plan.storage_quota < 0 || (plan.seat_quota.is_none() && plan.storage_quota == 0) {
+      return Err(format!("plan {name} storageQuota must be positive"));
+    }
+    if plan.history_period <= 0 {
+      return Err(format!("plan {name} historyPeriod must be positive"));
+    }
+    if plan.member_limit.is_some_and(|limit| limit <= 0) {
+      return Err(format!("plan {name} memberLimit must be positive"));
+    }
+    if let Some(seat_quota) = plan.seat_quota {
+      if seat_quota <= 0 {
+        return Err(format!("plan {name} seatQuota must be positive"));
+      }
+      if plan.member_limit.is_some() {
+        return Err(format!(
+          "plan {name} cannot set memberLimit because seat based plans follow the purchased quantity"
+        ));
+      }
+    }
+    if plan.copilot_action_limit.is_some_and(|limit| limit < 0) {
+      return Err(format!("plan {name} copilotActionLimit must not be negative"));
+    }
   }
+  Ok(())
 }
 
-fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
+/// Selfhosted instances may use configured plans without a license, except
+/// for names of built-in commercial plans which stay license gated.
+fn is_unsigned_selfhost_plan(plans: &PlanCatalog, plan: &str) -> bool {
+  plan == "selfhost_free" || (!BUILTIN_PLANS.contains(&plan) && plans.contains_key(plan))
+}
+
+fn quantity_for_plan(plans: &PlanCatalog, plan: &str, quantity: Option<i32>) -> Option<i32> {
+  let seat_based = match plans.get(plan) {
+    Some(definition) => definition.seat_quota.is_some(),
+    None => matches!(plan, "team" | "selfhost_team"),
+  };
+  if seat_based { quantity } else { None }
+}
+
+fn plan_catalog(plans: &PlanCatalog, plan: &str, quantity: Option<i32>) -> PlanQuota {
+  match plans.get(plan) {
+    Some(definition) => configured_plan_quota(plan, definition, quantity),
+    None => builtin_plan_catalog(plan, quantity),
+  }
+}
+
+fn configured_plan_quota(name: &str, plan: &PlanDefinition, quantity: Option<i32>) -> PlanQuota {
+  let (storage_quota, member_limit) = match plan.seat_quota {
+    Some(seat_quota) => {
+      let seats = quantity.unwrap_or(1);
+      let storage_quota = (seats as i64)
+        .checked_mul(seat_quota)
+        .and_then(|storage| storage.checked_add(plan.storage_quota))
+        .unwrap_or(i64::MAX);
+      (storage_quota, Some(seats))
+    }
+    None => (plan.storage_quota, plan.member_limit),
+  };
+  PlanQuota {
+    name: name.to_string(),
+    blob_limit: plan.blob_limit,
+    storage_quota,
+    history_period: plan.history_period,
+    member_limit,
+    seat_quota: plan.seat_quota,
+    copilot_action_limit: plan.copilot_action_limit,
+    unlimited_copilot: plan.flags.get("unlimitedCopilot").copied().unwrap_or(false),
+    flags: plan.flags.clone(),
+  }
+}
+
+fn builtin_plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
   let seats = quantity.unwrap_or(1);
   match plan {
     "pro" => PlanQuota {
-      name: "pro",
+      name: "pro".to_string(),
       blob_limit: 100 * ONE_MB,
       storage_quota: 100 * ONE_GB,
       history_period: 30 * ONE_DAY_SECONDS,
@@ -404,9 +662,10 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
       seat_quota: None,
       copilot_action_limit: Some(10),
       unlimited_copilot: false,
+      flags: HashMap::new(),
     },
     "lifetime_pro" => PlanQuota {
-      name: "lifetime_pro",
+      name: "lifetime_pro".to_string(),
       blob_limit: 100 * ONE_MB,
       storage_quota: 1024 * ONE_GB,
       history_period: 30 * ONE_DAY_SECONDS,
@@ -414,9 +673,10 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
       seat_quota: None,
       copilot_action_limit: Some(10),
       unlimited_copilot: false,
+      flags: HashMap::new(),
     },
     "ai" => PlanQuota {
-      name: "ai",
+      name: "ai".to_string(),
       blob_limit: 10 * ONE_MB,
       storage_quota: 10 * ONE_GB,
       history_period: 7 * ONE_DAY_SECONDS,
@@ -424,6 +684,7 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
       seat_quota: None,
       copilot_action_limit: None,
       unlimited_copilot: true,
+      flags: HashMap::new(),
     },
     "team" | "selfhost_team" => {
       let seat_quota = 20 * ONE_GB;
@@ -432,7 +693,7 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
         .and_then(|storage| storage.checked_add(100 * ONE_GB))
         .unwrap_or(i64::MAX);
       PlanQuota {
-        name: if plan == "team" { "team" } else { "selfhost_team" },
+        name: if plan == "team" { "team" } else { "selfhost_team" }.to_string(),
         blob_limit: 500 * ONE_MB,
         storage_quota,
         history_period: 30 * ONE_DAY_SECONDS,
@@ -440,10 +701,11 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
         seat_quota: Some(seat_quota),
         copilot_action_limit: None,
         unlimited_copilot: false,
+        flags: HashMap::new(),
       }
     }
     "selfhost_free" => PlanQuota {
-      name: "selfhost_free",
+      name: "selfhost_free".to_string(),
       blob_limit: 100 * ONE_MB,
       storage_quota: 100 * ONE_GB,
       history_period: 30 * ONE_DAY_SECONDS,
@@ -451,9 +713,10 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
       seat_quota: None,
       copilot_action_limit: Some(10),
       unlimited_copilot: false,
+      flags: HashMap::new(),
     },
     _ => PlanQuota {
-      name: "free",
+      name: "free".to_string(),
       blob_limit: 10 * ONE_MB,
       storage_quota: 10 * ONE_GB,
       history_period: 7 * ONE_DAY_SECONDS,
@@ -461,6 +724,7 @@ fn plan_catalog(plan: &str, quantity: Option<i32>) -> PlanQuota {
       seat_quota: None,
       copilot_action_limit: Some(10),
       unlimited_copilot: false,
+      flags: HashMap::new(),
     },
   }
 }
@@ -477,7 +741,7 @@ fn quota(catalog: &PlanQuota) -> ResolvedQuota {
 }
 
 fn flags(catalog: &PlanQuota) -> HashMap<String, bool> {
-  let mut flags = HashMap::new();
+  let mut flags = catalog.flags.clone();
   flags.insert("unlimitedCopilot".to_string(), catalog.unlimited_copilot);
   flags
 }
@@ -514,6 +778,8 @@ Hc3w7v4FGmoA5MNzzhrkho1ckDYw2wrX6zBnehFzcivURv80HherE2GQjg==\n\
       signed_payload: None,
       public_key: None,
       license_aes_key: None,
+      plans: None,
+      license_grace_period_days: None,
       now: "2026-05-14T00:00:00Z".to_string(),
     }
   }
@@ -531,6 +797,8 @@ Hc3w7v4FGmoA5MNzzhrkho1ckDYw2wrX6zBnehFzcivURv80HherE2GQjg==\n\
       signed_payload: Some(std::fs::read(fixture).unwrap().into()),
       public_key: Some(TEST_PUBLIC_KEY.to_string()),
       license_aes_key: Some(TEST_LICENSE_AES_KEY.to_string()),
+      plans: None,
+      license_grace_period_days: None,
       now: "2026-05-14T00:00:00Z".to_string(),
     }
   }
@@ -610,6 +878,119 @@ Hc3w7v4FGmoA5MNzzhrkho1ckDYw2wrX6zBnehFzcivURv80HherE2GQjg==\n\
     }
   }
 
+  fn plan_definition(value: Value) -> PlanDefinition {
+    serde_json::from_value(value).unwrap()
+  }
+
+  #[test]
+  fn resolves_configured_plans_before_builtins() {
+    let plans = serde_json::json!({
+      "auditor": {
+        "blobLimit": 5 * ONE_MB,
+        "storageQuota": 2 * ONE_GB,
+        "historyPeriod": ONE_DAY_SECONDS,
+        "memberLimit": 50,
+        "copilotActionLimit": 0,
+        "flags": { "readOnly": true },
+      },
+      "seats": {
+        "blobLimit": 50 * ONE_MB,
+        "storageQuota": 10 * ONE_GB,
+        "historyPeriod": 14 * ONE_DAY_SECONDS,
+        "seatQuota": ONE_GB,
+        "flags": { "unlimitedCopilot": true },
+      },
+    });
+    let with_plans = |plan: &str, quantity: Option<i32>| {
+      let mut input = input(Some(plan), quantity);
+      input.plans = Some(plans.clone());
+      input
+    };
+
+    let mut auditor = with_plans("auditor", Some(7));
+    auditor.deployment_type = "selfhosted".to_string();
+    let resolved = resolve_entitlement_v1(auditor).unwrap();
+    assert_eq!(resolved.plan, "auditor");
+    assert_eq!(resolved.quantity, None);
+    assert_eq!(resolved.quota.seat_limit, Some(50));
+    assert_eq!(resolved.quota.copilot_action_limit, Some(0));
+    assert_eq!(resolved.flags.get("readOnly"), Some(&true));
+    assert_eq!(resolved.flags.get("unlimitedCopilot"), Some(&false));
+
+    let resolved = resolve_entitlement_v1(with_plans("seats", Some(4))).unwrap();
+    assert_eq!(resolved.quantity, Some(4));
+    assert_eq!(resolved.quota.seat_limit, Some(4));
+    assert_eq!(resolved.quota.storage_quota, 14 * ONE_GB);
+    assert_eq!(resolved.flags.get("unlimitedCopilot"), Some(&true));
+
+    let resolved = resolve_entitlement_v1(with_plans("unknown", None)).unwrap();
+    assert_eq!(resolved.plan, "free");
+
+    let resolved = resolve_entitlement_v1(input(Some("auditor"), None)).unwrap();
+    assert_eq!(resolved.plan, "free", "plans are only those passed with the call");
+
+    let mut invalid = with_plans("auditor", None);
+    invalid.plans = Some(serde_json::json!({ "auditor": { "blobLimit": 0, "storageQuota": 1, "historyPeriod": 1 } }));
+    let err = resolve_entitlement_v1(invalid).unwrap_err();
+    assert_eq!(err.status, Status::InvalidArg);
+  }
+
+  #[test]
+  fn configured_plan_may_override_builtin_quota() {
+    let plan = plan_definition(serde_json::json!({
+      "blobLimit": ONE_GB,
+      "storageQuota": 50 * ONE_GB,
+      "historyPeriod": 90 * ONE_DAY_SECONDS,
+      "seatQuota": 100 * ONE_GB,
+    }));
+    let catalog = configured_plan_quota("selfhost_team", &plan, Some(3));
+    assert_eq!(catalog.name, "selfhost_team");
+    assert_eq!(catalog.storage_quota, 350 * ONE_GB);
+    assert_eq!(catalog.member_limit, Some(3));
+    let plans = PlanCatalog::from([("selfhost_team".to_string(), plan)]);
+    assert!(!is_unsigned_selfhost_plan(&plans, "selfhost_team"));
+  }
+
+  #[test]
+  fn rejects_invalid_plan_catalog() {
+    let valid = serde_json::json!({
+      "blobLimit": ONE_MB,
+      "storageQuota": ONE_GB,
+      "historyPeriod": ONE_DAY_SECONDS,
+    });
+    assert!(
+      validate_plan_catalog(&PlanCatalog::from([(
+        "custom".to_string(),
+        plan_definition(valid.clone())
+      )]))
+      .is_ok()
+    );
+
+    let mut cases = vec![("Custom Plan".to_string(), valid.clone())];
+    for (field, value) in [
+      ("blobLimit", serde_json::json!(0)),
+      ("storageQuota", serde_json::json!(0)),
+      ("historyPeriod", serde_json::json!(-1)),
+      ("memberLimit", serde_json::json!(0)),
+      ("seatQuota", serde_json::json!(0)),
+      ("copilotActionLimit", serde_json::json!(-1)),
+    ] {
+      let mut plan = valid.clone();
+      plan[field] = value;
+      cases.push((format!("invalid_{field}"), plan));
+    }
+    let mut seat_plan = valid.clone();
+    seat_plan["seatQuota"] = serde_json::json!(ONE_GB);
+    seat_plan["memberLimit"] = serde_json::json!(10);
+    cases.push(("seats_with_member_limit".to_string(), seat_plan));
+
+    for (name, plan) in cases {
+      let catalog = PlanCatalog::from([(name.clone(), plan_definition(plan))]);
+      assert!(validate_plan_catalog(&catalog).is_err(), "{name}");
+    }
+    assert!(serde_json::from_value::<PlanDefinition>(serde_json::json!({ "blobLimit": 1 })).is_err());
+  }
+
   #[test]
   fn rejects_unsigned_selfhosted_commercial_entitlements() {
     for plan in ["pro", "lifetime_pro", "ai", "team", "selfhost_team"] {
@@ -692,6 +1073,77 @@ Hc3w7v4FGmoA5MNzzhrkho1ckDYw2wrX6zBnehFzcivURv80HherE2GQjg==\n\
     }
   }
 
+  #[test]
+  fn applies_license_grace_period_only_when_requested() {
+    let mut input = license_input("expired.license", TEST_WORKSPACE_ID);
+    input.now = "2025-06-01T00:00:00Z".to_string();
+    let resolved = resolve_entitlement_v1(input).unwrap();
+    assert_eq!(resolved.status, "expired");
+    assert_eq!(resolved.grace_until, None);
+
+    let mut input = license_input("expired.license", TEST_WORKSPACE_ID);
+    input.now = "2025-06-01T00:00:00Z".to_string();
+    input.license_grace_period_days = Some(14);
+    let resolved = resolve_entitlement_v1(input).unwrap();
+    assert!(resolved.valid);
+    assert_eq!(resolved.status, "grace");
+    assert_eq!(resolved.error_code.as_deref(), Some("grace_period"));
+    assert_eq!(resolved.grace_until.as_deref(), Some("2025-06-10T08:57:54.092+00:00"));
+  }
+
+  fn offline_input(file: &str, now: &str) -> OfflineLicenseInput {
+    let input = license_input(file, TEST_WORKSPACE_ID);
+    OfflineLicenseInput {
+      signed_payload: input.signed_payload.unwrap(),
+      workspace_id: input.target_id.unwrap(),
+      public_key: input.public_key,
+      license_aes_key: input.license_aes_key,
+      grace_period_days: None,
+      seats_used: None,
+      last_checked_at: None,
+      plans: None,
+      now: now.to_string(),
+    }
+  }
+
+  #[test]
+  fn verifies_offline_license_with_grace_period() {
+    let mut input = offline_input("valid.license", "2026-05-14T00:00:00Z");
+    input.seats_used = Some(23);
+    let status = verify_offline_license_v1(input).unwrap();
+    assert_eq!(status.status, "active");
+    assert!(status.valid);
+    assert_eq!(status.seat_limit, Some(20));
+    assert_eq!(status.seat_overuse, 3);
+    assert_eq!(status.grace_ends_at, None);
+
+    let status = verify_offline_license_v1(offline_input("expired.license", "2025-06-01T00:00:00Z")).unwrap();
+    assert_eq!(status.status, "grace");
+    assert!(status.valid);
+    assert_eq!(status.entitlement.status, "grace");
+    assert_eq!(status.entitlement.error_code.as_deref(), Some("grace_period"));
+    assert_eq!(status.entitlement.plan, "selfhost_team");
+    assert_eq!(status.grace_ends_at.as_deref(), Some("2025-06-10T08:57:54.092+00:00"));
+
+    let mut input = offline_input("expired.license", "2025-06-01T00:00:00Z");
+    input.grace_period_days = Some(1);
+    let status = verify_offline_license_v1(input).unwrap();
+    assert_eq!(status.status, "expired");
+    assert!(!status.valid);
+
+    let mut input = offline_input("expired.license", "2025-06-01T00:00:00Z");
+    input.last_checked_at = Some("2026-01-01T00:00:00Z".to_string());
+    let status = verify_offline_license_v1(input).unwrap();
+    assert_eq!(status.status, "expired");
+    assert_eq!(status.checked_at, "2026-01-01T00:00:00+00:00");
+
+    let mut input = offline_input("valid.license", "2026-05-14T00:00:00Z");
+    input.workspace_id = "other-workspace".to_string();
+    let status = verify_offline_license_v1(input).unwrap();
+    assert_eq!(status.status, "invalid");
+    assert_eq!(status.entitlement.error_code.as_deref(), Some("workspace_mismatch"));
+  }
+
   #[test]
   fn verifies_signature_branch() {
     let (iv, decrypted) = decrypted_license("valid.license");
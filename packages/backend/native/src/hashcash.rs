@@ -1,4 +1,8 @@
-use std::convert::TryFrom;
+use std::{
+  collections::{BTreeMap, BTreeSet, HashMap},
+  convert::TryFrom,
+  sync::{LazyLock, Mutex},
+};
 
 use affine_common::hashcash::Stamp;
 use napi::{Env, Result as NapiResult, Task, bindgen_prelude::AsyncTask};
@@ -64,3 +68,121 @@ impl Task for AsyncMintChallengeResponse {
 pub fn mint_challenge_response(resource: String, bits: Option<u32>) -> AsyncTask<AsyncMintChallengeResponse> {
   AsyncTask::new(AsyncMintChallengeResponse { bits, resource })
 }
+
+const MEMORY_REPLAY_CAPACITY: usize = 65_536;
+
+static MEMORY_REPLAY: LazyLock<Mutex<StampReplayCache>> =
+  LazyLock::new(|| Mutex::new(StampReplayCache::new(MEMORY_REPLAY_CAPACITY)));
+
+/// Remembers accepted stamps until they expire so each one verifies only
+/// once. Used when the runtime has no database to record them in. When full,
+/// expired stamps are dropped first, then the least recently seen one.
+pub(crate) struct StampReplayCache {
+  capacity: usize,
+  tick: u64,
+  entries: HashMap<String, ReplayEntry>,
+  by_expiry: BTreeSet<(i64, String)>,
+  by_use: BTreeMap<u64, String>,
+}
+
+struct ReplayEntry {
+  expires_at_ms: i64,
+  last_seen: u64,
+}
+
+impl StampReplayCache {
+  pub(crate) fn new(capacity: usize) -> Self {
+    Self {
+      capacity: capacity.max(1),
+      tick: 0,
+      entries: HashMap::new(),
+      by_expiry: BTreeSet::new(),
+      by_use: BTreeMap::new(),
+    }
+  }
+
+  /// Records `key` until `expires_at_ms`, returning `false` if it is already
+  /// recorded and not yet expired.
+  pub(crate) fn insert_if_absent(&mut self, key: &str, expires_at_ms: i64, now_ms: i64) -> bool {
+    self.tick += 1;
+    if let Some(entry) = self.entries.get_mut(key)
+      && entry.expires_at_ms > now_ms
+    {
+      self.by_use.remove(&entry.last_seen);
+      entry.last_seen = self.tick;
+      self.by_use.insert(self.tick, key.to_string());
+      return false;
+    }
+    self.remove(key);
+
+    while let Some((oldest_expiry, _)) = self.by_expiry.first()
+      && *oldest_expiry <= now_ms
+    {
+      let (_, expired) = self.by_expiry.pop_first().expect("checked above");
+      self.remove(&expired);
+    }
+    while self.entries.len() >= self.capacity {
+      let Some((_, least_recent)) = self.by_use.pop_first() else {
+        break;
+      };
+      self.remove(&least_recent);
+    }
+
+    self.entries.insert(
+      key.to_string(),
+      ReplayEntry {
+        expires_at_ms,
+        last_seen: self.tick,
+      },
+    );
+    self.by_expiry.insert((expires_at_ms, key.to_string()));
+    self.by_use.insert(self.tick, key.to_string());
+    true
+  }
+
+  fn remove(&mut self, key: &str) {
+    if let Some(entry) = self.entries.remove(key) {
+      self.by_expiry.remove(&(entry.expires_at_ms, key.to_string()));
+      self.by_use.remove(&entry.last_seen);
+    }
+  }
+}
+
+pub(crate) fn record_stamp_in_memory(key: &str, expires_at_ms: i64, now_ms: i64) -> bool {
+  MEMORY_REPLAY
+    .lock()
+    .unwrap_or_else(|poisoned| poisoned.into_inner())
+    .insert_if_absent(key, expires_at_ms, now_ms)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn replay_cache_rejects_until_expiry_and_evicts_least_recent() {
+    let mut cache = StampReplayCache::new(2);
+    assert!(cache.insert_if_absent("a", 100, 0));
+    assert!(!cache.insert_if_absent("a", 100, 50));
+    assert!(cache.insert_if_absent("a", 200, 100));
+
+    assert!(cache.insert_if_absent("b", 300, 100));
+    assert!(!cache.insert_if_absent("a", 200, 120), "a replay refreshes a");
+    assert!(cache.insert_if_absent("c", 300, 150));
+    assert!(!cache.insert_if_absent("a", 200, 150), "b was least recently seen");
+    assert!(cache.insert_if_absent("b", 300, 150));
+  }
+
+  #[test]
+  fn replay_cache_evicts_expired_entries_first() {
+    let mut cache = StampReplayCache::new(2);
+    assert!(cache.insert_if_absent("live", 1_000, 0));
+    assert!(cache.insert_if_absent("short", 100, 0));
+    assert!(!cache.insert_if_absent("live", 1_000, 50));
+
+    assert!(cache.insert_if_absent("new", 1_000, 200));
+    assert!(!cache.insert_if_absent("live", 1_000, 200), "live entries survive");
+    assert!(!cache.insert_if_absent("new", 1_000, 200));
+    assert_eq!(cache.entries.len(), 2);
+  }
+}
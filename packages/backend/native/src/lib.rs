@@ -5,7 +5,9 @@ mod utils;
 pub mod auth_session;
 pub mod content_policy;
 pub mod doc;
+pub mod doc_diff;
 pub mod doc_loader;
+pub mod doc_restore;
 pub mod entitlement;
 pub mod file_type;
 pub mod hashcash;
@@ -146,6 +146,20 @@ pub(super) fn doc_actions_for_role(role: DocRole) -> BTreeSet<String> {
   }
 }
 
+/// Every action a role can be granted, i.e. everything `role_matrix_json`
+/// lists for the scope.
+pub(super) fn known_workspace_actions() -> BTreeSet<String> {
+  let mut actions = workspace_actions_for_role(WorkspaceRole::Owner);
+  actions.insert(WORKSPACE_PREVIEW_ACTION.to_string());
+  actions
+}
+
+pub(super) fn known_doc_actions() -> BTreeSet<String> {
+  let mut actions = doc_actions_for_role(DocRole::Owner);
+  actions.insert(DOC_PREVIEW_ACTION.to_string());
+  actions
+}
+
 pub(super) fn is_write_action(action: &str) -> bool {
   WORKSPACE_WRITE_ACTIONS.contains(&action) || DOC_WRITE_ACTIONS.contains(&action)
 }
@@ -233,6 +247,25 @@ pub(super) fn role_matrix_json() -> Value {
 mod tests {
   use super::*;
 
+  #[test]
+  fn known_actions_match_role_matrix() {
+    let artifact = role_matrix_json();
+    for (scope, known, preview) in [
+      ("workspace", known_workspace_actions(), "workspacePreview"),
+      ("doc", known_doc_actions(), "docPreview"),
+    ] {
+      let listed = artifact[scope]["roles"]
+        .as_object()
+        .unwrap()
+        .values()
+        .chain([&artifact[scope]["capabilityProfiles"][preview]])
+        .flat_map(|actions| actions.as_array().unwrap())
+        .map(|action| action.as_str().unwrap().to_string())
+        .collect::<BTreeSet<_>>();
+      assert_eq!(known, listed, "{scope}");
+    }
+  }
+
   #[test]
   fn matrix_artifact_exposes_profiles_and_restrictions() {
     let artifact = role_matrix_json();
@@ -5,11 +5,13 @@ use serde::Serialize;
 use super::{
   actions::{
     DOC_PREVIEW_ACTION, WORKSPACE_PREVIEW_ACTION, doc_actions_for_role, is_readonly_restricted_action, is_write_action,
-    workspace_actions_for_role,
+    known_doc_actions, known_workspace_actions, workspace_actions_for_role,
   },
   types::{
-    Candidate, DocRole, PermissionDecisionRestrictionV1, PermissionDecisionSourceV1, PermissionDecisionV1,
-    PermissionDocInputV1, PermissionEvaluationInputV1, WorkspaceRole,
+    Candidate, Deny, DocRole, PermissionCustomRoleInputV1, PermissionDecisionRestrictionV1, PermissionDecisionSourceV1,
+    PermissionDecisionTraceV1, PermissionDecisionV1, PermissionDenyInputV1, PermissionDocInputV1,
+    PermissionEvaluationInputV1, PermissionGrantWindowV1, PermissionTraceCandidateV1, PermissionTraceDenyV1,
+    WorkspaceRole,
   },
 };
 
@@ -36,6 +38,95 @@ fn parse_doc_role(role: &str) -> anyhow::Result<DocRole> {
   }
 }
 
+fn custom_role<'a>(input: &'a PermissionEvaluationInputV1, name: &str) -> Option<&'a PermissionCustomRoleInputV1> {
+  input.custom_roles.iter().find(|role| role.name == name)
+}
+
+pub(super) fn validate_custom_roles(input: &PermissionEvaluationInputV1) -> anyhow::Result<()> {
+  let known_workspace_actions = known_workspace_actions();
+  let known_doc_actions = known_doc_actions();
+  let mut names = BTreeSet::new();
+  for role in &input.custom_roles {
+    if parse_workspace_role(&role.name).is_ok() || parse_doc_role(&role.name).is_ok() || role.name == "preview" {
+      anyhow::bail!("custom role shadows a built-in role: {}", role.name);
+    }
+    if !names.insert(role.name.as_str()) {
+      anyhow::bail!("duplicate custom role: {}", role.name);
+    }
+    for (actions, known) in [
+      (&role.workspace_actions, &known_workspace_actions),
+      (&role.doc_actions, &known_doc_actions),
+    ] {
+      if let Some(action) = actions.iter().find(|action| !known.contains(*action)) {
+        anyhow::bail!("unknown action in custom role {}: {action}", role.name);
+      }
+    }
+  }
+  Ok(())
+}
+
+/// A built-in or custom doc role resolved to its action set.
+struct ResolvedDocRole {
+  builtin: Option<DocRole>,
+  name: String,
+  actions: BTreeSet<String>,
+}
+
+impl ResolvedDocRole {
+  fn builtin(role: DocRole) -> Self {
+    Self {
+      builtin: Some(role),
+      name: role_name(role),
+      actions: doc_actions_for_role(role),
+    }
+  }
+
+  fn is_none(&self) -> bool {
+    self.builtin == Some(DocRole::None)
+  }
+
+  /// Grants to non-members never go beyond editor access.
+  fn capped_for_non_member(self) -> Self {
+    match self.builtin {
+      Some(role) => Self::builtin(role.min(DocRole::Editor)),
+      None => {
+        let editor_actions = doc_actions_for_role(DocRole::Editor);
+        Self {
+          actions: self.actions.intersection(&editor_actions).cloned().collect(),
+          ..self
+        }
+      }
+    }
+  }
+
+  fn candidate(
+    self,
+    source_type: &'static str,
+    inactive_reason: Option<&'static str>,
+    ancestor_id: Option<String>,
+  ) -> Candidate {
+    Candidate {
+      source_type,
+      role: self.name,
+      actions: self.actions,
+      owner: false,
+      inactive_reason,
+      ancestor_id,
+    }
+  }
+}
+
+fn resolve_doc_role(input: &PermissionEvaluationInputV1, role: &str) -> anyhow::Result<ResolvedDocRole> {
+  if let Some(custom) = custom_role(input, role) {
+    return Ok(ResolvedDocRole {
+      builtin: None,
+      name: custom.name.clone(),
+      actions: custom.doc_actions.iter().cloned().collect(),
+    });
+  }
+  parse_doc_role(role).map(ResolvedDocRole::builtin)
+}
+
 pub(super) fn role_name(role: impl Serialize) -> String {
   serde_json::to_value(role)
     .ok()
@@ -56,7 +147,10 @@ fn active_workspace_role(input: &PermissionEvaluationInputV1) -> anyhow::Result<
   if input.workspace.member_state.as_deref().unwrap_or("active") != "active" {
     return Ok(None);
   }
-  let role = parse_workspace_role(role)?;
+  let role = match custom_role(input, role) {
+    Some(_) => WorkspaceRole::Member,
+    None => parse_workspace_role(role)?,
+  };
   if role == WorkspaceRole::External {
     return Ok(None);
   }
@@ -110,26 +204,161 @@ fn restricted_decision(input: &PermissionEvaluationInputV1, action: &str) -> Vec
   restrictions
 }
 
+fn grant_inactive_reason(input: &PermissionEvaluationInputV1, window: PermissionGrantWindowV1) -> Option<&'static str> {
+  if window.not_before.is_none() && window.expires_at.is_none() {
+    return None;
+  }
+  let Some(now) = input.runtime.now else {
+    return Some("grant-window-unknown");
+  };
+  if window.expires_at.is_some_and(|expires_at| now >= expires_at) {
+    Some("grant-expired")
+  } else if window.not_before.is_some_and(|not_before| now < not_before) {
+    Some("grant-not-yet-valid")
+  } else {
+    None
+  }
+}
+
+fn matching_denies<'a>(
+  input: &'a PermissionEvaluationInputV1,
+  deny_type: &'static str,
+  ancestor_id: Option<&'a str>,
+  denies: &'a [PermissionDenyInputV1],
+) -> impl Iterator<Item = Deny> + 'a {
+  denies
+    .iter()
+    .filter(|deny| {
+      deny
+        .user_id
+        .as_ref()
+        .is_none_or(|user_id| input.subject.user_id.as_ref() == Some(user_id))
+        && deny
+          .group_id
+          .as_ref()
+          .is_none_or(|group_id| input.subject.group_ids.contains(group_id))
+    })
+    .map(move |deny| Deny {
+      deny_type,
+      ancestor_id: ancestor_id.map(str::to_string),
+      user_id: deny.user_id.clone(),
+      group_id: deny.group_id.clone(),
+      reason: deny.reason.clone(),
+      actions: deny.actions.iter().cloned().collect(),
+    })
+}
+
+pub(super) fn workspace_denies(input: &PermissionEvaluationInputV1) -> Vec<Deny> {
+  matching_denies(input, "workspace-deny", None, &input.workspace.denies).collect()
+}
+
+/// Workspace, ancestor and doc denies that match the subject.
+pub(super) fn doc_denies(input: &PermissionEvaluationInputV1, doc: &PermissionDocInputV1) -> Vec<Deny> {
+  let mut denies = workspace_denies(input);
+  for ancestor in &doc.ancestors {
+    denies.extend(matching_denies(
+      input,
+      "inherited-deny",
+      Some(&ancestor.id),
+      &ancestor.denies,
+    ));
+  }
+  denies.extend(matching_denies(input, "doc-deny", None, &doc.denies));
+  denies
+}
+
+fn decision_trace(
+  action: &str,
+  allowed: bool,
+  candidates: &[Candidate],
+  blocking: &[&Deny],
+) -> PermissionDecisionTraceV1 {
+  PermissionDecisionTraceV1 {
+    candidates: candidates
+      .iter()
+      .map(|candidate| PermissionTraceCandidateV1 {
+        source_type: candidate.source_type,
+        role: candidate.role.clone(),
+        ancestor_id: candidate.ancestor_id.clone(),
+        outcome: if !candidate.actions.contains(action) {
+          "not-granted"
+        } else if candidate.inactive_reason.is_some() {
+          "inactive"
+        } else if allowed {
+          "granted"
+        } else {
+          "blocked"
+        },
+        inactive_reason: candidate.inactive_reason,
+      })
+      .collect(),
+    denies: blocking
+      .iter()
+      .map(|deny| PermissionTraceDenyV1 {
+        deny_type: deny.deny_type,
+        ancestor_id: deny.ancestor_id.clone(),
+        user_id: deny.user_id.clone(),
+        group_id: deny.group_id.clone(),
+        reason: deny.reason.clone(),
+      })
+      .collect(),
+  }
+}
+
 pub(super) fn decide(
   input: &PermissionEvaluationInputV1,
   action: &str,
   candidates: &[Candidate],
+  denies: &[Deny],
 ) -> PermissionDecisionV1 {
   let sources = candidates
     .iter()
-    .filter(|candidate| candidate.actions.contains(action))
+    .filter(|candidate| candidate.inactive_reason.is_none() && candidate.actions.contains(action))
     .map(|candidate| PermissionDecisionSourceV1 {
       source_type: candidate.source_type,
       role: Some(candidate.role.clone()),
+      ancestor_id: candidate.ancestor_id.clone(),
     })
     .collect::<Vec<_>>();
-  let restrictions = restricted_decision(input, action);
+  let mut restrictions = restricted_decision(input, action);
+  if sources.is_empty() {
+    for candidate in candidates.iter().filter(|candidate| candidate.actions.contains(action)) {
+      let Some(restriction_type) = candidate.inactive_reason else {
+        continue;
+      };
+      let restriction = PermissionDecisionRestrictionV1 {
+        restriction_type,
+        reason: Some(candidate.source_type.to_string()),
+      };
+      if !restrictions.contains(&restriction) {
+        restrictions.push(restriction);
+      }
+    }
+  }
+  // Denies win over every grant.
+  let blocking = denies
+    .iter()
+    .filter(|deny| deny.actions.contains(action))
+    .collect::<Vec<_>>();
+  for deny in &blocking {
+    let restriction = PermissionDecisionRestrictionV1 {
+      restriction_type: "explicit-deny",
+      reason: deny.reason.clone(),
+    };
+    if !restrictions.contains(&restriction) {
+      restrictions.push(restriction);
+    }
+  }
 
+  let allowed = !sources.is_empty() && restrictions.is_empty();
   PermissionDecisionV1 {
     action: action.to_string(),
-    allowed: !sources.is_empty() && restrictions.is_empty(),
+    allowed,
     sources,
     restrictions,
+    trace: input
+      .explain
+      .then(|| decision_trace(action, allowed, candidates, &blocking)),
   }
 }
 
@@ -138,14 +367,22 @@ pub(super) fn decide_doc(
   doc: &PermissionDocInputV1,
   action: &str,
   candidates: &[Candidate],
+  denies: &[Deny],
 ) -> PermissionDecisionV1 {
-  let mut decision = decide(input, action, candidates);
+  let mut decision = decide(input, action, candidates, denies);
   if action == "Doc.Publish" && !sharing_enabled(input, Some(doc)) {
     decision.restrictions.push(PermissionDecisionRestrictionV1 {
       restriction_type: "sharing-disabled",
       reason: None,
     });
     decision.allowed = false;
+    if let Some(trace) = &mut decision.trace {
+      for candidate in &mut trace.candidates {
+        if candidate.outcome == "granted" {
+          candidate.outcome = "blocked";
+        }
+      }
+    }
   }
   decision
 }
@@ -153,11 +390,28 @@ pub(super) fn decide_doc(
 pub(super) fn workspace_candidates(input: &PermissionEvaluationInputV1) -> anyhow::Result<Vec<Candidate>> {
   let mut candidates = Vec::new();
   if let Some(role) = active_workspace_role(input)? {
-    candidates.push(Candidate {
-      source_type: "workspace-member",
-      role: role_name(role),
-      actions: workspace_actions_for_role(role),
-      owner: role == WorkspaceRole::Owner,
+    let custom = input
+      .workspace
+      .role
+      .as_deref()
+      .and_then(|role| custom_role(input, role));
+    candidates.push(match custom {
+      Some(custom) => Candidate {
+        source_type: "workspace-member",
+        role: custom.name.clone(),
+        actions: custom.workspace_actions.iter().cloned().collect(),
+        owner: false,
+        inactive_reason: None,
+        ancestor_id: None,
+      },
+      None => Candidate {
+        source_type: "workspace-member",
+        role: role_name(role),
+        actions: workspace_actions_for_role(role),
+        owner: role == WorkspaceRole::Owner,
+        inactive_reason: None,
+        ancestor_id: None,
+      },
     });
   }
 
@@ -167,6 +421,8 @@ pub(super) fn workspace_candidates(input: &PermissionEvaluationInputV1) -> anyho
       role: "owner".to_string(),
       actions: workspace_actions_for_role(WorkspaceRole::Owner),
       owner: true,
+      inactive_reason: None,
+      ancestor_id: None,
     });
   }
 
@@ -176,6 +432,8 @@ pub(super) fn workspace_candidates(input: &PermissionEvaluationInputV1) -> anyho
       role: "external".to_string(),
       actions: workspace_actions_for_role(WorkspaceRole::External),
       owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
     });
   }
 
@@ -185,24 +443,90 @@ pub(super) fn workspace_candidates(input: &PermissionEvaluationInputV1) -> anyho
       role: "preview".to_string(),
       actions: BTreeSet::from([WORKSPACE_PREVIEW_ACTION.to_string()]),
       owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
     });
   }
 
   Ok(candidates)
 }
 
-pub(super) fn best_doc_role(candidates: &[Candidate]) -> Option<String> {
+/// Custom roles rank like `member`.
+pub(super) fn workspace_effective_role(
+  input: &PermissionEvaluationInputV1,
+  candidates: &[Candidate],
+) -> Option<String> {
   candidates
     .iter()
+    .filter_map(|candidate| match parse_workspace_role(&candidate.role) {
+      Ok(role) => Some((role, role_name(role))),
+      Err(_) => custom_role(input, &candidate.role).map(|custom| (WorkspaceRole::Member, custom.name.clone())),
+    })
+    .max_by_key(|(role, _)| *role)
+    .map(|(_, name)| name)
+}
+
+/// Custom roles are not ranked against built-in ones and are only reported
+/// when no built-in role applies.
+pub(super) fn best_doc_role(input: &PermissionEvaluationInputV1, candidates: &[Candidate]) -> Option<String> {
+  let active = || {
+    candidates
+      .iter()
+      .filter(|candidate| candidate.inactive_reason.is_none())
+  };
+  active()
     .filter_map(|candidate| parse_doc_role(&candidate.role).ok())
     .filter(|role| *role != DocRole::None)
     .max()
     .map(role_name)
+    .or_else(|| {
+      active()
+        .find(|candidate| custom_role(input, &candidate.role).is_some())
+        .map(|candidate| candidate.role.clone())
+    })
+}
+
+pub(super) type ExplicitGrant<'a> = Option<(&'a str, PermissionGrantWindowV1)>;
+
+fn explicit_grant(role: Option<&str>, not_before: Option<i64>, expires_at: Option<i64>) -> ExplicitGrant<'_> {
+  role.map(|role| (role, PermissionGrantWindowV1 { not_before, expires_at }))
 }
 
 pub(super) fn doc_candidates(
   input: &PermissionEvaluationInputV1,
   doc: &PermissionDocInputV1,
+) -> anyhow::Result<Vec<Candidate>> {
+  let ancestor_user_roles = doc
+    .ancestors
+    .iter()
+    .map(|ancestor| {
+      explicit_grant(
+        ancestor.explicit_user_role.as_deref(),
+        ancestor.explicit_user_role_not_before,
+        ancestor.explicit_user_role_expires_at,
+      )
+    })
+    .collect::<Vec<_>>();
+  doc_candidates_with_explicit_roles(
+    input,
+    doc,
+    explicit_grant(
+      doc.explicit_user_role.as_deref(),
+      doc.explicit_user_role_not_before,
+      doc.explicit_user_role_expires_at,
+    ),
+    &ancestor_user_roles,
+  )
+}
+
+/// Batch evaluation keeps per-subject grants outside the shared doc input,
+/// so the explicit roles of the doc and of each ancestor are passed
+/// separately.
+pub(super) fn doc_candidates_with_explicit_roles(
+  input: &PermissionEvaluationInputV1,
+  doc: &PermissionDocInputV1,
+  explicit_user_role: ExplicitGrant<'_>,
+  ancestor_user_roles: &[ExplicitGrant<'_>],
 ) -> anyhow::Result<Vec<Candidate>> {
   let mut candidates = Vec::new();
   let active_workspace_role = active_workspace_role(input)?;
@@ -210,6 +534,11 @@ pub(super) fn doc_candidates(
     active_workspace_role,
     Some(WorkspaceRole::Member | WorkspaceRole::Admin | WorkspaceRole::Owner)
   );
+  // Members holding a custom role get its doc actions on every doc instead
+  // of the member default, which could otherwise exceed the custom role.
+  let workspace_custom_role = active_workspace_role
+    .and(input.workspace.role.as_deref())
+    .and_then(|role| custom_role(input, role));
   let sharing = sharing_enabled(input, Some(doc));
 
   match active_workspace_role {
@@ -218,76 +547,109 @@ pub(super) fn doc_candidates(
       role: "owner".to_string(),
       actions: doc_actions_for_role(DocRole::Owner),
       owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
     }),
     Some(WorkspaceRole::Admin) => candidates.push(Candidate {
       source_type: "inherited-workspace-role",
       role: "manager".to_string(),
       actions: doc_actions_for_role(DocRole::Manager),
       owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
     }),
     _ => {}
   }
+  if let Some(custom) = workspace_custom_role {
+    candidates.push(Candidate {
+      source_type: "inherited-workspace-role",
+      role: custom.name.clone(),
+      actions: custom.doc_actions.iter().cloned().collect(),
+      owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
+    });
+  }
 
-  let explicit_user_role = doc
-    .explicit_user_role
-    .as_deref()
-    .map(parse_doc_role)
+  let explicit_user_role = explicit_user_role
+    .map(|(role, window)| resolve_doc_role(input, role).map(|role| (role, window)))
     .transpose()?
-    .filter(|role| *role != DocRole::None);
+    .filter(|(role, _)| !role.is_none());
+  // A grant outside its window behaves like a missing grant, apart from
+  // explaining denials it would otherwise have allowed.
+  let explicit_inactive_reason = explicit_user_role
+    .as_ref()
+    .and_then(|(_, window)| grant_inactive_reason(input, *window));
+  let has_active_explicit_user_role = explicit_user_role.is_some() && explicit_inactive_reason.is_none();
 
-  if let Some(mut role) = explicit_user_role {
+  if let Some((mut role, _)) = explicit_user_role {
     if !active_workspace_member {
-      role = role.min(DocRole::Editor);
+      role = role.capped_for_non_member();
     }
     if active_workspace_member || sharing {
+      let owner = role.builtin == Some(DocRole::Owner) && explicit_inactive_reason.is_none();
       candidates.push(Candidate {
-        source_type: "doc-grant",
-        role: role_name(role),
-        actions: doc_actions_for_role(role),
-        owner: role == DocRole::Owner,
+        owner,
+        ..role.candidate("doc-grant", explicit_inactive_reason, None)
       });
     }
   }
 
-  if doc.group_grants_enabled && !input.subject.group_ids.is_empty() {
-    let subject_groups = input.subject.group_ids.iter().collect::<BTreeSet<_>>();
+  let subject_groups = input.subject.group_ids.iter().collect::<BTreeSet<_>>();
+  if doc.group_grants_enabled && !subject_groups.is_empty() {
     for grant in &doc.group_grants {
       if subject_groups.contains(&grant.group_id) {
-        let role = parse_doc_role(&grant.role)?;
-        candidates.push(Candidate {
-          source_type: "group-grant",
-          role: role_name(role),
-          actions: doc_actions_for_role(role),
-          owner: false,
-        });
+        let role = resolve_doc_role(input, &grant.role)?;
+        candidates.push(role.candidate("group-grant", grant_inactive_reason(input, grant.window), None));
+      }
+    }
+  }
+
+  // Grants on folders, collections and parent docs flow down unchanged,
+  // except that they never make the subject the doc's resource owner.
+  for (ancestor, explicit_user_role) in doc.ancestors.iter().zip(ancestor_user_roles) {
+    if let Some((role, window)) = *explicit_user_role {
+      let mut role = resolve_doc_role(input, role)?;
+      if !active_workspace_member {
+        role = role.capped_for_non_member();
+      }
+      if !role.is_none() && (active_workspace_member || sharing) {
+        candidates.push(role.candidate(
+          "inherited",
+          grant_inactive_reason(input, window),
+          Some(ancestor.id.clone()),
+        ));
+      }
+    }
+    if doc.group_grants_enabled && !subject_groups.is_empty() {
+      for grant in &ancestor.group_grants {
+        if subject_groups.contains(&grant.group_id) {
+          let role = resolve_doc_role(input, &grant.role)?;
+          candidates.push(role.candidate(
+            "inherited",
+            grant_inactive_reason(input, grant.window),
+            Some(ancestor.id.clone()),
+          ));
+        }
       }
     }
   }
 
   if matches!(active_workspace_role, Some(role) if role != WorkspaceRole::External)
-    && explicit_user_role.is_none()
+    && workspace_custom_role.is_none()
+    && !has_active_explicit_user_role
     && let Some(role) = doc.member_default_role.as_deref()
   {
-    let role = parse_doc_role(role)?;
-    candidates.push(Candidate {
-      source_type: "member-default-policy",
-      role: role_name(role),
-      actions: doc_actions_for_role(role),
-      owner: false,
-    });
+    let role = resolve_doc_role(input, role)?;
+    candidates.push(role.candidate("member-default-policy", None, None));
   }
 
   if sharing
     && doc.visibility.as_deref() == Some("public")
     && let Some(role) = doc.public_role.as_deref()
   {
-    let role = parse_doc_role(role)?;
-    candidates.push(Candidate {
-      source_type: "public-policy",
-      role: role_name(role),
-      actions: doc_actions_for_role(role),
-      owner: false,
-    });
+    let role = resolve_doc_role(input, role)?;
+    candidates.push(role.candidate("public-policy", None, None));
   }
 
   if sharing && (doc.preview_enabled || doc.visibility.as_deref() == Some("public") || url_preview_enabled(input)) {
@@ -296,6 +658,8 @@ pub(super) fn doc_candidates(
       role: "preview".to_string(),
       actions: BTreeSet::from([DOC_PREVIEW_ACTION.to_string()]),
       owner: false,
+      inactive_reason: None,
+      ancestor_id: None,
     });
   }
 
@@ -1,30 +1,37 @@
+use std::collections::{HashMap, HashSet};
+
 use super::{
   actions::VERSION,
   candidates::{
-    best_doc_role, decide, decide_doc, doc_candidates, parse_workspace_role, role_name, workspace_candidates,
+    ExplicitGrant, best_doc_role, decide, decide_doc, doc_candidates, doc_candidates_with_explicit_roles, doc_denies,
+    validate_custom_roles, workspace_candidates, workspace_denies, workspace_effective_role,
   },
   types::{
-    PermissionDocEvaluationOutputV1, PermissionEvaluationInputV1, PermissionEvaluationOutputV1,
-    PermissionWorkspaceEvaluationOutputV1,
+    PermissionBatchDocRoleInputV1, PermissionBatchEvaluationInputV1, PermissionBatchEvaluationOutputV1,
+    PermissionBatchSubjectInputV1, PermissionBatchSubjectOutputV1, PermissionDocEvaluationOutputV1,
+    PermissionEvaluationInputV1, PermissionEvaluationOutputV1, PermissionGrantWindowV1, PermissionSubjectInputV1,
+    PermissionWorkspaceEvaluationOutputV1, PermissionWorkspaceInputV1,
   },
 };
 
+/// Workspace role, member state and local access: everything about a subject
+/// that workspace-level candidates depend on.
+type MembershipKey = (Option<String>, Option<String>, bool);
+
 pub fn evaluate_permission(input: PermissionEvaluationInputV1) -> anyhow::Result<PermissionEvaluationOutputV1> {
   if input.version != VERSION {
     anyhow::bail!("unsupported permission evaluation input version: {}", input.version);
   }
+  validate_custom_roles(&input)?;
 
   let workspace_candidates = workspace_candidates(&input)?;
+  let workspace_denies = workspace_denies(&input);
   let workspace_decisions = input
     .workspace_actions
     .iter()
-    .map(|action| decide(&input, action, &workspace_candidates))
+    .map(|action| decide(&input, action, &workspace_candidates, &workspace_denies))
     .collect::<Vec<_>>();
-  let workspace_effective_role = workspace_candidates
-    .iter()
-    .filter_map(|candidate| parse_workspace_role(&candidate.role).ok())
-    .max()
-    .map(role_name);
+  let workspace_effective_role = workspace_effective_role(&input, &workspace_candidates);
   let workspace_resource_owner_role = workspace_candidates
     .iter()
     .any(|candidate| candidate.owner)
@@ -33,10 +40,11 @@ pub fn evaluate_permission(input: PermissionEvaluationInputV1) -> anyhow::Result
   let mut docs = Vec::with_capacity(input.docs.len());
   for doc in &input.docs {
     let candidates = doc_candidates(&input, doc)?;
+    let denies = doc_denies(&input, doc);
     let decisions = doc
       .actions
       .iter()
-      .map(|action| decide_doc(&input, doc, action, &candidates))
+      .map(|action| decide_doc(&input, doc, action, &candidates, &denies))
       .collect::<Vec<_>>();
     let resource_owner_role = candidates
       .iter()
@@ -45,7 +53,7 @@ pub fn evaluate_permission(input: PermissionEvaluationInputV1) -> anyhow::Result
     docs.push(PermissionDocEvaluationOutputV1 {
       doc_id: doc.doc_id.clone(),
       resource_owner_role,
-      effective_role: best_doc_role(&candidates),
+      effective_role: best_doc_role(&input, &candidates),
       decisions,
     });
   }
@@ -61,12 +69,166 @@ pub fn evaluate_permission(input: PermissionEvaluationInputV1) -> anyhow::Result
   })
 }
 
+fn action_bits(allowed: impl Iterator<Item = bool>) -> Vec<u32> {
+  let mut words = Vec::new();
+  for (index, allowed) in allowed.enumerate() {
+    if index % 32 == 0 {
+      words.push(0);
+    }
+    if allowed {
+      words[index / 32] |= 1 << (index % 32);
+    }
+  }
+  words
+}
+
+fn batch_subject_input(
+  input: &PermissionBatchEvaluationInputV1,
+  subject: &PermissionBatchSubjectInputV1,
+) -> PermissionEvaluationInputV1 {
+  PermissionEvaluationInputV1 {
+    version: input.version,
+    legacy_compat_mode: input.legacy_compat_mode,
+    subject: PermissionSubjectInputV1 {
+      user_id: subject.user_id.clone(),
+      group_ids: subject.group_ids.clone(),
+      allow_local: subject.allow_local,
+    },
+    runtime: input.runtime.clone(),
+    workspace: PermissionWorkspaceInputV1 {
+      role: subject.role.clone(),
+      member_state: subject.member_state.clone(),
+      ..input.workspace.clone()
+    },
+    workspace_actions: Vec::new(),
+    docs: Vec::new(),
+    explain: false,
+    custom_roles: input.custom_roles.clone(),
+  }
+}
+
+fn batch_explicit_grant<'a>(subject: &'a PermissionBatchSubjectInputV1, id: &str) -> ExplicitGrant<'a> {
+  subject.doc_roles.get(id).map(|grant| match grant {
+    PermissionBatchDocRoleInputV1::Role(role) => (role.as_str(), PermissionGrantWindowV1::default()),
+    PermissionBatchDocRoleInputV1::Grant {
+      role,
+      not_before,
+      expires_at,
+    } => (
+      role.as_str(),
+      PermissionGrantWindowV1 {
+        not_before: *not_before,
+        expires_at: *expires_at,
+      },
+    ),
+  })
+}
+
+pub fn evaluate_permission_batch(
+  input: PermissionBatchEvaluationInputV1,
+) -> anyhow::Result<PermissionBatchEvaluationOutputV1> {
+  if input.version != VERSION {
+    anyhow::bail!("unsupported permission evaluation input version: {}", input.version);
+  }
+  validate_custom_roles(&batch_subject_input(&input, &PermissionBatchSubjectInputV1::default()))?;
+
+  let deny_inputs = input.workspace.denies.iter().chain(input.docs.iter().flat_map(|doc| {
+    doc
+      .denies
+      .iter()
+      .chain(doc.ancestors.iter().flat_map(|ancestor| &ancestor.denies))
+  }));
+  let mut denied_user_ids = HashSet::new();
+  let mut group_denies = false;
+  for deny in deny_inputs {
+    denied_user_ids.extend(deny.user_id.as_deref());
+    group_denies |= deny.group_id.is_some();
+  }
+
+  let mut workspace_rows = HashMap::<MembershipKey, Vec<u32>>::new();
+  // Subjects without their own grants or denies only differ by membership,
+  // so their rows are computed once per membership.
+  let mut shared_doc_rows = HashMap::<MembershipKey, Vec<Vec<u32>>>::new();
+  let mut subjects = Vec::with_capacity(input.subjects.len());
+  for subject in &input.subjects {
+    let subject_input = batch_subject_input(&input, subject);
+    let key = (subject.role.clone(), subject.member_state.clone(), subject.allow_local);
+    let denied = subject
+      .user_id
+      .as_deref()
+      .is_some_and(|user_id| denied_user_ids.contains(user_id))
+      || (group_denies && !subject.group_ids.is_empty());
+
+    let workspace = match workspace_rows.get(&key).filter(|_| !denied) {
+      Some(row) => row.clone(),
+      None => {
+        let candidates = workspace_candidates(&subject_input)?;
+        let denies = workspace_denies(&subject_input);
+        let row = action_bits(
+          input
+            .workspace_actions
+            .iter()
+            .map(|action| decide(&subject_input, action, &candidates, &denies).allowed),
+        );
+        if !denied {
+          workspace_rows.insert(key.clone(), row.clone());
+        }
+        row
+      }
+    };
+
+    let shared = !denied && subject.doc_roles.is_empty() && subject.group_ids.is_empty();
+    let docs = match shared_doc_rows.get(&key).filter(|_| shared) {
+      Some(rows) => rows.clone(),
+      None => {
+        let mut rows = Vec::with_capacity(input.docs.len());
+        for doc in &input.docs {
+          let ancestor_user_roles = doc
+            .ancestors
+            .iter()
+            .map(|ancestor| batch_explicit_grant(subject, &ancestor.id))
+            .collect::<Vec<_>>();
+          let candidates = doc_candidates_with_explicit_roles(
+            &subject_input,
+            doc,
+            batch_explicit_grant(subject, &doc.doc_id),
+            &ancestor_user_roles,
+          )?;
+          let denies = doc_denies(&subject_input, doc);
+          rows.push(action_bits(input.doc_actions.iter().map(|action| {
+            decide_doc(&subject_input, doc, action, &candidates, &denies).allowed
+          })));
+        }
+        if shared {
+          shared_doc_rows.insert(key, rows.clone());
+        }
+        rows
+      }
+    };
+
+    subjects.push(PermissionBatchSubjectOutputV1 {
+      key: subject.key.clone(),
+      workspace,
+      docs,
+    });
+  }
+
+  Ok(PermissionBatchEvaluationOutputV1 {
+    version: VERSION,
+    doc_ids: input.docs.iter().map(|doc| doc.doc_id.clone()).collect(),
+    workspace_actions: input.workspace_actions,
+    doc_actions: input.doc_actions,
+    subjects,
+  })
+}
+
 #[cfg(test)]
 mod tests {
   use super::*;
   use crate::permission::types::{
-    PermissionDecisionV1, PermissionDocInputV1, PermissionGroupGrantInputV1, PermissionRuntimeInputV1,
-    PermissionSubjectInputV1, PermissionWorkspaceInputV1,
+    PermissionCustomRoleInputV1, PermissionDecisionV1, PermissionDenyInputV1, PermissionDocAncestorInputV1,
+    PermissionDocInputV1, PermissionGroupGrantInputV1, PermissionRuntimeInputV1, PermissionSubjectInputV1,
+    PermissionWorkspaceInputV1,
   };
 
   fn base_input() -> PermissionEvaluationInputV1 {
@@ -92,6 +254,8 @@ mod tests {
         member_default_role: Some("manager".to_string()),
         ..Default::default()
       }],
+      explain: false,
+      custom_roles: Vec::new(),
     }
   }
 
@@ -355,6 +519,333 @@ mod tests {
     assert!(decision(&output.docs[0].decisions, "Doc.Update").allowed);
   }
 
+  #[test]
+  fn batch_matrix_matches_single_subject_evaluation() {
+    let base = base_input();
+    let doc_actions = vec![
+      "Doc.Read".to_string(),
+      "Doc.Update".to_string(),
+      "Doc.Users.Manage".to_string(),
+    ];
+    let docs = vec![
+      PermissionDocInputV1 {
+        doc_id: "default".to_string(),
+        member_default_role: Some("reader".to_string()),
+        ..Default::default()
+      },
+      PermissionDocInputV1 {
+        doc_id: "public".to_string(),
+        visibility: Some("public".to_string()),
+        public_role: Some("external".to_string()),
+        ..Default::default()
+      },
+    ];
+    let subject = |key: &str, role: Option<&str>, doc_roles: &[(&str, &str)]| PermissionBatchSubjectInputV1 {
+      key: key.to_string(),
+      role: role.map(str::to_string),
+      doc_roles: doc_roles
+        .iter()
+        .map(|(doc, role)| (doc.to_string(), PermissionBatchDocRoleInputV1::Role(role.to_string())))
+        .collect(),
+      ..Default::default()
+    };
+    let input = PermissionBatchEvaluationInputV1 {
+      version: 1,
+      legacy_compat_mode: false,
+      subjects: vec![
+        subject("member", Some("member"), &[]),
+        subject("admin", Some("admin"), &[]),
+        subject("editor", Some("member"), &[("default", "editor")]),
+        subject("guest", None, &[]),
+        subject("member-again", Some("member"), &[]),
+      ],
+      runtime: base.runtime.clone(),
+      workspace: base.workspace.clone(),
+      workspace_actions: base.workspace_actions.clone(),
+      doc_actions: doc_actions.clone(),
+      docs: docs.clone(),
+    };
+
+    let output = evaluate_permission_batch(input.clone()).unwrap();
+    assert_eq!(output.doc_ids, vec!["default".to_string(), "public".to_string()]);
+    assert_eq!(output.subjects[0].docs, vec![vec![0b001], vec![0b001]]);
+    assert_eq!(output.subjects[1].docs, vec![vec![0b111], vec![0b111]]);
+    assert_eq!(output.subjects[2].docs, vec![vec![0b011], vec![0b001]]);
+    assert_eq!(output.subjects[3].workspace, vec![0]);
+    assert_eq!(output.subjects[3].docs, vec![vec![0], vec![0b001]]);
+    assert_eq!(
+      output.subjects[4],
+      PermissionBatchSubjectOutputV1 {
+        key: "member-again".to_string(),
+        ..output.subjects[0].clone()
+      }
+    );
+
+    for (subject, row) in input.subjects.iter().zip(&output.subjects) {
+      let mut single = base_input();
+      single.workspace.role = subject.role.clone();
+      single.docs = docs
+        .iter()
+        .map(|doc| PermissionDocInputV1 {
+          actions: doc_actions.clone(),
+          explicit_user_role: subject.doc_roles.get(&doc.doc_id).map(|grant| match grant {
+            PermissionBatchDocRoleInputV1::Role(role) | PermissionBatchDocRoleInputV1::Grant { role, .. } => {
+              role.clone()
+            }
+          }),
+          ..doc.clone()
+        })
+        .collect();
+      let single = evaluate_permission(single).unwrap();
+      assert_eq!(
+        row.workspace,
+        action_bits(single.workspace.decisions.iter().map(|decision| decision.allowed)),
+        "{}",
+        subject.key
+      );
+      for (bits, doc) in row.docs.iter().zip(&single.docs) {
+        assert_eq!(
+          *bits,
+          action_bits(doc.decisions.iter().map(|decision| decision.allowed)),
+          "{} {}",
+          subject.key,
+          doc.doc_id
+        );
+      }
+    }
+  }
+
+  #[test]
+  fn grants_outside_their_window_explain_denials() {
+    let mut input = base_input();
+    input.runtime.now = Some(1_000);
+    input.subject.group_ids = vec!["contractors".to_string()];
+    input.docs[0].member_default_role = Some("reader".to_string());
+    input.docs[0].explicit_user_role = Some("editor".to_string());
+    input.docs[0].explicit_user_role_expires_at = Some(1_000);
+    input.docs[0].group_grants_enabled = true;
+    input.docs[0].group_grants = vec![PermissionGroupGrantInputV1 {
+      group_id: "contractors".to_string(),
+      role: "manager".to_string(),
+      window: PermissionGrantWindowV1 {
+        not_before: Some(2_000),
+        expires_at: None,
+      },
+    }];
+    input.docs[0].actions = vec![
+      "Doc.Read".to_string(),
+      "Doc.Update".to_string(),
+      "Doc.Users.Manage".to_string(),
+    ];
+    let output = evaluate_permission(input.clone()).unwrap();
+    let doc = &output.docs[0];
+
+    let read = decision(&doc.decisions, "Doc.Read");
+    assert!(read.allowed);
+    assert_eq!(read.sources.len(), 1);
+    assert_eq!(read.sources[0].source_type, "member-default-policy");
+    assert!(read.restrictions.is_empty());
+    let update = decision(&doc.decisions, "Doc.Update");
+    assert!(!update.allowed);
+    assert_eq!(
+      update
+        .restrictions
+        .iter()
+        .map(|restriction| (restriction.restriction_type, restriction.reason.as_deref()))
+        .collect::<Vec<_>>(),
+      vec![
+        ("grant-expired", Some("doc-grant")),
+        ("grant-not-yet-valid", Some("group-grant")),
+      ]
+    );
+    let manage = decision(&doc.decisions, "Doc.Users.Manage");
+    assert_eq!(manage.restrictions[0].restriction_type, "grant-not-yet-valid");
+    assert_eq!(doc.effective_role.as_deref(), Some("reader"));
+
+    input.runtime.now = Some(999);
+    let output = evaluate_permission(input.clone()).unwrap();
+    assert!(decision(&output.docs[0].decisions, "Doc.Update").allowed);
+
+    input.runtime.now = None;
+    let output = evaluate_permission(input).unwrap();
+    let update = decision(&output.docs[0].decisions, "Doc.Update");
+    assert!(!update.allowed);
+    assert_eq!(update.restrictions[0].restriction_type, "grant-window-unknown");
+  }
+
+  #[test]
+  fn ancestor_grants_flow_to_nested_docs_as_inherited_sources() {
+    let mut input = base_input();
+    input.workspace.role = None;
+    input.subject.group_ids = vec!["design".to_string()];
+    input.docs[0].member_default_role = None;
+    input.docs[0].group_grants_enabled = true;
+    input.docs[0].ancestors = vec![
+      PermissionDocAncestorInputV1 {
+        id: "folder".to_string(),
+        group_grants: vec![PermissionGroupGrantInputV1 {
+          group_id: "design".to_string(),
+          role: "commenter".to_string(),
+          ..Default::default()
+        }],
+        ..Default::default()
+      },
+      PermissionDocAncestorInputV1 {
+        id: "collection".to_string(),
+        explicit_user_role: Some("owner".to_string()),
+        ..Default::default()
+      },
+    ];
+    input.docs[0].actions = vec![
+      "Doc.Comments.Create".to_string(),
+      "Doc.Update".to_string(),
+      "Doc.Users.Manage".to_string(),
+    ];
+    let output = evaluate_permission(input).unwrap();
+    let doc = &output.docs[0];
+
+    let comment = decision(&doc.decisions, "Doc.Comments.Create");
+    assert!(comment.allowed);
+    assert_eq!(
+      comment
+        .sources
+        .iter()
+        .map(|source| (source.source_type, source.ancestor_id.as_deref()))
+        .collect::<Vec<_>>(),
+      vec![("inherited", Some("folder")), ("inherited", Some("collection"))]
+    );
+    let update = decision(&doc.decisions, "Doc.Update");
+    assert!(update.allowed);
+    assert_eq!(update.sources[0].role.as_deref(), Some("editor"));
+    assert_eq!(update.sources[0].ancestor_id.as_deref(), Some("collection"));
+    assert!(!decision(&doc.decisions, "Doc.Users.Manage").allowed);
+    assert_eq!(doc.effective_role.as_deref(), Some("editor"));
+    assert_eq!(doc.resource_owner_role, None);
+  }
+
+  #[test]
+  fn explicit_denies_override_grants_and_explain_the_decision() {
+    let mut input = base_input();
+    input.explain = true;
+    input.subject.user_id = Some("alice".to_string());
+    input.subject.group_ids = vec!["contractors".to_string()];
+    input.workspace.role = Some("admin".to_string());
+    input.workspace.denies = vec![PermissionDenyInputV1 {
+      group_id: Some("contractors".to_string()),
+      actions: vec!["Workspace.CreateDoc".to_string()],
+      ..Default::default()
+    }];
+    input.docs[0].denies = vec![
+      PermissionDenyInputV1 {
+        user_id: Some("alice".to_string()),
+        actions: vec!["Doc.Update".to_string()],
+        reason: Some("legal hold".to_string()),
+        ..Default::default()
+      },
+      PermissionDenyInputV1 {
+        user_id: Some("bob".to_string()),
+        actions: vec!["Doc.Read".to_string()],
+        ..Default::default()
+      },
+    ];
+    let output = evaluate_permission(input).unwrap();
+
+    assert!(decision(&output.workspace.decisions, "Workspace.Read").allowed);
+    let create_doc = decision(&output.workspace.decisions, "Workspace.CreateDoc");
+    assert!(!create_doc.allowed);
+    assert_eq!(create_doc.restrictions[0].restriction_type, "explicit-deny");
+    assert_eq!(
+      create_doc.trace.as_ref().unwrap().denies[0].group_id.as_deref(),
+      Some("contractors")
+    );
+
+    let read = decision(&output.docs[0].decisions, "Doc.Read");
+    assert!(read.allowed);
+    let trace = read.trace.as_ref().unwrap();
+    assert!(trace.denies.is_empty());
+    assert_eq!(
+      trace
+        .candidates
+        .iter()
+        .map(|candidate| (candidate.source_type, candidate.outcome))
+        .collect::<Vec<_>>(),
+      vec![
+        ("inherited-workspace-role", "granted"),
+        ("member-default-policy", "granted")
+      ]
+    );
+
+    let update = decision(&output.docs[0].decisions, "Doc.Update");
+    assert!(!update.allowed);
+    assert_eq!(update.sources.len(), 2);
+    assert_eq!(update.restrictions[0].reason.as_deref(), Some("legal hold"));
+    let trace = update.trace.as_ref().unwrap();
+    assert!(trace.candidates.iter().all(|candidate| candidate.outcome == "blocked"));
+    assert_eq!(trace.denies.len(), 1);
+    assert_eq!(trace.denies[0].deny_type, "doc-deny");
+    assert_eq!(trace.denies[0].user_id.as_deref(), Some("alice"));
+  }
+
+  #[test]
+  fn custom_roles_apply_to_members_grants_and_defaults() {
+    let mut input = base_input();
+    input.custom_roles = vec![PermissionCustomRoleInputV1 {
+      name: "auditor".to_string(),
+      workspace_actions: vec!["Workspace.Read".to_string(), "Workspace.Users.Read".to_string()],
+      doc_actions: vec![
+        "Doc.Read".to_string(),
+        "Doc.Comments.Read".to_string(),
+        "Doc.Users.Manage".to_string(),
+      ],
+    }];
+    input.workspace.role = Some("auditor".to_string());
+    input.workspace_actions = vec!["Workspace.Users.Read".to_string(), "Workspace.CreateDoc".to_string()];
+    // The member default grants editor access, which the auditor role must
+    // not inherit.
+    input.docs[0].member_default_role = Some("editor".to_string());
+    input.docs[0].actions = vec![
+      "Doc.Read".to_string(),
+      "Doc.Update".to_string(),
+      "Doc.Comments.Create".to_string(),
+      "Doc.Users.Manage".to_string(),
+    ];
+    let output = evaluate_permission(input.clone()).unwrap();
+    assert_eq!(output.workspace.effective_role.as_deref(), Some("auditor"));
+    assert!(decision(&output.workspace.decisions, "Workspace.Users.Read").allowed);
+    assert!(!decision(&output.workspace.decisions, "Workspace.CreateDoc").allowed);
+    assert_eq!(output.docs[0].effective_role.as_deref(), Some("auditor"));
+    assert!(decision(&output.docs[0].decisions, "Doc.Read").allowed);
+    assert!(!decision(&output.docs[0].decisions, "Doc.Update").allowed);
+    assert!(!decision(&output.docs[0].decisions, "Doc.Comments.Create").allowed);
+    assert!(decision(&output.docs[0].decisions, "Doc.Users.Manage").allowed);
+
+    let mut read_only = input.clone();
+    read_only.custom_roles[0].doc_actions = vec!["Doc.Read".to_string()];
+    let read_only_output = evaluate_permission(read_only).unwrap();
+    assert!(decision(&read_only_output.docs[0].decisions, "Doc.Read").allowed);
+    assert!(!decision(&read_only_output.docs[0].decisions, "Doc.Update").allowed);
+    assert!(!decision(&read_only_output.docs[0].decisions, "Doc.Users.Manage").allowed);
+
+    let mut guest_input = input.clone();
+    guest_input.workspace.role = None;
+    guest_input.docs[0].explicit_user_role = Some("auditor".to_string());
+    let guest_output = evaluate_permission(guest_input).unwrap();
+    let read = decision(&guest_output.docs[0].decisions, "Doc.Read");
+    assert!(read.allowed);
+    assert_eq!(read.sources[0].role.as_deref(), Some("auditor"));
+    assert!(!decision(&guest_output.docs[0].decisions, "Doc.Users.Manage").allowed);
+
+    let mut unknown_action = input.clone();
+    unknown_action.custom_roles[0]
+      .doc_actions
+      .push("Doc.Launch".to_string());
+    assert!(evaluate_permission(unknown_action).is_err());
+
+    let mut shadowing = input;
+    shadowing.custom_roles[0].name = "editor".to_string();
+    assert!(evaluate_permission(shadowing).is_err());
+  }
+
   #[test]
   fn empty_group_ids_do_not_enable_group_grants() {
     let mut input = base_input();
@@ -363,6 +854,7 @@ mod tests {
     input.docs[0].group_grants = vec![PermissionGroupGrantInputV1 {
       group_id: "group".to_string(),
       role: "manager".to_string(),
+      ..Default::default()
     }];
     input.docs[0].actions = vec!["Doc.Update".to_string()];
     let output = evaluate_permission(input).unwrap();
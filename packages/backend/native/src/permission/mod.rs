@@ -4,7 +4,7 @@ mod evaluator;
 mod types;
 
 use actions::role_matrix_json;
-pub use evaluator::evaluate_permission;
+pub use evaluator::{evaluate_permission, evaluate_permission_batch};
 use napi::{Error as NapiError, Result, Status};
 use napi_derive::napi;
 use serde_json::Value;
@@ -19,6 +19,15 @@ pub fn evaluate_permission_v1(input: Value) -> Result<Value> {
     .map_err(|err| NapiError::new(Status::GenericFailure, err.to_string()))
 }
 
+#[napi]
+pub fn evaluate_permission_batch_v1(input: Value) -> Result<Value> {
+  let input = serde_json::from_value::<PermissionBatchEvaluationInputV1>(input)
+    .map_err(|err| NapiError::new(Status::InvalidArg, err.to_string()))?;
+  evaluate_permission_batch(input)
+    .and_then(|output| serde_json::to_value(output).map_err(Into::into))
+    .map_err(|err| NapiError::new(Status::GenericFailure, err.to_string()))
+}
+
 #[napi]
 pub fn permission_action_role_matrix_v1() -> Value {
   role_matrix_json()
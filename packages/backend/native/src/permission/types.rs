@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
 use serde::{Deserialize, Serialize};
 
@@ -49,6 +49,10 @@ pub struct PermissionRuntimeInputV1 {
   pub sharing_enabled: Option<bool>,
   #[serde(default)]
   pub url_preview_enabled: Option<bool>,
+  /// Unix epoch milliseconds that grant validity windows are checked
+  /// against. Grants with a window are treated as inactive without it.
+  #[serde(default)]
+  pub now: Option<i64>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -66,6 +70,23 @@ pub struct PermissionWorkspaceInputV1 {
   pub url_preview_enabled: Option<bool>,
   #[serde(default)]
   pub local: bool,
+  /// Also applies to doc actions on every doc in the workspace.
+  #[serde(default)]
+  pub denies: Vec<PermissionDenyInputV1>,
+}
+
+/// Blocks `actions` for the matching subjects regardless of grants. Every
+/// subject matches when neither `userId` nor `groupId` is set.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionDenyInputV1 {
+  #[serde(default)]
+  pub user_id: Option<String>,
+  #[serde(default)]
+  pub group_id: Option<String>,
+  pub actions: Vec<String>,
+  #[serde(default)]
+  pub reason: Option<String>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -73,6 +94,35 @@ pub struct PermissionWorkspaceInputV1 {
 pub struct PermissionGroupGrantInputV1 {
   pub group_id: String,
   pub role: String,
+  #[serde(flatten)]
+  pub window: PermissionGrantWindowV1,
+}
+
+/// Validity window of a grant in Unix epoch milliseconds; `notBefore` is
+/// inclusive and `expiresAt` exclusive.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionGrantWindowV1 {
+  #[serde(default)]
+  pub not_before: Option<i64>,
+  #[serde(default)]
+  pub expires_at: Option<i64>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionDocAncestorInputV1 {
+  pub id: String,
+  #[serde(default)]
+  pub explicit_user_role: Option<String>,
+  #[serde(default)]
+  pub explicit_user_role_not_before: Option<i64>,
+  #[serde(default)]
+  pub explicit_user_role_expires_at: Option<i64>,
+  #[serde(default)]
+  pub group_grants: Vec<PermissionGroupGrantInputV1>,
+  #[serde(default)]
+  pub denies: Vec<PermissionDenyInputV1>,
 }
 
 #[derive(Clone, Debug, Default, Deserialize)]
@@ -84,9 +134,19 @@ pub struct PermissionDocInputV1 {
   #[serde(default)]
   pub explicit_user_role: Option<String>,
   #[serde(default)]
+  pub explicit_user_role_not_before: Option<i64>,
+  #[serde(default)]
+  pub explicit_user_role_expires_at: Option<i64>,
+  #[serde(default)]
   pub group_grants: Vec<PermissionGroupGrantInputV1>,
   #[serde(default)]
   pub group_grants_enabled: bool,
+  /// Folders, collections or parent docs whose grants flow down to this
+  /// doc, nearest first.
+  #[serde(default)]
+  pub ancestors: Vec<PermissionDocAncestorInputV1>,
+  #[serde(default)]
+  pub denies: Vec<PermissionDenyInputV1>,
   #[serde(default)]
   pub member_default_role: Option<String>,
   #[serde(default)]
@@ -115,6 +175,23 @@ pub struct PermissionEvaluationInputV1 {
   pub workspace_actions: Vec<String>,
   #[serde(default)]
   pub docs: Vec<PermissionDocInputV1>,
+  /// Attach a resolution trace to every decision.
+  #[serde(default)]
+  pub explain: bool,
+  #[serde(default)]
+  pub custom_roles: Vec<PermissionCustomRoleInputV1>,
+}
+
+/// A named action set usable wherever a workspace or doc role is accepted.
+/// As a workspace role it ranks like `member`.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionCustomRoleInputV1 {
+  pub name: String,
+  #[serde(default)]
+  pub workspace_actions: Vec<String>,
+  #[serde(default)]
+  pub doc_actions: Vec<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
@@ -124,6 +201,9 @@ pub struct PermissionDecisionSourceV1 {
   pub source_type: &'static str,
   #[serde(skip_serializing_if = "Option::is_none")]
   pub role: Option<String>,
+  /// Ancestor that provided the role, for `inherited` sources.
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ancestor_id: Option<String>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
@@ -142,6 +222,45 @@ pub struct PermissionDecisionV1 {
   pub allowed: bool,
   pub sources: Vec<PermissionDecisionSourceV1>,
   pub restrictions: Vec<PermissionDecisionRestrictionV1>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub trace: Option<PermissionDecisionTraceV1>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionTraceCandidateV1 {
+  #[serde(rename = "type")]
+  pub source_type: &'static str,
+  pub role: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ancestor_id: Option<String>,
+  /// One of `granted`, `blocked`, `inactive` or `not-granted`.
+  pub outcome: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub inactive_reason: Option<&'static str>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionTraceDenyV1 {
+  #[serde(rename = "type")]
+  pub deny_type: &'static str,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ancestor_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub user_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub group_id: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub reason: Option<String>,
+}
+
+/// Every candidate considered for the action and the denies that blocked it.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionDecisionTraceV1 {
+  pub candidates: Vec<PermissionTraceCandidateV1>,
+  pub denies: Vec<PermissionTraceDenyV1>,
 }
 
 #[derive(Clone, Debug, Eq, PartialEq, Serialize)]
@@ -173,10 +292,106 @@ pub struct PermissionEvaluationOutputV1 {
   pub docs: Vec<PermissionDocEvaluationOutputV1>,
 }
 
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionBatchSubjectInputV1 {
+  /// Caller-chosen identifier echoed back in the output.
+  pub key: String,
+  #[serde(default)]
+  pub user_id: Option<String>,
+  #[serde(default)]
+  pub group_ids: Vec<String>,
+  #[serde(default)]
+  pub allow_local: bool,
+  #[serde(default)]
+  pub role: Option<String>,
+  #[serde(default)]
+  pub member_state: Option<String>,
+  /// Explicit user grants keyed by doc or ancestor id.
+  #[serde(default)]
+  pub doc_roles: HashMap<String, PermissionBatchDocRoleInputV1>,
+}
+
+/// A bare role name, or a role with a validity window.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum PermissionBatchDocRoleInputV1 {
+  Role(String),
+  #[serde(rename_all = "camelCase")]
+  Grant {
+    role: String,
+    #[serde(default)]
+    not_before: Option<i64>,
+    #[serde(default)]
+    expires_at: Option<i64>,
+  },
+}
+
+/// Evaluates every subject against the same workspace and docs. The
+/// workspace `role`/`memberState`, the doc `actions` and the doc and
+/// ancestor `explicitUserRole*` fields are ignored in favour of the per-subject and batch-level values.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionBatchEvaluationInputV1 {
+  pub version: u32,
+  #[serde(default)]
+  pub legacy_compat_mode: bool,
+  #[serde(default)]
+  pub subjects: Vec<PermissionBatchSubjectInputV1>,
+  #[serde(default)]
+  pub runtime: PermissionRuntimeInputV1,
+  #[serde(default)]
+  pub workspace: PermissionWorkspaceInputV1,
+  #[serde(default)]
+  pub workspace_actions: Vec<String>,
+  #[serde(default)]
+  pub doc_actions: Vec<String>,
+  #[serde(default)]
+  pub docs: Vec<PermissionDocInputV1>,
+  #[serde(default)]
+  pub custom_roles: Vec<PermissionCustomRoleInputV1>,
+}
+
+/// Allowed actions are bitsets of 32-bit words: bit `i % 32` of word `i / 32`
+/// is set when action `i` of `workspaceActions` or `docActions` is allowed.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionBatchSubjectOutputV1 {
+  pub key: String,
+  pub workspace: Vec<u32>,
+  /// One bitset per doc, in `docIds` order.
+  pub docs: Vec<Vec<u32>>,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PermissionBatchEvaluationOutputV1 {
+  pub version: u32,
+  pub workspace_actions: Vec<String>,
+  pub doc_actions: Vec<String>,
+  pub doc_ids: Vec<String>,
+  pub subjects: Vec<PermissionBatchSubjectOutputV1>,
+}
+
 #[derive(Clone)]
 pub(super) struct Candidate {
   pub source_type: &'static str,
   pub role: String,
   pub actions: BTreeSet<String>,
   pub owner: bool,
+  /// Restriction type for a grant outside its validity window. Inactive
+  /// candidates never authorize and only explain denials.
+  pub inactive_reason: Option<&'static str>,
+  pub ancestor_id: Option<String>,
+}
+
+/// A deny entry that matched the subject.
+#[derive(Clone)]
+pub(super) struct Deny {
+  pub deny_type: &'static str,
+  pub ancestor_id: Option<String>,
+  pub user_id: Option<String>,
+  pub group_id: Option<String>,
+  pub reason: Option<String>,
+  pub actions: BTreeSet<String>,
 }
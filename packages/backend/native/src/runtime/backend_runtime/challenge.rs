@@ -0,0 +1,70 @@
+use affine_common::hashcash::Stamp;
+use chrono::{Duration, Utc};
+use napi::Result;
+
+use super::{
+  BackendRuntime, constants::HASHCASH_REPLAY_GATE_PREFIX, gate::RuntimeGateStore, napi_error, token_hash,
+  types::RuntimeChallengeVerifyResult,
+};
+use crate::hashcash::record_stamp_in_memory;
+
+impl RuntimeChallengeVerifyResult {
+  fn fail(reason: &str) -> Self {
+    Self {
+      ok: false,
+      reason: Some(reason.to_string()),
+      expires_at_ms: None,
+    }
+  }
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  /// Verifies a hashcash stamp and records it so the same stamp is rejected
+  /// as `replayed` for the rest of its validity window. Accepted stamps are
+  /// kept in `runtime_gates`, or in process memory before the runtime starts.
+  #[napi]
+  pub async fn verify_challenge_response_once(
+    &self,
+    response: String,
+    bits: u32,
+    resource: String,
+    validity_ms: Option<i64>,
+  ) -> Result<RuntimeChallengeVerifyResult> {
+    let validity = match validity_ms {
+      Some(validity_ms) => Duration::try_milliseconds(validity_ms)
+        .filter(|validity| *validity > Duration::zero())
+        .ok_or_else(|| napi_error("challenge validity must be positive"))?,
+      None => Stamp::DEFAULT_VALIDITY,
+    };
+    let Ok(stamp) = Stamp::try_from(response.as_str()) else {
+      return Ok(RuntimeChallengeVerifyResult::fail("invalid"));
+    };
+    let now = Utc::now();
+    let expires_at = match stamp.verify(bits, &resource, validity, now) {
+      Ok(expires_at) => expires_at,
+      Err(rejection) => return Ok(RuntimeChallengeVerifyResult::fail(rejection.as_str())),
+    };
+
+    let key = format!("{HASHCASH_REPLAY_GATE_PREFIX}{}", token_hash(&stamp.format()));
+    let expires_at_ms = expires_at.timestamp_millis();
+    let database = self.database.lock().await.as_ref().cloned();
+    let recorded = match database {
+      Some(database) => RuntimeGateStore::new(database)
+        .put_if_absent(&key, (expires_at - now).num_milliseconds().max(1))
+        .await
+        .map_err(napi::Error::from)?,
+      None => record_stamp_in_memory(&key, expires_at_ms, now.timestamp_millis()),
+    };
+
+    Ok(if recorded {
+      RuntimeChallengeVerifyResult {
+        ok: true,
+        reason: None,
+        expires_at_ms: Some(expires_at_ms),
+      }
+    } else {
+      RuntimeChallengeVerifyResult::fail("replayed")
+    })
+  }
+}
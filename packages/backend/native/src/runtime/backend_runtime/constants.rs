@@ -1,5 +1,8 @@
+pub(super) const AUTH_REFRESH_TOKEN_FAMILY_PURPOSE: &str = "auth_refresh_token:family";
+pub(super) const AUTH_REFRESH_TOKEN_PURPOSE: &str = "auth_refresh_token:token";
 pub(super) const BYOK_LOCAL_LEASE_ACTIVE_PURPOSE: &str = "copilot_byok_local_lease:active";
 pub(super) const BYOK_LOCAL_LEASE_PURPOSE: &str = "copilot_byok_local_lease";
+pub(super) const HASHCASH_REPLAY_GATE_PREFIX: &str = "hashcash:";
 pub(super) const MAGIC_LINK_OTP_PURPOSE: &str = "magic_link_otp";
 pub(super) const MAX_MAGIC_LINK_OTP_ATTEMPTS: i32 = 10;
 pub(super) const WORKSPACE_INVITE_LINK_ID_PURPOSE: &str = "workspace_invite_link:id";
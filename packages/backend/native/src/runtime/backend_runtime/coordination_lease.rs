@@ -1,7 +1,10 @@
 use napi::Result;
-use sqlx::{FromRow, PgPool};
+use sqlx::{FromRow, PgPool, SqlitePool};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::CoordinationLeaseGrant};
+use super::{
+  BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, database::sqlite_now_ms, napi_error,
+  types::CoordinationLeaseGrant,
+};
 
 #[derive(FromRow)]
 struct LeaseGrantRow {
@@ -9,34 +12,19 @@ struct LeaseGrantRow {
 }
 
 struct CoordinationLeaseStore {
-  pool: PgPool,
+  database: RuntimeDatabase,
 }
 
 impl CoordinationLeaseStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
   async fn acquire(&self, key: String, owner: String, ttl_ms: i64) -> RuntimeResult<Option<CoordinationLeaseGrant>> {
-    let row = sqlx::query_as::<_, LeaseGrantRow>(
-      r#"
-      INSERT INTO runtime_leases (key, owner, fencing_token, expires_at)
-      VALUES ($1, $2, 1, CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'))
-      ON CONFLICT (key) DO UPDATE
-        SET owner = EXCLUDED.owner,
-            fencing_token = runtime_leases.fencing_token + 1,
-            expires_at = EXCLUDED.expires_at,
-            updated_at = CURRENT_TIMESTAMP
-        WHERE runtime_leases.expires_at <= CURRENT_TIMESTAMP
-      RETURNING fencing_token
-      "#,
-    )
-    .bind(&key)
-    .bind(&owner)
-    .bind(ttl_ms as f64)
-    .fetch_optional(&self.pool)
-    .await
-    .map_err(|err| RuntimeError::database("CoordinationLease acquire failed", err))?;
+    let row = match &self.database {
+      RuntimeDatabase::Postgres(pool) => acquire_postgres(pool, &key, &owner, ttl_ms).await?,
+      RuntimeDatabase::Sqlite(pool) => acquire_sqlite(pool, &key, &owner, ttl_ms).await?,
+    };
 
     Ok(row.map(|row| CoordinationLeaseGrant {
       key,
@@ -46,46 +34,133 @@ impl CoordinationLeaseStore {
   }
 
   async fn release(&self, key: &str, owner: &str, fencing_token: i64) -> RuntimeResult<bool> {
-    let result = sqlx::query(
-      r#"
-      DELETE FROM runtime_leases
-      WHERE key = $1 AND owner = $2 AND fencing_token = $3
-      "#,
-    )
-    .bind(key)
-    .bind(owner)
-    .bind(fencing_token)
-    .execute(&self.pool)
-    .await
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_leases
+        WHERE key = $1 AND owner = $2 AND fencing_token = $3
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_leases
+        WHERE key = ?1 AND owner = ?2 AND fencing_token = ?3
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
     .map_err(|err| RuntimeError::database("CoordinationLease release failed", err))?;
 
-    Ok(result.rows_affected() == 1)
+    Ok(rows_affected == 1)
   }
 
   async fn renew(&self, key: &str, owner: &str, fencing_token: i64, ttl_ms: i64) -> RuntimeResult<bool> {
-    let result = sqlx::query(
-      r#"
-      UPDATE runtime_leases
-      SET expires_at = CURRENT_TIMESTAMP + ($4 * INTERVAL '1 millisecond'),
-          updated_at = CURRENT_TIMESTAMP
-      WHERE key = $1
-        AND owner = $2
-        AND fencing_token = $3
-        AND expires_at > CURRENT_TIMESTAMP
-      "#,
-    )
-    .bind(key)
-    .bind(owner)
-    .bind(fencing_token)
-    .bind(ttl_ms as f64)
-    .execute(&self.pool)
-    .await
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_leases
+        SET expires_at = CURRENT_TIMESTAMP + ($4 * INTERVAL '1 millisecond'),
+            updated_at = CURRENT_TIMESTAMP
+        WHERE key = $1
+          AND owner = $2
+          AND fencing_token = $3
+          AND expires_at > CURRENT_TIMESTAMP
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .bind(ttl_ms as f64)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        UPDATE runtime_leases
+        SET expires_at = ?5 + ?4,
+            updated_at = ?5
+        WHERE key = ?1
+          AND owner = ?2
+          AND fencing_token = ?3
+          AND expires_at > ?5
+        "#,
+      )
+      .bind(key)
+      .bind(owner)
+      .bind(fencing_token)
+      .bind(ttl_ms)
+      .bind(sqlite_now_ms())
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+    }
     .map_err(|err| RuntimeError::database("CoordinationLease renew failed", err))?;
 
-    Ok(result.rows_affected() == 1)
+    Ok(rows_affected == 1)
   }
 }
 
+async fn acquire_postgres(pool: &PgPool, key: &str, owner: &str, ttl_ms: i64) -> RuntimeResult<Option<LeaseGrantRow>> {
+  sqlx::query_as::<_, LeaseGrantRow>(
+    r#"
+    INSERT INTO runtime_leases (key, owner, fencing_token, expires_at)
+    VALUES ($1, $2, 1, CURRENT_TIMESTAMP + ($3 * INTERVAL '1 millisecond'))
+    ON CONFLICT (key) DO UPDATE
+      SET owner = EXCLUDED.owner,
+          fencing_token = runtime_leases.fencing_token + 1,
+          expires_at = EXCLUDED.expires_at,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE runtime_leases.expires_at <= CURRENT_TIMESTAMP
+    RETURNING fencing_token
+    "#,
+  )
+  .bind(key)
+  .bind(owner)
+  .bind(ttl_ms as f64)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("CoordinationLease acquire failed", err))
+}
+
+async fn acquire_sqlite(
+  pool: &SqlitePool,
+  key: &str,
+  owner: &str,
+  ttl_ms: i64,
+) -> RuntimeResult<Option<LeaseGrantRow>> {
+  sqlx::query_as::<_, LeaseGrantRow>(
+    r#"
+    INSERT INTO runtime_leases (key, owner, fencing_token, expires_at, created_at, updated_at)
+    VALUES (?1, ?2, 1, ?4 + ?3, ?4, ?4)
+    ON CONFLICT (key) DO UPDATE
+      SET owner = excluded.owner,
+          fencing_token = runtime_leases.fencing_token + 1,
+          expires_at = excluded.expires_at,
+          updated_at = excluded.updated_at
+      WHERE runtime_leases.expires_at <= ?4
+    RETURNING fencing_token
+    "#,
+  )
+  .bind(key)
+  .bind(owner)
+  .bind(ttl_ms)
+  .bind(sqlite_now_ms())
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("CoordinationLease acquire failed", err))
+}
+
 #[napi_derive::napi]
 impl BackendRuntime {
   pub(crate) async fn acquire_coordination_lease_inner(
@@ -101,7 +176,7 @@ impl BackendRuntime {
       return Err(RuntimeError::invalid_input("coordination lease owner is required"));
     }
 
-    CoordinationLeaseStore::new(self.pool().await?)
+    CoordinationLeaseStore::new(self.database().await?)
       .acquire(key, owner, ttl_ms)
       .await
   }
@@ -112,7 +187,7 @@ impl BackendRuntime {
     owner: String,
     fencing_token: i64,
   ) -> RuntimeResult<bool> {
-    CoordinationLeaseStore::new(self.pool().await?)
+    CoordinationLeaseStore::new(self.database().await?)
       .release(&key, &owner, fencing_token)
       .await
   }
@@ -155,7 +230,7 @@ impl BackendRuntime {
       return Err(napi_error("coordination lease ttl must be positive"));
     }
 
-    CoordinationLeaseStore::new(self.pool().await?)
+    CoordinationLeaseStore::new(self.database().await?)
       .renew(&key, &owner, fencing_token, ttl_ms)
       .await
       .map_err(napi::Error::from)
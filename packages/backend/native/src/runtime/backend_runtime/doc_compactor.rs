@@ -1,8 +1,26 @@
+use std::collections::{BTreeMap, HashMap};
+
 use chrono::{DateTime, Duration, Utc};
 use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
-use y_octo::Doc;
+use tokio::task::JoinSet;
+use y_octo::{Array, Doc, Map, Text, TextDeltaOp, TextInsert, Value};
+
+use super::{
+  BackendRuntime, RuntimeError, RuntimeResult,
+  doc_history::{HistoryEntry, insert_history},
+  napi_error,
+  types::{
+    CoordinationLeaseGrant, RuntimeDocCompactionFailure, RuntimeDocCompactionResult, RuntimeDocCompactionScheduleResult,
+  },
+};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::RuntimeDocCompactionResult};
+/// How many seconds of waiting weigh as much as one more pending update when
+/// the scheduler ranks docs, so small but old backlogs are not starved.
+const PENDING_UPDATE_AGE_WEIGHT_SECONDS: f64 = 60.0;
+
+/// How long the scheduler leaves out a doc whose merge diverged, as long as
+/// its next batch would be the same updates again.
+const DIVERGED_DOC_BACKOFF_SECONDS: f64 = 6.0 * 60.0 * 60.0;
 
 #[derive(FromRow)]
 struct SnapshotRow {
@@ -18,6 +36,84 @@ struct UpdateRow {
   created_by: Option<String>,
 }
 
+#[derive(FromRow)]
+struct PendingDocRow {
+  doc_id: String,
+}
+
+#[derive(FromRow)]
+struct BacklogRow {
+  docs: i64,
+  updates: i64,
+  oldest_update_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Clone, Copy)]
+struct CompactionOptions {
+  batch_limit: i64,
+  history_min_interval_ms: i64,
+  history_max_age_seconds: i64,
+  verify_round_trip: bool,
+  /// Encodes the merged doc; tests swap in a faulty encoder.
+  encode: fn(&Doc) -> RuntimeResult<Vec<u8>>,
+}
+
+#[derive(Default)]
+struct CompactionOutcome {
+  updates_merged: i64,
+  history_created: bool,
+  diverged: bool,
+}
+
+/// Why a merged snapshot was refused in round-trip verification mode.
+#[derive(Debug, PartialEq, Eq)]
+enum MergeDivergence {
+  Decode(String),
+  StateVector,
+  Content { path: String },
+}
+
+impl MergeDivergence {
+  fn kind(&self) -> &'static str {
+    match self {
+      Self::Decode(_) => "decode",
+      Self::StateVector => "state_vector",
+      Self::Content { .. } => "content",
+    }
+  }
+
+  fn detail(&self) -> String {
+    match self {
+      Self::Decode(err) => format!("merged snapshot failed to decode: {err}"),
+      Self::StateVector => "merged snapshot state vector differs from the applied updates".to_string(),
+      Self::Content { path } => format!("{path} differs from the applied updates"),
+    }
+  }
+}
+
+impl CompactionOptions {
+  fn validate(&self) -> napi::Result<()> {
+    if self.batch_limit <= 0 {
+      return Err(napi_error("doc compactor batch limit must be positive"));
+    }
+    if self.history_min_interval_ms < 0 {
+      return Err(napi_error("doc compactor history interval must be non-negative"));
+    }
+    if self.history_max_age_seconds < 0 {
+      return Err(napi_error("doc compactor history max age must be non-negative"));
+    }
+    checked_milliseconds(self.history_min_interval_ms, "history interval")?;
+    if self.history_max_age_seconds > 0 {
+      let max_age = checked_seconds(self.history_max_age_seconds, "history max age")?;
+      Utc::now()
+        .checked_add_signed(max_age)
+        .ok_or_else(|| RuntimeError::invalid_input("DocCompactor history max age is out of range"))?;
+    }
+    Ok(())
+  }
+}
+
+#[derive(Clone)]
 struct DocCompactorStore {
   pool: PgPool,
 }
@@ -31,38 +127,203 @@ impl DocCompactorStore {
     &self,
     workspace_id: &str,
     doc_id: &str,
+    lease: &CoordinationLeaseGrant,
+    options: CompactionOptions,
+  ) -> RuntimeResult<CompactionOutcome> {
+    compact_doc(self.pool.clone(), workspace_id, doc_id, lease, options).await
+  }
+
+  /// Docs with pending updates, most urgent first.
+  ///
+  /// A doc whose merge diverged within `DIVERGED_DOC_BACKOFF_SECONDS` is left
+  /// out while a compaction would load the same batch again: the batch starts
+  /// at the same update and either nothing newer arrived or it was full.
+  async fn load_pending_docs(
+    &self,
+    workspace_id: &str,
+    doc_limit: i64,
     batch_limit: i64,
-    history_min_interval_ms: i64,
-    history_max_age_seconds: i64,
-  ) -> RuntimeResult<(i64, bool)> {
-    compact_doc(
-      self.pool.clone(),
-      workspace_id,
-      doc_id,
-      batch_limit,
-      history_min_interval_ms,
-      history_max_age_seconds,
+  ) -> RuntimeResult<Vec<PendingDocRow>> {
+    sqlx::query_as::<_, PendingDocRow>(
+      r#"
+      SELECT pending.guid AS doc_id
+      FROM updates pending
+      WHERE pending.workspace_id = $1
+      GROUP BY pending.guid
+      HAVING NOT EXISTS (
+        SELECT 1
+        FROM doc_compaction_diagnostics diagnostic
+        WHERE diagnostic.workspace_id = $1
+          AND diagnostic.doc_id = pending.guid
+          AND diagnostic.created_at > CURRENT_TIMESTAMP - make_interval(secs => $4)
+          AND diagnostic.first_update_at = MIN(pending.created_at)
+          AND (diagnostic.last_update_at = MAX(pending.created_at) OR diagnostic.update_count >= $5)
+      )
+      ORDER BY
+        COUNT(*) + EXTRACT(EPOCH FROM (CURRENT_TIMESTAMP - MIN(pending.created_at)))::float8 / $2 DESC,
+        pending.guid ASC
+      LIMIT $3
+      "#,
     )
+    .bind(workspace_id)
+    .bind(PENDING_UPDATE_AGE_WEIGHT_SECONDS)
+    .bind(doc_limit)
+    .bind(DIVERGED_DOC_BACKOFF_SECONDS)
+    .bind(batch_limit)
+    .fetch_all(&self.pool)
     .await
+    .map_err(|err| RuntimeError::database("DocCompactor load pending docs failed", err))
+  }
+
+  async fn backlog(&self, workspace_id: &str) -> RuntimeResult<BacklogRow> {
+    sqlx::query_as::<_, BacklogRow>(
+      r#"
+      SELECT
+        COUNT(DISTINCT guid) AS docs,
+        COUNT(*) AS updates,
+        MIN(created_at) AS oldest_update_at
+      FROM updates
+      WHERE workspace_id = $1
+      "#,
+    )
+    .bind(workspace_id)
+    .fetch_one(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("DocCompactor load backlog failed", err))
   }
 }
 
+fn doc_lease_key(workspace_id: &str, doc_id: &str) -> String {
+  format!("doc:update:{workspace_id}:{doc_id}")
+}
+
 fn is_empty_doc(bin: &[u8]) -> bool {
   bin.is_empty() || (bin.len() == 1 && bin[0] == 0) || (bin.len() == 2 && bin[0] == 0 && bin[1] == 0)
 }
 
-fn apply_updates(updates: impl IntoIterator<Item = Vec<u8>>) -> RuntimeResult<Vec<u8>> {
+fn apply_updates(updates: &[Vec<u8>]) -> RuntimeResult<Doc> {
   let mut doc = Doc::default();
   for update in updates {
     doc
-      .apply_update_from_binary_v1(&update)
+      .apply_update_from_binary_v1(update)
       .map_err(|err| RuntimeError::invalid_state(format!("DocCompactor merge failed: {err}")))?;
   }
+  Ok(doc)
+}
+
+fn encode_doc(doc: &Doc) -> RuntimeResult<Vec<u8>> {
   doc
     .encode_update_v1()
     .map_err(|err| RuntimeError::invalid_state(format!("DocCompactor encode failed: {err}")))
 }
 
+fn canonical_value(value: &Value) -> String {
+  match value {
+    Value::Map(map) => canonical_map(map),
+    Value::Array(array) => canonical_array(array),
+    Value::Text(text) => canonical_text(text),
+    Value::Any(any) => format!("{any:?}"),
+    Value::Doc(doc) => format!("doc:{:?}", doc.guid()),
+    // The XML types, which AFFiNE docs never hold.
+    _ => "xml".to_string(),
+  }
+}
+
+fn canonical_map(map: &Map) -> String {
+  let mut keys = map.keys().map(|key| key.to_string()).collect::<Vec<_>>();
+  keys.sort();
+  let entries = keys
+    .into_iter()
+    .filter_map(|key| {
+      map
+        .get(&key)
+        .map(|value| format!("{key:?}:{}", canonical_value(&value)))
+    })
+    .collect::<Vec<_>>();
+  format!("{{{}}}", entries.join(","))
+}
+
+fn canonical_array(array: &Array) -> String {
+  let items = (0..array.len())
+    .filter_map(|index| array.get(index))
+    .map(|value| canonical_value(&value))
+    .collect::<Vec<_>>();
+  format!("[{}]", items.join(","))
+}
+
+/// Text as its delta, so formatting and embeds are compared as well.
+fn canonical_text(text: &Text) -> String {
+  let ops = text
+    .to_delta()
+    .into_iter()
+    .filter_map(|op| match op {
+      TextDeltaOp::Insert { insert, format } => {
+        let insert = match insert {
+          TextInsert::Text(text) => format!("{text:?}"),
+          TextInsert::Embed(embed) => format!("embed:{embed:?}"),
+        };
+        let mut attributes = format
+          .unwrap_or_default()
+          .into_iter()
+          .map(|(key, value)| format!("{key:?}:{value:?}"))
+          .collect::<Vec<_>>();
+        attributes.sort();
+        Some(format!("{insert}{{{}}}", attributes.join(",")))
+      }
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  format!("<{}>", ops.join(","))
+}
+
+fn crawl_error(err: impl std::fmt::Display) -> RuntimeError {
+  RuntimeError::invalid_state(format!("DocCompactor crawl failed: {err}"))
+}
+
+/// Canonical rendering of every root type, keyed by `root/key` for map
+/// entries and `root[]` for sequence content. A root decoded from an update
+/// has no kind until it is first read, so map entries are read from `doc` and
+/// sequence content from `sequences`, a second copy of the same doc.
+fn crawl_roots(doc: &Doc, sequences: &Doc) -> RuntimeResult<BTreeMap<String, String>> {
+  let mut content = BTreeMap::new();
+  for root in doc.keys() {
+    let map = doc.get_or_create_map(&root).map_err(crawl_error)?;
+    for key in map.keys() {
+      if let Some(value) = map.get(key) {
+        content.insert(format!("{root}/{key}"), canonical_value(&value));
+      }
+    }
+    let array = sequences.get_or_create_array(&root).map_err(crawl_error)?;
+    content.insert(format!("{root}[]"), canonical_array(&array));
+  }
+  Ok(content)
+}
+
+/// Decodes `merged` again and checks that it carries the same state and the
+/// same content as `applied`, the doc `inputs` were applied to one by one.
+fn verify_round_trip(inputs: &[Vec<u8>], applied: &Doc, merged: &[u8]) -> RuntimeResult<Option<MergeDivergence>> {
+  let mut decoded = Doc::default();
+  if let Err(err) = decoded.apply_update_from_binary_v1(merged) {
+    return Ok(Some(MergeDivergence::Decode(err.to_string())));
+  }
+  if decoded.get_state_vector() != applied.get_state_vector() {
+    return Ok(Some(MergeDivergence::StateVector));
+  }
+
+  let expected = crawl_roots(applied, &apply_updates(inputs)?)?;
+  let mut decoded_sequences = Doc::default();
+  decoded_sequences
+    .apply_update_from_binary_v1(merged)
+    .map_err(crawl_error)?;
+  let actual = crawl_roots(&decoded, &decoded_sequences)?;
+  let differing = expected
+    .iter()
+    .find(|(path, content)| actual.get(*path) != Some(*content))
+    .map(|(path, _)| path)
+    .or_else(|| actual.keys().find(|path| !expected.contains_key(*path)));
+  Ok(differing.map(|path| MergeDivergence::Content { path: path.clone() }))
+}
+
 fn checked_milliseconds(value: i64, field: &str) -> RuntimeResult<Duration> {
   Duration::try_milliseconds(value)
     .ok_or_else(|| RuntimeError::invalid_input(format!("DocCompactor {field} is too large")))
@@ -218,27 +479,49 @@ async fn create_history(
   let expired_at = Utc::now()
     .checked_add_signed(max_age)
     .ok_or_else(|| RuntimeError::invalid_input("DocCompactor history max age is out of range"))?;
-  sqlx::query(
+  insert_history(
+    tx,
+    HistoryEntry {
+      workspace_id,
+      doc_id,
+      timestamp: snapshot.updated_at,
+      blob: &snapshot.blob,
+      expired_at,
+      created_by: snapshot.updated_by.as_deref(),
+    },
+  )
+  .await?;
+
+  Ok(true)
+}
+
+/// Locks the lease row for the rest of the transaction, so a takeover cannot
+/// bump the fencing token until this compaction has committed or rolled back.
+async fn ensure_lease_held(tx: &mut Transaction<'_, Postgres>, lease: &CoordinationLeaseGrant) -> RuntimeResult<()> {
+  let row = sqlx::query(
     r#"
-    INSERT INTO snapshot_histories
-      (workspace_id, guid, timestamp, blob, expired_at, created_by)
-    VALUES
-      ($1, $2, $3, $4, $5, $6)
-    ON CONFLICT (workspace_id, guid, timestamp)
-    DO UPDATE SET expired_at = EXCLUDED.expired_at
+    SELECT 1
+    FROM runtime_leases
+    WHERE key = $1
+      AND owner = $2
+      AND fencing_token = $3
+      AND expires_at > CURRENT_TIMESTAMP
+    FOR SHARE
     "#,
   )
-  .bind(workspace_id)
-  .bind(doc_id)
-  .bind(snapshot.updated_at)
-  .bind(&snapshot.blob)
-  .bind(expired_at)
-  .bind(snapshot.updated_by.as_deref())
-  .execute(&mut **tx)
+  .bind(&lease.key)
+  .bind(&lease.owner)
+  .bind(lease.fencing_token)
+  .fetch_optional(&mut **tx)
   .await
-  .map_err(|err| RuntimeError::database("DocCompactor create history failed", err))?;
+  .map_err(|err| RuntimeError::database("DocCompactor check coordination lease failed", err))?;
 
-  Ok(true)
+  if row.is_none() {
+    return Err(RuntimeError::invalid_state(
+      "DocCompactor coordination lease is no longer held",
+    ));
+  }
+  Ok(())
 }
 
 async fn delete_updates(
@@ -265,26 +548,57 @@ async fn delete_updates(
   Ok(result.rows_affected() as i64)
 }
 
+/// Written outside the compaction transaction, which is rolled back.
+async fn record_divergence(
+  pool: &PgPool,
+  workspace_id: &str,
+  doc_id: &str,
+  divergence: &MergeDivergence,
+  updates: &[UpdateRow],
+  merged_size: usize,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    INSERT INTO doc_compaction_diagnostics
+      (workspace_id, doc_id, kind, detail, update_count, merged_size, first_update_at, last_update_at)
+    VALUES
+      ($1, $2, $3, $4, $5, $6, $7, $8)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .bind(divergence.kind())
+  .bind(divergence.detail())
+  .bind(updates.len() as i32)
+  .bind(merged_size as i64)
+  .bind(updates.first().map(|update| update.created_at))
+  .bind(updates.last().map(|update| update.created_at))
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("DocCompactor record divergence failed", err))?;
+  Ok(())
+}
+
 async fn compact_doc(
   pool: PgPool,
   workspace_id: &str,
   doc_id: &str,
-  batch_limit: i64,
-  history_min_interval_ms: i64,
-  history_max_age_seconds: i64,
-) -> RuntimeResult<(i64, bool)> {
+  lease: &CoordinationLeaseGrant,
+  options: CompactionOptions,
+) -> RuntimeResult<CompactionOutcome> {
   let mut tx = pool
     .begin()
     .await
     .map_err(|err| RuntimeError::database("DocCompactor begin transaction failed", err))?;
 
+  ensure_lease_held(&mut tx, lease).await?;
   let snapshot = load_snapshot(&mut tx, workspace_id, doc_id).await?;
-  let updates = load_updates(&mut tx, workspace_id, doc_id, batch_limit).await?;
+  let updates = load_updates(&mut tx, workspace_id, doc_id, options.batch_limit).await?;
   if updates.is_empty() {
     tx.commit()
       .await
       .map_err(|err| RuntimeError::database("DocCompactor commit transaction failed", err))?;
-    return Ok((0, false));
+    return Ok(CompactionOutcome::default());
   }
 
   let last = updates.last().expect("updates is not empty");
@@ -297,7 +611,21 @@ async fn compact_doc(
   let final_blob = if merge_inputs.len() == 1 {
     merge_inputs.remove(0)
   } else {
-    apply_updates(merge_inputs)?
+    let applied = apply_updates(&merge_inputs)?;
+    let merged = (options.encode)(&applied)?;
+    if options.verify_round_trip
+      && let Some(divergence) = verify_round_trip(&merge_inputs, &applied, &merged)?
+    {
+      tx.rollback()
+        .await
+        .map_err(|err| RuntimeError::database("DocCompactor rollback transaction failed", err))?;
+      record_divergence(&pool, workspace_id, doc_id, &divergence, &updates, merged.len()).await?;
+      return Ok(CompactionOutcome {
+        diverged: true,
+        ..Default::default()
+      });
+    }
+    merged
   };
 
   let snapshot_updated = upsert_snapshot(
@@ -313,9 +641,9 @@ async fn compact_doc(
   let mut history_created = false;
   if snapshot_updated
     && let Some(snapshot) = &snapshot
-    && should_create_history(&mut tx, snapshot, workspace_id, doc_id, history_min_interval_ms).await?
+    && should_create_history(&mut tx, snapshot, workspace_id, doc_id, options.history_min_interval_ms).await?
   {
-    history_created = create_history(&mut tx, workspace_id, doc_id, snapshot, history_max_age_seconds).await?;
+    history_created = create_history(&mut tx, workspace_id, doc_id, snapshot, options.history_max_age_seconds).await?;
   }
 
   let timestamps = updates.iter().map(|update| update.created_at).collect::<Vec<_>>();
@@ -325,7 +653,11 @@ async fn compact_doc(
     .await
     .map_err(|err| RuntimeError::database("DocCompactor commit transaction failed", err))?;
 
-  Ok((deleted, history_created))
+  Ok(CompactionOutcome {
+    updates_merged: deleted,
+    history_created,
+    diverged: false,
+  })
 }
 
 #[napi_derive::napi]
@@ -333,7 +665,11 @@ impl BackendRuntime {
   /// Merge pending doc updates with y-octo and persist the merged snapshot.
   ///
   /// Do not use this for snapshots that will be sent back to yjs clients until
-  /// the y-octo/yjs round-trip compatibility issue is resolved.
+  /// the y-octo/yjs round-trip compatibility issue is resolved. With
+  /// `verify_round_trip`, every merge is decoded again and compared with the
+  /// updates applied one by one; a divergent merge is recorded in
+  /// `doc_compaction_diagnostics` and not persisted, leaving the updates
+  /// pending.
   ///
   /// The caller owns quota reconciliation and must pass a fresh
   /// history_max_age_seconds value. The compactor intentionally does not read
@@ -350,25 +686,18 @@ impl BackendRuntime {
     history_max_age_seconds: i64,
     owner: String,
     lease_ttl_ms: i64,
+    verify_round_trip: Option<bool>,
   ) -> napi::Result<RuntimeDocCompactionResult> {
-    if batch_limit <= 0 {
-      return Err(napi_error("doc compactor batch limit must be positive"));
-    }
-    if history_min_interval_ms < 0 {
-      return Err(napi_error("doc compactor history interval must be non-negative"));
-    }
-    if history_max_age_seconds < 0 {
-      return Err(napi_error("doc compactor history max age must be non-negative"));
-    }
-    checked_milliseconds(history_min_interval_ms, "history interval")?;
-    if history_max_age_seconds > 0 {
-      let max_age = checked_seconds(history_max_age_seconds, "history max age")?;
-      Utc::now()
-        .checked_add_signed(max_age)
-        .ok_or_else(|| RuntimeError::invalid_input("DocCompactor history max age is out of range"))?;
-    }
+    let options = CompactionOptions {
+      batch_limit,
+      history_min_interval_ms,
+      history_max_age_seconds,
+      verify_round_trip: verify_round_trip.unwrap_or(false),
+      encode: encode_doc,
+    };
+    options.validate()?;
 
-    let lease_key = format!("doc:update:{workspace_id}:{doc_id}");
+    let lease_key = doc_lease_key(&workspace_id, &doc_id);
     let Some(lease) = self.acquire_coordination_lease(lease_key, owner, lease_ttl_ms).await? else {
       return Ok(RuntimeDocCompactionResult {
         lease_acquired: false,
@@ -377,17 +706,12 @@ impl BackendRuntime {
         doc_id,
         updates_merged: 0,
         history_created: false,
+        diverged: false,
       });
     };
 
     let result = DocCompactorStore::new(self.pool().await?)
-      .compact_doc(
-        &workspace_id,
-        &doc_id,
-        batch_limit,
-        history_min_interval_ms,
-        history_max_age_seconds,
-      )
+      .compact_doc(&workspace_id, &doc_id, &lease, options)
       .await;
 
     let released = self
@@ -397,14 +721,492 @@ impl BackendRuntime {
       return Err(RuntimeError::invalid_state("DocCompactor failed to release coordination lease").into());
     }
 
-    let (updates_merged, history_created) = result?;
+    let outcome = result?;
     Ok(RuntimeDocCompactionResult {
       lease_acquired: true,
-      merged: updates_merged > 0,
+      merged: outcome.updates_merged > 0,
       workspace_id,
       doc_id,
-      updates_merged,
-      history_created,
+      updates_merged: outcome.updates_merged,
+      history_created: outcome.history_created,
+      diverged: outcome.diverged,
     })
   }
+
+  /// Compact the docs of one workspace that have pending updates.
+  ///
+  /// Docs are ranked by pending update count and by the age of their oldest
+  /// update, and up to `doc_limit` of them are compacted, `concurrency` at a
+  /// time. Each doc takes the same per-doc lease as
+  /// `compact_pending_doc_updates`, and docs whose lease is held elsewhere are
+  /// skipped. Docs whose merge diverged recently are left out until their
+  /// pending updates change. The backlog fields describe what is still pending
+  /// afterwards.
+  ///
+  /// The same y-octo, quota and `verify_round_trip` notes as
+  /// `compact_pending_doc_updates` apply.
+  #[napi]
+  #[allow(clippy::too_many_arguments)]
+  pub async fn compact_pending_workspace_docs(
+    &self,
+    workspace_id: String,
+    doc_limit: i64,
+    concurrency: i64,
+    batch_limit: i64,
+    history_min_interval_ms: i64,
+    history_max_age_seconds: i64,
+    owner: String,
+    lease_ttl_ms: i64,
+    verify_round_trip: Option<bool>,
+  ) -> napi::Result<RuntimeDocCompactionScheduleResult> {
+    if doc_limit <= 0 {
+      return Err(napi_error("doc compactor doc limit must be positive"));
+    }
+    if concurrency <= 0 {
+      return Err(napi_error("doc compactor concurrency must be positive"));
+    }
+    let options = CompactionOptions {
+      batch_limit,
+      history_min_interval_ms,
+      history_max_age_seconds,
+      verify_round_trip: verify_round_trip.unwrap_or(false),
+      encode: encode_doc,
+    };
+    options.validate()?;
+
+    let store = DocCompactorStore::new(self.pool().await?);
+    let pending = store
+      .load_pending_docs(&workspace_id, doc_limit, options.batch_limit)
+      .await?;
+    let mut result = RuntimeDocCompactionScheduleResult {
+      workspace_id: workspace_id.clone(),
+      scanned_docs: pending.len() as i64,
+      compacted_docs: 0,
+      skipped_docs: 0,
+      failed_docs: 0,
+      failures: Vec::new(),
+      diverged_docs: 0,
+      updates_merged: 0,
+      histories_created: 0,
+      backlog_docs: 0,
+      backlog_updates: 0,
+      oldest_update_age_ms: None,
+    };
+
+    for batch in pending.chunks(concurrency as usize) {
+      let mut tasks = JoinSet::new();
+      let mut leases = HashMap::new();
+      // Docs already leased in this batch are still compacted and released
+      // before an acquire or release error is returned.
+      let mut batch_error = None;
+      for doc in batch {
+        let lease_key = doc_lease_key(&workspace_id, &doc.doc_id);
+        let lease = match self
+          .acquire_coordination_lease_inner(lease_key, owner.clone(), lease_ttl_ms)
+          .await
+        {
+          Ok(Some(lease)) => lease,
+          Ok(None) => {
+            result.skipped_docs += 1;
+            continue;
+          }
+          Err(err) => {
+            batch_error = Some(err);
+            break;
+          }
+        };
+
+        let store = store.clone();
+        let workspace_id = workspace_id.clone();
+        let doc_id = doc.doc_id.clone();
+        let task_lease = lease.clone();
+        let task = tasks.spawn(async move { store.compact_doc(&workspace_id, &doc_id, &task_lease, options).await });
+        leases.insert(task.id(), (doc.doc_id.clone(), lease));
+      }
+
+      while let Some(joined) = tasks.join_next_with_id().await {
+        let (task_id, compacted) = match joined {
+          Ok((task_id, compacted)) => (task_id, compacted),
+          Err(err) => (
+            err.id(),
+            Err(RuntimeError::invalid_state(format!(
+              "DocCompactor compaction task failed: {err}"
+            ))),
+          ),
+        };
+        let (doc_id, lease) = leases.remove(&task_id).expect("every compaction task holds a lease");
+        // A lease that expired mid-compaction is caught by the fencing check
+        // inside the transaction, so only a release error is reported.
+        if let Err(err) = self
+          .release_coordination_lease_inner(lease.key, lease.owner, lease.fencing_token)
+          .await
+        {
+          batch_error.get_or_insert(err);
+        }
+        match compacted {
+          Ok(outcome) => {
+            result.compacted_docs += i64::from(outcome.updates_merged > 0);
+            result.diverged_docs += i64::from(outcome.diverged);
+            result.updates_merged += outcome.updates_merged;
+            result.histories_created += i64::from(outcome.history_created);
+          }
+          Err(err) => {
+            result.failed_docs += 1;
+            result.failures.push(RuntimeDocCompactionFailure {
+              doc_id,
+              error: err.to_string(),
+            });
+          }
+        }
+      }
+      if let Some(err) = batch_error {
+        return Err(err.into());
+      }
+    }
+
+    let backlog = store.backlog(&workspace_id).await?;
+    result.backlog_docs = backlog.docs;
+    result.backlog_updates = backlog.updates;
+    result.oldest_update_age_ms = backlog
+      .oldest_update_at
+      .map(|oldest| (Utc::now() - oldest).num_milliseconds().max(0));
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use affine_doc_loader as doc_loader;
+
+  use super::{
+    super::tests::{pg_test_lock, runtime_from_database_url},
+    *,
+  };
+
+  /// `updates` and `snapshots` belong to the server schema, so these tests
+  /// also skip when the database has not been migrated by the server.
+  async fn compactor_runtime() -> Option<(BackendRuntime, PgPool)> {
+    let runtime = runtime_from_database_url().await.unwrap()?;
+    let pool = runtime.pool().await.unwrap();
+    let exists: bool = sqlx::query_scalar(
+      "SELECT to_regclass('updates') IS NOT NULL AND to_regclass('snapshots') IS NOT NULL \
+       AND to_regclass('snapshot_histories') IS NOT NULL",
+    )
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+    if !exists {
+      return None;
+    }
+    for cleanup in [
+      "DELETE FROM updates WHERE workspace_id LIKE 'rust-test:%'",
+      "DELETE FROM snapshots WHERE workspace_id LIKE 'rust-test:%'",
+      "DELETE FROM snapshot_histories WHERE workspace_id LIKE 'rust-test:%'",
+      "DELETE FROM doc_compaction_diagnostics WHERE workspace_id LIKE 'rust-test:%'",
+      "DELETE FROM runtime_leases WHERE key LIKE 'doc:update:rust-test:%'",
+    ] {
+      sqlx::query(cleanup).execute(&pool).await.unwrap();
+    }
+    Some((runtime, pool))
+  }
+
+  /// A full doc followed by `count - 1` incremental updates.
+  fn doc_updates(count: usize) -> Vec<Vec<u8>> {
+    let base = doc_loader::build_full_doc("Compact", "first paragraph", "doc-compactor-test").unwrap();
+    let doc = apply_updates(std::slice::from_ref(&base)).unwrap();
+    let mut edits = doc.get_or_create_map("compactor-test").unwrap();
+    let mut updates = vec![base];
+    for revision in 1..count {
+      let state_vector = doc.get_state_vector();
+      edits.insert(format!("rev-{revision}"), "edited").unwrap();
+      updates.push(doc.encode_state_as_update_v1(&state_vector).unwrap());
+    }
+    updates
+  }
+
+  async fn insert_updates(pool: &PgPool, workspace_id: &str, doc_id: &str, updates: &[Vec<u8>], start: DateTime<Utc>) {
+    for (index, blob) in updates.iter().enumerate() {
+      sqlx::query("INSERT INTO updates (workspace_id, guid, blob, created_at) VALUES ($1, $2, $3, $4)")
+        .bind(workspace_id)
+        .bind(doc_id)
+        .bind(blob)
+        .bind(start + Duration::milliseconds(index as i64))
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+  }
+
+  async fn pending_docs(pool: &PgPool, workspace_id: &str) -> Vec<String> {
+    sqlx::query_scalar("SELECT DISTINCT guid FROM updates WHERE workspace_id = $1 ORDER BY guid")
+      .bind(workspace_id)
+      .fetch_all(pool)
+      .await
+      .unwrap()
+  }
+
+  fn compaction_options() -> CompactionOptions {
+    CompactionOptions {
+      batch_limit: 100,
+      history_min_interval_ms: 60_000,
+      history_max_age_seconds: 0,
+      verify_round_trip: false,
+      encode: encode_doc,
+    }
+  }
+
+  async fn compact_workspace(
+    runtime: &BackendRuntime,
+    workspace_id: &str,
+    doc_limit: i64,
+  ) -> RuntimeDocCompactionScheduleResult {
+    runtime
+      .compact_pending_workspace_docs(
+        workspace_id.to_string(),
+        doc_limit,
+        2,
+        100,
+        60_000,
+        0,
+        "rust-test-owner".to_string(),
+        30_000,
+        None,
+      )
+      .await
+      .unwrap()
+  }
+
+  #[test]
+  fn round_trip_verification_accepts_merges_and_flags_divergence() {
+    let doc_id = "doc-compactor-test";
+    let base = doc_loader::build_full_doc("Compact", "first paragraph", doc_id).unwrap();
+    let edit = doc_loader::update_doc(&base, "first paragraph\n\nsecond paragraph", doc_id).unwrap();
+
+    let inputs = [base.clone(), edit];
+    let applied = apply_updates(&inputs).unwrap();
+    let merged = encode_doc(&applied).unwrap();
+    assert_eq!(verify_round_trip(&inputs, &applied, &merged).unwrap(), None);
+
+    let divergence = verify_round_trip(&inputs, &applied, &base).unwrap().unwrap();
+    assert_ne!(divergence.kind(), "decode");
+    assert!(matches!(
+      verify_round_trip(&inputs, &applied, &[0xff, 0xff, 0xff]).unwrap(),
+      Some(MergeDivergence::Decode(_))
+    ));
+  }
+
+  #[test]
+  fn canonical_rendering_covers_formatting_and_every_root() {
+    let doc = Doc::default();
+    let hello = |format| {
+      [TextDeltaOp::Insert {
+        insert: TextInsert::Text("hello".to_string()),
+        format,
+      }]
+    };
+    let mut plain = doc.get_or_create_text("plain").unwrap();
+    plain.apply_delta(&hello(None)).unwrap();
+    let mut bold = doc.get_or_create_text("bold").unwrap();
+    bold
+      .apply_delta(&hello(Some(
+        [("bold".to_string(), y_octo::Any::True)].into_iter().collect(),
+      )))
+      .unwrap();
+    assert_eq!(plain.to_string(), bold.to_string());
+    assert_ne!(canonical_text(&plain), canonical_text(&bold));
+
+    let mut meta = doc.get_or_create_map("meta").unwrap();
+    meta.insert("title".to_string(), "Compact").unwrap();
+    let bin = encode_doc(&doc).unwrap();
+    let roots = crawl_roots(&apply_updates(&[bin.clone()]).unwrap(), &apply_updates(&[bin]).unwrap()).unwrap();
+    for path in ["meta/title", "meta[]", "plain[]", "bold[]"] {
+      assert!(roots.contains_key(path), "{path} was not crawled");
+    }
+  }
+
+  #[tokio::test]
+  async fn workspace_compaction_ranks_pending_docs_and_skips_leased_ones() {
+    let _guard = pg_test_lock().lock().await;
+    let Some((runtime, pool)) = compactor_runtime().await else {
+      eprintln!("skipping postgres integration test: doc storage tables are not available");
+      return;
+    };
+    let workspace_id = "rust-test:compactor-schedule";
+    let now = Utc::now();
+    insert_updates(&pool, workspace_id, "busy", &doc_updates(3), now).await;
+    insert_updates(&pool, workspace_id, "old", &doc_updates(1), now - Duration::minutes(10)).await;
+    insert_updates(&pool, workspace_id, "fresh", &doc_updates(1), now).await;
+
+    // Ten minutes of waiting outweigh two more pending updates.
+    let ranked = compact_workspace(&runtime, workspace_id, 2).await;
+    assert_eq!(
+      (
+        ranked.scanned_docs,
+        ranked.compacted_docs,
+        ranked.skipped_docs,
+        ranked.failed_docs
+      ),
+      (2, 2, 0, 0)
+    );
+    assert_eq!(ranked.updates_merged, 4);
+    assert_eq!((ranked.backlog_docs, ranked.backlog_updates), (1, 1));
+    assert_eq!(pending_docs(&pool, workspace_id).await, vec!["fresh".to_string()]);
+
+    insert_updates(&pool, workspace_id, "busy", &doc_updates(2), now + Duration::seconds(1)).await;
+    let broken = [doc_updates(1).remove(0), vec![0xff, 0xff, 0xff]];
+    insert_updates(&pool, workspace_id, "broken", &broken, now).await;
+    let held = runtime
+      .acquire_coordination_lease_inner(doc_lease_key(workspace_id, "busy"), "other-owner".to_string(), 30_000)
+      .await
+      .unwrap()
+      .unwrap();
+
+    let skipped = compact_workspace(&runtime, workspace_id, 10).await;
+    assert_eq!(
+      (
+        skipped.scanned_docs,
+        skipped.compacted_docs,
+        skipped.skipped_docs,
+        skipped.failed_docs
+      ),
+      (3, 1, 1, 1)
+    );
+    assert_eq!(skipped.failures.len(), 1);
+    assert_eq!(skipped.failures[0].doc_id, "broken");
+    assert!(skipped.failures[0].error.contains("merge failed"));
+    assert_eq!(
+      pending_docs(&pool, workspace_id).await,
+      vec!["broken".to_string(), "busy".to_string()]
+    );
+
+    // The held lease is untouched and the failed doc's lease was released.
+    assert!(
+      runtime
+        .release_coordination_lease_inner(held.key, held.owner, held.fencing_token)
+        .await
+        .unwrap()
+    );
+    assert!(
+      runtime
+        .acquire_coordination_lease_inner(doc_lease_key(workspace_id, "broken"), "other-owner".to_string(), 30_000)
+        .await
+        .unwrap()
+        .is_some()
+    );
+  }
+
+  #[tokio::test]
+  async fn compaction_under_a_superseded_lease_is_refused() {
+    let _guard = pg_test_lock().lock().await;
+    let Some((runtime, pool)) = compactor_runtime().await else {
+      eprintln!("skipping postgres integration test: doc storage tables are not available");
+      return;
+    };
+    let workspace_id = "rust-test:compactor-fencing";
+    insert_updates(&pool, workspace_id, "doc", &doc_updates(2), Utc::now()).await;
+    let key = doc_lease_key(workspace_id, "doc");
+    let stale = runtime
+      .acquire_coordination_lease_inner(key.clone(), "owner-1".to_string(), 1)
+      .await
+      .unwrap()
+      .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+    let current = runtime
+      .acquire_coordination_lease_inner(key, "owner-2".to_string(), 30_000)
+      .await
+      .unwrap()
+      .unwrap();
+    assert!(current.fencing_token > stale.fencing_token);
+
+    let store = DocCompactorStore::new(pool.clone());
+    assert!(
+      store
+        .compact_doc(workspace_id, "doc", &stale, compaction_options())
+        .await
+        .is_err()
+    );
+    assert_eq!(pending_docs(&pool, workspace_id).await, vec!["doc".to_string()]);
+
+    let outcome = store
+      .compact_doc(workspace_id, "doc", &current, compaction_options())
+      .await
+      .unwrap();
+    assert_eq!(outcome.updates_merged, 2);
+    assert!(pending_docs(&pool, workspace_id).await.is_empty());
+  }
+
+  #[tokio::test]
+  async fn divergent_merge_is_recorded_and_backed_off_until_new_updates_arrive() {
+    let _guard = pg_test_lock().lock().await;
+    let Some((runtime, pool)) = compactor_runtime().await else {
+      eprintln!("skipping postgres integration test: doc storage tables are not available");
+      return;
+    };
+    let workspace_id = "rust-test:compactor-divergence";
+    let now = Utc::now();
+    insert_updates(&pool, workspace_id, "doc", &doc_updates(2), now).await;
+    let lease = runtime
+      .acquire_coordination_lease_inner(
+        doc_lease_key(workspace_id, "doc"),
+        "rust-test-owner".to_string(),
+        30_000,
+      )
+      .await
+      .unwrap()
+      .unwrap();
+
+    let store = DocCompactorStore::new(pool.clone());
+    let options = CompactionOptions {
+      verify_round_trip: true,
+      encode: |_| encode_doc(&Doc::default()),
+      ..compaction_options()
+    };
+    let outcome = store.compact_doc(workspace_id, "doc", &lease, options).await.unwrap();
+    assert!(
+      runtime
+        .release_coordination_lease_inner(lease.key, lease.owner, lease.fencing_token)
+        .await
+        .unwrap()
+    );
+    assert!(outcome.diverged);
+    assert_eq!(outcome.updates_merged, 0);
+    assert_eq!(pending_docs(&pool, workspace_id).await, vec!["doc".to_string()]);
+    let snapshots: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM snapshots WHERE workspace_id = $1")
+      .bind(workspace_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(snapshots, 0);
+    let (kind, update_count): (String, i32) =
+      sqlx::query_as("SELECT kind, update_count FROM doc_compaction_diagnostics WHERE workspace_id = $1")
+        .bind(workspace_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+    assert_eq!((kind.as_str(), update_count), ("state_vector", 2));
+
+    // The same batch is not retried, but a new update brings the doc back.
+    assert!(store.load_pending_docs(workspace_id, 10, 100).await.unwrap().is_empty());
+    assert!(store.load_pending_docs(workspace_id, 10, 2).await.unwrap().is_empty());
+    insert_updates(&pool, workspace_id, "doc", &doc_updates(1), now + Duration::seconds(1)).await;
+    assert_eq!(store.load_pending_docs(workspace_id, 10, 100).await.unwrap().len(), 1);
+    assert!(store.load_pending_docs(workspace_id, 10, 2).await.unwrap().is_empty());
+
+    // Housekeeping drops diagnostics past their retention.
+    sqlx::query(
+      "UPDATE doc_compaction_diagnostics SET created_at = CURRENT_TIMESTAMP - INTERVAL '90 days' \
+       WHERE workspace_id = $1",
+    )
+    .bind(workspace_id)
+    .execute(&pool)
+    .await
+    .unwrap();
+    assert!(runtime.cleanup_expired_doc_compaction_diagnostics(1000).await.unwrap() >= 1);
+    let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM doc_compaction_diagnostics WHERE workspace_id = $1")
+      .bind(workspace_id)
+      .fetch_one(&pool)
+      .await
+      .unwrap();
+    assert_eq!(remaining, 0);
+  }
 }
@@ -0,0 +1,479 @@
+//! Snapshot history rows are either keyframes holding the full doc
+//! (`base_timestamp IS NULL`) or y-octo updates holding only what changed
+//! since the keyframe recorded at `base_timestamp`. Deltas always point at a
+//! keyframe directly, so reading any history point takes at most one merge.
+
+use std::collections::HashSet;
+
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Postgres, Row, Transaction};
+use y_octo::Doc;
+
+use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::RuntimeDocHistoryDeltaMigrationResult};
+
+/// Deltas recorded against one keyframe before the next history point is
+/// stored in full again.
+const HISTORY_KEYFRAME_INTERVAL: i64 = 16;
+
+#[derive(FromRow)]
+struct HistoryRow {
+  blob: Vec<u8>,
+  base_timestamp: Option<DateTime<Utc>>,
+}
+
+struct Keyframe {
+  timestamp: DateTime<Utc>,
+  blob: Vec<u8>,
+  deltas: i64,
+}
+
+pub(super) struct HistoryEntry<'a> {
+  pub(super) workspace_id: &'a str,
+  pub(super) doc_id: &'a str,
+  pub(super) timestamp: DateTime<Utc>,
+  pub(super) blob: &'a [u8],
+  pub(super) expired_at: DateTime<Utc>,
+  pub(super) created_by: Option<&'a str>,
+}
+
+fn load_doc(blob: &[u8], context: &str) -> RuntimeResult<Doc> {
+  let mut doc = Doc::default();
+  doc
+    .apply_update_from_binary_v1(blob)
+    .map_err(|err| RuntimeError::invalid_state(format!("DocHistory {context} decode failed: {err}")))?;
+  Ok(doc)
+}
+
+/// Encodes `blob` as the changes it holds beyond `keyframe`, or `None` when
+/// that would not be smaller than storing it in full.
+///
+/// Reading a delta back merges it into the keyframe, so a blob that does not
+/// descend from the keyframe, such as a doc deleted and recreated, is also
+/// stored in full. It descends when merging the keyframe into it adds nothing.
+fn encode_delta(keyframe: &[u8], blob: &[u8]) -> RuntimeResult<Option<Vec<u8>>> {
+  let state_vector = load_doc(keyframe, "keyframe")?.get_state_vector();
+  let doc = load_doc(blob, "snapshot")?;
+  let mut merged = load_doc(blob, "snapshot")?;
+  merged
+    .apply_update_from_binary_v1(keyframe)
+    .map_err(|err| RuntimeError::invalid_state(format!("DocHistory keyframe merge failed: {err}")))?;
+  if merged.get_state_vector() != doc.get_state_vector() {
+    return Ok(None);
+  }
+
+  let delta = doc
+    .encode_state_as_update_v1(&state_vector)
+    .map_err(|err| RuntimeError::invalid_state(format!("DocHistory delta encode failed: {err}")))?;
+  Ok((delta.len() < blob.len()).then_some(delta))
+}
+
+/// Like [`encode_delta`], but a blob that does not decode is kept in full, as
+/// history was stored before delta encoding, instead of failing the write.
+fn try_encode_delta(keyframe: &[u8], blob: &[u8]) -> Option<Vec<u8>> {
+  encode_delta(keyframe, blob).ok().flatten()
+}
+
+fn apply_delta(keyframe: &[u8], delta: &[u8]) -> RuntimeResult<Vec<u8>> {
+  let mut doc = load_doc(keyframe, "keyframe")?;
+  doc
+    .apply_update_from_binary_v1(delta)
+    .map_err(|err| RuntimeError::invalid_state(format!("DocHistory delta apply failed: {err}")))?;
+  doc
+    .encode_update_v1()
+    .map_err(|err| RuntimeError::invalid_state(format!("DocHistory encode failed: {err}")))
+}
+
+/// Serializes history writers of one doc, so a keyframe can not be converted
+/// into a delta while another transaction records a delta against it.
+async fn lock_doc_history(tx: &mut Transaction<'_, Postgres>, workspace_id: &str, doc_id: &str) -> RuntimeResult<()> {
+  sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+    .bind(format!("snapshot_histories:{workspace_id}/{doc_id}"))
+    .execute(&mut **tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocHistory lock failed", err))?;
+  Ok(())
+}
+
+async fn latest_keyframe(
+  tx: &mut Transaction<'_, Postgres>,
+  workspace_id: &str,
+  doc_id: &str,
+) -> RuntimeResult<Option<Keyframe>> {
+  let row = sqlx::query(
+    r#"
+    SELECT
+      keyframe.timestamp,
+      keyframe.blob,
+      (
+        SELECT COUNT(*)
+        FROM snapshot_histories delta
+        WHERE delta.workspace_id = keyframe.workspace_id
+          AND delta.guid = keyframe.guid
+          AND delta.base_timestamp = keyframe.timestamp
+      ) AS deltas
+    FROM snapshot_histories keyframe
+    WHERE keyframe.workspace_id = $1
+      AND keyframe.guid = $2
+      AND keyframe.base_timestamp IS NULL
+    ORDER BY keyframe.timestamp DESC
+    LIMIT 1
+    FOR SHARE OF keyframe
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .fetch_optional(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocHistory load keyframe failed", err))?;
+
+  Ok(row.map(|row| Keyframe {
+    timestamp: row.get("timestamp"),
+    blob: row.get("blob"),
+    deltas: row.get("deltas"),
+  }))
+}
+
+/// A keyframe must outlive every delta that needs it for reconstruction.
+async fn extend_keyframe_expiry(
+  tx: &mut Transaction<'_, Postgres>,
+  workspace_id: &str,
+  doc_id: &str,
+  keyframe_timestamp: DateTime<Utc>,
+  expired_at: DateTime<Utc>,
+) -> RuntimeResult<()> {
+  sqlx::query(
+    r#"
+    UPDATE snapshot_histories
+    SET expired_at = GREATEST(expired_at, $4)
+    WHERE workspace_id = $1 AND guid = $2 AND timestamp = $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .bind(keyframe_timestamp)
+  .bind(expired_at)
+  .execute(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocHistory extend keyframe expiry failed", err))?;
+  Ok(())
+}
+
+/// Records a history point, storing it as a delta against the latest keyframe
+/// when that is smaller and the keyframe has not reached
+/// [`HISTORY_KEYFRAME_INTERVAL`] deltas yet.
+pub(super) async fn insert_history(tx: &mut Transaction<'_, Postgres>, entry: HistoryEntry<'_>) -> RuntimeResult<()> {
+  lock_doc_history(tx, entry.workspace_id, entry.doc_id).await?;
+  let mut blob = entry.blob.to_vec();
+  let mut base_timestamp = None;
+  if let Some(keyframe) = latest_keyframe(tx, entry.workspace_id, entry.doc_id).await?
+    && keyframe.timestamp < entry.timestamp
+    && keyframe.deltas < HISTORY_KEYFRAME_INTERVAL
+    && let Some(delta) = try_encode_delta(&keyframe.blob, entry.blob)
+  {
+    blob = delta;
+    base_timestamp = Some(keyframe.timestamp);
+  }
+
+  // An existing row keeps its original encoding; only its lifetime changes.
+  let base_timestamp: Option<DateTime<Utc>> = sqlx::query_scalar(
+    r#"
+    INSERT INTO snapshot_histories
+      (workspace_id, guid, timestamp, blob, expired_at, created_by, base_timestamp)
+    VALUES
+      ($1, $2, $3, $4, $5, $6, $7)
+    ON CONFLICT (workspace_id, guid, timestamp)
+    DO UPDATE SET expired_at = EXCLUDED.expired_at
+    RETURNING base_timestamp
+    "#,
+  )
+  .bind(entry.workspace_id)
+  .bind(entry.doc_id)
+  .bind(entry.timestamp)
+  .bind(&blob)
+  .bind(entry.expired_at)
+  .bind(entry.created_by)
+  .bind(base_timestamp)
+  .fetch_one(&mut **tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocHistory create history failed", err))?;
+
+  if let Some(base_timestamp) = base_timestamp {
+    extend_keyframe_expiry(tx, entry.workspace_id, entry.doc_id, base_timestamp, entry.expired_at).await?;
+  }
+  Ok(())
+}
+
+async fn load_history_row(
+  pool: &PgPool,
+  workspace_id: &str,
+  doc_id: &str,
+  timestamp: DateTime<Utc>,
+) -> RuntimeResult<Option<HistoryRow>> {
+  sqlx::query_as::<_, HistoryRow>(
+    r#"
+    SELECT blob, base_timestamp
+    FROM snapshot_histories
+    WHERE workspace_id = $1 AND guid = $2 AND timestamp = $3
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .bind(timestamp)
+  .fetch_optional(pool)
+  .await
+  .map_err(|err| RuntimeError::database("DocHistory load history failed", err))
+}
+
+/// Returns the full doc recorded at `timestamp`, merging delta rows onto
+/// their keyframe.
+pub(super) async fn load_history_blob(
+  pool: &PgPool,
+  workspace_id: &str,
+  doc_id: &str,
+  timestamp: DateTime<Utc>,
+) -> RuntimeResult<Option<Vec<u8>>> {
+  let Some(row) = load_history_row(pool, workspace_id, doc_id, timestamp).await? else {
+    return Ok(None);
+  };
+  let Some(base_timestamp) = row.base_timestamp else {
+    return Ok(Some(row.blob));
+  };
+  let keyframe = load_history_row(pool, workspace_id, doc_id, base_timestamp)
+    .await?
+    .ok_or_else(|| {
+      RuntimeError::invalid_state(format!(
+        "DocHistory keyframe {base_timestamp} missing for {workspace_id}/{doc_id}"
+      ))
+    })?;
+  apply_delta(&keyframe.blob, &row.blob).map(Some)
+}
+
+/// Re-encodes the full history rows of one doc as deltas, keeping a keyframe
+/// after every [`HISTORY_KEYFRAME_INTERVAL`] deltas (and any point a delta
+/// would not shrink or that does not descend from its keyframe). Rows that are already deltas, and keyframes they point
+/// at, are left untouched.
+async fn convert_doc_to_deltas(pool: &PgPool, workspace_id: &str, doc_id: &str) -> RuntimeResult<i64> {
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("DocHistory migration begin transaction failed", err))?;
+  lock_doc_history(&mut tx, workspace_id, doc_id).await?;
+  let rows = sqlx::query(
+    r#"
+    SELECT timestamp, blob, base_timestamp, expired_at
+    FROM snapshot_histories
+    WHERE workspace_id = $1 AND guid = $2
+    ORDER BY timestamp ASC
+    FOR UPDATE
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(doc_id)
+  .fetch_all(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("DocHistory migration load histories failed", err))?;
+
+  let referenced = rows
+    .iter()
+    .filter_map(|row| row.get::<Option<DateTime<Utc>>, _>("base_timestamp"))
+    .collect::<HashSet<_>>();
+  let mut keyframe: Option<Keyframe> = None;
+  let mut converted = 0;
+  for row in rows {
+    let timestamp: DateTime<Utc> = row.get("timestamp");
+    let blob: Vec<u8> = row.get("blob");
+    let base_timestamp: Option<DateTime<Utc>> = row.get("base_timestamp");
+    let expired_at: DateTime<Utc> = row.get("expired_at");
+
+    if let Some(base_timestamp) = base_timestamp {
+      if let Some(keyframe) = keyframe.as_mut()
+        && keyframe.timestamp == base_timestamp
+      {
+        keyframe.deltas += 1;
+      }
+      continue;
+    }
+
+    let Some(current) = keyframe
+      .as_mut()
+      .filter(|keyframe| keyframe.deltas < HISTORY_KEYFRAME_INTERVAL && !referenced.contains(&timestamp))
+    else {
+      keyframe = Some(Keyframe {
+        timestamp,
+        blob,
+        deltas: 0,
+      });
+      continue;
+    };
+    let Some(delta) = try_encode_delta(&current.blob, &blob) else {
+      keyframe = Some(Keyframe {
+        timestamp,
+        blob,
+        deltas: 0,
+      });
+      continue;
+    };
+
+    // A keyframe some delta points at must stay in full, even one the delta
+    // was recorded against after the rows above were read.
+    let updated = sqlx::query(
+      r#"
+      UPDATE snapshot_histories history
+      SET blob = $4, base_timestamp = $5
+      WHERE history.workspace_id = $1
+        AND history.guid = $2
+        AND history.timestamp = $3
+        AND NOT EXISTS (
+          SELECT 1
+          FROM snapshot_histories delta
+          WHERE delta.workspace_id = $1 AND delta.guid = $2 AND delta.base_timestamp = $3
+        )
+      "#,
+    )
+    .bind(workspace_id)
+    .bind(doc_id)
+    .bind(timestamp)
+    .bind(&delta)
+    .bind(current.timestamp)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("DocHistory migration update history failed", err))?
+    .rows_affected();
+    if updated == 0 {
+      keyframe = Some(Keyframe {
+        timestamp,
+        blob,
+        deltas: 0,
+      });
+      continue;
+    }
+    extend_keyframe_expiry(&mut tx, workspace_id, doc_id, current.timestamp, expired_at).await?;
+    current.deltas += 1;
+    converted += 1;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("DocHistory migration commit failed", err))?;
+  Ok(converted)
+}
+
+fn decode_migration_cursor(cursor: Option<&str>) -> RuntimeResult<(String, String)> {
+  let Some(cursor) = cursor else {
+    return Ok(Default::default());
+  };
+  serde_json::from_str(cursor).map_err(|_| RuntimeError::invalid_input("Invalid doc history migration cursor"))
+}
+
+#[napi_derive::napi]
+impl BackendRuntime {
+  /// Returns the full doc recorded in history at `timestamp_ms`, whether the
+  /// row is stored as a keyframe or as a delta.
+  #[napi]
+  pub async fn get_doc_history(
+    &self,
+    workspace_id: String,
+    doc_id: String,
+    timestamp_ms: i64,
+  ) -> napi::Result<Option<napi::bindgen_prelude::Buffer>> {
+    let timestamp = DateTime::<Utc>::from_timestamp_millis(timestamp_ms)
+      .ok_or_else(|| RuntimeError::invalid_input(format!("Invalid doc history timestamp: {timestamp_ms}")))?;
+    let pool = self.pool().await?;
+    Ok(
+      load_history_blob(&pool, &workspace_id, &doc_id, timestamp)
+        .await?
+        .map(Into::into),
+    )
+  }
+
+  /// Converts full-blob history rows written before delta encoding, one batch
+  /// of docs at a time. Pass the returned `next_cursor` back in until it is
+  /// `None`.
+  #[napi]
+  pub async fn migrate_doc_history_deltas(
+    &self,
+    cursor: Option<String>,
+    doc_limit: i64,
+  ) -> napi::Result<RuntimeDocHistoryDeltaMigrationResult> {
+    if doc_limit <= 0 {
+      return Err(napi_error("doc history migration limit must be positive"));
+    }
+    let (after_workspace_id, after_doc_id) = decode_migration_cursor(cursor.as_deref())?;
+    let pool = self.pool().await?;
+    let docs = sqlx::query(
+      r#"
+      SELECT DISTINCT workspace_id, guid
+      FROM snapshot_histories
+      WHERE (workspace_id, guid) > ($1, $2)
+      ORDER BY workspace_id, guid
+      LIMIT $3
+      "#,
+    )
+    .bind(&after_workspace_id)
+    .bind(&after_doc_id)
+    .bind(doc_limit)
+    .fetch_all(&pool)
+    .await
+    .map_err(|err| RuntimeError::database("DocHistory migration list docs failed", err))?;
+
+    let mut result = RuntimeDocHistoryDeltaMigrationResult {
+      scanned_docs: 0,
+      converted_rows: 0,
+      next_cursor: None,
+    };
+    let mut last_doc = None;
+    for doc in &docs {
+      let workspace_id: String = doc.get("workspace_id");
+      let doc_id: String = doc.get("guid");
+      result.converted_rows += convert_doc_to_deltas(&pool, &workspace_id, &doc_id).await?;
+      result.scanned_docs += 1;
+      last_doc = Some((workspace_id, doc_id));
+    }
+    if result.scanned_docs == doc_limit {
+      result.next_cursor = last_doc.map(|last_doc| serde_json::json!([last_doc.0, last_doc.1]).to_string());
+    }
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use affine_doc_loader as doc_loader;
+
+  use super::*;
+
+  #[test]
+  fn history_delta_round_trips_against_keyframe() {
+    let keyframe = doc_loader::build_full_doc("History", "first paragraph", "history-doc").unwrap();
+    let doc = load_doc(&keyframe, "keyframe").unwrap();
+    let mut edits = doc.get_or_create_map("history-test").unwrap();
+    edits.insert("edited".to_string(), "second revision").unwrap();
+    let next = doc.encode_update_v1().unwrap();
+
+    let delta = encode_delta(&keyframe, &next)
+      .unwrap()
+      .expect("delta should be smaller");
+    let restored = load_doc(&apply_delta(&keyframe, &delta).unwrap(), "restored").unwrap();
+    assert!(
+      load_doc(&keyframe, "keyframe")
+        .unwrap()
+        .get_or_create_map("history-test")
+        .unwrap()
+        .get("edited")
+        .is_none()
+    );
+    assert!(
+      restored
+        .get_or_create_map("history-test")
+        .unwrap()
+        .get("edited")
+        .is_some()
+    );
+
+    assert!(decode_migration_cursor(Some("not-json")).is_err());
+    assert_eq!(
+      decode_migration_cursor(Some(r#"["ws","doc/with/slash"]"#)).unwrap(),
+      ("ws".to_string(), "doc/with/slash".to_string())
+    );
+  }
+}
@@ -2,7 +2,12 @@ use chrono::{DateTime, Duration, Utc};
 use napi::bindgen_prelude::Buffer;
 use sqlx::{PgPool, Row};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error, types::RuntimeDocHistoryInput};
+use super::{
+  BackendRuntime, RuntimeError, RuntimeResult,
+  doc_history::{HistoryEntry, insert_history},
+  napi_error,
+  types::RuntimeDocHistoryInput,
+};
 
 fn is_empty_doc(bin: &[u8]) -> bool {
   bin.is_empty() || (bin.len() == 1 && bin[0] == 0) || (bin.len() == 2 && bin[0] == 0 && bin[1] == 0)
@@ -104,25 +109,25 @@ impl BackendRuntime {
     }
 
     let expired_at = Utc::now() + Duration::milliseconds(input.history_max_age_ms);
-    sqlx::query(
-      r#"
-      INSERT INTO snapshot_histories
-        (workspace_id, guid, timestamp, blob, expired_at, created_by)
-      VALUES
-        ($1, $2, $3, $4, $5, $6)
-      ON CONFLICT (workspace_id, guid, timestamp)
-      DO UPDATE SET expired_at = EXCLUDED.expired_at
-      "#,
+    let mut tx = pool
+      .begin()
+      .await
+      .map_err(|err| RuntimeError::database("DocStorage create history begin transaction failed", err))?;
+    insert_history(
+      &mut tx,
+      HistoryEntry {
+        workspace_id: &input.workspace_id,
+        doc_id: &input.doc_id,
+        timestamp,
+        blob: input.blob.as_ref(),
+        expired_at,
+        created_by: input.editor_id.as_deref(),
+      },
     )
-    .bind(&input.workspace_id)
-    .bind(&input.doc_id)
-    .bind(timestamp)
-    .bind(input.blob.as_ref())
-    .bind(expired_at)
-    .bind(input.editor_id.as_deref())
-    .execute(&pool)
-    .await
-    .map_err(|err| RuntimeError::database("DocStorage create history failed", err))?;
+    .await?;
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("DocStorage create history commit failed", err))?;
 
     Ok(true)
   }
@@ -1,71 +1,122 @@
 use napi::Result;
-use sqlx::PgPool;
+use sqlx::{PgPool, SqlitePool};
 
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
+use super::{BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, database::sqlite_now_ms, napi_error};
 
-struct RuntimeGateStore {
-  pool: PgPool,
+pub(super) struct RuntimeGateStore {
+  database: RuntimeDatabase,
 }
 
 impl RuntimeGateStore {
-  fn new(pool: PgPool) -> Self {
-    Self { pool }
+  pub(super) fn new(database: RuntimeDatabase) -> Self {
+    Self { database }
   }
 
-  async fn put_if_absent(&self, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
-    let mut tx = self
-      .pool
-      .begin()
-      .await
-      .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+  pub(super) async fn put_if_absent(&self, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
+    match &self.database {
+      RuntimeDatabase::Postgres(pool) => put_if_absent_postgres(pool, key, ttl_ms).await,
+      RuntimeDatabase::Sqlite(pool) => put_if_absent_sqlite(pool, key, ttl_ms).await,
+    }
+  }
 
-    sqlx::query("DELETE FROM runtime_gates WHERE key = $1 AND expires_at <= CURRENT_TIMESTAMP")
-      .bind(key)
-      .execute(&mut *tx)
+  async fn cleanup_expired(&self, limit: i64) -> RuntimeResult<i64> {
+    let rows_affected = match &self.database {
+      RuntimeDatabase::Postgres(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_gates
+        WHERE key IN (
+          SELECT key FROM runtime_gates
+          WHERE expires_at <= CURRENT_TIMESTAMP
+          ORDER BY expires_at ASC
+          LIMIT $1
+        )
+        "#,
+      )
+      .bind(limit)
+      .execute(pool)
+      .await
+      .map(|result| result.rows_affected()),
+      RuntimeDatabase::Sqlite(pool) => sqlx::query(
+        r#"
+        DELETE FROM runtime_gates
+        WHERE key IN (
+          SELECT key FROM runtime_gates
+          WHERE expires_at <= ?2
+          ORDER BY expires_at ASC
+          LIMIT ?1
+        )
+        "#,
+      )
+      .bind(limit)
+      .bind(sqlite_now_ms())
+      .execute(pool)
       .await
-      .map_err(|err| RuntimeError::database("RuntimeGate expired cleanup failed", err))?;
+      .map(|result| result.rows_affected()),
+    }
+    .map_err(|err| RuntimeError::database("RuntimeGate cleanup failed", err))?;
+
+    Ok(rows_affected as i64)
+  }
+}
 
-    let inserted = sqlx::query(
-      r#"
-      INSERT INTO runtime_gates (key, expires_at)
-      VALUES ($1, CURRENT_TIMESTAMP + ($2 * INTERVAL '1 millisecond'))
-      ON CONFLICT (key) DO NOTHING
-      "#,
-    )
+async fn put_if_absent_postgres(pool: &PgPool, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeGate transaction failed", err))?;
+
+  sqlx::query("DELETE FROM runtime_gates WHERE key = $1 AND expires_at <= CURRENT_TIMESTAMP")
     .bind(key)
-    .bind(ttl_ms as f64)
     .execute(&mut *tx)
     .await
-    .map_err(|err| RuntimeError::database("RuntimeGate put_if_absent failed", err))?
-    .rows_affected()
-      == 1;
-
-    tx.commit()
-      .await
-      .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
+    .map_err(|err| RuntimeError::database("RuntimeGate expired cleanup failed", err))?;
 
-    Ok(inserted)
-  }
+  let inserted = sqlx::query(
+    r#"
+    INSERT INTO runtime_gates (key, expires_at)
+    VALUES ($1, CURRENT_TIMESTAMP + ($2 * INTERVAL '1 millisecond'))
+    ON CONFLICT (key) DO NOTHING
+    "#,
+  )
+  .bind(key)
+  .bind(ttl_ms as f64)
+  .execute(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("RuntimeGate put_if_absent failed", err))?
+  .rows_affected()
+    == 1;
 
-  async fn cleanup_expired(&self, limit: i64) -> RuntimeResult<i64> {
-    let result = sqlx::query(
-      r#"
-      DELETE FROM runtime_gates
-      WHERE key IN (
-        SELECT key FROM runtime_gates
-        WHERE expires_at <= CURRENT_TIMESTAMP
-        ORDER BY expires_at ASC
-        LIMIT $1
-      )
-      "#,
-    )
-    .bind(limit)
-    .execute(&self.pool)
+  tx.commit()
     .await
-    .map_err(|err| RuntimeError::database("RuntimeGate cleanup failed", err))?;
+    .map_err(|err| RuntimeError::database("RuntimeGate transaction commit failed", err))?;
 
-    Ok(result.rows_affected() as i64)
-  }
+  Ok(inserted)
+}
+
+async fn put_if_absent_sqlite(pool: &SqlitePool, key: &str, ttl_ms: i64) -> RuntimeResult<bool> {
+  // A single upsert is enough here: the pool holds one connection, and the
+  // conflict branch only replaces a gate whose ttl already elapsed.
+  let now_ms = sqlite_now_ms();
+  let inserted = sqlx::query(
+    r#"
+    INSERT INTO runtime_gates (key, expires_at, created_at)
+    VALUES (?1, ?3 + ?2, ?3)
+    ON CONFLICT (key) DO UPDATE
+      SET expires_at = excluded.expires_at,
+          created_at = excluded.created_at
+      WHERE runtime_gates.expires_at <= ?3
+    "#,
+  )
+  .bind(key)
+  .bind(ttl_ms)
+  .bind(now_ms)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("RuntimeGate put_if_absent failed", err))?
+  .rows_affected()
+    == 1;
+
+  Ok(inserted)
 }
 
 #[napi_derive::napi]
@@ -75,7 +126,7 @@ impl BackendRuntime {
     if ttl_ms <= 0 {
       return Err(napi_error("runtime gate ttl must be positive"));
     }
-    RuntimeGateStore::new(self.pool().await?)
+    RuntimeGateStore::new(self.database().await?)
       .put_if_absent(&key, ttl_ms)
       .await
       .map_err(napi::Error::from)
@@ -86,7 +137,7 @@ impl BackendRuntime {
     if limit <= 0 {
       return Err(napi_error("runtime gate cleanup limit must be positive"));
     }
-    RuntimeGateStore::new(self.pool().await?)
+    RuntimeGateStore::new(self.database().await?)
       .cleanup_expired(limit)
       .await
       .map_err(napi::Error::from)
@@ -3,6 +3,9 @@ use sqlx::PgPool;
 
 use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
 
+/// Days a doc compaction divergence is kept for investigation.
+const DOC_COMPACTION_DIAGNOSTICS_RETENTION_DAYS: i32 = 30;
+
 struct HousekeepingStore {
   pool: PgPool,
 }
@@ -38,8 +41,16 @@ impl HousekeepingStore {
       DELETE FROM snapshot_histories
       WHERE (workspace_id, guid, timestamp) IN (
         SELECT workspace_id, guid, timestamp
-        FROM snapshot_histories
+        FROM snapshot_histories keyframe
         WHERE expired_at <= CURRENT_TIMESTAMP
+          AND NOT EXISTS (
+            SELECT 1
+            FROM snapshot_histories delta
+            WHERE delta.workspace_id = keyframe.workspace_id
+              AND delta.guid = keyframe.guid
+              AND delta.base_timestamp = keyframe.timestamp
+              AND delta.expired_at > CURRENT_TIMESTAMP
+          )
         ORDER BY expired_at ASC
         LIMIT $1
       )
@@ -52,6 +63,27 @@ impl HousekeepingStore {
 
     Ok(result.rows_affected() as i64)
   }
+
+  async fn cleanup_expired_doc_compaction_diagnostics(&self, limit: i64) -> RuntimeResult<i64> {
+    let result = sqlx::query(
+      r#"
+      DELETE FROM doc_compaction_diagnostics
+      WHERE id IN (
+        SELECT id FROM doc_compaction_diagnostics
+        WHERE created_at <= CURRENT_TIMESTAMP - make_interval(days => $1)
+        ORDER BY created_at ASC
+        LIMIT $2
+      )
+      "#,
+    )
+    .bind(DOC_COMPACTION_DIAGNOSTICS_RETENTION_DAYS)
+    .bind(limit)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("Housekeeping doc compaction diagnostics cleanup failed", err))?;
+
+    Ok(result.rows_affected() as i64)
+  }
 }
 
 #[napi_derive::napi]
@@ -79,4 +111,16 @@ impl BackendRuntime {
       .await
       .map_err(napi::Error::from)
   }
+
+  #[napi]
+  pub async fn cleanup_expired_doc_compaction_diagnostics(&self, limit: i64) -> Result<i64> {
+    if limit <= 0 {
+      return Err(napi_error("doc compaction diagnostics cleanup limit must be positive"));
+    }
+
+    HousekeepingStore::new(self.pool().await?)
+      .cleanup_expired_doc_compaction_diagnostics(limit)
+      .await
+      .map_err(napi::Error::from)
+  }
 }
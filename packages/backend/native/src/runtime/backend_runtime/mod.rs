@@ -1,6 +1,9 @@
+mod challenge;
 mod constants;
 mod coordination_lease;
+mod database;
 mod doc_compactor;
+mod doc_history;
 mod doc_storage;
 mod gate;
 mod housekeeping;
@@ -9,18 +12,24 @@ mod runtime_state;
 #[cfg(test)]
 mod tests;
 mod workspace_stats;
-use std::{sync::RwLock, time::Duration};
+use std::sync::RwLock;
 
 use napi::Result;
 use sha2::{Digest, Sha256};
-use sqlx::{PgPool, Row, postgres::PgPoolOptions};
+use sqlx::PgPool;
 use tokio::sync::Mutex;
 
-use self::types::BackendRuntimeHealth;
+pub(crate) use self::database::RuntimeDatabase;
+use self::types::{BackendRuntimeHealth, RuntimeMigrationPlan};
 pub(crate) use super::types;
 pub(super) use super::{
-  BackendRuntimeConfig, InviteQuotaConfig, RuntimeError, RuntimeResult, migrations::migrate_runtime_tables, napi_error,
-  to_napi_error,
+  BackendRuntimeConfig, InviteQuotaConfig, RuntimeError, RuntimeResult,
+  migrations::{
+    RUNTIME_MIGRATIONS, RUNTIME_SQLITE_MIGRATIONS, applied_runtime_migrations, applied_sqlite_runtime_migrations,
+    migrate_runtime_tables, migrate_sqlite_runtime_tables, plan_runtime_migrations, revert_runtime_tables,
+    revert_sqlite_runtime_tables,
+  },
+  napi_error, to_napi_error,
 };
 
 pub(super) fn token_hash(token: &str) -> String {
@@ -30,16 +39,17 @@ pub(super) fn token_hash(token: &str) -> String {
 #[napi_derive::napi]
 pub struct BackendRuntime {
   config: RwLock<BackendRuntimeConfig>,
-  pool: Mutex<Option<PgPool>>,
+  database: Mutex<Option<RuntimeDatabase>>,
 }
 
 #[napi_derive::napi]
 impl BackendRuntime {
   #[napi(constructor)]
   pub fn new() -> Result<Self> {
+    let config = BackendRuntimeConfig::from_config_files().map_err(to_napi_error)?;
     Ok(Self {
-      config: RwLock::new(BackendRuntimeConfig::from_config_files().map_err(to_napi_error)?),
-      pool: Mutex::new(None),
+      config: RwLock::new(config),
+      database: Mutex::new(None),
     })
   }
 
@@ -49,72 +59,109 @@ impl BackendRuntime {
   }
 
   async fn start_inner(&self) -> RuntimeResult<()> {
-    let mut guard = self.pool.lock().await;
+    let mut guard = self.database.lock().await;
     if guard.is_some() {
       return Ok(());
     }
 
     let database_url = self.config()?.database_url;
-    let pool = PgPoolOptions::new()
-      .max_connections(5)
-      .acquire_timeout(Duration::from_secs(5))
-      .connect(&database_url)
-      .await
-      .map_err(|err| RuntimeError::database("BackendRuntime failed to connect postgres", err))?;
-
-    sqlx::query("SELECT 1")
-      .execute(&pool)
-      .await
-      .map_err(|err| RuntimeError::database("BackendRuntime postgres health check failed", err))?;
+    let database = RuntimeDatabase::connect(&database_url).await?;
+    if !database.ping().await {
+      database.close().await;
+      return Err(RuntimeError::invalid_state(
+        "BackendRuntime database health check failed",
+      ));
+    }
 
-    let config = self.config()?.with_db_overrides(&pool).await?;
-    self.update_config(config)?;
+    // App config overrides live in the Postgres application schema; an
+    // embedded SQLite runtime only uses file config.
+    if let RuntimeDatabase::Postgres(pool) = &database {
+      let config = self.config()?.with_db_overrides(pool).await?;
+      self.update_config(config)?;
+    }
 
-    *guard = Some(pool);
+    *guard = Some(database);
     Ok(())
   }
 
   #[napi]
   pub async fn stop(&self) -> Result<()> {
-    let pool = self.pool.lock().await.take();
-    if let Some(pool) = pool {
-      pool.close().await;
+    let database = self.database.lock().await.take();
+    if let Some(database) = database {
+      database.close().await;
     }
     Ok(())
   }
 
   #[napi]
   pub async fn health(&self) -> Result<BackendRuntimeHealth> {
-    let pool = self.pool.lock().await.as_ref().cloned();
-    let database_connected = match pool.as_ref() {
-      Some(pool) => sqlx::query("SELECT 1")
-        .fetch_one(pool)
-        .await
-        .map(|row| row.try_get::<i32, _>(0).unwrap_or(0) == 1)
-        .unwrap_or(false),
+    let database = self.database.lock().await.as_ref().cloned();
+    let database_connected = match database.as_ref() {
+      Some(database) => database.ping().await,
       None => false,
     };
 
     Ok(BackendRuntimeHealth {
-      started: pool.is_some(),
+      started: database.is_some(),
       database_connected,
     })
   }
 
   #[napi]
   pub async fn run_migrations(&self) -> Result<()> {
-    let pool = self.pool().await?;
-    migrate_runtime_tables(&pool).await.map_err(to_napi_error)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => migrate_runtime_tables(&pool).await,
+      RuntimeDatabase::Sqlite(pool) => migrate_sqlite_runtime_tables(&pool).await,
+    }
+    .map_err(to_napi_error)
   }
 
-  pub(crate) async fn pool(&self) -> RuntimeResult<PgPool> {
+  /// Reports applied and pending runtime migrations without changing the
+  /// database.
+  #[napi]
+  pub async fn plan_migrations(&self) -> Result<RuntimeMigrationPlan> {
+    self.migration_plan().await.map_err(to_napi_error)
+  }
+
+  /// Rolls back every applied migration newer than `target_version`. Pass `0`
+  /// to drop all runtime tables.
+  #[napi]
+  pub async fn revert_migrations(&self, target_version: i64) -> Result<RuntimeMigrationPlan> {
+    if target_version < 0 {
+      return Err(napi_error("migration target version must be non-negative"));
+    }
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => revert_runtime_tables(&pool, target_version).await,
+      RuntimeDatabase::Sqlite(pool) => revert_sqlite_runtime_tables(&pool, target_version).await,
+    }
+    .map_err(to_napi_error)?;
+    self.migration_plan().await.map_err(to_napi_error)
+  }
+
+  async fn migration_plan(&self) -> RuntimeResult<RuntimeMigrationPlan> {
+    Ok(match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => {
+        plan_runtime_migrations(RUNTIME_MIGRATIONS, applied_runtime_migrations(&pool).await?)
+      }
+      RuntimeDatabase::Sqlite(pool) => plan_runtime_migrations(
+        RUNTIME_SQLITE_MIGRATIONS,
+        applied_sqlite_runtime_migrations(&pool).await?,
+      ),
+    })
+  }
+
+  pub(crate) async fn database(&self) -> RuntimeResult<RuntimeDatabase> {
     self
-      .pool
+      .database
       .lock()
       .await
       .as_ref()
       .cloned()
-      .ok_or_else(|| RuntimeError::invalid_state("BackendRuntime must be started before using postgres operations"))
+      .ok_or_else(|| RuntimeError::invalid_state("BackendRuntime must be started before using database operations"))
+  }
+
+  pub(crate) async fn pool(&self) -> RuntimeResult<PgPool> {
+    self.database().await?.postgres()
   }
 
   pub(crate) fn config(&self) -> RuntimeResult<BackendRuntimeConfig> {
@@ -3,6 +3,7 @@ use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 mod invite_abuse_actions;
 mod mail_delivery;
 mod reservation;
+mod sqlite_reservation;
 mod workspace_invite;
 mod workspace_invite_policy;
 
@@ -22,7 +23,7 @@ use workspace_invite_policy::{
 #[cfg(test)]
 pub(super) use super::types::{RuntimeMailDeliveryQuotaMetadataInput, RuntimeMailDeliveryQuotaRecipientInput};
 pub(super) use super::{
-  BackendRuntime, InviteQuotaConfig, RuntimeError, RuntimeResult, napi_error,
+  BackendRuntime, InviteQuotaConfig, RuntimeDatabase, RuntimeError, RuntimeResult, napi_error,
   types::{
     RuntimeInviteAbuseActionRequired, RuntimeInviteAbuseClaimedAction, RuntimeMailDeliveryQuotaDecision,
     RuntimeMailDeliveryQuotaInput, RuntimeQuotaSourceInput, RuntimeQuotaTargetDomainInput,
@@ -90,9 +91,15 @@ impl BackendRuntime {
         requested: Some(1),
       });
     };
-    let pool = self.pool().await?;
     let scopes = build_mail_scopes(&input, class, &config);
-    match reserve_scopes(&pool, "mail_delivery", input.request_id.as_deref(), scopes).await? {
+    let request_id = input.request_id.as_deref();
+    let reservation = match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => reserve_scopes(&pool, "mail_delivery", request_id, scopes).await?,
+      RuntimeDatabase::Sqlite(pool) => {
+        sqlite_reservation::reserve_scopes(&pool, "mail_delivery", request_id, scopes).await?
+      }
+    };
+    match reservation {
       Ok(reservation) => Ok(RuntimeMailDeliveryQuotaDecision {
         allowed: true,
         reservation_id: Some(reservation.reservation_id),
@@ -111,16 +118,21 @@ impl BackendRuntime {
 
   #[napi]
   pub async fn commit_mail_delivery_quota_v1(&self, reservation_id: String) -> Result<bool> {
-    let pool = self.pool().await?;
-    commit_reservation(&pool, &reservation_id, 1, |_, reserved_count| reserved_count.min(1))
-      .await
-      .map_err(Into::into)
+    let settle = |_: &str, reserved_count: i32| reserved_count.min(1);
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => commit_reservation(&pool, &reservation_id, 1, settle).await,
+      RuntimeDatabase::Sqlite(pool) => sqlite_reservation::commit_reservation(&pool, &reservation_id, 1, settle).await,
+    }
+    .map_err(Into::into)
   }
 
   #[napi]
   pub async fn release_mail_delivery_quota_v1(&self, reservation_id: String) -> Result<bool> {
-    let pool = self.pool().await?;
-    release_reservation(&pool, &reservation_id).await.map_err(Into::into)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => release_reservation(&pool, &reservation_id).await,
+      RuntimeDatabase::Sqlite(pool) => sqlite_reservation::release_reservation(&pool, &reservation_id).await,
+    }
+    .map_err(Into::into)
   }
 
   #[napi]
@@ -128,8 +140,11 @@ impl BackendRuntime {
     if limit <= 0 {
       return Err(napi_error("rolling quota cleanup limit must be positive"));
     }
-    let pool = self.pool().await?;
-    cleanup_expired(&pool, limit).await.map_err(Into::into)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => cleanup_expired(&pool, limit).await,
+      RuntimeDatabase::Sqlite(pool) => sqlite_reservation::cleanup_expired(&pool, limit).await,
+    }
+    .map_err(Into::into)
   }
 }
 
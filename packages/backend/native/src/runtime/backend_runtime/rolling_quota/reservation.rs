@@ -5,7 +5,7 @@ use uuid::Uuid;
 
 use super::{RuntimeError, RuntimeResult};
 
-const RESERVATION_TTL_SECONDS: i64 = 120;
+pub(super) const RESERVATION_TTL_SECONDS: i64 = 120;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub(super) struct ScopeLimit {
@@ -40,7 +40,7 @@ pub(super) fn bucket_seconds(window_seconds: i32) -> i64 {
   }
 }
 
-fn bucket_start(now: DateTime<Utc>, bucket_seconds: i64) -> DateTime<Utc> {
+pub(super) fn bucket_start(now: DateTime<Utc>, bucket_seconds: i64) -> DateTime<Utc> {
   let timestamp = now.timestamp();
   Utc
     .timestamp_opt(timestamp - timestamp.rem_euclid(bucket_seconds), 0)
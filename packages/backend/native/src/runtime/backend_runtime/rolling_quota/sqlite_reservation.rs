@@ -0,0 +1,262 @@
+//! SQLite dialect of the rolling quota reservation state machine.
+//!
+//! Mirrors `reservation.rs` with unix-millisecond timestamps. The embedded
+//! runtime pool holds a single connection, so the per-scope advisory locks
+//! used on Postgres are unnecessary: every transaction is already serialized.
+
+use chrono::{DateTime, Utc};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+use super::{
+  RuntimeError, RuntimeResult,
+  reservation::{QuotaViolation, RESERVATION_TTL_SECONDS, ReservationDecision, ScopeLimit, bucket_start},
+};
+
+const MILLIS_PER_SECOND: i64 = 1000;
+
+fn now_ms() -> (DateTime<Utc>, i64) {
+  let now = Utc::now();
+  (now, now.timestamp_millis())
+}
+
+pub(super) async fn reserve_scopes(
+  pool: &SqlitePool,
+  purpose: &str,
+  request_id: Option<&str>,
+  scopes: Vec<ScopeLimit>,
+) -> RuntimeResult<std::result::Result<ReservationDecision, QuotaViolation>> {
+  let (now, now_ms) = now_ms();
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("failed to begin quota reservation transaction", err))?;
+
+  let reservation_id = Uuid::new_v4().to_string();
+  for scope in &scopes {
+    let bucket_start_ms = bucket_start(now, scope.bucket_seconds).timestamp_millis();
+    let window_start_ms = now_ms - scope.window_seconds as i64 * MILLIS_PER_SECOND;
+    let committed: i64 = sqlx::query_scalar(
+      r#"
+      SELECT COALESCE(SUM(count), 0)
+      FROM runtime_rolling_quota_counters
+      WHERE scope_key = ?1
+        AND window_seconds = ?2
+        AND bucket_start >= ?3
+      "#,
+    )
+    .bind(&scope.scope_key)
+    .bind(scope.window_seconds)
+    .bind(window_start_ms)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("failed to read rolling quota counter", err))?;
+    let reserved: i64 = sqlx::query_scalar(
+      r#"
+      SELECT COALESCE(SUM(count), 0)
+      FROM runtime_rolling_quota_reservations
+      WHERE scope_key = ?1
+        AND window_seconds = ?2
+        AND bucket_start >= ?3
+        AND status = 'reserved'
+        AND expires_at > ?4
+      "#,
+    )
+    .bind(&scope.scope_key)
+    .bind(scope.window_seconds)
+    .bind(window_start_ms)
+    .bind(now_ms)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("failed to read rolling quota reservations", err))?;
+    let current = committed.saturating_add(reserved) as i32;
+    if current.saturating_add(scope.requested) > scope.limit {
+      tx.rollback()
+        .await
+        .map_err(|err| RuntimeError::database("failed to rollback rejected quota reservation", err))?;
+      return Ok(Err(QuotaViolation {
+        scope_key: scope.scope_key.clone(),
+        window_seconds: scope.window_seconds,
+        limit: scope.limit,
+        current,
+        requested: scope.requested,
+      }));
+    }
+    sqlx::query(
+      r#"
+      INSERT INTO runtime_rolling_quota_reservations (
+        id, scope_key, window_seconds, bucket_start, count, status, purpose, request_id, expires_at, created_at,
+        updated_at
+      )
+      VALUES (?1, ?2, ?3, ?4, ?5, 'reserved', ?6, ?7, ?8, ?9, ?9)
+      "#,
+    )
+    .bind(&reservation_id)
+    .bind(&scope.scope_key)
+    .bind(scope.window_seconds)
+    .bind(bucket_start_ms)
+    .bind(scope.requested)
+    .bind(purpose)
+    .bind(request_id)
+    .bind(now_ms + RESERVATION_TTL_SECONDS * MILLIS_PER_SECOND)
+    .bind(now_ms)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("failed to create rolling quota reservation", err))?;
+  }
+
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("failed to commit quota reservation", err))?;
+  Ok(Ok(ReservationDecision { reservation_id }))
+}
+
+pub(super) async fn commit_reservation<F>(
+  pool: &SqlitePool,
+  reservation_id: &str,
+  settle_usage: i32,
+  actual_usage_for_scope: F,
+) -> RuntimeResult<bool>
+where
+  F: Fn(&str, i32) -> i32,
+{
+  let reservation_id = Uuid::parse_str(reservation_id)
+    .map_err(|_| RuntimeError::invalid_input("invalid reservation id"))?
+    .to_string();
+  let (_, now_ms) = now_ms();
+  let mut tx = pool
+    .begin()
+    .await
+    .map_err(|err| RuntimeError::database("failed to begin quota commit transaction", err))?;
+  let rows = sqlx::query(
+    r#"
+    SELECT scope_key, window_seconds, bucket_start, count
+    FROM runtime_rolling_quota_reservations
+    WHERE id = ?1 AND status = 'reserved'
+    "#,
+  )
+  .bind(&reservation_id)
+  .fetch_all(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("failed to load quota reservation", err))?;
+  if rows.is_empty() {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("failed to rollback empty quota commit", err))?;
+    return Ok(false);
+  }
+  for row in &rows {
+    let reserved_count: i32 = row.get("count");
+    let scope_key: String = row.get("scope_key");
+    let count = actual_usage_for_scope(&scope_key, reserved_count)
+      .min(reserved_count)
+      .max(0);
+    if count > 0 {
+      let window_seconds: i32 = row.get("window_seconds");
+      sqlx::query(
+        r#"
+        INSERT INTO runtime_rolling_quota_counters (scope_key, window_seconds, bucket_start, count, expires_at, updated_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        ON CONFLICT (scope_key, window_seconds, bucket_start)
+        DO UPDATE SET count = runtime_rolling_quota_counters.count + excluded.count,
+                      expires_at = MAX(runtime_rolling_quota_counters.expires_at, excluded.expires_at),
+                      updated_at = excluded.updated_at
+        "#,
+      )
+      .bind(scope_key)
+      .bind(window_seconds)
+      .bind(row.get::<i64, _>("bucket_start"))
+      .bind(count)
+      .bind(now_ms + window_seconds as i64 * 2 * MILLIS_PER_SECOND)
+      .bind(now_ms)
+      .execute(&mut *tx)
+      .await
+      .map_err(|err| RuntimeError::database("failed to commit rolling quota counter", err))?;
+    }
+  }
+  sqlx::query(
+    r#"
+    UPDATE runtime_rolling_quota_reservations
+    SET status = CASE WHEN ?2 > 0 THEN 'committed' ELSE 'released' END,
+        committed_at = CASE WHEN ?2 > 0 THEN ?3 ELSE committed_at END,
+        released_at = CASE WHEN ?2 <= 0 THEN ?3 ELSE released_at END,
+        updated_at = ?3
+    WHERE id = ?1 AND status = 'reserved'
+    "#,
+  )
+  .bind(&reservation_id)
+  .bind(settle_usage)
+  .bind(now_ms)
+  .execute(&mut *tx)
+  .await
+  .map_err(|err| RuntimeError::database("failed to settle quota reservation", err))?;
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("failed to commit quota commit transaction", err))?;
+  Ok(true)
+}
+
+pub(super) async fn release_reservation(pool: &SqlitePool, reservation_id: &str) -> RuntimeResult<bool> {
+  let reservation_id = Uuid::parse_str(reservation_id)
+    .map_err(|_| RuntimeError::invalid_input("invalid reservation id"))?
+    .to_string();
+  let (_, now_ms) = now_ms();
+  let result = sqlx::query(
+    r#"
+    UPDATE runtime_rolling_quota_reservations
+    SET status = 'released',
+        released_at = ?2,
+        updated_at = ?2
+    WHERE id = ?1 AND status = 'reserved'
+    "#,
+  )
+  .bind(reservation_id)
+  .bind(now_ms)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("failed to release quota reservation", err))?;
+  Ok(result.rows_affected() > 0)
+}
+
+pub(super) async fn cleanup_expired(pool: &SqlitePool, limit: i64) -> RuntimeResult<i64> {
+  let (_, now_ms) = now_ms();
+  let released = sqlx::query(
+    r#"
+    UPDATE runtime_rolling_quota_reservations
+    SET status = 'expired',
+        updated_at = ?2
+    WHERE rowid IN (
+      SELECT rowid
+      FROM runtime_rolling_quota_reservations
+      WHERE status = 'reserved' AND expires_at <= ?2
+      LIMIT ?1
+    )
+    "#,
+  )
+  .bind(limit)
+  .bind(now_ms)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("failed to expire rolling quota reservations", err))?
+  .rows_affected() as i64;
+
+  let counters = sqlx::query(
+    r#"
+    DELETE FROM runtime_rolling_quota_counters
+    WHERE rowid IN (
+      SELECT rowid
+      FROM runtime_rolling_quota_counters
+      WHERE expires_at <= ?2
+      LIMIT ?1
+    )
+    "#,
+  )
+  .bind(limit)
+  .bind(now_ms)
+  .execute(pool)
+  .await
+  .map_err(|err| RuntimeError::database("failed to delete expired rolling quota counters", err))?
+  .rows_affected() as i64;
+
+  Ok(released + counters)
+}
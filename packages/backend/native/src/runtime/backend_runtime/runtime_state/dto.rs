@@ -187,6 +187,29 @@ impl RuntimeStateRows {
     Ok(row.map(payload_row))
   }
 
+  pub(super) async fn payload_with_expires(
+    &self,
+    purpose: &str,
+    token: &str,
+    context: &str,
+  ) -> Result<Option<RuntimeStatePayloadRow>> {
+    let row = sqlx::query(
+      r#"
+      SELECT payload, (EXTRACT(EPOCH FROM expires_at) * 1000)::BIGINT AS expires_at_ms
+      FROM runtime_states
+      WHERE purpose = $1
+        AND token_hash = $2
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database(context, err))?;
+
+    Ok(row.map(payload_row))
+  }
+
   pub(super) async fn consume_payload(
     &self,
     purpose: &str,
@@ -381,6 +404,59 @@ impl RuntimeStateRows {
     Ok(())
   }
 
+  pub(super) async fn update_payload_in_tx(
+    &self,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    purpose: &str,
+    token: &str,
+    payload: &serde_json::Value,
+    context: &str,
+  ) -> Result<()> {
+    sqlx::query(
+      r#"
+      UPDATE runtime_states
+      SET payload = $3,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE purpose = $1
+        AND token_hash = $2
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .bind(payload)
+    .execute(&mut **tx)
+    .await
+    .map_err(|err| RuntimeError::database(context, err))?;
+
+    Ok(())
+  }
+
+  pub(super) async fn consume_by_key_in_tx(
+    &self,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    purpose: &str,
+    token: &str,
+    context: &str,
+  ) -> Result<()> {
+    sqlx::query(
+      r#"
+      UPDATE runtime_states
+      SET consumed_at = CURRENT_TIMESTAMP,
+          updated_at = CURRENT_TIMESTAMP
+      WHERE purpose = $1
+        AND token_hash = $2
+        AND consumed_at IS NULL
+      "#,
+    )
+    .bind(purpose)
+    .bind(token_hash(token))
+    .execute(&mut **tx)
+    .await
+    .map_err(|err| RuntimeError::database(context, err))?;
+
+    Ok(())
+  }
+
   pub(super) async fn delete_by_key_in_tx(
     &self,
     tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
@@ -1,13 +1,14 @@
-use super::{BackendRuntime, RuntimeError, RuntimeResult, napi_error};
+use super::{BackendRuntime, RuntimeDatabase, RuntimeError, RuntimeResult, napi_error};
 pub(super) use super::{
   constants::{
-    BYOK_LOCAL_LEASE_ACTIVE_PURPOSE, BYOK_LOCAL_LEASE_PURPOSE, MAGIC_LINK_OTP_PURPOSE, MAX_MAGIC_LINK_OTP_ATTEMPTS,
-    WORKSPACE_INVITE_LINK_ID_PURPOSE, WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
+    AUTH_REFRESH_TOKEN_FAMILY_PURPOSE, AUTH_REFRESH_TOKEN_PURPOSE, BYOK_LOCAL_LEASE_ACTIVE_PURPOSE,
+    BYOK_LOCAL_LEASE_PURPOSE, MAGIC_LINK_OTP_PURPOSE, MAX_MAGIC_LINK_OTP_ATTEMPTS, WORKSPACE_INVITE_LINK_ID_PURPOSE,
+    WORKSPACE_INVITE_LINK_WORKSPACE_PURPOSE,
   },
   token_hash,
   types::{
-    RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeVerificationTokenRecord,
-    RuntimeWorkspaceInviteLinkRecord,
+    RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeRefreshTokenFamilyRecord,
+    RuntimeRefreshTokenRotateResult, RuntimeVerificationTokenRecord, RuntimeWorkspaceInviteLinkRecord,
   },
 };
 
@@ -16,8 +17,11 @@ mod byok_local_lease;
 mod dto;
 mod invite_link;
 mod magic_link_otp;
+mod refresh_token_family;
+mod sqlite_store;
 mod store;
 mod verification_token;
+use sqlite_store::SqliteRuntimeStateStore;
 use store::RuntimeStateStore;
 
 pub(super) type Result<T> = RuntimeResult<T>;
@@ -43,18 +47,32 @@ impl BackendRuntime {
     if ttl_ms <= 0 {
       return Err(napi_error("auth challenge ttl must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
-      .create_auth_challenge(&purpose, &token, payload, ttl_ms)
-      .await
-      .map_err(napi::Error::from)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => {
+        RuntimeStateStore::new(pool)
+          .create_auth_challenge(&purpose, &token, payload, ttl_ms)
+          .await
+      }
+      RuntimeDatabase::Sqlite(pool) => {
+        SqliteRuntimeStateStore::new(pool)
+          .create_auth_challenge(&purpose, &token, payload, ttl_ms)
+          .await
+      }
+    }
+    .map_err(napi::Error::from)
   }
 
   #[napi]
   pub async fn get_auth_challenge(&self, purpose: String, token: String) -> napi::Result<Option<serde_json::Value>> {
-    RuntimeStateStore::new(self.pool().await?)
-      .get_auth_challenge(&purpose, &token)
-      .await
-      .map_err(napi::Error::from)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => RuntimeStateStore::new(pool).get_auth_challenge(&purpose, &token).await,
+      RuntimeDatabase::Sqlite(pool) => {
+        SqliteRuntimeStateStore::new(pool)
+          .get_auth_challenge(&purpose, &token)
+          .await
+      }
+    }
+    .map_err(napi::Error::from)
   }
 
   #[napi]
@@ -63,10 +81,19 @@ impl BackendRuntime {
     purpose: String,
     token: String,
   ) -> napi::Result<Option<serde_json::Value>> {
-    RuntimeStateStore::new(self.pool().await?)
-      .consume_auth_challenge(&purpose, &token)
-      .await
-      .map_err(napi::Error::from)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => {
+        RuntimeStateStore::new(pool)
+          .consume_auth_challenge(&purpose, &token)
+          .await
+      }
+      RuntimeDatabase::Sqlite(pool) => {
+        SqliteRuntimeStateStore::new(pool)
+          .consume_auth_challenge(&purpose, &token)
+          .await
+      }
+    }
+    .map_err(napi::Error::from)
   }
 
   #[napi]
@@ -219,15 +246,54 @@ impl BackendRuntime {
       .map_err(napi::Error::from)
   }
 
+  #[napi]
+  pub async fn issue_refresh_token_family(
+    &self,
+    user_id: String,
+    auth_session_id: String,
+    ttl_ms: i64,
+    family_ttl_ms: i64,
+  ) -> napi::Result<RuntimeRefreshTokenFamilyRecord> {
+    RuntimeStateStore::new(self.pool().await?)
+      .issue_refresh_token_family(user_id, auth_session_id, ttl_ms, family_ttl_ms)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn rotate_refresh_token(
+    &self,
+    token: String,
+    ttl_ms: i64,
+  ) -> napi::Result<RuntimeRefreshTokenRotateResult> {
+    RuntimeStateStore::new(self.pool().await?)
+      .rotate_refresh_token(token, ttl_ms)
+      .await
+      .map_err(napi::Error::from)
+  }
+
+  #[napi]
+  pub async fn revoke_refresh_token_family(&self, family_id: String) -> napi::Result<bool> {
+    RuntimeStateStore::new(self.pool().await?)
+      .revoke_refresh_token_family(family_id)
+      .await
+      .map_err(napi::Error::from)
+  }
+
   #[napi]
   pub async fn cleanup_expired_runtime_states(&self, limit: i64) -> napi::Result<i64> {
     if limit <= 0 {
       return Err(napi_error("runtime state cleanup limit must be positive"));
     }
-    RuntimeStateStore::new(self.pool().await?)
-      .cleanup_expired_runtime_states(limit)
-      .await
-      .map_err(napi::Error::from)
+    match self.database().await? {
+      RuntimeDatabase::Postgres(pool) => RuntimeStateStore::new(pool).cleanup_expired_runtime_states(limit).await,
+      RuntimeDatabase::Sqlite(pool) => {
+        SqliteRuntimeStateStore::new(pool)
+          .cleanup_expired_runtime_states(limit)
+          .await
+      }
+    }
+    .map_err(napi::Error::from)
   }
 }
 
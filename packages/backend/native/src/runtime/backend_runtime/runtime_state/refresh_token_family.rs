@@ -0,0 +1,263 @@
+use super::{
+  AUTH_REFRESH_TOKEN_FAMILY_PURPOSE, AUTH_REFRESH_TOKEN_PURPOSE, Result, RuntimeError, RuntimeRefreshTokenFamilyRecord,
+  RuntimeRefreshTokenRotateResult,
+  dto::{RuntimeStateInsertPayload, RuntimeStateRows},
+};
+use crate::auth_session::{create_auth_session_refresh_token, parse_auth_session_refresh_token};
+
+impl RuntimeRefreshTokenRotateResult {
+  fn ok(refresh_token: RuntimeRefreshTokenFamilyRecord) -> Self {
+    Self {
+      ok: true,
+      reason: None,
+      family_id: Some(refresh_token.family_id.clone()),
+      user_id: Some(refresh_token.user_id.clone()),
+      auth_session_id: Some(refresh_token.auth_session_id.clone()),
+      refresh_token: Some(refresh_token),
+    }
+  }
+
+  fn fail(reason: &'static str, family: Option<&FamilyState>) -> Self {
+    Self {
+      ok: false,
+      reason: Some(reason.to_string()),
+      family_id: family.map(|family| family.family_id.clone()),
+      user_id: family.map(|family| family.user_id.clone()),
+      auth_session_id: family.map(|family| family.auth_session_id.clone()),
+      refresh_token: None,
+    }
+  }
+}
+
+struct FamilyState {
+  family_id: String,
+  user_id: String,
+  auth_session_id: String,
+  generation: i64,
+}
+
+impl FamilyState {
+  fn from_payload(payload: &serde_json::Value) -> Result<Self> {
+    let field = |name: &str| {
+      payload
+        .get(name)
+        .and_then(serde_json::Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| RuntimeError::invalid_state(format!("RuntimeState refresh token payload missing {name}")))
+    };
+    Ok(Self {
+      family_id: field("familyId")?,
+      user_id: field("userId")?,
+      auth_session_id: field("authSessionId")?,
+      generation: payload
+        .get("generation")
+        .and_then(serde_json::Value::as_i64)
+        .ok_or_else(|| RuntimeError::invalid_state("RuntimeState refresh token payload missing generation"))?,
+    })
+  }
+
+  fn payload(&self) -> serde_json::Value {
+    serde_json::json!({
+      "familyId": self.family_id,
+      "userId": self.user_id,
+      "authSessionId": self.auth_session_id,
+      "generation": self.generation,
+    })
+  }
+}
+
+pub(super) async fn issue(
+  rows: &RuntimeStateRows,
+  user_id: String,
+  auth_session_id: String,
+  ttl_ms: i64,
+  family_ttl_ms: i64,
+) -> Result<RuntimeRefreshTokenFamilyRecord> {
+  if ttl_ms <= 0 || family_ttl_ms <= 0 {
+    return Err(RuntimeError::invalid_input("refresh token ttl must be positive"));
+  }
+
+  let family = FamilyState {
+    family_id: uuid::Uuid::new_v4().to_string(),
+    user_id,
+    auth_session_id,
+    generation: 0,
+  };
+
+  let mut tx = rows.begin("RuntimeState refresh token family").await?;
+  let family_expires_at_ms = rows
+    .insert_payload_returning_expires_in_tx(
+      &mut tx,
+      RuntimeStateInsertPayload {
+        purpose: AUTH_REFRESH_TOKEN_FAMILY_PURPOSE,
+        token: &family.family_id,
+        lookup_key: &family.user_id,
+        payload: &family.payload(),
+        ttl_ms: family_ttl_ms,
+        context: "RuntimeState refresh token family create",
+      },
+    )
+    .await?;
+  let record = insert_token(rows, &mut tx, &family, ttl_ms.min(family_ttl_ms), family_expires_at_ms).await?;
+
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState refresh token family transaction commit failed", err))?;
+
+  Ok(record)
+}
+
+/// Exchanges a refresh token for its successor in the same family.
+///
+/// Presenting any token other than the latest generation revokes the whole
+/// family, since it means a rotated token has leaked and been replayed.
+pub(super) async fn rotate(
+  rows: &RuntimeStateRows,
+  token: String,
+  ttl_ms: i64,
+) -> Result<RuntimeRefreshTokenRotateResult> {
+  if ttl_ms <= 0 {
+    return Err(RuntimeError::invalid_input("refresh token ttl must be positive"));
+  }
+
+  let Some(parsed) = parse_auth_session_refresh_token(token) else {
+    return Ok(RuntimeRefreshTokenRotateResult::fail("invalid", None));
+  };
+  let Some(token_row) = rows
+    .payload_with_expires(
+      AUTH_REFRESH_TOKEN_PURPOSE,
+      &parsed.id,
+      "RuntimeState refresh token lookup",
+    )
+    .await?
+  else {
+    return Ok(RuntimeRefreshTokenRotateResult::fail("invalid", None));
+  };
+  if token_row.payload.get("secretHash").and_then(serde_json::Value::as_str) != Some(parsed.secret_hash.as_str()) {
+    return Ok(RuntimeRefreshTokenRotateResult::fail("invalid", None));
+  }
+  let presented = FamilyState::from_payload(&token_row.payload)?;
+
+  let mut tx = rows.begin("RuntimeState refresh token rotate").await?;
+  sqlx::query("SELECT pg_advisory_xact_lock(hashtextextended($1, 0))")
+    .bind(&presented.family_id)
+    .execute(&mut *tx)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState refresh token family lock failed", err))?;
+
+  let Some(family_row) = rows
+    .unconsumed_row_for_update_in_tx(
+      &mut tx,
+      AUTH_REFRESH_TOKEN_FAMILY_PURPOSE,
+      &presented.family_id,
+      "RuntimeState refresh token family lookup",
+    )
+    .await?
+  else {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState refresh token transaction rollback failed", err))?;
+    return Ok(RuntimeRefreshTokenRotateResult::fail("revoked", Some(&presented)));
+  };
+  let mut family = FamilyState::from_payload(&family_row.payload)?;
+
+  if presented.generation != family.generation {
+    rows
+      .consume_by_key_in_tx(
+        &mut tx,
+        AUTH_REFRESH_TOKEN_FAMILY_PURPOSE,
+        &family.family_id,
+        "RuntimeState refresh token family revoke",
+      )
+      .await?;
+    tx.commit()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState refresh token transaction commit failed", err))?;
+    return Ok(RuntimeRefreshTokenRotateResult::fail("reused", Some(&family)));
+  }
+
+  let now = chrono::Utc::now();
+  let family_remaining_ms = (family_row.expires_at - now).num_milliseconds();
+  if family_remaining_ms <= 0 || token_row.expires_at_ms <= now.timestamp_millis() {
+    tx.rollback()
+      .await
+      .map_err(|err| RuntimeError::database("RuntimeState refresh token transaction rollback failed", err))?;
+    return Ok(RuntimeRefreshTokenRotateResult::fail("expired", Some(&family)));
+  }
+
+  family.generation += 1;
+  rows
+    .update_payload_in_tx(
+      &mut tx,
+      AUTH_REFRESH_TOKEN_FAMILY_PURPOSE,
+      &family.family_id,
+      &family.payload(),
+      "RuntimeState refresh token family advance",
+    )
+    .await?;
+  let record = insert_token(
+    rows,
+    &mut tx,
+    &family,
+    ttl_ms.min(family_remaining_ms),
+    family_row.expires_at.timestamp_millis(),
+  )
+  .await?;
+
+  tx.commit()
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState refresh token transaction commit failed", err))?;
+
+  Ok(RuntimeRefreshTokenRotateResult::ok(record))
+}
+
+pub(super) async fn revoke(rows: &RuntimeStateRows, family_id: String) -> Result<bool> {
+  Ok(
+    rows
+      .consume_payload(
+        AUTH_REFRESH_TOKEN_FAMILY_PURPOSE,
+        &family_id,
+        "RuntimeState refresh token family revoke",
+      )
+      .await?
+      .is_some(),
+  )
+}
+
+async fn insert_token(
+  rows: &RuntimeStateRows,
+  tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+  family: &FamilyState,
+  ttl_ms: i64,
+  family_expires_at_ms: i64,
+) -> Result<RuntimeRefreshTokenFamilyRecord> {
+  let refresh_token = create_auth_session_refresh_token();
+  let mut payload = family.payload();
+  payload["secretHash"] = serde_json::Value::String(refresh_token.secret_hash);
+
+  // Rotated tokens keep their row until it expires so a replay can still be
+  // traced back to its family.
+  let expires_at_ms = rows
+    .insert_payload_returning_expires_in_tx(
+      tx,
+      RuntimeStateInsertPayload {
+        purpose: AUTH_REFRESH_TOKEN_PURPOSE,
+        token: &refresh_token.id,
+        lookup_key: &family.family_id,
+        payload: &payload,
+        ttl_ms,
+        context: "RuntimeState refresh token create",
+      },
+    )
+    .await?;
+
+  Ok(RuntimeRefreshTokenFamilyRecord {
+    token: refresh_token.token,
+    family_id: family.family_id.clone(),
+    generation: family.generation,
+    user_id: family.user_id.clone(),
+    auth_session_id: family.auth_session_id.clone(),
+    expires_at_ms,
+    family_expires_at_ms,
+  })
+}
@@ -0,0 +1,117 @@
+use sqlx::{Row, SqlitePool};
+
+use super::{super::database::sqlite_now_ms, Result, RuntimeError, auth_challenge_purpose, token_hash};
+
+/// Embedded counterpart of `RuntimeStateStore`.
+///
+/// Only auth challenges are stored here; verification tokens, magic link
+/// OTPs, invite links and BYOK leases depend on Postgres row locking and stay
+/// on the Postgres store.
+pub(super) struct SqliteRuntimeStateStore {
+  pool: SqlitePool,
+}
+
+impl SqliteRuntimeStateStore {
+  pub(super) fn new(pool: SqlitePool) -> Self {
+    Self { pool }
+  }
+
+  pub(super) async fn create_auth_challenge(
+    &self,
+    purpose: &str,
+    token: &str,
+    payload: serde_json::Value,
+    ttl_ms: i64,
+  ) -> Result<bool> {
+    let now_ms = sqlite_now_ms();
+    let inserted = sqlx::query(
+      r#"
+      INSERT INTO runtime_states (purpose, token_hash, lookup_key, payload, expires_at, created_at, updated_at)
+      VALUES (?1, ?2, NULL, ?3, ?5 + ?4, ?5, ?5)
+      ON CONFLICT (purpose, token_hash) DO NOTHING
+      "#,
+    )
+    .bind(auth_challenge_purpose(purpose))
+    .bind(token_hash(token))
+    .bind(payload.to_string())
+    .bind(ttl_ms)
+    .bind(now_ms)
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState auth challenge create", err))?
+    .rows_affected()
+      == 1;
+
+    Ok(inserted)
+  }
+
+  pub(super) async fn get_auth_challenge(&self, purpose: &str, token: &str) -> Result<Option<serde_json::Value>> {
+    let row = sqlx::query(
+      r#"
+      SELECT payload
+      FROM runtime_states
+      WHERE purpose = ?1
+        AND token_hash = ?2
+        AND consumed_at IS NULL
+        AND expires_at > ?3
+      "#,
+    )
+    .bind(auth_challenge_purpose(purpose))
+    .bind(token_hash(token))
+    .bind(sqlite_now_ms())
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState auth challenge get", err))?;
+
+    row.map(|row| parse_payload(row.get("payload"))).transpose()
+  }
+
+  pub(super) async fn consume_auth_challenge(&self, purpose: &str, token: &str) -> Result<Option<serde_json::Value>> {
+    let row = sqlx::query(
+      r#"
+      UPDATE runtime_states
+      SET consumed_at = ?3,
+          updated_at = ?3
+      WHERE purpose = ?1
+        AND token_hash = ?2
+        AND consumed_at IS NULL
+        AND expires_at > ?3
+      RETURNING payload
+      "#,
+    )
+    .bind(auth_challenge_purpose(purpose))
+    .bind(token_hash(token))
+    .bind(sqlite_now_ms())
+    .fetch_optional(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState auth challenge consume", err))?;
+
+    row.map(|row| parse_payload(row.get("payload"))).transpose()
+  }
+
+  pub(super) async fn cleanup_expired_runtime_states(&self, limit: i64) -> Result<i64> {
+    let result = sqlx::query(
+      r#"
+      DELETE FROM runtime_states
+      WHERE rowid IN (
+        SELECT rowid FROM runtime_states
+        WHERE expires_at <= ?2
+           OR consumed_at IS NOT NULL
+        ORDER BY expires_at ASC
+        LIMIT ?1
+      )
+      "#,
+    )
+    .bind(limit)
+    .bind(sqlite_now_ms())
+    .execute(&self.pool)
+    .await
+    .map_err(|err| RuntimeError::database("RuntimeState cleanup", err))?;
+
+    Ok(result.rows_affected() as i64)
+  }
+}
+
+fn parse_payload(raw: String) -> Result<serde_json::Value> {
+  serde_json::from_str(&raw).map_err(|err| RuntimeError::json("RuntimeState payload is not valid json", err))
+}
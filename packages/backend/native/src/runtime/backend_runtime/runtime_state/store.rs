@@ -1,9 +1,9 @@
 use sqlx::PgPool;
 
 use super::{
-  Result, RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeVerificationTokenRecord,
-  RuntimeWorkspaceInviteLinkRecord, auth_challenge, byok_local_lease, dto::RuntimeStateRows, invite_link,
-  magic_link_otp, verification_token,
+  Result, RuntimeByokLocalLeaseRecord, RuntimeMagicLinkOtpConsumeResult, RuntimeRefreshTokenFamilyRecord,
+  RuntimeRefreshTokenRotateResult, RuntimeVerificationTokenRecord, RuntimeWorkspaceInviteLinkRecord, auth_challenge,
+  byok_local_lease, dto::RuntimeStateRows, invite_link, magic_link_otp, refresh_token_family, verification_token,
 };
 
 pub(super) struct RuntimeStateStore {
@@ -135,4 +135,26 @@ impl RuntimeStateStore {
   pub(super) async fn get_byok_local_lease(&self, lease_id: String) -> Result<Option<RuntimeByokLocalLeaseRecord>> {
     byok_local_lease::get(&self.rows, lease_id).await
   }
+
+  pub(super) async fn issue_refresh_token_family(
+    &self,
+    user_id: String,
+    auth_session_id: String,
+    ttl_ms: i64,
+    family_ttl_ms: i64,
+  ) -> Result<RuntimeRefreshTokenFamilyRecord> {
+    refresh_token_family::issue(&self.rows, user_id, auth_session_id, ttl_ms, family_ttl_ms).await
+  }
+
+  pub(super) async fn rotate_refresh_token(
+    &self,
+    token: String,
+    ttl_ms: i64,
+  ) -> Result<RuntimeRefreshTokenRotateResult> {
+    refresh_token_family::rotate(&self.rows, token, ttl_ms).await
+  }
+
+  pub(super) async fn revoke_refresh_token_family(&self, family_id: String) -> Result<bool> {
+    refresh_token_family::revoke(&self.rows, family_id).await
+  }
 }
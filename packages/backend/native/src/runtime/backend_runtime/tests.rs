@@ -1,7 +1,11 @@
+use std::time::Duration;
+
 use anyhow::{Context, Result as AnyResult, anyhow};
+use sqlx::{Row, postgres::PgPoolOptions};
 
 use super::{
-  super::migrations::{RUNTIME_MIGRATIONS, migrate_runtime_tables},
+  super::migrations::{RUNTIME_MIGRATIONS, RUNTIME_SQLITE_MIGRATIONS, RuntimeMigration, migrate_runtime_tables},
+  doc_history::{HistoryEntry, insert_history, load_history_blob},
   runtime_state::*,
   *,
 };
@@ -9,20 +13,30 @@ use super::{
 static PG_TEST_LOCK: std::sync::OnceLock<tokio::sync::Mutex<()>> = std::sync::OnceLock::new();
 const TEST_VERIFICATION_TOKEN_TYPE: i32 = 99_999;
 
-fn pg_test_lock() -> &'static tokio::sync::Mutex<()> {
+pub(super) fn pg_test_lock() -> &'static tokio::sync::Mutex<()> {
   PG_TEST_LOCK.get_or_init(|| tokio::sync::Mutex::new(()))
 }
 
+fn up_sql(migrations: &[RuntimeMigration]) -> String {
+  migrations
+    .iter()
+    .map(|migration| migration.up)
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
 #[test]
 fn migrations_include_runtime_tables_without_worker_heartbeats() {
-  assert!(RUNTIME_MIGRATIONS.contains("runtime_states"));
-  assert!(RUNTIME_MIGRATIONS.contains("runtime_gates"));
-  assert!(RUNTIME_MIGRATIONS.contains("runtime_leases"));
-  assert!(RUNTIME_MIGRATIONS.contains("blob_reconciliation_runs"));
-  assert!(RUNTIME_MIGRATIONS.contains("blob_reconciliation_checkpoints"));
-  assert!(RUNTIME_MIGRATIONS.contains("doc_blob_refs"));
-  assert!(RUNTIME_MIGRATIONS.contains("blob_cleanup_candidates"));
-  assert!(!RUNTIME_MIGRATIONS.contains("runtime_worker_heartbeats"));
+  let runtime_migrations = up_sql(RUNTIME_MIGRATIONS);
+  assert!(runtime_migrations.contains("runtime_states"));
+  assert!(runtime_migrations.contains("runtime_gates"));
+  assert!(runtime_migrations.contains("runtime_leases"));
+  assert!(runtime_migrations.contains("blob_reconciliation_runs"));
+  assert!(runtime_migrations.contains("blob_reconciliation_checkpoints"));
+  assert!(runtime_migrations.contains("doc_blob_refs"));
+  assert!(runtime_migrations.contains("blob_cleanup_candidates"));
+  assert!(runtime_migrations.contains("doc_compaction_diagnostics"));
+  assert!(!runtime_migrations.contains("runtime_worker_heartbeats"));
 }
 
 #[test]
@@ -41,7 +55,20 @@ fn verification_token_state_uses_typed_purpose_and_token_hash() {
   assert_ne!(token_hash("verification-token"), token_hash("other-token"));
 }
 
-async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
+async fn sqlite_runtime() -> BackendRuntime {
+  let runtime = BackendRuntime {
+    config: std::sync::RwLock::new(BackendRuntimeConfig {
+      database_url: "sqlite::memory:".to_string(),
+      invite_quota: Default::default(),
+    }),
+    database: Mutex::new(None),
+  };
+  runtime.start().await.unwrap();
+  runtime.run_migrations().await.unwrap();
+  runtime
+}
+
+pub(super) async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
   let Ok(database_url) = std::env::var("DATABASE_URL") else {
     return Ok(None);
   };
@@ -59,6 +86,7 @@ async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
     WHERE purpose LIKE 'rust_test:%'
        OR purpose LIKE 'auth_challenge:rust_test:%'
        OR purpose = 'verification_token:99999'
+       OR (purpose LIKE 'auth_refresh_token:%' AND payload->>'userId' LIKE 'rust-test:%')
     "#,
   )
   .execute(&pool)
@@ -100,7 +128,7 @@ async fn runtime_from_database_url() -> AnyResult<Option<BackendRuntime>> {
       database_url,
       invite_quota: Default::default(),
     }),
-    pool: Mutex::new(Some(pool)),
+    database: Mutex::new(Some(RuntimeDatabase::Postgres(pool))),
   }))
 }
 
@@ -248,7 +276,7 @@ async fn runtime_gate_sql_semantics_are_atomic_and_ttl_bound() {
   for _ in 0..16 {
     let runtime = BackendRuntime {
       config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
+      database: Mutex::new(Some(runtime.database().await.unwrap())),
     };
     tasks.push(tokio::spawn(async move {
       runtime
@@ -579,7 +607,7 @@ async fn coordination_lease_sql_semantics_are_fenced_and_ttl_bound() {
   for index in 0..16 {
     let runtime = BackendRuntime {
       config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
+      database: Mutex::new(Some(runtime.database().await.unwrap())),
     };
     tasks.push(tokio::spawn(async move {
       runtime
@@ -688,6 +716,84 @@ async fn runtime_state_cleanup_deletes_expired_and_consumed_rows() {
   assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 0);
 }
 
+#[tokio::test]
+async fn refresh_token_family_rotates_and_revokes_on_reuse() {
+  let _guard = pg_test_lock().lock().await;
+  let Some(runtime) = runtime_from_database_url().await.unwrap() else {
+    eprintln!("skipping postgres integration test: DATABASE_URL is not set");
+    return;
+  };
+
+  let first = runtime
+    .issue_refresh_token_family(
+      "rust-test:refresh:user".to_string(),
+      "rust-test:refresh:session".to_string(),
+      30_000,
+      60_000,
+    )
+    .await
+    .unwrap();
+  assert_eq!(first.generation, 0);
+  assert!(first.expires_at_ms <= first.family_expires_at_ms);
+
+  let rotated = runtime.rotate_refresh_token(first.token.clone(), 30_000).await.unwrap();
+  assert!(rotated.ok);
+  let second = rotated.refresh_token.unwrap();
+  assert_eq!(second.family_id, first.family_id);
+  assert_eq!(second.generation, 1);
+  assert_eq!(second.auth_session_id, "rust-test:refresh:session");
+  assert_ne!(second.token, first.token);
+
+  let last = if second.token.ends_with('A') { 'E' } else { 'A' };
+  let tampered = format!("{}{last}", &second.token[..second.token.len() - 1]);
+  let invalid = runtime.rotate_refresh_token(tampered, 30_000).await.unwrap();
+  assert_eq!(invalid.reason.as_deref(), Some("invalid"));
+
+  let reused = runtime.rotate_refresh_token(first.token.clone(), 30_000).await.unwrap();
+  assert!(!reused.ok);
+  assert_eq!(reused.reason.as_deref(), Some("reused"));
+  assert_eq!(reused.family_id.as_deref(), Some(first.family_id.as_str()));
+  assert_eq!(reused.user_id.as_deref(), Some("rust-test:refresh:user"));
+
+  let revoked = runtime
+    .rotate_refresh_token(second.token.clone(), 30_000)
+    .await
+    .unwrap();
+  assert_eq!(revoked.reason.as_deref(), Some("revoked"));
+  assert!(
+    !runtime
+      .revoke_refresh_token_family(first.family_id.clone())
+      .await
+      .unwrap()
+  );
+
+  let other = runtime
+    .issue_refresh_token_family(
+      "rust-test:refresh:user".to_string(),
+      "rust-test:refresh:other".to_string(),
+      30_000,
+      60_000,
+    )
+    .await
+    .unwrap();
+  assert!(
+    runtime
+      .revoke_refresh_token_family(other.family_id.clone())
+      .await
+      .unwrap()
+  );
+  let after_logout = runtime.rotate_refresh_token(other.token, 30_000).await.unwrap();
+  assert_eq!(after_logout.reason.as_deref(), Some("revoked"));
+  assert!(
+    runtime
+      .rotate_refresh_token("aff_rt_v1.bad".to_string(), 30_000)
+      .await
+      .unwrap()
+      .reason
+      .is_some()
+  );
+}
+
 #[tokio::test]
 async fn verification_token_sql_state_machine_handles_keep_verify_and_cleanup() {
   let _guard = pg_test_lock().lock().await;
@@ -783,7 +889,7 @@ async fn verification_token_sql_state_machine_handles_keep_verify_and_cleanup()
   for _ in 0..16 {
     let runtime = BackendRuntime {
       config: std::sync::RwLock::new(runtime.config().unwrap()),
-      pool: Mutex::new(Some(runtime.pool().await.unwrap())),
+      database: Mutex::new(Some(runtime.database().await.unwrap())),
     };
     let token = concurrent_token.clone();
     tasks.push(tokio::spawn(async move {
@@ -822,3 +928,623 @@ async fn verification_token_sql_state_machine_handles_keep_verify_and_cleanup()
   assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 1);
   assert_eq!(runtime.cleanup_expired_verification_tokens(100).await.unwrap(), 0);
 }
+
+#[test]
+fn sqlite_migrations_cover_embedded_runtime_tables_only() {
+  let sqlite_migrations = up_sql(RUNTIME_SQLITE_MIGRATIONS);
+  for table in [
+    "runtime_states",
+    "runtime_gates",
+    "runtime_leases",
+    "runtime_rolling_quota_counters",
+    "runtime_rolling_quota_reservations",
+  ] {
+    assert!(sqlite_migrations.contains(table), "{table}");
+  }
+  assert!(!sqlite_migrations.contains("JSONB"));
+  assert!(!sqlite_migrations.contains("TIMESTAMPTZ"));
+  assert!(!sqlite_migrations.contains("doc_blob_refs"));
+  assert!(!sqlite_migrations.contains("doc_compaction_diagnostics"));
+}
+
+#[tokio::test]
+async fn sqlite_migration_ledger_plans_applies_and_reverts() {
+  let runtime = BackendRuntime {
+    config: std::sync::RwLock::new(BackendRuntimeConfig {
+      database_url: "sqlite::memory:".to_string(),
+      invite_quota: Default::default(),
+    }),
+    database: Mutex::new(None),
+  };
+  runtime.start().await.unwrap();
+
+  let plan = runtime.plan_migrations().await.unwrap();
+  assert_eq!(plan.current_version, 0);
+  assert_eq!(plan.pending, RUNTIME_SQLITE_MIGRATIONS.len() as i64);
+  assert!(plan.migrations.iter().all(|migration| migration.state == "pending"));
+
+  runtime.run_migrations().await.unwrap();
+  runtime.run_migrations().await.unwrap();
+  let plan = runtime.plan_migrations().await.unwrap();
+  assert_eq!(plan.current_version, RUNTIME_SQLITE_MIGRATIONS.len() as i64);
+  assert_eq!(plan.pending, 0);
+  assert!(plan.migrations.iter().all(|migration| migration.state == "applied"));
+
+  assert!(runtime.revert_migrations(-1).await.is_err());
+  let plan = runtime.revert_migrations(0).await.unwrap();
+  assert_eq!(plan.current_version, 0);
+  assert_eq!(plan.pending, RUNTIME_SQLITE_MIGRATIONS.len() as i64);
+  assert!(
+    runtime
+      .put_runtime_gate_if_absent("rust-test:gate:reverted".to_string(), 30_000)
+      .await
+      .is_err()
+  );
+
+  runtime.run_migrations().await.unwrap();
+  assert!(
+    runtime
+      .put_runtime_gate_if_absent("rust-test:gate:reverted".to_string(), 30_000)
+      .await
+      .unwrap()
+  );
+}
+
+#[tokio::test]
+async fn challenge_response_is_accepted_once_with_or_without_database() {
+  let memory_runtime = BackendRuntime {
+    config: std::sync::RwLock::new(BackendRuntimeConfig {
+      database_url: "sqlite::memory:".to_string(),
+      invite_quota: Default::default(),
+    }),
+    database: Mutex::new(None),
+  };
+  let sqlite_runtime = sqlite_runtime().await;
+
+  for runtime in [&memory_runtime, &sqlite_runtime] {
+    let stamp = affine_common::hashcash::Stamp::mint("rust-test-challenge".to_string(), Some(8)).format();
+    let accepted = runtime
+      .verify_challenge_response_once(stamp.clone(), 8, "rust-test-challenge".to_string(), Some(60_000))
+      .await
+      .unwrap();
+    assert!(accepted.ok);
+    assert!(accepted.expires_at_ms.is_some());
+
+    let replayed = runtime
+      .verify_challenge_response_once(stamp.clone(), 8, "rust-test-challenge".to_string(), Some(60_000))
+      .await
+      .unwrap();
+    assert!(!replayed.ok);
+    assert_eq!(replayed.reason.as_deref(), Some("replayed"));
+
+    let invalid = runtime
+      .verify_challenge_response_once(stamp, 8, "rust-test-other".to_string(), None)
+      .await
+      .unwrap();
+    assert_eq!(invalid.reason.as_deref(), Some("invalid"));
+  }
+
+  let stale = "1:8:20200101000000:rust-test-challenge::salt:0".to_string();
+  let expired = memory_runtime
+    .verify_challenge_response_once(stale, 8, "rust-test-challenge".to_string(), Some(1))
+    .await
+    .unwrap();
+  assert_eq!(expired.reason.as_deref(), Some("expired"));
+  assert!(
+    memory_runtime
+      .verify_challenge_response_once("not-a-stamp".to_string(), 8, "rust-test-challenge".to_string(), Some(0))
+      .await
+      .is_err()
+  );
+}
+
+#[tokio::test]
+async fn sqlite_runtime_gate_and_lease_semantics_match_postgres() {
+  let runtime = sqlite_runtime().await;
+  assert!(runtime.health().await.unwrap().database_connected);
+
+  assert!(
+    runtime
+      .put_runtime_gate_if_absent("rust-test:gate:same-key".to_string(), 30_000)
+      .await
+      .unwrap()
+  );
+  assert!(
+    !runtime
+      .put_runtime_gate_if_absent("rust-test:gate:same-key".to_string(), 30_000)
+      .await
+      .unwrap()
+  );
+  assert!(
+    runtime
+      .put_runtime_gate_if_absent("rust-test:gate:expired-key".to_string(), 1)
+      .await
+      .unwrap()
+  );
+  tokio::time::sleep(Duration::from_millis(20)).await;
+  assert!(
+    runtime
+      .put_runtime_gate_if_absent("rust-test:gate:expired-key".to_string(), 1)
+      .await
+      .unwrap()
+  );
+  tokio::time::sleep(Duration::from_millis(20)).await;
+  assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 1);
+  assert_eq!(runtime.cleanup_expired_runtime_gates(100).await.unwrap(), 0);
+
+  let stale = runtime
+    .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-1".to_string(), 1)
+    .await
+    .unwrap()
+    .expect("first owner should acquire lease");
+  assert_eq!(stale.fencing_token, 1);
+  tokio::time::sleep(Duration::from_millis(20)).await;
+  let takeover = runtime
+    .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-2".to_string(), 30_000)
+    .await
+    .unwrap()
+    .expect("expired lease should be taken over");
+  assert_eq!(takeover.fencing_token, stale.fencing_token + 1);
+  assert!(
+    runtime
+      .acquire_coordination_lease("rust-test:lease:stale".to_string(), "owner-3".to_string(), 30_000)
+      .await
+      .unwrap()
+      .is_none()
+  );
+  assert!(
+    !runtime
+      .renew_coordination_lease(
+        takeover.key.clone(),
+        "owner-1".to_string(),
+        takeover.fencing_token,
+        30_000
+      )
+      .await
+      .unwrap()
+  );
+  assert!(
+    runtime
+      .renew_coordination_lease(
+        takeover.key.clone(),
+        takeover.owner.clone(),
+        takeover.fencing_token,
+        30_000
+      )
+      .await
+      .unwrap()
+  );
+  assert!(
+    !runtime
+      .release_coordination_lease(stale.key.clone(), stale.owner.clone(), stale.fencing_token)
+      .await
+      .unwrap()
+  );
+  assert!(
+    runtime
+      .release_coordination_lease(takeover.key.clone(), takeover.owner.clone(), takeover.fencing_token)
+      .await
+      .unwrap()
+  );
+}
+
+#[tokio::test]
+async fn sqlite_mail_delivery_quota_reserves_commits_and_rejects() {
+  let runtime = sqlite_runtime().await;
+  let input = |request_id: String| types::RuntimeMailDeliveryQuotaInput {
+    request_id: Some(request_id),
+    mail_name: "SignIn".to_string(),
+    recipient: types::RuntimeMailDeliveryQuotaRecipientInput {
+      email: "rust-test-sqlite@example.com".to_string(),
+      domain: "example.com".to_string(),
+      user_id: None,
+    },
+    metadata: types::RuntimeMailDeliveryQuotaMetadataInput {
+      actor_user_id: None,
+      workspace_id: None,
+      notification_id: None,
+      abuse_subject_key: None,
+    },
+    source: None,
+  };
+
+  let released = runtime
+    .assert_mail_delivery_quota_v1(input("rust-test:sqlite:released".to_string()))
+    .await
+    .unwrap();
+  assert!(released.allowed);
+  assert!(
+    runtime
+      .release_mail_delivery_quota_v1(released.reservation_id.unwrap())
+      .await
+      .unwrap()
+  );
+
+  for index in 0..20 {
+    let decision = runtime
+      .assert_mail_delivery_quota_v1(input(format!("rust-test:sqlite:{index}")))
+      .await
+      .unwrap();
+    assert!(decision.allowed, "{index}");
+    assert!(
+      runtime
+        .commit_mail_delivery_quota_v1(decision.reservation_id.unwrap())
+        .await
+        .unwrap()
+    );
+  }
+
+  let rejected = runtime
+    .assert_mail_delivery_quota_v1(input("rust-test:sqlite:rejected".to_string()))
+    .await
+    .unwrap();
+  assert!(!rejected.allowed);
+  assert_eq!(rejected.current, Some(20));
+  assert_eq!(rejected.limit, Some(20));
+  assert!(rejected.scope_key.unwrap().starts_with("mail:recipient:"));
+}
+
+#[tokio::test]
+async fn sqlite_auth_challenge_lifecycle_and_postgres_only_guard() {
+  let runtime = sqlite_runtime().await;
+
+  assert!(
+    runtime
+      .create_auth_challenge(
+        "rust_test:sqlite".to_string(),
+        "token".to_string(),
+        serde_json::json!({ "state": "oauth" }),
+        30_000,
+      )
+      .await
+      .unwrap()
+  );
+  assert!(
+    !runtime
+      .create_auth_challenge(
+        "rust_test:sqlite".to_string(),
+        "token".to_string(),
+        serde_json::json!({}),
+        30_000,
+      )
+      .await
+      .unwrap()
+  );
+  assert_eq!(
+    runtime
+      .get_auth_challenge("rust_test:sqlite".to_string(), "token".to_string())
+      .await
+      .unwrap(),
+    Some(serde_json::json!({ "state": "oauth" }))
+  );
+  assert_eq!(
+    runtime
+      .consume_auth_challenge("rust_test:sqlite".to_string(), "token".to_string())
+      .await
+      .unwrap(),
+    Some(serde_json::json!({ "state": "oauth" }))
+  );
+  assert!(
+    runtime
+      .consume_auth_challenge("rust_test:sqlite".to_string(), "token".to_string())
+      .await
+      .unwrap()
+      .is_none()
+  );
+  assert_eq!(runtime.cleanup_expired_runtime_states(100).await.unwrap(), 1);
+
+  assert!(
+    runtime
+      .create_verification_token(TEST_VERIFICATION_TOKEN_TYPE, None, 30_000)
+      .await
+      .is_err()
+  );
+}
+
+#[tokio::test]
+async fn workspace_doc_compaction_validates_limits_and_requires_postgres() {
+  let runtime = sqlite_runtime().await;
+  let compact = |doc_limit, concurrency| {
+    runtime.compact_pending_workspace_docs(
+      "rust-test-workspace".to_string(),
+      doc_limit,
+      concurrency,
+      100,
+      60_000,
+      86_400,
+      "owner-1".to_string(),
+      30_000,
+      None,
+    )
+  };
+
+  assert!(compact(0, 4).await.is_err());
+  assert!(compact(10, 0).await.is_err());
+  assert!(compact(10, 4).await.is_err());
+}
+
+/// `snapshot_histories` belongs to the server schema, so these tests also
+/// skip when the database has not been migrated by the server.
+async fn snapshot_history_runtime() -> Option<(BackendRuntime, PgPool)> {
+  let runtime = runtime_from_database_url().await.unwrap()?;
+  let pool = runtime.pool().await.unwrap();
+  let exists: bool = sqlx::query_scalar("SELECT to_regclass('snapshot_histories') IS NOT NULL")
+    .fetch_one(&pool)
+    .await
+    .unwrap();
+  if !exists {
+    return None;
+  }
+  sqlx::query("DELETE FROM snapshot_histories WHERE workspace_id LIKE 'rust-test:%'")
+    .execute(&pool)
+    .await
+    .unwrap();
+  Some((runtime, pool))
+}
+
+/// Successive versions of one doc, each adding a `rev-<n>` map entry.
+fn history_versions(count: usize) -> Vec<Vec<u8>> {
+  let base = affine_doc_loader::build_full_doc("History", "first paragraph", "rust-test-history").unwrap();
+  let mut doc = y_octo::Doc::default();
+  doc.apply_update_from_binary_v1(&base).unwrap();
+  let mut edits = doc.get_or_create_map("history-test").unwrap();
+  (0..count)
+    .map(|revision| {
+      edits
+        .insert(format!("rev-{revision}"), format!("revision {revision}"))
+        .unwrap();
+      doc.encode_update_v1().unwrap()
+    })
+    .collect()
+}
+
+fn history_revisions(blob: &[u8]) -> usize {
+  let mut doc = y_octo::Doc::default();
+  doc.apply_update_from_binary_v1(blob).unwrap();
+  let edits = doc.get_or_create_map("history-test").unwrap();
+  (0..)
+    .take_while(|revision| edits.get(&format!("rev-{revision}")).is_some())
+    .count()
+}
+
+fn history_timestamp(index: usize) -> chrono::DateTime<chrono::Utc> {
+  chrono::DateTime::from_timestamp_millis(1_700_000_000_000 + index as i64 * 1_000).unwrap()
+}
+
+async fn history_bases(pool: &PgPool, workspace_id: &str) -> Vec<Option<chrono::DateTime<chrono::Utc>>> {
+  sqlx::query_scalar("SELECT base_timestamp FROM snapshot_histories WHERE workspace_id = $1 ORDER BY timestamp")
+    .bind(workspace_id)
+    .fetch_all(pool)
+    .await
+    .unwrap()
+}
+
+async fn insert_full_history(
+  pool: &PgPool,
+  workspace_id: &str,
+  timestamp: chrono::DateTime<chrono::Utc>,
+  blob: &[u8],
+  expired_at: chrono::DateTime<chrono::Utc>,
+) {
+  sqlx::query(
+    r#"
+    INSERT INTO snapshot_histories (workspace_id, guid, timestamp, blob, expired_at)
+    VALUES ($1, 'doc', $2, $3, $4)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(timestamp)
+  .bind(blob)
+  .bind(expired_at)
+  .execute(pool)
+  .await
+  .unwrap();
+}
+
+#[tokio::test]
+async fn doc_history_stores_deltas_against_keyframe_and_reads_them_back() {
+  let _guard = pg_test_lock().lock().await;
+  let Some((_runtime, pool)) = snapshot_history_runtime().await else {
+    eprintln!("skipping postgres integration test: snapshot_histories is not available");
+    return;
+  };
+  let workspace_id = "rust-test:history-insert";
+  let versions = history_versions(3);
+  let expired_at = chrono::Utc::now() + chrono::Duration::hours(1);
+  for (index, blob) in versions.iter().enumerate() {
+    let mut tx = pool.begin().await.unwrap();
+    insert_history(
+      &mut tx,
+      HistoryEntry {
+        workspace_id,
+        doc_id: "doc",
+        timestamp: history_timestamp(index),
+        blob,
+        expired_at,
+        created_by: None,
+      },
+    )
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+  }
+
+  assert_eq!(
+    history_bases(&pool, workspace_id).await,
+    vec![None, Some(history_timestamp(0)), Some(history_timestamp(0))]
+  );
+  for index in 0..versions.len() {
+    let blob = load_history_blob(&pool, workspace_id, "doc", history_timestamp(index))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(history_revisions(&blob), index + 1);
+  }
+  assert!(
+    load_history_blob(&pool, workspace_id, "doc", history_timestamp(9))
+      .await
+      .unwrap()
+      .is_none()
+  );
+
+  sqlx::query("DELETE FROM snapshot_histories WHERE workspace_id = $1 AND timestamp = $2")
+    .bind(workspace_id)
+    .bind(history_timestamp(0))
+    .execute(&pool)
+    .await
+    .unwrap();
+  assert!(
+    load_history_blob(&pool, workspace_id, "doc", history_timestamp(1))
+      .await
+      .is_err()
+  );
+}
+
+#[tokio::test]
+async fn doc_history_stores_blobs_not_descending_from_the_keyframe_in_full() {
+  let _guard = pg_test_lock().lock().await;
+  let Some((_runtime, pool)) = snapshot_history_runtime().await else {
+    eprintln!("skipping postgres integration test: snapshot_histories is not available");
+    return;
+  };
+  let workspace_id = "rust-test:history-branch";
+  let versions = history_versions(3);
+  // The first revision edited by another client, as when a snapshot is
+  // replaced by an older version out of band.
+  let mut branch = y_octo::Doc::default();
+  branch.apply_update_from_binary_v1(&versions[0]).unwrap();
+  branch
+    .get_or_create_map("history-test")
+    .unwrap()
+    .insert("branch".to_string(), "replaced")
+    .unwrap();
+  let branch = branch.encode_update_v1().unwrap();
+
+  let expired_at = chrono::Utc::now() + chrono::Duration::hours(1);
+  for (index, blob) in [&versions[2], &branch].into_iter().enumerate() {
+    let mut tx = pool.begin().await.unwrap();
+    insert_history(
+      &mut tx,
+      HistoryEntry {
+        workspace_id,
+        doc_id: "doc",
+        timestamp: history_timestamp(index),
+        blob,
+        expired_at,
+        created_by: None,
+      },
+    )
+    .await
+    .unwrap();
+    tx.commit().await.unwrap();
+  }
+
+  assert_eq!(history_bases(&pool, workspace_id).await, vec![None, None]);
+  let blob = load_history_blob(&pool, workspace_id, "doc", history_timestamp(1))
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(blob, branch);
+  assert_eq!(history_revisions(&blob), 1);
+}
+
+#[tokio::test]
+async fn snapshot_history_cleanup_keeps_keyframes_of_live_deltas() {
+  let _guard = pg_test_lock().lock().await;
+  let Some((runtime, pool)) = snapshot_history_runtime().await else {
+    eprintln!("skipping postgres integration test: snapshot_histories is not available");
+    return;
+  };
+  let workspace_id = "rust-test:history-cleanup";
+  let versions = history_versions(3);
+  let expired = chrono::Utc::now() - chrono::Duration::hours(1);
+  let live = chrono::Utc::now() + chrono::Duration::hours(1);
+  insert_full_history(&pool, workspace_id, history_timestamp(0), &versions[0], expired).await;
+  insert_full_history(&pool, workspace_id, history_timestamp(1), &versions[1], expired).await;
+  sqlx::query(
+    r#"
+    INSERT INTO snapshot_histories (workspace_id, guid, timestamp, blob, expired_at, base_timestamp)
+    VALUES ($1, 'doc', $2, $3, $4, $5)
+    "#,
+  )
+  .bind(workspace_id)
+  .bind(history_timestamp(2))
+  .bind(&versions[2])
+  .bind(live)
+  .bind(history_timestamp(1))
+  .execute(&pool)
+  .await
+  .unwrap();
+
+  runtime.cleanup_expired_snapshot_histories(1_000).await.unwrap();
+
+  let remaining: Vec<chrono::DateTime<chrono::Utc>> =
+    sqlx::query_scalar("SELECT timestamp FROM snapshot_histories WHERE workspace_id = $1 ORDER BY timestamp")
+      .bind(workspace_id)
+      .fetch_all(&pool)
+      .await
+      .unwrap();
+  assert_eq!(remaining, vec![history_timestamp(1), history_timestamp(2)]);
+}
+
+#[tokio::test]
+async fn doc_history_migration_converts_full_rows_and_keeps_referenced_keyframes() {
+  let _guard = pg_test_lock().lock().await;
+  let Some((runtime, pool)) = snapshot_history_runtime().await else {
+    eprintln!("skipping postgres integration test: snapshot_histories is not available");
+    return;
+  };
+  let workspace_id = "rust-test:history-migration";
+  let versions = history_versions(5);
+  let expired_at = chrono::Utc::now() + chrono::Duration::hours(1);
+  insert_full_history(&pool, workspace_id, history_timestamp(0), &versions[0], expired_at).await;
+  insert_full_history(&pool, workspace_id, history_timestamp(1), &versions[1], expired_at).await;
+  // Recorded by a writer that already stores deltas, so it points at the
+  // second row and that row has to stay a keyframe.
+  let mut tx = pool.begin().await.unwrap();
+  insert_history(
+    &mut tx,
+    HistoryEntry {
+      workspace_id,
+      doc_id: "doc",
+      timestamp: history_timestamp(4),
+      blob: &versions[4],
+      expired_at,
+      created_by: None,
+    },
+  )
+  .await
+  .unwrap();
+  tx.commit().await.unwrap();
+  insert_full_history(&pool, workspace_id, history_timestamp(2), &versions[2], expired_at).await;
+  insert_full_history(&pool, workspace_id, history_timestamp(3), &versions[3], expired_at).await;
+
+  let cursor = Some(serde_json::json!([workspace_id, ""]).to_string());
+  let result = runtime.migrate_doc_history_deltas(cursor.clone(), 1).await.unwrap();
+  assert_eq!(result.scanned_docs, 1);
+  assert_eq!(result.converted_rows, 2);
+  assert_eq!(
+    result.next_cursor,
+    Some(serde_json::json!([workspace_id, "doc"]).to_string())
+  );
+  assert_eq!(
+    history_bases(&pool, workspace_id).await,
+    vec![
+      None,
+      None,
+      Some(history_timestamp(1)),
+      Some(history_timestamp(1)),
+      Some(history_timestamp(1)),
+    ]
+  );
+  for index in 0..versions.len() {
+    let blob = load_history_blob(&pool, workspace_id, "doc", history_timestamp(index))
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(history_revisions(&blob), index + 1);
+  }
+
+  let rerun = runtime.migrate_doc_history_deltas(cursor, 1).await.unwrap();
+  assert_eq!(rerun.converted_rows, 0);
+}
@@ -9,6 +9,7 @@ use serde_json::Map;
 use sqlx::{PgPool, Row};
 
 use super::{RuntimeError, RuntimeResult};
+use crate::entitlement::{PlanCatalog, validate_plan_catalog};
 
 #[derive(Clone, Debug)]
 pub(crate) struct BackendRuntimeConfig {
@@ -50,6 +51,9 @@ impl BackendRuntimeConfig {
     let database_url = database_url_from_env()
       .or(app_config.database_url())
       .unwrap_or_else(|| "postgresql://localhost:5432/affine".to_string());
+    // The server passes `entitlement.plans` with each resolution; checking
+    // them here only makes a bad catalog fail at startup.
+    app_config.plan_catalog()?;
     Ok(Self {
       database_url,
       invite_quota: app_config.invite_quota_config(),
@@ -59,6 +63,7 @@ impl BackendRuntimeConfig {
   pub(crate) async fn with_db_overrides(&self, pool: &PgPool) -> RuntimeResult<Self> {
     let mut app_config = app_config_from_config_files()?;
     app_config.apply_file_config(load_app_config_overrides_from_db(pool).await?);
+    app_config.plan_catalog()?;
     Ok(Self {
       // The DB override is loaded after this connection already exists, so it
       // must not rewrite the active datasource URL.
@@ -71,6 +76,7 @@ impl BackendRuntimeConfig {
 #[derive(Debug, Default, Deserialize)]
 struct AppConfigFile {
   db: Option<DbConfigFile>,
+  entitlement: Option<EntitlementConfigFile>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -79,6 +85,11 @@ struct DbConfigFile {
   datasource_url: Option<String>,
 }
 
+#[derive(Debug, Default, Deserialize)]
+struct EntitlementConfigFile {
+  plans: Option<PlanCatalog>,
+}
+
 impl AppConfigFile {
   fn database_url(&self) -> Option<String> {
     self
@@ -91,6 +102,16 @@ impl AppConfigFile {
   fn invite_quota_config(&self) -> InviteQuotaConfig {
     InviteQuotaConfig::default()
   }
+
+  fn plan_catalog(&self) -> RuntimeResult<PlanCatalog> {
+    let plans = self
+      .entitlement
+      .as_ref()
+      .and_then(|entitlement| entitlement.plans.clone())
+      .unwrap_or_default();
+    validate_plan_catalog(&plans).map_err(|err| RuntimeError::config(format!("invalid entitlement.plans: {err}")))?;
+    Ok(plans)
+  }
 }
 
 fn database_url_from_env() -> Option<String> {
@@ -121,6 +142,9 @@ impl AppConfigFile {
     if config.db.is_some() {
       self.db = config.db;
     }
+    if config.entitlement.is_some() {
+      self.entitlement = config.entitlement;
+    }
   }
 }
 
@@ -273,6 +297,38 @@ mod tests {
     );
   }
 
+  #[test]
+  fn loads_and_validates_entitlement_plans() {
+    let app_config = app_config_from_flat_overrides([(
+      "entitlement.plans",
+      serde_json::json!({
+        "auditor": {
+          "blobLimit": 1_048_576,
+          "storageQuota": 1_073_741_824,
+          "historyPeriod": 86_400,
+          "memberLimit": 20,
+        },
+      }),
+    )])
+    .unwrap();
+    let plans = app_config.plan_catalog().unwrap();
+    assert_eq!(plans.get("auditor").and_then(|plan| plan.member_limit), Some(20));
+
+    let app_config = app_config_from_flat_overrides([(
+      "entitlement.plans",
+      serde_json::json!({
+        "auditor": { "blobLimit": 0, "storageQuota": 1, "historyPeriod": 1 },
+      }),
+    )])
+    .unwrap();
+    assert!(app_config.plan_catalog().is_err());
+    assert!(
+      AppConfigFile::default()
+        .plan_catalog()
+        .is_ok_and(|plans| plans.is_empty())
+    );
+  }
+
   #[test]
   fn invite_quota_policy_is_internal_not_app_configurable() {
     let app_config = app_config_from_flat_overrides([
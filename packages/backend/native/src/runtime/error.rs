@@ -22,6 +22,13 @@ pub(crate) enum RuntimeError {
     source: sqlx::Error,
   },
 
+  #[error("{context}: {source}")]
+  Migrate {
+    context: String,
+    #[source]
+    source: sqlx::migrate::MigrateError,
+  },
+
   #[error("{context}: {source}")]
   Io {
     context: String,
@@ -70,6 +77,13 @@ impl RuntimeError {
     }
   }
 
+  pub(crate) fn migrate(context: impl Into<String>, source: sqlx::migrate::MigrateError) -> Self {
+    Self::Migrate {
+      context: context.into(),
+      source,
+    }
+  }
+
   pub(crate) fn io(context: impl Into<String>, source: std::io::Error) -> Self {
     Self::Io {
       context: context.into(),
@@ -1,20 +1,285 @@
-use sqlx::PgPool;
+use std::{borrow::Cow, collections::HashMap};
 
-use super::{RuntimeError, RuntimeResult};
+use sqlx::{
+  PgPool, Row, SqlitePool,
+  migrate::{Migration, MigrationType, Migrator},
+};
 
-pub(crate) const RUNTIME_MIGRATIONS: &str = include_str!("sql/runtime_migrations.sql");
+use super::{
+  RuntimeError, RuntimeResult,
+  types::{RuntimeMigrationPlan, RuntimeMigrationStatus},
+};
+
+pub(crate) struct RuntimeMigration {
+  pub(crate) description: &'static str,
+  pub(crate) up: &'static str,
+  pub(crate) down: Option<&'static str>,
+}
+
+// ORDER MATTERS: the position in the list is the version recorded in the
+// `_sqlx_migrations` ledger. Never edit an applied migration, append a new one.
+pub(crate) const RUNTIME_MIGRATIONS: &[RuntimeMigration] = &[
+  RuntimeMigration {
+    description: "runtime_baseline",
+    up: include_str!("sql/postgres/0001_runtime_baseline.up.sql"),
+    down: Some(include_str!("sql/postgres/0001_runtime_baseline.down.sql")),
+  },
+  RuntimeMigration {
+    description: "doc_compaction_diagnostics",
+    up: include_str!("sql/postgres/0002_doc_compaction_diagnostics.up.sql"),
+    down: Some(include_str!("sql/postgres/0002_doc_compaction_diagnostics.down.sql")),
+  },
+  RuntimeMigration {
+    description: "doc_compaction_diagnostic_ranges",
+    up: include_str!("sql/postgres/0003_doc_compaction_diagnostic_ranges.up.sql"),
+    down: Some(include_str!(
+      "sql/postgres/0003_doc_compaction_diagnostic_ranges.down.sql"
+    )),
+  },
+];
+
+pub(crate) const RUNTIME_SQLITE_MIGRATIONS: &[RuntimeMigration] = &[
+  RuntimeMigration {
+    description: "runtime_baseline",
+    up: include_str!("sql/sqlite/0001_runtime_baseline.up.sql"),
+    down: Some(include_str!("sql/sqlite/0001_runtime_baseline.down.sql")),
+  },
+  RuntimeMigration {
+    description: "doc_compaction_diagnostics",
+    up: include_str!("sql/sqlite/0002_doc_compaction_diagnostics.up.sql"),
+    down: Some(include_str!("sql/sqlite/0002_doc_compaction_diagnostics.down.sql")),
+  },
+  RuntimeMigration {
+    description: "doc_compaction_diagnostic_ranges",
+    up: include_str!("sql/sqlite/0003_doc_compaction_diagnostic_ranges.up.sql"),
+    down: Some(include_str!(
+      "sql/sqlite/0003_doc_compaction_diagnostic_ranges.down.sql"
+    )),
+  },
+];
+
+const LEDGER_TABLE: &str = "_sqlx_migrations";
+
+pub(crate) struct AppliedRuntimeMigration {
+  pub(crate) version: i64,
+  pub(crate) checksum: Vec<u8>,
+  pub(crate) success: bool,
+}
+
+fn migrator(migrations: &[RuntimeMigration]) -> Migrator {
+  let mut entries = Vec::with_capacity(migrations.len() * 2);
+  for (index, migration) in migrations.iter().enumerate() {
+    let version = index as i64 + 1;
+    entries.push(Migration::new(
+      version,
+      Cow::Borrowed(migration.description),
+      if migration.down.is_some() {
+        MigrationType::ReversibleUp
+      } else {
+        MigrationType::Simple
+      },
+      Cow::Borrowed(migration.up),
+      false,
+    ));
+    if let Some(down) = migration.down {
+      entries.push(Migration::new(
+        version,
+        Cow::Borrowed(migration.description),
+        MigrationType::ReversibleDown,
+        Cow::Borrowed(down),
+        false,
+      ));
+    }
+  }
+
+  Migrator {
+    migrations: Cow::Owned(entries),
+    ..Migrator::DEFAULT
+  }
+}
 
 pub(crate) async fn migrate_runtime_tables(pool: &PgPool) -> RuntimeResult<()> {
-  for statement in RUNTIME_MIGRATIONS
-    .split(';')
-    .map(str::trim)
-    .filter(|statement| !statement.is_empty())
-  {
-    sqlx::query(statement)
-      .execute(pool)
+  migrator(RUNTIME_MIGRATIONS)
+    .run(pool)
+    .await
+    .map_err(|err| RuntimeError::migrate("Runtime migration failed", err))
+}
+
+pub(crate) async fn migrate_sqlite_runtime_tables(pool: &SqlitePool) -> RuntimeResult<()> {
+  migrator(RUNTIME_SQLITE_MIGRATIONS)
+    .run(pool)
+    .await
+    .map_err(|err| RuntimeError::migrate("Runtime sqlite migration failed", err))
+}
+
+pub(crate) async fn revert_runtime_tables(pool: &PgPool, target_version: i64) -> RuntimeResult<()> {
+  migrator(RUNTIME_MIGRATIONS)
+    .undo(pool, target_version)
+    .await
+    .map_err(|err| RuntimeError::migrate("Runtime migration revert failed", err))
+}
+
+pub(crate) async fn revert_sqlite_runtime_tables(pool: &SqlitePool, target_version: i64) -> RuntimeResult<()> {
+  migrator(RUNTIME_SQLITE_MIGRATIONS)
+    .undo(pool, target_version)
+    .await
+    .map_err(|err| RuntimeError::migrate("Runtime sqlite migration revert failed", err))
+}
+
+/// Reads the ledger without creating it, so planning is safe on a database
+/// that has never been migrated.
+pub(crate) async fn applied_runtime_migrations(pool: &PgPool) -> RuntimeResult<Vec<AppliedRuntimeMigration>> {
+  let exists: bool = sqlx::query_scalar("SELECT to_regclass($1) IS NOT NULL")
+    .bind(LEDGER_TABLE)
+    .fetch_one(pool)
+    .await
+    .map_err(|err| RuntimeError::database("Runtime migration ledger lookup failed", err))?;
+  if !exists {
+    return Ok(Vec::new());
+  }
+
+  let rows = sqlx::query("SELECT version, checksum, success FROM _sqlx_migrations ORDER BY version")
+    .fetch_all(pool)
+    .await
+    .map_err(|err| RuntimeError::database("Runtime migration ledger read failed", err))?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|row| AppliedRuntimeMigration {
+        version: row.get("version"),
+        checksum: row.get("checksum"),
+        success: row.get("success"),
+      })
+      .collect(),
+  )
+}
+
+pub(crate) async fn applied_sqlite_runtime_migrations(
+  pool: &SqlitePool,
+) -> RuntimeResult<Vec<AppliedRuntimeMigration>> {
+  let exists: bool =
+    sqlx::query_scalar("SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = ?1)")
+      .bind(LEDGER_TABLE)
+      .fetch_one(pool)
       .await
-      .map_err(|err| RuntimeError::database("Runtime migration failed", err))?;
+      .map_err(|err| RuntimeError::database("Runtime migration ledger lookup failed", err))?;
+  if !exists {
+    return Ok(Vec::new());
+  }
+
+  let rows = sqlx::query("SELECT version, checksum, success FROM _sqlx_migrations ORDER BY version")
+    .fetch_all(pool)
+    .await
+    .map_err(|err| RuntimeError::database("Runtime migration ledger read failed", err))?;
+  Ok(
+    rows
+      .into_iter()
+      .map(|row| AppliedRuntimeMigration {
+        version: row.get("version"),
+        checksum: row.get("checksum"),
+        success: row.get("success"),
+      })
+      .collect(),
+  )
+}
+
+pub(crate) fn plan_runtime_migrations(
+  migrations: &[RuntimeMigration],
+  applied: Vec<AppliedRuntimeMigration>,
+) -> RuntimeMigrationPlan {
+  let migrator = migrator(migrations);
+  let applied: HashMap<_, _> = applied.into_iter().map(|row| (row.version, row)).collect();
+  let mut current_version = 0;
+  let mut pending = 0;
+  let statuses = migrator
+    .iter()
+    .filter(|migration| !migration.migration_type.is_down_migration())
+    .map(|migration| {
+      let state = match applied.get(&migration.version) {
+        None => {
+          pending += 1;
+          "pending"
+        }
+        Some(row) if !row.success => "failed",
+        Some(row) if row.checksum != migration.checksum.as_ref() => "checksum_mismatch",
+        Some(_) => {
+          current_version = current_version.max(migration.version);
+          "applied"
+        }
+      };
+      RuntimeMigrationStatus {
+        version: migration.version,
+        description: migration.description.to_string(),
+        state: state.to_string(),
+        reversible: migration.migration_type == MigrationType::ReversibleUp,
+      }
+    })
+    .collect();
+
+  RuntimeMigrationPlan {
+    current_version,
+    pending,
+    migrations: statuses,
   }
+}
 
-  Ok(())
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn applied(version: i64, sql: &str, success: bool) -> AppliedRuntimeMigration {
+    let migration = Migration::new(
+      version,
+      Cow::Borrowed("test"),
+      MigrationType::Simple,
+      Cow::Owned(sql.to_string()),
+      false,
+    );
+    AppliedRuntimeMigration {
+      version,
+      checksum: migration.checksum.to_vec(),
+      success,
+    }
+  }
+
+  #[test]
+  fn migrator_pairs_reversible_up_and_down_on_the_same_version() {
+    let migrator = migrator(RUNTIME_MIGRATIONS);
+    let versions = migrator
+      .iter()
+      .map(|migration| (migration.version, migration.migration_type))
+      .collect::<Vec<_>>();
+    let expected = (1..=RUNTIME_MIGRATIONS.len() as i64)
+      .flat_map(|version| {
+        [
+          (version, MigrationType::ReversibleUp),
+          (version, MigrationType::ReversibleDown),
+        ]
+      })
+      .collect::<Vec<_>>();
+    assert_eq!(versions, expected);
+    assert_eq!(RUNTIME_MIGRATIONS.len(), RUNTIME_SQLITE_MIGRATIONS.len());
+  }
+
+  #[test]
+  fn plan_reports_pending_applied_and_tampered_migrations() {
+    let plan = plan_runtime_migrations(RUNTIME_MIGRATIONS, Vec::new());
+    assert_eq!(plan.current_version, 0);
+    assert_eq!(plan.pending, RUNTIME_MIGRATIONS.len() as i64);
+    assert_eq!(plan.migrations[0].state, "pending");
+    assert!(plan.migrations[0].reversible);
+
+    let plan = plan_runtime_migrations(RUNTIME_MIGRATIONS, vec![applied(1, RUNTIME_MIGRATIONS[0].up, true)]);
+    assert_eq!(plan.current_version, 1);
+    assert_eq!(plan.pending, RUNTIME_MIGRATIONS.len() as i64 - 1);
+    assert_eq!(plan.migrations[0].state, "applied");
+    assert_eq!(plan.migrations[1].state, "pending");
+
+    let plan = plan_runtime_migrations(RUNTIME_MIGRATIONS, vec![applied(1, "CREATE TABLE edited ()", true)]);
+    assert_eq!(plan.current_version, 0);
+    assert_eq!(plan.migrations[0].state, "checksum_mismatch");
+
+    let plan = plan_runtime_migrations(RUNTIME_MIGRATIONS, vec![applied(1, RUNTIME_MIGRATIONS[0].up, false)]);
+    assert_eq!(plan.migrations[0].state, "failed");
+  }
 }
@@ -0,0 +1,12 @@
+DROP TABLE IF EXISTS runtime_invite_abuse_actions;
+DROP TABLE IF EXISTS runtime_invite_abuse_evidence;
+DROP TABLE IF EXISTS runtime_invite_abuse_subjects;
+DROP TABLE IF EXISTS runtime_rolling_quota_reservations;
+DROP TABLE IF EXISTS runtime_rolling_quota_counters;
+DROP TABLE IF EXISTS blob_cleanup_candidates;
+DROP TABLE IF EXISTS doc_blob_refs;
+DROP TABLE IF EXISTS blob_reconciliation_checkpoints;
+DROP TABLE IF EXISTS blob_reconciliation_runs;
+DROP TABLE IF EXISTS runtime_leases;
+DROP TABLE IF EXISTS runtime_gates;
+DROP TABLE IF EXISTS runtime_states;
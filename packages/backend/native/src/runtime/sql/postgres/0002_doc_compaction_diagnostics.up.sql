@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS doc_compaction_diagnostics (
+  id BIGSERIAL PRIMARY KEY,
+  workspace_id TEXT NOT NULL,
+  doc_id TEXT NOT NULL,
+  kind TEXT NOT NULL,
+  detail TEXT NOT NULL,
+  update_count INTEGER NOT NULL,
+  merged_size BIGINT NOT NULL,
+  created_at TIMESTAMPTZ(3) NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS doc_compaction_diagnostics_doc_idx
+  ON doc_compaction_diagnostics (workspace_id, doc_id, created_at);
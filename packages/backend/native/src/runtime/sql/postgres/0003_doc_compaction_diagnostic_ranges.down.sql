@@ -0,0 +1,5 @@
+DROP INDEX IF EXISTS doc_compaction_diagnostics_created_at_idx;
+
+ALTER TABLE doc_compaction_diagnostics
+  DROP COLUMN IF EXISTS last_update_at,
+  DROP COLUMN IF EXISTS first_update_at;
@@ -0,0 +1,6 @@
+ALTER TABLE doc_compaction_diagnostics
+  ADD COLUMN IF NOT EXISTS first_update_at TIMESTAMPTZ(3),
+  ADD COLUMN IF NOT EXISTS last_update_at TIMESTAMPTZ(3);
+
+CREATE INDEX IF NOT EXISTS doc_compaction_diagnostics_created_at_idx
+  ON doc_compaction_diagnostics (created_at);
@@ -0,0 +1,5 @@
+DROP TABLE IF EXISTS runtime_rolling_quota_reservations;
+DROP TABLE IF EXISTS runtime_rolling_quota_counters;
+DROP TABLE IF EXISTS runtime_leases;
+DROP TABLE IF EXISTS runtime_gates;
+DROP TABLE IF EXISTS runtime_states;
@@ -0,0 +1,77 @@
+CREATE TABLE IF NOT EXISTS runtime_states (
+  purpose TEXT NOT NULL,
+  token_hash TEXT NOT NULL,
+  lookup_key TEXT,
+  payload TEXT NOT NULL,
+  attempts INTEGER NOT NULL DEFAULT 0,
+  consumed_at INTEGER,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  updated_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  PRIMARY KEY (purpose, token_hash)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_states_lookup_idx
+  ON runtime_states (purpose, lookup_key)
+  WHERE lookup_key IS NOT NULL AND consumed_at IS NULL;
+
+CREATE INDEX IF NOT EXISTS runtime_states_expires_at_idx
+  ON runtime_states (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_gates (
+  key TEXT PRIMARY KEY,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_gates_expires_at_idx
+  ON runtime_gates (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_leases (
+  key TEXT PRIMARY KEY,
+  owner TEXT NOT NULL,
+  fencing_token INTEGER NOT NULL,
+  expires_at INTEGER NOT NULL,
+  created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  updated_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_leases_expires_at_idx
+  ON runtime_leases (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_rolling_quota_counters (
+  scope_key TEXT NOT NULL,
+  window_seconds INTEGER NOT NULL,
+  bucket_start INTEGER NOT NULL,
+  count INTEGER NOT NULL CHECK (count >= 0),
+  expires_at INTEGER NOT NULL,
+  updated_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  PRIMARY KEY (scope_key, window_seconds, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_rolling_quota_counters_expires_at_idx
+  ON runtime_rolling_quota_counters (expires_at);
+
+CREATE TABLE IF NOT EXISTS runtime_rolling_quota_reservations (
+  id TEXT NOT NULL,
+  scope_key TEXT NOT NULL,
+  window_seconds INTEGER NOT NULL,
+  bucket_start INTEGER NOT NULL,
+  count INTEGER NOT NULL CHECK (count >= 0),
+  status TEXT NOT NULL CHECK (status IN ('reserved', 'committed', 'released', 'expired')),
+  purpose TEXT NOT NULL,
+  request_id TEXT,
+  expires_at INTEGER NOT NULL,
+  committed_at INTEGER,
+  released_at INTEGER,
+  created_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  updated_at INTEGER NOT NULL DEFAULT (CAST(strftime('%s', 'now') AS INTEGER) * 1000),
+  PRIMARY KEY (id, scope_key, window_seconds, bucket_start)
+);
+
+CREATE INDEX IF NOT EXISTS runtime_rolling_quota_reservations_status_expires_at_idx
+  ON runtime_rolling_quota_reservations (status, expires_at);
+
+CREATE INDEX IF NOT EXISTS runtime_rolling_quota_reservations_scope_idx
+  ON runtime_rolling_quota_reservations (scope_key, window_seconds, bucket_start)
+  WHERE status = 'reserved';
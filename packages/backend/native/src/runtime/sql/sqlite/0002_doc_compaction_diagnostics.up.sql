@@ -0,0 +1,3 @@
+-- Doc compaction only runs against Postgres; this keeps the ledger versions
+-- aligned with the Postgres migrations.
+SELECT 1;
@@ -11,8 +11,9 @@ use assetpack_core::{
 use sqlx::Row;
 
 use super::{
-  FsStorageConfig, MAX_BLOB_SIZE, ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, RuntimeError,
-  RuntimeResult, fs_bucket_path, normalize_storage_key, system_time_ms,
+  EncryptionEnvelope, FsStorageConfig, MAX_BLOB_SIZE, ObjectByteRange, ObjectGetResult, ObjectListEntry,
+  ObjectMetadata, ObjectPutMetadata, ObjectStorageError, RuntimeError, RuntimeResult, encryption::EncryptionRotation,
+  fs_bucket_path, fs_encryption, normalize_storage_key, system_time_ms,
 };
 
 pub(super) async fn put(
@@ -36,6 +37,16 @@ pub(super) async fn put(
     ));
   }
 
+  // Encrypted bodies are opaque to the pipeline, so encryption trades away
+  // assetpack's transforms, compression and cross-object dedup.
+  let (body, envelope) = match config.encryption.as_ref() {
+    Some(encryption) => {
+      let (ciphertext, envelope) = encryption.encrypt(&body)?;
+      (ciphertext, Some(envelope))
+    }
+    None => (body, None),
+  };
+
   let store = open_store(config).await?;
   let transform_config = FileTransformConfig::default();
   let bucket_path = fs_bucket_path(config);
@@ -47,7 +58,7 @@ pub(super) async fn put(
   let original_hash = Hash32::sha3_256(&body);
   let hint = FileHint {
     size: body.len() as u64,
-    extension: extension_from_key(key),
+    extension: envelope.is_none().then(|| extension_from_key(key)).flatten(),
     head: Some(body.iter().take(4096).copied().collect()),
   };
   let plan = Pipeline::new(PipelineConfig::default())
@@ -115,15 +126,18 @@ pub(super) async fn put(
   sqlx::query(
     r#"
     INSERT INTO storage_assetpack_blobs
-      (scope, key, recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms)
-    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+      (scope, key, recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms, encryption_key_id,
+       encryption_wrapped_key)
+    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
     ON CONFLICT (scope, key)
     DO UPDATE SET
       recipe_hash = excluded.recipe_hash,
       content_type = excluded.content_type,
       content_length = excluded.content_length,
       checksum_crc32 = excluded.checksum_crc32,
-      last_modified_ms = excluded.last_modified_ms
+      last_modified_ms = excluded.last_modified_ms,
+      encryption_key_id = excluded.encryption_key_id,
+      encryption_wrapped_key = excluded.encryption_wrapped_key
     "#,
   )
   .bind(scope)
@@ -133,6 +147,8 @@ pub(super) async fn put(
   .bind(object_metadata.content_length)
   .bind(&object_metadata.checksum_crc32)
   .bind(object_metadata.last_modified_ms)
+  .bind(envelope.as_ref().map(|envelope| envelope.key_id.as_str()))
+  .bind(envelope.as_ref().map(|envelope| envelope.wrapped_key.as_str()))
   .execute(&mut *tx)
   .await
   .map_err(|err| RuntimeError::database("Assetpack manifest write failed", err))?;
@@ -152,6 +168,17 @@ pub(super) async fn head(config: &FsStorageConfig, scope: &str, key: &str) -> Ru
 }
 
 pub(super) async fn get(config: &FsStorageConfig, scope: &str, key: &str) -> RuntimeResult<Option<ObjectGetResult>> {
+  get_range(config, scope, key, None).await
+}
+
+/// Assetpack chunks hold the transformed stream, so a range read still has to
+/// rebuild the whole body before slicing it.
+pub(super) async fn get_range(
+  config: &FsStorageConfig,
+  scope: &str,
+  key: &str,
+  range: Option<ObjectByteRange>,
+) -> RuntimeResult<Option<ObjectGetResult>> {
   normalize_storage_key(key)?;
   let store = open_store(config).await?;
   let Some(row) = manifest_row(&store, scope, key).await? else {
@@ -196,10 +223,27 @@ pub(super) async fn get(config: &FsStorageConfig, scope: &str, key: &str) -> Run
       "Assetpack reconstructed body failed integrity check for {key}"
     )));
   }
+  let body = match row.encryption.as_ref() {
+    Some(envelope) => fs_encryption(config, key)?.decrypt(envelope, &body)?,
+    None => body,
+  };
 
+  let Some(range) = range else {
+    return Ok(Some(ObjectGetResult {
+      body,
+      metadata: row.metadata,
+      content_range: None,
+    }));
+  };
+  let content_range = range
+    .resolve(body.len() as u64)
+    .ok_or(ObjectStorageError::RangeNotSatisfiable {
+      length: body.len() as i64,
+    })?;
   Ok(Some(ObjectGetResult {
-    body,
+    body: body[content_range.start as usize..=content_range.end as usize].to_vec(),
     metadata: row.metadata,
+    content_range: Some(content_range),
   }))
 }
 
@@ -265,6 +309,58 @@ pub(super) async fn delete(config: &FsStorageConfig, scope: &str, key: &str) ->
   Ok(())
 }
 
+/// Brings one object under the active encryption key: plaintext objects are
+/// re-put encrypted, others only get their data key re-wrapped in the manifest.
+pub(super) async fn rotate_encryption(
+  config: &FsStorageConfig,
+  scope: &str,
+  key: &str,
+) -> RuntimeResult<Option<EncryptionRotation>> {
+  let encryption = fs_encryption(config, key)?;
+  let store = open_store(config).await?;
+  let Some(row) = manifest_row(&store, scope, key).await? else {
+    return Ok(None);
+  };
+  let Some(envelope) = row.encryption else {
+    let Some(object) = get(config, scope, key).await? else {
+      return Ok(None);
+    };
+    put(
+      config,
+      scope,
+      key,
+      object.body,
+      ObjectPutMetadata {
+        content_type: Some(object.metadata.content_type),
+        content_length: Some(object.metadata.content_length),
+        checksum_crc32: object.metadata.checksum_crc32,
+      },
+    )
+    .await?;
+    return Ok(Some(EncryptionRotation::Encrypted));
+  };
+  if envelope.key_id == encryption.active_key_id() {
+    return Ok(Some(EncryptionRotation::Skipped));
+  }
+  let rewrapped = encryption.rewrap(&envelope)?;
+  sqlx::query(
+    r#"
+    UPDATE storage_assetpack_blobs
+    SET encryption_key_id = ?3, encryption_wrapped_key = ?4
+    WHERE scope = ?1 AND key = ?2 AND encryption_key_id = ?5
+    "#,
+  )
+  .bind(scope)
+  .bind(key)
+  .bind(&rewrapped.key_id)
+  .bind(&rewrapped.wrapped_key)
+  .bind(&envelope.key_id)
+  .execute(store.pool())
+  .await
+  .map_err(|err| RuntimeError::database("Assetpack manifest key rotation failed", err))?;
+  Ok(Some(EncryptionRotation::Rewrapped))
+}
+
 async fn open_store(config: &FsStorageConfig) -> RuntimeResult<SqliteStore> {
   let store = SqliteStore::open(store_path(config))
     .await
@@ -286,6 +382,7 @@ fn extension_from_key(key: &str) -> Option<String> {
 struct ManifestRow {
   recipe_hash: String,
   metadata: ObjectMetadata,
+  encryption: Option<EncryptionEnvelope>,
 }
 
 async fn ensure_manifest_schema(store: &SqliteStore) -> RuntimeResult<()> {
@@ -299,6 +396,8 @@ async fn ensure_manifest_schema(store: &SqliteStore) -> RuntimeResult<()> {
       content_length INTEGER NOT NULL,
       checksum_crc32 TEXT,
       last_modified_ms INTEGER NOT NULL,
+      encryption_key_id TEXT,
+      encryption_wrapped_key TEXT,
       PRIMARY KEY (scope, key)
     )
     "#,
@@ -306,6 +405,19 @@ async fn ensure_manifest_schema(store: &SqliteStore) -> RuntimeResult<()> {
   .execute(store.pool())
   .await
   .map_err(|err| RuntimeError::database("Assetpack manifest schema create failed", err))?;
+  // Manifests created before encryption support lack the envelope columns.
+  let columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('storage_assetpack_blobs')")
+    .fetch_all(store.pool())
+    .await
+    .map_err(|err| RuntimeError::database("Assetpack manifest schema inspect failed", err))?;
+  for column in ["encryption_key_id", "encryption_wrapped_key"] {
+    if !columns.iter().any(|name| name == column) {
+      sqlx::query(&format!("ALTER TABLE storage_assetpack_blobs ADD COLUMN {column} TEXT"))
+        .execute(store.pool())
+        .await
+        .map_err(|err| RuntimeError::database("Assetpack manifest schema upgrade failed", err))?;
+    }
+  }
   sqlx::query(
     "CREATE INDEX IF NOT EXISTS storage_assetpack_blobs_scope_prefix_idx ON storage_assetpack_blobs (scope, key)",
   )
@@ -318,7 +430,8 @@ async fn ensure_manifest_schema(store: &SqliteStore) -> RuntimeResult<()> {
 async fn manifest_row(store: &SqliteStore, scope: &str, key: &str) -> RuntimeResult<Option<ManifestRow>> {
   let row = sqlx::query(
     r#"
-    SELECT recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms
+    SELECT recipe_hash, content_type, content_length, checksum_crc32, last_modified_ms, encryption_key_id,
+      encryption_wrapped_key
     FROM storage_assetpack_blobs
     WHERE scope = ?1 AND key = ?2
     "#,
@@ -339,6 +452,10 @@ async fn manifest_row(store: &SqliteStore, scope: &str, key: &str) -> RuntimeRes
           checksum_crc32: row.get("checksum_crc32"),
           last_modified_ms: row.get("last_modified_ms"),
         },
+        encryption: row
+          .get::<Option<String>, _>("encryption_key_id")
+          .zip(row.get::<Option<String>, _>("encryption_wrapped_key"))
+          .map(|(key_id, wrapped_key)| EncryptionEnvelope { key_id, wrapped_key }),
       })
     })
     .transpose()
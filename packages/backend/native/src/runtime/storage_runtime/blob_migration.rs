@@ -0,0 +1,391 @@
+use super::{
+  FsStorageConfig, ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, RuntimeBlobMigrationResult,
+  RuntimeError, RuntimeResult, StorageBackendConfig, StorageRuntime, assetpack, checksum_crc32_base64, fs_bucket_path,
+  fs_delete, fs_get, fs_list, fs_put,
+  key_checkpoint::{KeyCheckpointKind, next_cursor, take_retries},
+  napi_error, sha256_base64_url,
+};
+
+#[derive(Debug, PartialEq, Eq)]
+enum BlobMigrationOutcome {
+  Copied,
+  /// The destination already held an identical body, e.g. from an
+  /// interrupted earlier run.
+  Existing,
+  /// The object disappeared from the source after it was listed.
+  Missing,
+}
+
+impl StorageBackendConfig {
+  /// Where the objects of `scope` physically live. Differently named scopes
+  /// can share a directory or bucket, and an assetpack keeps scopes apart in
+  /// its manifest.
+  fn physical_location(&self, scope: &str) -> String {
+    let canonical_bucket_path = |config: &FsStorageConfig| {
+      let path = fs_bucket_path(config);
+      std::fs::canonicalize(&path).unwrap_or(path)
+    };
+    match self {
+      Self::Fs(config) => format!("fs:{}", canonical_bucket_path(config).display()),
+      Self::Assetpack(config) => format!("assetpack:{}:{scope}", canonical_bucket_path(config).display()),
+      Self::S3(config) => format!(
+        "s3:{}/{}",
+        config
+          .endpoint
+          .as_deref()
+          .map(|endpoint| endpoint.trim_end_matches('/').to_ascii_lowercase())
+          .unwrap_or_default(),
+        config.bucket
+      ),
+    }
+  }
+
+  pub(super) async fn list_after(
+    &self,
+    scope: &str,
+    prefix: String,
+    start_after: Option<String>,
+    limit: usize,
+  ) -> RuntimeResult<(Vec<ObjectListEntry>, bool)> {
+    let mut entries = match self {
+      Self::Fs(config) => fs_list(config, Some(prefix))?,
+      Self::Assetpack(config) => assetpack::list(config, scope, Some(prefix)).await?,
+      Self::S3(config) => {
+        let max_keys =
+          i32::try_from(limit + 1).map_err(|_| RuntimeError::invalid_input("blob migration limit exceeds i32::MAX"))?;
+        config
+          .build_client()?
+          .list_page(Some(prefix), None, start_after.clone(), max_keys)
+          .await?
+          .entries
+      }
+    };
+    if let Some(start_after) = start_after {
+      entries.retain(|entry| entry.key > start_after);
+    }
+    entries.sort_by(|a, b| a.key.cmp(&b.key));
+    let has_more = entries.len() > limit;
+    entries.truncate(limit);
+    Ok((entries, has_more))
+  }
+
+  pub(super) async fn get_object(&self, scope: &str, key: &str) -> RuntimeResult<Option<ObjectGetResult>> {
+    match self {
+      Self::Fs(config) => fs_get(config, key),
+      Self::Assetpack(config) => assetpack::get(config, scope, key).await,
+      Self::S3(config) => match config.build_client()?.get(key).await.map_err(RuntimeError::from) {
+        Err(err) if err.is_object_missing() => Ok(None),
+        result => result,
+      },
+    }
+  }
+
+  pub(super) async fn put_object(
+    &self,
+    scope: &str,
+    key: &str,
+    body: Vec<u8>,
+    metadata: ObjectPutMetadata,
+  ) -> RuntimeResult<ObjectMetadata> {
+    match self {
+      Self::Fs(config) => fs_put(config, key, body, metadata),
+      Self::Assetpack(config) => assetpack::put(config, scope, key, body, metadata).await,
+      Self::S3(config) => config
+        .build_client()?
+        .put(key, body, metadata)
+        .await
+        .map_err(Into::into),
+    }
+  }
+
+  pub(super) async fn delete_object(&self, scope: &str, key: &str) -> RuntimeResult<()> {
+    match self {
+      Self::Fs(config) => fs_delete(config, key),
+      Self::Assetpack(config) => assetpack::delete(config, scope, key).await,
+      Self::S3(config) => match config.build_client()?.delete(key).await.map_err(RuntimeError::from) {
+        Err(err) if err.is_object_missing() => Ok(()),
+        result => result,
+      },
+    }
+  }
+}
+
+/// Copies one object and reads it back from the destination before the
+/// source copy is considered redundant.
+async fn migrate_object(
+  from: (&StorageBackendConfig, &str),
+  to: (&StorageBackendConfig, &str),
+  key: &str,
+  delete_source: bool,
+) -> RuntimeResult<(BlobMigrationOutcome, bool)> {
+  let ((from, from_scope), (to, to_scope)) = (from, to);
+  let Some(source) = from.get_object(from_scope, key).await? else {
+    return Ok((BlobMigrationOutcome::Missing, false));
+  };
+  let checksum = checksum_crc32_base64(&source.body);
+  if source
+    .metadata
+    .checksum_crc32
+    .as_deref()
+    .is_some_and(|expected| expected != checksum)
+  {
+    return Err(RuntimeError::invalid_state(format!(
+      "Blob migration source checksum mismatch for {key}"
+    )));
+  }
+  let digest = sha256_base64_url(&source.body);
+  let matches_source =
+    |object: &ObjectGetResult| object.body.len() == source.body.len() && sha256_base64_url(&object.body) == digest;
+
+  let outcome = match to.get_object(to_scope, key).await? {
+    Some(existing) if matches_source(&existing) => BlobMigrationOutcome::Existing,
+    _ => {
+      to.put_object(
+        to_scope,
+        key,
+        source.body.clone(),
+        ObjectPutMetadata {
+          content_type: Some(source.metadata.content_type.clone()),
+          content_length: Some(source.metadata.content_length),
+          checksum_crc32: Some(checksum),
+        },
+      )
+      .await?;
+      if !to
+        .get_object(to_scope, key)
+        .await?
+        .is_some_and(|copy| matches_source(&copy))
+      {
+        return Err(RuntimeError::invalid_state(format!(
+          "Blob migration destination verification failed for {key}"
+        )));
+      }
+      BlobMigrationOutcome::Copied
+    }
+  };
+
+  if delete_source {
+    from.delete_object(from_scope, key).await?;
+  }
+  Ok((outcome, delete_source))
+}
+
+const MIGRATION_CHECKPOINT: KeyCheckpointKind = KeyCheckpointKind {
+  kind: "blob_migration",
+  label: "Blob migration",
+  restart_when_completed: false,
+};
+
+fn checkpoint_scope(from_scope: &str, to_scope: &str, workspace_id: &str) -> String {
+  format!("{from_scope}->{to_scope}:{workspace_id}")
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Copies a workspace's blobs from the `from_scope` backend to the
+  /// `to_scope` backend, `limit` objects per call, verifying each copy by
+  /// reading it back. With `delete_source` the source object is removed once
+  /// its copy verified. Call until `nextCursor` is `null`. Objects that failed
+  /// are recorded and retried, up to `limit` of them, at the start of the
+  /// next call, and keep `nextCursor` set until they migrate.
+  #[napi]
+  pub async fn migrate_workspace_blobs(
+    &self,
+    from_scope: String,
+    to_scope: String,
+    workspace_id: String,
+    limit: i64,
+    delete_source: Option<bool>,
+  ) -> napi::Result<RuntimeBlobMigrationResult> {
+    if limit <= 0 {
+      return Err(napi_error("blob migration limit must be positive"));
+    }
+    if from_scope == to_scope {
+      return Err(napi_error("blob migration source and destination scopes must differ"));
+    }
+    if workspace_id.is_empty() || workspace_id.contains('/') {
+      return Err(napi_error("blob migration workspace id is invalid"));
+    }
+    let delete_source = delete_source.unwrap_or(false);
+    // No fallback to the `blob` scope here: an unconfigured scope resolving to
+    // the source backend would make `delete_source` destroy the only copy.
+    let config = self.config()?;
+    let (Some(from), Some(to)) = (config.backends.get(&from_scope), config.backends.get(&to_scope)) else {
+      return Err(napi_error("blob migration scopes must both be configured explicitly"));
+    };
+    if from.physical_location(&from_scope) == to.physical_location(&to_scope) {
+      return Err(napi_error(
+        "blob migration source and destination scopes resolve to the same storage",
+      ));
+    }
+
+    let pool = self.pool().await?;
+    let checkpoint_scope = checkpoint_scope(&from_scope, &to_scope, &workspace_id);
+    let checkpoint = MIGRATION_CHECKPOINT.load(&pool, &checkpoint_scope).await?;
+    let last_key = checkpoint.last_key;
+    let (retries, mut retry_keys) = take_retries(checkpoint.failed_keys, limit as usize);
+    let (entries, has_more) = from
+      .list_after(
+        &from_scope,
+        format!("{workspace_id}/"),
+        last_key.clone(),
+        limit as usize,
+      )
+      .await?;
+
+    let mut result = RuntimeBlobMigrationResult {
+      scanned: 0,
+      copied: 0,
+      skipped_existing: 0,
+      skipped_missing: 0,
+      deleted_source: 0,
+      failed: 0,
+      next_cursor: None,
+    };
+    let mut failed_keys = Vec::new();
+    for key in retries.iter().chain(entries.iter().map(|entry| &entry.key)) {
+      result.scanned += 1;
+      match migrate_object((from, &from_scope), (to, &to_scope), key, delete_source).await {
+        Ok((outcome, deleted)) => {
+          match outcome {
+            BlobMigrationOutcome::Copied => result.copied += 1,
+            BlobMigrationOutcome::Existing => result.skipped_existing += 1,
+            BlobMigrationOutcome::Missing => result.skipped_missing += 1,
+          }
+          if deleted {
+            result.deleted_source += 1;
+          }
+        }
+        Err(err) => {
+          result.failed += 1;
+          retry_keys.push(key.clone());
+          failed_keys.push(serde_json::json!({ "key": key, "error": err.to_string() }));
+        }
+      }
+    }
+    let last_scanned_key = entries.last().map(|entry| entry.key.clone());
+    result.next_cursor = next_cursor(has_more, last_scanned_key.as_deref(), last_key.as_deref(), &retry_keys);
+    MIGRATION_CHECKPOINT
+      .save(
+        &pool,
+        &checkpoint_scope,
+        last_scanned_key.as_deref(),
+        &retry_keys,
+        result.next_cursor.is_none(),
+      )
+      .await?;
+
+    MIGRATION_CHECKPOINT
+      .record_run(
+        &pool,
+        Some(&workspace_id),
+        (result.scanned, result.copied, result.failed),
+        serde_json::json!({
+          "fromScope": from_scope,
+          "toScope": to_scope,
+          "skippedExisting": result.skipped_existing,
+          "skippedMissing": result.skipped_missing,
+          "deletedSource": result.deleted_source,
+          "failedKeys": failed_keys,
+          "checkpointScope": checkpoint_scope,
+          "nextCursor": result.next_cursor,
+        }),
+      )
+      .await?;
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn backend(provider: &str, root: &std::path::Path) -> StorageBackendConfig {
+    let config = FsStorageConfig {
+      provider: provider.to_string(),
+      root: root.to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: None,
+    };
+    match provider {
+      "assetpack" => StorageBackendConfig::Assetpack(config),
+      _ => StorageBackendConfig::Fs(config),
+    }
+  }
+
+  #[tokio::test]
+  async fn blob_migration_copies_verifies_and_deletes_source() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let from = backend("fs", &temp.path().join("fs"));
+    let to = backend("assetpack", &temp.path().join("assetpack"));
+    let scope = format!("test_{}", uuid::Uuid::new_v4().simple());
+    for (key, body) in [("workspace/a", b"first".to_vec()), ("workspace/b", b"second".to_vec())] {
+      from
+        .put_object(
+          &scope,
+          key,
+          body,
+          ObjectPutMetadata {
+            content_type: Some("text/plain".to_string()),
+            ..Default::default()
+          },
+        )
+        .await?;
+    }
+    from
+      .put_object(&scope, "other/c", b"other".to_vec(), ObjectPutMetadata::default())
+      .await?;
+
+    let (entries, has_more) = from.list_after(&scope, "workspace/".to_string(), None, 1).await?;
+    assert_eq!(
+      entries.iter().map(|entry| entry.key.as_str()).collect::<Vec<_>>(),
+      ["workspace/a"]
+    );
+    assert!(has_more);
+    let (entries, has_more) = from
+      .list_after(&scope, "workspace/".to_string(), Some("workspace/a".to_string()), 1)
+      .await?;
+    assert_eq!(
+      entries.iter().map(|entry| entry.key.as_str()).collect::<Vec<_>>(),
+      ["workspace/b"]
+    );
+    assert!(!has_more);
+
+    assert_eq!(
+      migrate_object((&from, &scope), (&to, &scope), "workspace/a", false).await?,
+      (BlobMigrationOutcome::Copied, false)
+    );
+    assert_eq!(
+      migrate_object((&from, &scope), (&to, &scope), "workspace/a", true).await?,
+      (BlobMigrationOutcome::Existing, true)
+    );
+    assert!(from.get_object(&scope, "workspace/a").await?.is_none());
+    let copy = to.get_object(&scope, "workspace/a").await?.unwrap();
+    assert_eq!(copy.body, b"first");
+    assert_eq!(copy.metadata.content_type, "text/plain");
+
+    assert_eq!(
+      migrate_object((&from, &scope), (&to, &scope), "workspace/a", true).await?,
+      (BlobMigrationOutcome::Missing, false)
+    );
+    Ok(())
+  }
+
+  #[test]
+  fn blob_migration_scopes_resolve_to_physical_locations() {
+    let temp = tempfile::tempdir().unwrap();
+    let fs = backend("fs", temp.path());
+    assert_eq!(fs.physical_location("blob"), fs.physical_location("avatar"));
+    assert_ne!(
+      fs.physical_location("blob"),
+      backend("fs", &temp.path().join("other")).physical_location("blob")
+    );
+    let assetpack = backend("assetpack", temp.path());
+    assert_ne!(
+      assetpack.physical_location("blob"),
+      assetpack.physical_location("avatar")
+    );
+    assert_ne!(assetpack.physical_location("blob"), fs.physical_location("blob"));
+  }
+}
@@ -0,0 +1,237 @@
+//! Envelope encryption for blobs kept on local disk by the fs and assetpack
+//! backends.
+//!
+//! Every object gets a random data key that encrypts its body with
+//! AES-256-GCM. The data key is wrapped by the configured key-encryption key
+//! and stored next to the object (fs sidecar or assetpack manifest) together
+//! with the key id, so rotating keys only has to re-wrap data keys.
+
+use std::{collections::HashMap, fmt};
+
+use aes_gcm::{
+  Aes256Gcm, KeyInit, Nonce,
+  aead::{Aead, AeadCore, OsRng},
+};
+use base64::{Engine as _, engine::general_purpose::STANDARD};
+use serde::{Deserialize, Serialize};
+
+use super::{RuntimeError, RuntimeResult};
+
+const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+/// Bytes added to every encrypted payload: the nonce prefix and the GCM tag.
+const ENCRYPTION_OVERHEAD: usize = NONCE_LEN + 16;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct StorageEncryptionConfigFile {
+  active_key_id: String,
+  /// Key id to 32-byte key, hex or base64 encoded.
+  keys: HashMap<String, String>,
+}
+
+/// Key-encryption keys for a storage backend. `active_key_id` wraps new
+/// objects; the others are kept so older objects stay readable until rotated.
+#[derive(Clone)]
+pub(super) struct StorageEncryptionConfig {
+  active_key_id: String,
+  keys: HashMap<String, [u8; KEY_LEN]>,
+}
+
+impl fmt::Debug for StorageEncryptionConfig {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let mut key_ids = self.keys.keys().collect::<Vec<_>>();
+    key_ids.sort();
+    f.debug_struct("StorageEncryptionConfig")
+      .field("active_key_id", &self.active_key_id)
+      .field("key_ids", &key_ids)
+      .finish()
+  }
+}
+
+/// Wrapped data key persisted alongside an encrypted object.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct EncryptionEnvelope {
+  pub(super) key_id: String,
+  pub(super) wrapped_key: String,
+}
+
+/// What the key-rotation job did to a single object.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) enum EncryptionRotation {
+  /// The data key was re-wrapped with the active key.
+  Rewrapped,
+  /// A plaintext object written before encryption was enabled got encrypted.
+  Encrypted,
+  /// Already wrapped by the active key.
+  Skipped,
+}
+
+impl StorageEncryptionConfig {
+  pub(super) fn from_config_file(config: StorageEncryptionConfigFile) -> RuntimeResult<Self> {
+    let keys = config
+      .keys
+      .into_iter()
+      .map(|(key_id, key)| decode_key(&key_id, &key).map(|key| (key_id, key)))
+      .collect::<RuntimeResult<HashMap<_, _>>>()?;
+    if !keys.contains_key(&config.active_key_id) {
+      return Err(RuntimeError::config(format!(
+        "storage encryption active key {} is not configured",
+        config.active_key_id
+      )));
+    }
+    Ok(Self {
+      active_key_id: config.active_key_id,
+      keys,
+    })
+  }
+
+  pub(super) fn active_key_id(&self) -> &str {
+    &self.active_key_id
+  }
+
+  /// Encrypts `body` under a fresh data key wrapped by the active key.
+  pub(super) fn encrypt(&self, body: &[u8]) -> RuntimeResult<(Vec<u8>, EncryptionEnvelope)> {
+    let data_key = Aes256Gcm::generate_key(OsRng);
+    let ciphertext = seal(&data_key, body)?;
+    let envelope = EncryptionEnvelope {
+      key_id: self.active_key_id.clone(),
+      wrapped_key: STANDARD.encode(seal(self.key(&self.active_key_id)?, &data_key)?),
+    };
+    Ok((ciphertext, envelope))
+  }
+
+  pub(super) fn decrypt(&self, envelope: &EncryptionEnvelope, ciphertext: &[u8]) -> RuntimeResult<Vec<u8>> {
+    open(&self.data_key(envelope)?, ciphertext)
+  }
+
+  /// Re-wraps the data key of `envelope` with the active key. The object body
+  /// is left untouched.
+  pub(super) fn rewrap(&self, envelope: &EncryptionEnvelope) -> RuntimeResult<EncryptionEnvelope> {
+    let data_key = self.data_key(envelope)?;
+    Ok(EncryptionEnvelope {
+      key_id: self.active_key_id.clone(),
+      wrapped_key: STANDARD.encode(seal(self.key(&self.active_key_id)?, &data_key)?),
+    })
+  }
+
+  fn data_key(&self, envelope: &EncryptionEnvelope) -> RuntimeResult<[u8; KEY_LEN]> {
+    let wrapped = STANDARD
+      .decode(&envelope.wrapped_key)
+      .map_err(|_| RuntimeError::invalid_state("StorageRuntime encrypted object has a malformed wrapped key"))?;
+    open(self.key(&envelope.key_id)?, &wrapped)?
+      .try_into()
+      .map_err(|_| RuntimeError::invalid_state("StorageRuntime encrypted object data key has invalid length"))
+  }
+
+  fn key(&self, key_id: &str) -> RuntimeResult<&[u8; KEY_LEN]> {
+    self
+      .keys
+      .get(key_id)
+      .ok_or_else(|| RuntimeError::invalid_state(format!("StorageRuntime encryption key {key_id} is not configured")))
+  }
+}
+
+fn decode_key(key_id: &str, key: &str) -> RuntimeResult<[u8; KEY_LEN]> {
+  let decoded = if key.len() == KEY_LEN * 2 {
+    hex::decode(key).ok()
+  } else {
+    STANDARD.decode(key).ok()
+  };
+  decoded.and_then(|decoded| decoded.try_into().ok()).ok_or_else(|| {
+    RuntimeError::config(format!(
+      "storage encryption key {key_id} must be 32 bytes, hex or base64"
+    ))
+  })
+}
+
+/// Returns `nonce || ciphertext || tag`.
+fn seal(key: &[u8], plaintext: &[u8]) -> RuntimeResult<Vec<u8>> {
+  let cipher =
+    Aes256Gcm::new_from_slice(key).map_err(|_| RuntimeError::invalid_state("StorageRuntime invalid encryption key"))?;
+  let nonce = Aes256Gcm::generate_nonce(OsRng);
+  let ciphertext = cipher
+    .encrypt(&nonce, plaintext)
+    .map_err(|_| RuntimeError::invalid_state("StorageRuntime encryption failed"))?;
+  let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+  sealed.extend_from_slice(&nonce);
+  sealed.extend_from_slice(&ciphertext);
+  Ok(sealed)
+}
+
+fn open(key: &[u8], sealed: &[u8]) -> RuntimeResult<Vec<u8>> {
+  if sealed.len() < ENCRYPTION_OVERHEAD {
+    return Err(RuntimeError::invalid_state(
+      "StorageRuntime encrypted payload is truncated",
+    ));
+  }
+  let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+  Aes256Gcm::new_from_slice(key)
+    .map_err(|_| RuntimeError::invalid_state("StorageRuntime invalid encryption key"))?
+    .decrypt(Nonce::from_slice(nonce), ciphertext)
+    .map_err(|_| RuntimeError::invalid_state("StorageRuntime encrypted payload failed authentication"))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn config(active_key_id: &str) -> StorageEncryptionConfig {
+    StorageEncryptionConfig::from_config_file(StorageEncryptionConfigFile {
+      active_key_id: active_key_id.to_string(),
+      keys: HashMap::from([
+        ("k1".to_string(), "11".repeat(KEY_LEN)),
+        ("k2".to_string(), STANDARD.encode([2u8; KEY_LEN])),
+      ]),
+    })
+    .unwrap()
+  }
+
+  #[test]
+  fn encrypts_with_active_key_and_rewraps_without_touching_body() {
+    let old = config("k1");
+    let (ciphertext, envelope) = old.encrypt(b"secret body").unwrap();
+    assert_eq!(envelope.key_id, "k1");
+    assert_eq!(ciphertext.len(), b"secret body".len() + ENCRYPTION_OVERHEAD);
+    assert_eq!(old.decrypt(&envelope, &ciphertext).unwrap(), b"secret body");
+
+    let rotated = config("k2");
+    let rewrapped = rotated.rewrap(&envelope).unwrap();
+    assert_eq!(rewrapped.key_id, "k2");
+    assert_eq!(rotated.decrypt(&rewrapped, &ciphertext).unwrap(), b"secret body");
+  }
+
+  #[test]
+  fn rejects_tampered_payloads_and_unknown_keys() {
+    let config = config("k1");
+    let (mut ciphertext, envelope) = config.encrypt(b"secret body").unwrap();
+    *ciphertext.last_mut().unwrap() ^= 1;
+    assert!(config.decrypt(&envelope, &ciphertext).is_err());
+
+    let unknown = EncryptionEnvelope {
+      key_id: "retired".to_string(),
+      ..envelope
+    };
+    assert!(config.decrypt(&unknown, &ciphertext).is_err());
+  }
+
+  #[test]
+  fn config_requires_configured_active_key_of_valid_length() {
+    assert!(
+      StorageEncryptionConfig::from_config_file(StorageEncryptionConfigFile {
+        active_key_id: "missing".to_string(),
+        keys: HashMap::from([("k1".to_string(), "11".repeat(KEY_LEN))]),
+      })
+      .is_err()
+    );
+    assert!(
+      StorageEncryptionConfig::from_config_file(StorageEncryptionConfigFile {
+        active_key_id: "k1".to_string(),
+        keys: HashMap::from([("k1".to_string(), "short".to_string())]),
+      })
+      .is_err()
+    );
+    assert!(!format!("{:?}", config("k1")).contains("1111"));
+  }
+}
@@ -0,0 +1,199 @@
+use std::{
+  fs,
+  io::ErrorKind,
+  path::{Path, PathBuf},
+};
+
+use super::{
+  FsStorageConfig, RuntimeError, RuntimeResult, RuntimeStorageEncryptionRotationResult, StorageBackendConfig,
+  StorageRuntime, assetpack,
+  encryption::EncryptionRotation,
+  fs_encryption, fs_list, fs_object_path,
+  key_checkpoint::{KeyCheckpointKind, next_cursor, take_retries},
+  napi_error, read_fs_sidecar, write_fs_sidecar,
+};
+
+/// Suffix of an fs object body or sidecar written next to its final path
+/// before it is renamed over it; listings skip these files.
+pub(super) const FS_ENCRYPTING_SUFFIX: &str = ".encrypting";
+
+const ROTATION_CHECKPOINT: KeyCheckpointKind = KeyCheckpointKind {
+  kind: "storage_encryption_rotation",
+  label: "Storage encryption rotation",
+  restart_when_completed: false,
+};
+
+/// Checkpoints are keyed by the active key id, so switching keys again starts
+/// a fresh pass instead of resuming the previous one.
+fn checkpoint_scope(scope: &str, active_key_id: &str) -> String {
+  format!("{scope}:{active_key_id}")
+}
+
+pub(super) fn encrypting_path(path: &Path) -> PathBuf {
+  PathBuf::from(format!("{}{FS_ENCRYPTING_SUFFIX}", path.display()))
+}
+
+/// Renames a ciphertext left pending by an interrupted write over its object,
+/// once the sidecar already carries its key. Returns whether one was pending.
+pub(super) fn finish_pending_fs_body(path: &Path) -> RuntimeResult<bool> {
+  match fs::rename(encrypting_path(path), path) {
+    Ok(()) => Ok(true),
+    Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+    Err(err) => Err(RuntimeError::io("StorageRuntime fs rename object failed", err)),
+  }
+}
+
+/// The sidecar is rewritten before the ciphertext replaces the plaintext body,
+/// so a crash in between leaves an object that fails authentication rather
+/// than one that silently serves ciphertext; the next pass finishes the rename.
+pub(super) fn fs_rotate_encryption(config: &FsStorageConfig, key: &str) -> RuntimeResult<Option<EncryptionRotation>> {
+  let encryption = fs_encryption(config, key)?;
+  let path = fs_object_path(config, key)?;
+  let Some(sidecar) = read_fs_sidecar(&path)? else {
+    return Ok(None);
+  };
+  let (metadata, envelope) = sidecar.into_parts();
+  let pending = encrypting_path(&path);
+
+  let Some(envelope) = envelope else {
+    let body = match fs::read(&path) {
+      Ok(body) => body,
+      Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+      Err(err) => return Err(RuntimeError::io("StorageRuntime fs read object failed", err)),
+    };
+    let (ciphertext, envelope) = encryption.encrypt(&body)?;
+    fs::write(&pending, ciphertext).map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
+    write_fs_sidecar(&path, &metadata, Some(&envelope))?;
+    fs::rename(&pending, &path).map_err(|err| RuntimeError::io("StorageRuntime fs rename object failed", err))?;
+    return Ok(Some(EncryptionRotation::Encrypted));
+  };
+
+  let resumed = finish_pending_fs_body(&path)?;
+  if envelope.key_id == encryption.active_key_id() {
+    return Ok(Some(if resumed {
+      EncryptionRotation::Encrypted
+    } else {
+      EncryptionRotation::Skipped
+    }));
+  }
+  write_fs_sidecar(&path, &metadata, Some(&encryption.rewrap(&envelope)?))?;
+  Ok(Some(EncryptionRotation::Rewrapped))
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Re-wraps data keys still sealed by a retired key and encrypts objects
+  /// written before encryption was enabled, `limit` objects per call. Progress
+  /// is checkpointed per active key, so call until `nextCursor` is `null`.
+  /// Objects that failed are recorded and retried, up to `limit` of them, at
+  /// the start of the next call, and keep `nextCursor` set until they rotate.
+  #[napi]
+  pub async fn rotate_storage_encryption(
+    &self,
+    scope: String,
+    limit: i64,
+  ) -> napi::Result<RuntimeStorageEncryptionRotationResult> {
+    if limit <= 0 {
+      return Err(napi_error("storage encryption rotation limit must be positive"));
+    }
+
+    let backend = self.backend_for_scope(&scope)?;
+    let (StorageBackendConfig::Fs(config) | StorageBackendConfig::Assetpack(config)) = &backend else {
+      return Err(napi_error(
+        "storage encryption is only supported by fs and assetpack storage",
+      ));
+    };
+    let Some(encryption) = config.encryption.as_ref() else {
+      return Err(napi_error(format!(
+        "storage encryption is not configured for scope {scope}"
+      )));
+    };
+
+    let pool = self.pool().await?;
+    let checkpoint_scope = checkpoint_scope(&scope, encryption.active_key_id());
+    let checkpoint = ROTATION_CHECKPOINT.load(&pool, &checkpoint_scope).await?;
+    let last_key = checkpoint.last_key;
+    let (retries, mut failed_keys) = take_retries(checkpoint.failed_keys, limit as usize);
+    let mut keys = match &backend {
+      StorageBackendConfig::Assetpack(config) => assetpack::list(config, &scope, None).await?,
+      _ => fs_list(config, None)?,
+    }
+    .into_iter()
+    .map(|entry| entry.key)
+    .filter(|key| last_key.as_ref().is_none_or(|last_key| key > last_key))
+    .collect::<Vec<_>>();
+    keys.sort();
+    let has_more = keys.len() as i64 > limit;
+    keys.truncate(limit as usize);
+
+    let mut result = RuntimeStorageEncryptionRotationResult {
+      scanned: 0,
+      rewrapped: 0,
+      encrypted: 0,
+      skipped: 0,
+      failed: 0,
+      next_cursor: None,
+    };
+    for key in retries.iter().chain(&keys) {
+      result.scanned += 1;
+      let rotation = match &backend {
+        StorageBackendConfig::Assetpack(config) => assetpack::rotate_encryption(config, &scope, key).await,
+        _ => fs_rotate_encryption(config, key),
+      };
+      match rotation {
+        Ok(Some(EncryptionRotation::Rewrapped)) => result.rewrapped += 1,
+        Ok(Some(EncryptionRotation::Encrypted)) => result.encrypted += 1,
+        Ok(Some(EncryptionRotation::Skipped) | None) => result.skipped += 1,
+        Err(_) => {
+          result.failed += 1;
+          failed_keys.push(key.clone());
+        }
+      }
+    }
+    result.next_cursor = next_cursor(
+      has_more,
+      keys.last().map(String::as_str),
+      last_key.as_deref(),
+      &failed_keys,
+    );
+    ROTATION_CHECKPOINT
+      .save(
+        &pool,
+        &checkpoint_scope,
+        keys.last().map(String::as_str),
+        &failed_keys,
+        result.next_cursor.is_none(),
+      )
+      .await?;
+
+    ROTATION_CHECKPOINT
+      .record_run(
+        &pool,
+        None,
+        (result.scanned, result.rewrapped + result.encrypted, result.failed),
+        serde_json::json!({
+          "rewrapped": result.rewrapped,
+          "encrypted": result.encrypted,
+          "skipped": result.skipped,
+          "failedKeys": failed_keys,
+          "activeKeyId": encryption.active_key_id(),
+          "checkpointScope": checkpoint_scope,
+          "nextCursor": result.next_cursor,
+        }),
+      )
+      .await?;
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn storage_encryption_rotation_checkpoint_scope_tracks_active_key() {
+    assert_eq!(checkpoint_scope("blob", "k2"), "blob:k2");
+    assert_ne!(checkpoint_scope("blob", "k2"), checkpoint_scope("blob", "k3"));
+  }
+}
@@ -0,0 +1,181 @@
+//! Checkpoints and run records for storage passes that walk a scope in key
+//! order, `limit` keys per call.
+
+use serde_json::Value as JsonValue;
+use sqlx::{FromRow, PgPool};
+
+use super::{RuntimeError, RuntimeResult};
+
+#[derive(FromRow)]
+struct CheckpointRow {
+  last_key: Option<String>,
+  metadata: JsonValue,
+}
+
+/// Where a pass stopped, plus the keys that failed on earlier calls and are
+/// retried ahead of new ones.
+#[derive(Default)]
+pub(super) struct KeyCheckpoint {
+  pub(super) last_key: Option<String>,
+  pub(super) failed_keys: Vec<String>,
+}
+
+/// One `blob_reconciliation_checkpoints` / `blob_reconciliation_runs` kind.
+pub(super) struct KeyCheckpointKind {
+  pub(super) kind: &'static str,
+  /// Prefix of error messages, e.g. `Blob migration`.
+  pub(super) label: &'static str,
+  /// Clear `last_key` once a pass completes, so the next call starts over
+  /// instead of only picking up keys added after the last one seen.
+  pub(super) restart_when_completed: bool,
+}
+
+impl KeyCheckpointKind {
+  pub(super) async fn load(&self, pool: &PgPool, scope: &str) -> RuntimeResult<KeyCheckpoint> {
+    let row = sqlx::query_as::<_, CheckpointRow>(
+      "SELECT last_key, metadata FROM blob_reconciliation_checkpoints WHERE kind = $1 AND scope = $2",
+    )
+    .bind(self.kind)
+    .bind(scope)
+    .fetch_optional(pool)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{} checkpoint load failed", self.label), err))?;
+
+    Ok(row.map_or_else(KeyCheckpoint::default, |row| {
+      KeyCheckpoint {
+        last_key: row.last_key,
+        failed_keys: row
+          .metadata
+          .get("failedKeys")
+          .and_then(|keys| serde_json::from_value(keys.clone()).ok())
+          .unwrap_or_default(),
+      }
+    }))
+  }
+
+  /// `last_key` of `None` keeps the stored key, unless the pass completed
+  /// and `restart_when_completed` is set.
+  pub(super) async fn save(
+    &self,
+    pool: &PgPool,
+    scope: &str,
+    last_key: Option<&str>,
+    failed_keys: &[String],
+    completed: bool,
+  ) -> RuntimeResult<()> {
+    let status = if completed { "completed" } else { "running" };
+    sqlx::query(
+      r#"
+      INSERT INTO blob_reconciliation_checkpoints
+        (kind, scope, status, cursor, last_key, completed_at, metadata)
+      VALUES ($1, $2, $3, $4, $5, CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END, $7)
+      ON CONFLICT (kind, scope) DO UPDATE
+        SET status = EXCLUDED.status,
+            cursor = EXCLUDED.cursor,
+            last_key = CASE
+              WHEN $6 AND $8 THEN EXCLUDED.last_key
+              ELSE COALESCE(EXCLUDED.last_key, blob_reconciliation_checkpoints.last_key)
+            END,
+            completed_at = CASE WHEN $6 THEN CURRENT_TIMESTAMP ELSE NULL END,
+            updated_at = CURRENT_TIMESTAMP,
+            metadata = EXCLUDED.metadata
+      "#,
+    )
+    .bind(self.kind)
+    .bind(scope)
+    .bind(status)
+    .bind(serde_json::json!({ "lastKey": last_key }))
+    .bind(last_key)
+    .bind(completed)
+    .bind(serde_json::json!({ "failedKeys": failed_keys }))
+    .bind(self.restart_when_completed)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{} checkpoint write failed", self.label), err))?;
+    Ok(())
+  }
+
+  pub(super) async fn record_run(
+    &self,
+    pool: &PgPool,
+    workspace_id: Option<&str>,
+    (scanned, changed, failed): (i64, i64, i64),
+    metadata: JsonValue,
+  ) -> RuntimeResult<()> {
+    sqlx::query(
+      r#"
+      INSERT INTO blob_reconciliation_runs
+        (kind, mode, status, workspace_id, finished_at, scanned, changed, failed, metadata)
+      VALUES ($1, 'execute', 'finished', $2, CURRENT_TIMESTAMP, $3, $4, $5, $6)
+      "#,
+    )
+    .bind(self.kind)
+    .bind(workspace_id)
+    .bind(scanned as i32)
+    .bind(changed as i32)
+    .bind(failed as i32)
+    .bind(metadata)
+    .execute(pool)
+    .await
+    .map_err(|err| RuntimeError::database(format!("{} run record failed", self.label), err))?;
+    Ok(())
+  }
+}
+
+/// Splits the keys that failed on earlier calls into those retried now, at
+/// most `limit`, and those carried over to a later call.
+pub(super) fn take_retries(failed_keys: Vec<String>, limit: usize) -> (Vec<String>, Vec<String>) {
+  let mut retries = failed_keys;
+  let deferred = retries.split_off(retries.len().min(limit));
+  (retries, deferred)
+}
+
+/// Stays set while keys are left to list or failed keys wait to be retried,
+/// so a caller looping until it is `null` does not stop before they are done.
+pub(super) fn next_cursor(
+  has_more: bool,
+  last_scanned_key: Option<&str>,
+  checkpoint_key: Option<&str>,
+  retry_keys: &[String],
+) -> Option<String> {
+  if has_more {
+    return last_scanned_key.map(str::to_string);
+  }
+  if retry_keys.is_empty() {
+    return None;
+  }
+  last_scanned_key
+    .or(checkpoint_key)
+    .or(retry_keys.first().map(String::as_str))
+    .map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn take_retries_defers_keys_past_the_limit() {
+    let keys = ["a", "b", "c"].map(String::from).to_vec();
+    assert_eq!(
+      take_retries(keys.clone(), 2),
+      (vec!["a".to_string(), "b".to_string()], vec!["c".to_string()])
+    );
+    assert_eq!(take_retries(keys.clone(), 5), (keys, Vec::new()));
+  }
+
+  #[test]
+  fn next_cursor_waits_for_retries() {
+    let retries = vec!["workspace/b".to_string()];
+    assert_eq!(
+      next_cursor(true, Some("workspace/c"), None, &retries).as_deref(),
+      Some("workspace/c")
+    );
+    assert_eq!(next_cursor(false, Some("workspace/c"), None, &[]), None);
+    assert_eq!(
+      next_cursor(false, None, Some("workspace/c"), &retries).as_deref(),
+      Some("workspace/c")
+    );
+    assert_eq!(next_cursor(false, None, None, &retries).as_deref(), Some("workspace/b"));
+  }
+}
@@ -0,0 +1,54 @@
+use std::sync::Mutex;
+
+use super::{RuntimeError, object_storage::mock_server::MockObjectStorageServer, to_napi_error};
+
+/// Local S3-compatible server for development and tests. Point a storage at
+/// it by passing `storageConfig(bucket)` as a `storages.*` entry.
+#[napi_derive::napi]
+pub struct ObjectStorageMockServer {
+  endpoint: String,
+  server: Mutex<Option<MockObjectStorageServer>>,
+}
+
+#[napi_derive::napi]
+impl ObjectStorageMockServer {
+  /// Starts serving on a random localhost port.
+  #[napi(factory)]
+  pub fn start() -> napi::Result<Self> {
+    let server = MockObjectStorageServer::start()?;
+    Ok(Self {
+      endpoint: server.endpoint(),
+      server: Mutex::new(Some(server)),
+    })
+  }
+
+  #[napi(getter)]
+  pub fn endpoint(&self) -> String {
+    self.endpoint.clone()
+  }
+
+  /// JSON storage provider config for the `aws-s3` provider.
+  #[napi]
+  pub fn storage_config(&self, bucket: String) -> napi::Result<String> {
+    let guard = self
+      .server
+      .lock()
+      .map_err(|_| to_napi_error(RuntimeError::invalid_state("ObjectStorageMockServer lock poisoned")))?;
+    let server = guard
+      .as_ref()
+      .ok_or_else(|| to_napi_error(RuntimeError::invalid_state("ObjectStorageMockServer is stopped")))?;
+    Ok(server.provider_config(&bucket).to_string())
+  }
+
+  /// Stops accepting connections and drops every stored object.
+  #[napi]
+  pub fn stop(&self) -> napi::Result<()> {
+    let server = self
+      .server
+      .lock()
+      .map_err(|_| to_napi_error(RuntimeError::invalid_state("ObjectStorageMockServer lock poisoned")))?
+      .take();
+    drop(server);
+    Ok(())
+  }
+}
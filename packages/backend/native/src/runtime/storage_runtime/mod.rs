@@ -1,6 +1,7 @@
 use std::{
   collections::HashMap,
   env, fs,
+  io::{self, Read, Seek, SeekFrom},
   path::{Path, PathBuf},
   sync::RwLock,
   time::SystemTime,
@@ -16,26 +17,43 @@ use tokio::{sync::Mutex, task::JoinSet};
 
 mod assetpack;
 mod blob_cleanup;
+mod blob_migration;
 mod blob_reclaimer;
 mod blob_reconciliation;
 mod doc_blob_refs;
+mod encryption;
+mod encryption_rotation;
+mod key_checkpoint;
+mod mock_object_storage;
 pub(crate) mod object_storage;
-
-use self::object_storage::{
-  ObjectStorageConfig, StorageProviderConfig,
-  types::{
-    ObjectDeleteOutcome, ObjectGetResult, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, checksum_crc32_base64,
+mod object_stream;
+mod tombstones;
+
+use self::{
+  encryption::{EncryptionEnvelope, StorageEncryptionConfig, StorageEncryptionConfigFile},
+  encryption_rotation::{FS_ENCRYPTING_SUFFIX, encrypting_path, finish_pending_fs_body},
+  object_storage::{
+    ObjectStorageConfig, StorageProviderConfig,
+    error::ObjectStorageError,
+    types::{
+      ObjectByteRange, ObjectContentRange, ObjectDeleteOutcome, ObjectGetResult, ObjectListEntry, ObjectMetadata,
+      ObjectPutMetadata, checksum_crc32_base64,
+    },
   },
+  object_stream::{FsObjectBody, ObjectBody},
+  tombstones::{SoftDeleteConfig, is_tombstone_key},
 };
+pub use self::{mock_object_storage::ObjectStorageMockServer, object_stream::StorageObjectStream};
 pub(super) use super::{
   RuntimeError, RuntimeResult,
   migrations::migrate_runtime_tables,
   napi_error, to_napi_error,
   types::{
     RuntimeBlobCleanupExecuteResult, RuntimeBlobCleanupPlanResult, RuntimeBlobCleanupResult, RuntimeBlobCompleteResult,
-    RuntimeBlobMetadataBackfillResult, RuntimeDocBlobRefsResult, RuntimeMultipartUploadInit,
-    RuntimeMultipartUploadPart, RuntimeObjectGetResult, RuntimeObjectListEntry, RuntimeObjectMetadata,
-    RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest,
+    RuntimeBlobMetadataBackfillResult, RuntimeBlobMigrationResult, RuntimeDocBlobRefsResult,
+    RuntimeMultipartUploadInit, RuntimeMultipartUploadPart, RuntimeObjectContentRange, RuntimeObjectGetResult,
+    RuntimeObjectListEntry, RuntimeObjectMetadata, RuntimeObjectStoragePutOptions, RuntimeObjectTombstone,
+    RuntimePresignedObjectRequest, RuntimeStorageEncryptionRotationResult, RuntimeTombstonePurgeResult,
   },
 };
 
@@ -81,12 +99,14 @@ struct FsStorageConfig {
   provider: String,
   root: String,
   bucket: String,
+  encryption: Option<StorageEncryptionConfig>,
 }
 
 #[derive(Clone, Debug)]
 struct StorageRuntimeConfig {
   database_url: String,
   backends: HashMap<String, StorageBackendConfig>,
+  soft_delete: HashMap<String, SoftDeleteConfig>,
 }
 
 #[derive(Debug, Default, Deserialize)]
@@ -106,11 +126,21 @@ struct DbConfigFile {
 #[derive(Debug, Deserialize)]
 struct FsConfigFile {
   path: String,
+  #[serde(default)]
+  encryption: Option<StorageEncryptionConfigFile>,
 }
 
 #[derive(Debug, Default, Deserialize)]
 struct CopilotConfigFile {
-  storage: Option<StorageProviderConfig>,
+  storage: Option<Value>,
+}
+
+/// Provider-independent settings read from the same `storages.*` entry.
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct StorageRetentionConfigFile {
+  #[serde(default)]
+  soft_delete: Option<SoftDeleteConfig>,
 }
 
 impl StorageRuntimeConfig {
@@ -123,16 +153,24 @@ impl StorageRuntimeConfig {
       .or(app_config.database_url())
       .unwrap_or_else(|| "postgresql://localhost:5432/affine".to_string());
     let backends = app_config.storage_backends()?;
-    Ok(Self { database_url, backends })
+    let soft_delete = app_config.storage_soft_delete()?;
+    Ok(Self {
+      database_url,
+      backends,
+      soft_delete,
+    })
   }
 
   async fn with_db_overrides(&self, pool: &PgPool) -> RuntimeResult<Self> {
     let app_config = load_app_config_overrides_from_db(pool).await?;
     let mut backends = self.backends.clone();
     backends.extend(app_config.storage_backends()?);
+    let mut soft_delete = self.soft_delete.clone();
+    soft_delete.extend(app_config.storage_soft_delete()?);
     Ok(Self {
       database_url: self.database_url.clone(),
       backends,
+      soft_delete,
     })
   }
 }
@@ -151,6 +189,10 @@ impl StorageBackendConfig {
           provider: storage.provider,
           root: config.path,
           bucket: storage.bucket,
+          encryption: config
+            .encryption
+            .map(StorageEncryptionConfig::from_config_file)
+            .transpose()?,
         })))
       }
       "assetpack" => {
@@ -160,6 +202,10 @@ impl StorageBackendConfig {
           provider: storage.provider,
           root: config.path,
           bucket: storage.bucket,
+          encryption: config
+            .encryption
+            .map(StorageEncryptionConfig::from_config_file)
+            .transpose()?,
         })))
       }
       "aws-s3" | "cloudflare-r2" => ObjectStorageConfig::from_provider_config(Some(storage))
@@ -244,32 +290,42 @@ impl AppConfigFile {
 
   fn storage_backends(&self) -> RuntimeResult<HashMap<String, StorageBackendConfig>> {
     let mut backends = HashMap::new();
-    if let Some(storage) = self.storage_provider_config("blob.storage")?
-      && let Some(backend) = StorageBackendConfig::from_provider_config(Some(storage))?
-    {
-      backends.insert("blob".to_string(), backend);
-    }
-    if let Some(storage) = self.storage_provider_config("avatar.storage")?
-      && let Some(backend) = StorageBackendConfig::from_provider_config(Some(storage))?
-    {
-      backends.insert("avatar".to_string(), backend);
-    }
-    if let Some(storage) = self.copilot.as_ref().and_then(|copilot| copilot.storage.clone())
-      && let Some(backend) = StorageBackendConfig::from_provider_config(Some(storage))?
-    {
-      backends.insert("copilot".to_string(), backend);
+    for (scope, storage) in self.storage_provider_values() {
+      let storage = serde_json::from_value::<StorageProviderConfig>(storage)
+        .map_err(|err| RuntimeError::json("invalid storage provider config", err))?;
+      if let Some(backend) = StorageBackendConfig::from_provider_config(Some(storage))? {
+        backends.insert(scope.to_string(), backend);
+      }
     }
     Ok(backends)
   }
 
-  fn storage_provider_config(&self, key: &str) -> RuntimeResult<Option<StorageProviderConfig>> {
-    self
-      .storages
-      .as_ref()
-      .and_then(|storages| storages.get(key).cloned())
-      .map(serde_json::from_value)
-      .transpose()
-      .map_err(|err| RuntimeError::json("invalid storage provider config", err))
+  fn storage_soft_delete(&self) -> RuntimeResult<HashMap<String, SoftDeleteConfig>> {
+    let mut soft_delete = HashMap::new();
+    for (scope, storage) in self.storage_provider_values() {
+      let retention = serde_json::from_value::<StorageRetentionConfigFile>(storage)
+        .map_err(|err| RuntimeError::json("invalid storage soft delete config", err))?;
+      if let Some(config) = retention.soft_delete {
+        soft_delete.insert(scope.to_string(), config);
+      }
+    }
+    Ok(soft_delete)
+  }
+
+  /// Raw provider config of each storage scope, keyed by scope.
+  fn storage_provider_values(&self) -> Vec<(&'static str, Value)> {
+    let storage = |key: &str| self.storages.as_ref().and_then(|storages| storages.get(key).cloned());
+    [
+      ("blob", storage("blob.storage")),
+      ("avatar", storage("avatar.storage")),
+      (
+        "copilot",
+        self.copilot.as_ref().and_then(|copilot| copilot.storage.clone()),
+      ),
+    ]
+    .into_iter()
+    .filter_map(|(scope, storage)| Some((scope, storage?)))
+    .collect()
   }
 
   fn apply_file_config(&mut self, config: AppConfigFile) {
@@ -437,16 +493,49 @@ impl StorageRuntime {
     Ok(metadata.map(Into::into))
   }
 
+  /// `range` takes an HTTP `Range` header value such as `bytes=0-1023` or
+  /// `bytes=-500`; the result then carries the served `contentRange`.
   #[napi]
-  pub async fn get_object(&self, _scope: String, key: String) -> napi::Result<Option<RuntimeObjectGetResult>> {
+  pub async fn get_object(
+    &self,
+    _scope: String,
+    key: String,
+    range: Option<String>,
+  ) -> napi::Result<Option<RuntimeObjectGetResult>> {
+    let range = range.as_deref().map(ObjectByteRange::parse).transpose()?;
     let object = match self.backend_for_scope(&_scope)? {
-      StorageBackendConfig::Fs(config) => fs_get(&config, &key)?,
-      StorageBackendConfig::Assetpack(config) => assetpack::get(&config, &_scope, &key).await?,
-      StorageBackendConfig::S3(config) => config.build_client()?.get(&key).await?,
+      StorageBackendConfig::Fs(config) => fs_get_range(&config, &key, range)?,
+      StorageBackendConfig::Assetpack(config) => assetpack::get_range(&config, &_scope, &key, range).await?,
+      StorageBackendConfig::S3(config) => config.build_client()?.get_range(&key, range).await?,
     };
     Ok(object.map(Into::into))
   }
 
+  /// Streaming variant of `getObject` for serving large bodies without
+  /// buffering them; pull chunks with `read()` until it returns `null`.
+  #[napi]
+  pub async fn open_object_stream(
+    &self,
+    _scope: String,
+    key: String,
+    range: Option<String>,
+  ) -> napi::Result<Option<StorageObjectStream>> {
+    let range = range.as_deref().map(ObjectByteRange::parse).transpose()?;
+    let stream = match self.backend_for_scope(&_scope)? {
+      StorageBackendConfig::Fs(config) => fs_open(&config, &key, range)?
+        .map(|(metadata, content_range, body)| StorageObjectStream::new(metadata, content_range, body.into())),
+      StorageBackendConfig::Assetpack(config) => assetpack::get_range(&config, &_scope, &key, range)
+        .await?
+        .map(StorageObjectStream::buffered),
+      StorageBackendConfig::S3(config) => config
+        .build_client()?
+        .open_range(&key, range)
+        .await?
+        .map(|(metadata, content_range, body)| StorageObjectStream::new(metadata, content_range, ObjectBody::S3(body))),
+    };
+    Ok(stream)
+  }
+
   #[napi]
   pub async fn list_objects(
     &self,
@@ -458,11 +547,20 @@ impl StorageRuntime {
       StorageBackendConfig::Assetpack(config) => assetpack::list(&config, &_scope, prefix).await?,
       StorageBackendConfig::S3(config) => config.build_client()?.list(prefix).await?,
     };
-    Ok(entries.into_iter().map(Into::into).collect())
+    Ok(
+      entries
+        .into_iter()
+        .filter(|entry| !is_tombstone_key(&entry.key))
+        .map(Into::into)
+        .collect(),
+    )
   }
 
   #[napi]
   pub async fn delete_object(&self, _scope: String, key: String) -> napi::Result<()> {
+    if self.soft_delete_object(&_scope, &key).await? {
+      return Ok(());
+    }
     match self.backend_for_scope(&_scope)? {
       StorageBackendConfig::Fs(config) => Ok(fs_delete(&config, &key)?),
       StorageBackendConfig::Assetpack(config) => assetpack::delete(&config, &_scope, &key)
@@ -677,6 +775,9 @@ impl StorageRuntime {
   }
 
   pub(crate) async fn object_storage_delete_object(&self, key: &str) -> Result<()> {
+    if self.soft_delete_object("blob", key).await? {
+      return Ok(());
+    }
     match self.backend_for_scope("blob")? {
       StorageBackendConfig::Fs(config) => fs_delete(&config, key),
       StorageBackendConfig::Assetpack(config) => assetpack::delete(&config, "blob", key).await,
@@ -685,6 +786,9 @@ impl StorageRuntime {
   }
 
   pub(crate) async fn object_storage_delete_many(&self, keys: Vec<String>) -> Result<Vec<ObjectDeleteOutcome>> {
+    if let Some(outcomes) = self.soft_delete_many("blob", keys.clone()).await? {
+      return Ok(outcomes);
+    }
     let backend = self.backend_for_scope("blob")?;
     match backend {
       StorageBackendConfig::Fs(config) => Ok(delete_many_fs(config, keys)),
@@ -714,6 +818,7 @@ impl StorageRuntime {
     match self.backend_for_scope("blob")? {
       StorageBackendConfig::Fs(config) => {
         let mut entries = fs_list(&config, prefix)?;
+        entries.retain(|entry| !is_tombstone_key(&entry.key));
         if let Some(start_after) = start_after {
           entries.retain(|entry| entry.key > start_after);
         }
@@ -732,6 +837,7 @@ impl StorageRuntime {
       }
       StorageBackendConfig::Assetpack(config) => {
         let mut entries = assetpack::list(&config, "blob", prefix).await?;
+        entries.retain(|entry| !is_tombstone_key(&entry.key));
         if let Some(start_after) = start_after {
           entries.retain(|entry| entry.key > start_after);
         }
@@ -752,6 +858,10 @@ impl StorageRuntime {
         .build_client()?
         .list_page(prefix, continuation_token, start_after, max_keys)
         .await
+        .map(|mut page| {
+          page.entries.retain(|entry| !is_tombstone_key(&entry.key));
+          page
+        })
         .map_err(Into::into),
     }
   }
@@ -1098,21 +1208,51 @@ fn fs_put(config: &FsStorageConfig, key: &str, body: Vec<u8>, metadata: ObjectPu
   if let Some(parent) = path.parent() {
     fs::create_dir_all(parent).map_err(|err| RuntimeError::io("StorageRuntime fs create dir failed", err))?;
   }
-  fs::write(&path, &body).map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
+  let (body, envelope) = match config.encryption.as_ref() {
+    Some(encryption) => {
+      let (ciphertext, envelope) = encryption.encrypt(&body)?;
+      (ciphertext, Some(envelope))
+    }
+    None => (body, None),
+  };
+  let pending = encrypting_path(&path);
+  fs::write(&pending, &body).map_err(|err| RuntimeError::io("StorageRuntime fs write object failed", err))?;
   let object_metadata = metadata.into_object_metadata(system_time_ms(SystemTime::now())?);
-  let metadata_json = serde_json::json!({
-    "contentType": &object_metadata.content_type,
-    "contentLength": object_metadata.content_length,
-    "lastModified": object_metadata.last_modified_ms,
-    "checksumCRC32": &object_metadata.checksum_crc32,
+  let rename_body =
+    || fs::rename(&pending, &path).map_err(|err| RuntimeError::io("StorageRuntime fs rename object failed", err));
+  if envelope.is_some() {
+    // As in encryption rotation, the key goes in place before the ciphertext,
+    // so a crash in between leaves a pending body that the next read or
+    // rotation pass renames, never ciphertext without its key.
+    write_fs_sidecar(&path, &object_metadata, envelope.as_ref())?;
+    rename_body()?;
+  } else {
+    rename_body()?;
+    write_fs_sidecar(&path, &object_metadata, None)?;
+  }
+  Ok(object_metadata)
+}
+
+fn write_fs_sidecar(path: &Path, metadata: &ObjectMetadata, envelope: Option<&EncryptionEnvelope>) -> Result<()> {
+  let mut metadata_json = serde_json::json!({
+    "contentType": &metadata.content_type,
+    "contentLength": metadata.content_length,
+    "lastModified": metadata.last_modified_ms,
+    "checksumCRC32": &metadata.checksum_crc32,
   });
+  if let Some(envelope) = envelope {
+    metadata_json["encryption"] = serde_json::to_value(envelope)
+      .map_err(|err| RuntimeError::json("StorageRuntime fs serialize encryption metadata failed", err))?;
+  }
+  let sidecar = PathBuf::from(format!("{}.metadata.json", path.display()));
+  let pending = encrypting_path(&sidecar);
   fs::write(
-    PathBuf::from(format!("{}.metadata.json", path.display())),
+    &pending,
     serde_json::to_vec(&metadata_json)
       .map_err(|err| RuntimeError::json("StorageRuntime fs serialize metadata failed", err))?,
   )
   .map_err(|err| RuntimeError::io("StorageRuntime fs write metadata failed", err))?;
-  Ok(object_metadata)
+  fs::rename(&pending, &sidecar).map_err(|err| RuntimeError::io("StorageRuntime fs rename metadata failed", err))
 }
 
 fn fs_head(config: &FsStorageConfig, key: &str) -> Result<Option<ObjectMetadata>> {
@@ -1121,16 +1261,105 @@ fn fs_head(config: &FsStorageConfig, key: &str) -> Result<Option<ObjectMetadata>
 }
 
 fn fs_get(config: &FsStorageConfig, key: &str) -> Result<Option<ObjectGetResult>> {
+  fs_get_range(config, key, None)
+}
+
+fn fs_get_range(
+  config: &FsStorageConfig,
+  key: &str,
+  range: Option<ObjectByteRange>,
+) -> Result<Option<ObjectGetResult>> {
+  let Some((metadata, content_range, body)) = fs_open(config, key, range)? else {
+    return Ok(None);
+  };
+  let body = match body {
+    FsObjectBody::File(mut file) => {
+      let mut body = Vec::new();
+      file
+        .read_to_end(&mut body)
+        .map_err(|err| RuntimeError::io("StorageRuntime fs read object failed", err))?;
+      body
+    }
+    FsObjectBody::Buffered(body) => body,
+  };
+  Ok(Some(ObjectGetResult {
+    body,
+    metadata,
+    content_range,
+  }))
+}
+
+/// Opens an fs object positioned at the start of `range` and limited to its
+/// length, so only the requested bytes are ever read. Encrypted objects are
+/// authenticated as a whole and therefore decrypted into memory first.
+fn fs_open(
+  config: &FsStorageConfig,
+  key: &str,
+  range: Option<ObjectByteRange>,
+) -> Result<Option<(ObjectMetadata, Option<ObjectContentRange>, FsObjectBody)>> {
   let path = fs_object_path(config, key)?;
-  let Some(metadata) = read_fs_metadata(&path)? else {
+  let Some(sidecar) = read_fs_sidecar(&path)? else {
     return Ok(None);
   };
-  let body = match fs::read(&path) {
-    Ok(body) => body,
-    Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+  let (metadata, envelope) = sidecar.into_parts();
+  if envelope.is_some() {
+    finish_pending_fs_body(&path)?;
+  }
+  let mut file = match fs::File::open(&path) {
+    Ok(file) => file,
+    Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
     Err(err) => return Err(RuntimeError::io("StorageRuntime fs read object failed", err)),
   };
-  Ok(Some(ObjectGetResult { body, metadata }))
+
+  if let Some(envelope) = envelope {
+    let mut ciphertext = Vec::new();
+    file
+      .read_to_end(&mut ciphertext)
+      .map_err(|err| RuntimeError::io("StorageRuntime fs read object failed", err))?;
+    let body = fs_encryption(config, key)?.decrypt(&envelope, &ciphertext)?;
+    let (body, content_range) = match range {
+      Some(range) => {
+        let content_range = range
+          .resolve(body.len() as u64)
+          .ok_or(ObjectStorageError::RangeNotSatisfiable {
+            length: body.len() as i64,
+          })?;
+        (
+          body[content_range.start as usize..=content_range.end as usize].to_vec(),
+          Some(content_range),
+        )
+      }
+      None => (body, None),
+    };
+    return Ok(Some((metadata, content_range, FsObjectBody::Buffered(body))));
+  }
+
+  let total = file
+    .metadata()
+    .map_err(|err| RuntimeError::io("StorageRuntime fs stat object failed", err))?
+    .len();
+  let Some(range) = range else {
+    return Ok(Some((metadata, None, FsObjectBody::File(file.take(total)))));
+  };
+  let content_range = range
+    .resolve(total)
+    .ok_or(ObjectStorageError::RangeNotSatisfiable { length: total as i64 })?;
+  file
+    .seek(SeekFrom::Start(content_range.start))
+    .map_err(|err| RuntimeError::io("StorageRuntime fs seek object failed", err))?;
+  Ok(Some((
+    metadata,
+    Some(content_range),
+    FsObjectBody::File(file.take(content_range.len())),
+  )))
+}
+
+fn fs_encryption<'a>(config: &'a FsStorageConfig, key: &str) -> Result<&'a StorageEncryptionConfig> {
+  config.encryption.as_ref().ok_or_else(|| {
+    RuntimeError::config(format!(
+      "StorageRuntime object {key} is encrypted but no storage encryption keys are configured"
+    ))
+  })
 }
 
 fn fs_list(config: &FsStorageConfig, prefix: Option<String>) -> Result<Vec<ObjectListEntry>> {
@@ -1176,7 +1405,10 @@ fn collect_fs_entries(
       if name_prefix.is_none_or(|prefix| name.starts_with(prefix)) {
         collect_fs_entries(root, &path, None, entries)?;
       }
-    } else if !name.ends_with(".metadata.json") && name_prefix.is_none_or(|prefix| name.starts_with(prefix)) {
+    } else if !name.ends_with(".metadata.json")
+      && !name.ends_with(FS_ENCRYPTING_SUFFIX)
+      && name_prefix.is_none_or(|prefix| name.starts_with(prefix))
+    {
       let stat = entry
         .metadata()
         .map_err(|err| RuntimeError::io("StorageRuntime fs metadata failed", err))?;
@@ -1185,9 +1417,14 @@ fn collect_fs_entries(
         .map_err(|err| RuntimeError::invalid_state(format!("StorageRuntime fs path trim failed: {err}")))?
         .to_string_lossy()
         .replace('\\', "/");
+      // Encrypted bodies carry a nonce and tag, so report the plaintext size.
+      let content_length = match read_fs_sidecar(&path)? {
+        Some(sidecar) if sidecar.encryption.is_some() => sidecar.content_length,
+        _ => stat.len() as i64,
+      };
       entries.push(ObjectListEntry {
         key,
-        content_length: stat.len() as i64,
+        content_length,
         last_modified_ms: stat
           .modified()
           .ok()
@@ -1286,19 +1523,18 @@ async fn delete_many_s3(config: ObjectStorageConfig, keys: Vec<String>) -> Resul
 }
 
 fn read_fs_metadata(path: &Path) -> Result<Option<ObjectMetadata>> {
+  Ok(read_fs_sidecar(path)?.map(|sidecar| sidecar.into_parts().0))
+}
+
+fn read_fs_sidecar(path: &Path) -> Result<Option<FsBlobMetadata>> {
   let raw = match fs::read_to_string(PathBuf::from(format!("{}.metadata.json", path.display()))) {
     Ok(raw) => raw,
     Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
     Err(err) => return Err(RuntimeError::io("StorageRuntime fs read metadata failed", err)),
   };
-  let metadata: FsBlobMetadata =
-    serde_json::from_str(&raw).map_err(|err| RuntimeError::json("StorageRuntime fs parse metadata failed", err))?;
-  Ok(Some(ObjectMetadata {
-    content_type: metadata.content_type,
-    content_length: metadata.content_length,
-    last_modified_ms: metadata.last_modified,
-    checksum_crc32: metadata.checksum_crc32,
-  }))
+  serde_json::from_str(&raw)
+    .map(Some)
+    .map_err(|err| RuntimeError::json("StorageRuntime fs parse metadata failed", err))
 }
 
 #[derive(Deserialize)]
@@ -1309,6 +1545,22 @@ struct FsBlobMetadata {
   last_modified: i64,
   #[serde(rename = "checksumCRC32")]
   checksum_crc32: Option<String>,
+  #[serde(default)]
+  encryption: Option<EncryptionEnvelope>,
+}
+
+impl FsBlobMetadata {
+  fn into_parts(self) -> (ObjectMetadata, Option<EncryptionEnvelope>) {
+    (
+      ObjectMetadata {
+        content_type: self.content_type,
+        content_length: self.content_length,
+        last_modified_ms: self.last_modified,
+        checksum_crc32: self.checksum_crc32,
+      },
+      self.encryption,
+    )
+  }
 }
 
 async fn upsert_completed_blob(pool: &PgPool, workspace_id: &str, key: &str, mime: &str, size: i64) -> Result<()> {
@@ -1425,6 +1677,7 @@ mod tests {
       provider: "fs".to_string(),
       root: "/tmp".to_string(),
       bucket: "blob".to_string(),
+      encryption: None,
     })
     .capabilities();
     assert!(capabilities.put);
@@ -1489,6 +1742,7 @@ mod tests {
       provider: "assetpack".to_string(),
       root: "/tmp".to_string(),
       bucket: "blob".to_string(),
+      encryption: None,
     })
     .capabilities();
 
@@ -1517,6 +1771,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
     let body = b"hello".to_vec();
     let checksum = checksum_crc32_base64(&body);
@@ -1556,6 +1811,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
     let dir = temp.path().join("bucket/workspace");
     fs::create_dir_all(&dir).unwrap();
@@ -1592,6 +1848,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
     for key in ["root-a", "a/item", "a/b/item", "a/b/t/item", "a/b/tail", "z/item"] {
       fs_put(&config, key, key.as_bytes().to_vec(), ObjectPutMetadata::default()).unwrap();
@@ -1624,6 +1881,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
 
     fs_put(
@@ -1642,11 +1900,80 @@ mod tests {
     assert!(!temp.path().join("bucket/workspace/blob.metadata.json").exists());
   }
 
+  #[test]
+  fn fs_backend_reads_byte_ranges_without_loading_the_rest() {
+    let temp = tempfile::tempdir().unwrap();
+    let config = FsStorageConfig {
+      provider: "fs".to_string(),
+      root: temp.path().to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: None,
+    };
+    fs_put(
+      &config,
+      "workspace/audio",
+      b"0123456789".to_vec(),
+      ObjectPutMetadata::default(),
+    )
+    .unwrap();
+
+    let object = fs_get_range(
+      &config,
+      "workspace/audio",
+      Some(ObjectByteRange::Bounded { start: 2, end: 5 }),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(object.body, b"2345");
+    assert_eq!(object.metadata.content_length, 10);
+    assert_eq!(object.content_range.unwrap().header_value(), "bytes 2-5/10");
+
+    let object = fs_get_range(&config, "workspace/audio", Some(ObjectByteRange::Suffix { length: 3 }))
+      .unwrap()
+      .unwrap();
+    assert_eq!(object.body, b"789");
+
+    let err = fs_get_range(&config, "workspace/audio", Some(ObjectByteRange::From { start: 10 })).unwrap_err();
+    assert!(err.to_string().contains("not satisfiable"), "{err}");
+    assert!(
+      fs_get_range(&config, "workspace/missing", Some(ObjectByteRange::From { start: 0 }))
+        .unwrap()
+        .is_none()
+    );
+  }
+
+  #[tokio::test]
+  async fn fs_object_stream_serves_requested_range() {
+    let temp = tempfile::tempdir().unwrap();
+    let config = FsStorageConfig {
+      provider: "fs".to_string(),
+      root: temp.path().to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: None,
+    };
+    let body = (0..600_000).map(|index| (index % 251) as u8).collect::<Vec<_>>();
+    fs_put(&config, "workspace/large", body.clone(), ObjectPutMetadata::default()).unwrap();
+
+    let (metadata, content_range, body) =
+      fs_open(&config, "workspace/large", Some(ObjectByteRange::From { start: 1000 }))
+        .unwrap()
+        .unwrap();
+    assert!(matches!(body, FsObjectBody::File(_)));
+    let stream = StorageObjectStream::new(metadata, content_range, body.into());
+    assert_eq!(stream.content_range().unwrap().start, 1000);
+    let mut streamed = Vec::new();
+    while let Some(chunk) = stream.read().await.unwrap() {
+      streamed.extend_from_slice(&chunk);
+    }
+    assert_eq!(streamed, body[1000..]);
+  }
+
   fn test_storage_runtime() -> StorageRuntime {
     StorageRuntime {
       config: RwLock::new(StorageRuntimeConfig {
         database_url: "postgresql://unused".to_string(),
         backends: HashMap::new(),
+        soft_delete: HashMap::new(),
       }),
       pool: Mutex::new(None),
     }
@@ -1659,6 +1986,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
     let runtime = test_storage_runtime();
 
@@ -1764,6 +2092,97 @@ mod tests {
     assert_eq!(result.reason.as_deref(), Some("size_too_large"));
   }
 
+  #[tokio::test]
+  async fn s3_workspace_blob_upload_completes_and_deletes_against_mock_server() {
+    let server = object_storage::mock_server::MockObjectStorageServer::start().unwrap();
+    let runtime = StorageRuntime {
+      config: RwLock::new(StorageRuntimeConfig {
+        database_url: "postgresql://unused".to_string(),
+        backends: HashMap::from([(
+          "blob".to_string(),
+          StorageBackendConfig::S3(server.storage_config("blobs")),
+        )]),
+        soft_delete: HashMap::new(),
+      }),
+      pool: Mutex::new(None),
+    };
+    let body = b"blob body".to_vec();
+    let key = sha256_base64_url(&body);
+
+    runtime
+      .put_object(
+        "blob".to_string(),
+        format!("workspace/{key}"),
+        body.clone().into(),
+        Some(RuntimeObjectStoragePutOptions {
+          content_type: Some("text/plain".to_string()),
+          content_length: None,
+          checksum_crc32: None,
+        }),
+      )
+      .await
+      .unwrap();
+    for (expected_size, expected_mime, reason) in
+      [(10, "text/plain", "size_mismatch"), (9, "image/png", "mime_mismatch")]
+    {
+      let result = runtime
+        .complete_s3_workspace_blob(
+          "workspace".to_string(),
+          key.clone(),
+          expected_size,
+          expected_mime.to_string(),
+        )
+        .await
+        .unwrap();
+      assert_eq!(result.reason.as_deref(), Some(reason));
+    }
+
+    runtime
+      .put_object(
+        "blob".to_string(),
+        "workspace/not-the-sha-key".to_string(),
+        body.clone().into(),
+        None,
+      )
+      .await
+      .unwrap();
+    let result = runtime
+      .complete_s3_workspace_blob("workspace".to_string(), "not-the-sha-key".to_string(), 9, String::new())
+      .await
+      .unwrap();
+    assert_eq!(result.reason.as_deref(), Some("checksum_mismatch"));
+    assert!(
+      runtime
+        .object_storage_head("workspace/not-the-sha-key".to_string())
+        .await
+        .unwrap()
+        .is_none()
+    );
+
+    let object = runtime
+      .get_object(
+        "blob".to_string(),
+        format!("workspace/{key}"),
+        Some("bytes=0-3".to_string()),
+      )
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(object.body.to_vec(), b"blob");
+    let outcomes = runtime
+      .object_storage_delete_many(vec![format!("workspace/{key}")])
+      .await
+      .unwrap();
+    assert!(outcomes.iter().all(|outcome| outcome.error.is_none()));
+    assert!(
+      runtime
+        .list_objects("blob".to_string(), Some("workspace/".to_string()))
+        .await
+        .unwrap()
+        .is_empty()
+    );
+  }
+
   #[test]
   fn fs_backend_rejects_metadata_mismatch() {
     let temp = tempfile::tempdir().unwrap();
@@ -1771,6 +2190,7 @@ mod tests {
       provider: "fs".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
 
     assert!(
@@ -1808,6 +2228,7 @@ mod tests {
       provider: "assetpack".to_string(),
       root: temp.path().to_string_lossy().to_string(),
       bucket: "bucket".to_string(),
+      encryption: None,
     };
     let scope = format!("test_{}", uuid::Uuid::new_v4().simple());
     let key = "workspace/blob.txt";
@@ -1832,6 +2253,11 @@ mod tests {
 
     let object = assetpack::get(&config, &scope, key).await?.unwrap();
     assert_eq!(object.body, body);
+    let object = assetpack::get_range(&config, &scope, key, Some(ObjectByteRange::Suffix { length: 4 }))
+      .await?
+      .unwrap();
+    assert_eq!(object.body, body[body.len() - 4..]);
+    assert_eq!(object.content_range.unwrap().total, body.len() as u64);
     assert_eq!(
       assetpack::list(&config, &scope, Some("workspace/".to_string()))
         .await?
@@ -1863,4 +2289,137 @@ mod tests {
     assert!(assetpack::head(&config, &scope, key).await?.is_none());
     Ok(())
   }
+
+  fn test_encryption(active_key_id: &str, key_ids: &[&str]) -> StorageEncryptionConfig {
+    let keys = key_ids
+      .iter()
+      .map(|key_id| (key_id.to_string(), Value::String(key_id[1..].repeat(64))))
+      .collect::<Map<_, _>>();
+    let file: StorageEncryptionConfigFile =
+      serde_json::from_value(serde_json::json!({ "activeKeyId": active_key_id, "keys": keys })).unwrap();
+    StorageEncryptionConfig::from_config_file(file).unwrap()
+  }
+
+  #[test]
+  fn fs_backend_encrypts_bodies_at_rest_and_rotates_keys() {
+    let temp = tempfile::tempdir().unwrap();
+    let mut config = FsStorageConfig {
+      provider: "fs".to_string(),
+      root: temp.path().to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: None,
+    };
+    fs_put(
+      &config,
+      "workspace/legacy",
+      b"legacy body".to_vec(),
+      ObjectPutMetadata::default(),
+    )
+    .unwrap();
+
+    config.encryption = Some(test_encryption("k1", &["k1"]));
+    fs_put(
+      &config,
+      "workspace/secret",
+      b"secret body".to_vec(),
+      ObjectPutMetadata::default(),
+    )
+    .unwrap();
+    let path = fs_object_path(&config, "workspace/secret").unwrap();
+    assert!(!encrypting_path(&path).exists());
+    let on_disk = fs::read(&path).unwrap();
+    assert!(!on_disk.windows(6).any(|window| window == b"secret"));
+    let sidecar = read_fs_sidecar(&path).unwrap().unwrap();
+    assert_eq!(sidecar.encryption.as_ref().unwrap().key_id, "k1");
+    assert_eq!(sidecar.content_length, 11);
+    assert_eq!(
+      fs_get(&config, "workspace/secret").unwrap().unwrap().body,
+      b"secret body"
+    );
+    let object = fs_get_range(
+      &config,
+      "workspace/secret",
+      Some(ObjectByteRange::Bounded { start: 0, end: 5 }),
+    )
+    .unwrap()
+    .unwrap();
+    assert_eq!(object.body, b"secret");
+
+    // A put interrupted after its sidecar landed is finished on the next read.
+    fs::rename(&path, encrypting_path(&path)).unwrap();
+    fs::write(&path, b"stale body").unwrap();
+    assert_eq!(
+      fs_get(&config, "workspace/secret").unwrap().unwrap().body,
+      b"secret body"
+    );
+    assert!(!encrypting_path(&path).exists());
+
+    let listed = fs_list(&config, Some("workspace/".to_string())).unwrap();
+    assert_eq!(
+      listed.iter().map(|entry| entry.content_length).collect::<Vec<_>>(),
+      vec![11, 11]
+    );
+
+    config.encryption = Some(test_encryption("k2", &["k1", "k2"]));
+    assert_eq!(
+      encryption_rotation::fs_rotate_encryption(&config, "workspace/secret").unwrap(),
+      Some(encryption::EncryptionRotation::Rewrapped)
+    );
+    assert_eq!(
+      encryption_rotation::fs_rotate_encryption(&config, "workspace/legacy").unwrap(),
+      Some(encryption::EncryptionRotation::Encrypted)
+    );
+    assert_eq!(
+      encryption_rotation::fs_rotate_encryption(&config, "workspace/legacy").unwrap(),
+      Some(encryption::EncryptionRotation::Skipped)
+    );
+    assert_eq!(fs::read(&path).unwrap(), on_disk);
+
+    // Once everything is re-wrapped the retired key can be dropped.
+    config.encryption = Some(test_encryption("k2", &["k2"]));
+    assert_eq!(
+      fs_get(&config, "workspace/secret").unwrap().unwrap().body,
+      b"secret body"
+    );
+    assert_eq!(
+      fs_get(&config, "workspace/legacy").unwrap().unwrap().body,
+      b"legacy body"
+    );
+
+    config.encryption = None;
+    let err = fs_get(&config, "workspace/secret").unwrap_err();
+    assert!(err.to_string().contains("no storage encryption keys"), "{err}");
+  }
+
+  #[tokio::test]
+  async fn assetpack_backend_encrypts_manifest_bodies_and_rewraps_keys() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let mut config = FsStorageConfig {
+      provider: "assetpack".to_string(),
+      root: temp.path().to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: Some(test_encryption("k1", &["k1"])),
+    };
+    let scope = format!("test_{}", uuid::Uuid::new_v4().simple());
+    let key = "workspace/secret.txt";
+    let body = b"assetpack secret".repeat(64);
+    assetpack::put(&config, &scope, key, body.clone(), ObjectPutMetadata::default()).await?;
+    assert_eq!(
+      assetpack::head(&config, &scope, key).await?.unwrap().content_length,
+      body.len() as i64
+    );
+    assert_eq!(assetpack::get(&config, &scope, key).await?.unwrap().body, body);
+
+    config.encryption = Some(test_encryption("k2", &["k1", "k2"]));
+    assert_eq!(
+      assetpack::rotate_encryption(&config, &scope, key).await?,
+      Some(encryption::EncryptionRotation::Rewrapped)
+    );
+    assert_eq!(
+      assetpack::rotate_encryption(&config, &scope, key).await?,
+      Some(encryption::EncryptionRotation::Skipped)
+    );
+    assert_eq!(assetpack::get(&config, &scope, key).await?.unwrap().body, body);
+    Ok(())
+  }
 }
@@ -5,8 +5,10 @@ use std::{
 
 use chrono::{DateTime, FixedOffset};
 use reqwest::{
-  Client as ReqwestClient, Method, StatusCode,
-  header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, LAST_MODIFIED},
+  Client as ReqwestClient, Method, Response, StatusCode,
+  header::{
+    CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE, ETAG, HeaderMap, HeaderName, HeaderValue, LAST_MODIFIED, RANGE,
+  },
 };
 use rustls::RootCertStore;
 use rusty_s3::{
@@ -23,8 +25,9 @@ use url::Url;
 use super::{
   error::{ObjectStorageError, ObjectStorageResult},
   types::{
-    MultipartUploadInitResult, MultipartUploadPart, ObjectDeleteOutcome, ObjectGetResult, ObjectListEntry,
-    ObjectListPage, ObjectMetadata, ObjectPutMetadata, PresignedObjectRequest, completed_multipart_parts, trim_etag,
+    MultipartUploadInitResult, MultipartUploadPart, ObjectByteRange, ObjectContentRange, ObjectDeleteOutcome,
+    ObjectGetResult, ObjectListEntry, ObjectListPage, ObjectMetadata, ObjectPutMetadata, PresignedObjectRequest,
+    completed_multipart_parts, trim_etag,
   },
 };
 
@@ -81,7 +84,7 @@ impl ReqwestStorageHttpClient {
     )
   }
 
-  async fn execute(&self, request: StorageHttpRequest) -> ObjectStorageResult<StorageHttpResponse> {
+  async fn send(&self, request: StorageHttpRequest) -> ObjectStorageResult<Response> {
     let mut builder = self.client.request(request.method, request.url);
     for (key, value) in request.headers {
       let name =
@@ -92,27 +95,68 @@ impl ReqwestStorageHttpClient {
     if let Some(body) = request.body {
       builder = builder.body(body);
     }
-    let mut response = builder.send().await.map_err(ObjectStorageError::HttpRequest)?;
-    let status = response.status();
-    let headers = response.headers().clone();
-    if response
-      .content_length()
-      .is_some_and(|length| length > request.max_response_body_bytes as u64)
-    {
+    builder.send().await.map_err(ObjectStorageError::HttpRequest)
+  }
+
+  async fn execute(&self, request: StorageHttpRequest) -> ObjectStorageResult<StorageHttpResponse> {
+    let max_response_body_bytes = request.max_response_body_bytes;
+    let response = self.send(request).await?;
+    read_response(response, max_response_body_bytes).await
+  }
+}
+
+async fn read_response(
+  mut response: Response,
+  max_response_body_bytes: usize,
+) -> ObjectStorageResult<StorageHttpResponse> {
+  let status = response.status();
+  let headers = response.headers().clone();
+  if response
+    .content_length()
+    .is_some_and(|length| length > max_response_body_bytes as u64)
+  {
+    return Err(ObjectStorageError::BodyTooLarge {
+      limit: max_response_body_bytes,
+    });
+  }
+  let mut body = Vec::new();
+  while let Some(chunk) = response.chunk().await.map_err(ObjectStorageError::HttpRequest)? {
+    if body.len() + chunk.len() > max_response_body_bytes {
       return Err(ObjectStorageError::BodyTooLarge {
-        limit: request.max_response_body_bytes,
+        limit: max_response_body_bytes,
       });
     }
-    let mut body = Vec::new();
-    while let Some(chunk) = response.chunk().await.map_err(ObjectStorageError::HttpRequest)? {
-      if body.len() + chunk.len() > request.max_response_body_bytes {
-        return Err(ObjectStorageError::BodyTooLarge {
-          limit: request.max_response_body_bytes,
-        });
+    body.extend_from_slice(&chunk);
+  }
+  Ok(StorageHttpResponse { status, headers, body })
+}
+
+/// Body of a `GET` that is consumed chunk by chunk instead of being buffered.
+pub(crate) struct ObjectStorageBodyStream {
+  response: Response,
+  /// Bytes to drop before the requested range, for servers that ignore
+  /// `Range` and answer with the whole object.
+  skip: u64,
+  remaining: u64,
+}
+
+impl ObjectStorageBodyStream {
+  pub(crate) async fn next_chunk(&mut self) -> ObjectStorageResult<Option<Vec<u8>>> {
+    while self.remaining > 0 {
+      let Some(chunk) = self.response.chunk().await.map_err(ObjectStorageError::HttpRequest)? else {
+        return Ok(None);
+      };
+      let skipped = self.skip.min(chunk.len() as u64);
+      self.skip -= skipped;
+      let chunk = &chunk[skipped as usize..];
+      if chunk.is_empty() {
+        continue;
       }
-      body.extend_from_slice(&chunk);
+      let take = self.remaining.min(chunk.len() as u64);
+      self.remaining -= take;
+      return Ok(Some(chunk[..take as usize].to_vec()));
     }
-    Ok(StorageHttpResponse { status, headers, body })
+    Ok(None)
   }
 }
 
@@ -467,29 +511,94 @@ impl ObjectStorageClient {
   }
 
   pub(crate) async fn get(&self, key: &str) -> ObjectStorageResult<Option<ObjectGetResult>> {
-    let action = GetObject::new(&self.bucket, Some(&self.credentials), key);
+    self.get_range(key, None).await
+  }
+
+  pub(crate) async fn get_range(
+    &self,
+    key: &str,
+    range: Option<ObjectByteRange>,
+  ) -> ObjectStorageResult<Option<ObjectGetResult>> {
     let response = self
       .http
-      .execute(StorageHttpRequest {
-        method: Method::GET,
-        url: action.sign(expires_in(self.presign_expires_in_seconds)),
-        headers: HashMap::new(),
-        body: None,
-        max_response_body_bytes: MAX_RESPONSE_BODY_BYTES,
-      })
+      .execute(self.get_request(key, range))
       .await
       .map_err(|source| operation_error(format!("ObjectStorage get failed for {key}"), source))?;
     if response.status == StatusCode::NOT_FOUND && is_not_found_body(&response.body) {
       return Ok(None);
     }
-    ensure_success_status(&response, &format!("ObjectStorage get failed for {key}"))?;
-    let metadata = metadata_from_headers(&response.headers);
+    let (metadata, content_range) = ranged_response_metadata(&response, range, key)?;
+    let body = match content_range {
+      // The server ignored `Range` and sent the whole object.
+      Some(content_range) if response.status == StatusCode::OK => slice_full_body(&response.body, content_range)
+        .map_err(|source| operation_error(format!("ObjectStorage get failed for {key}"), source))?,
+      _ => response.body,
+    };
     Ok(Some(ObjectGetResult {
-      body: response.body,
+      body,
       metadata,
+      content_range,
     }))
   }
 
+  /// Starts a `GET` and hands back the body unbuffered, so callers can relay
+  /// large objects without holding them in memory.
+  pub(crate) async fn open_range(
+    &self,
+    key: &str,
+    range: Option<ObjectByteRange>,
+  ) -> ObjectStorageResult<Option<(ObjectMetadata, Option<ObjectContentRange>, ObjectStorageBodyStream)>> {
+    let response = self
+      .http
+      .send(self.get_request(key, range))
+      .await
+      .map_err(|source| operation_error(format!("ObjectStorage get failed for {key}"), source))?;
+    if !response.status().is_success() {
+      let response = read_response(response, MAX_RESPONSE_BODY_BYTES)
+        .await
+        .map_err(|source| operation_error(format!("ObjectStorage get failed for {key}"), source))?;
+      if response.status == StatusCode::NOT_FOUND && is_not_found_body(&response.body) {
+        return Ok(None);
+      }
+      return Err(get_status_error(&response, key));
+    }
+    let head = StorageHttpResponse {
+      status: response.status(),
+      headers: response.headers().clone(),
+      body: Vec::new(),
+    };
+    let (metadata, content_range) = ranged_response_metadata(&head, range, key)?;
+    let (skip, remaining) = match content_range {
+      Some(content_range) if head.status == StatusCode::OK => (content_range.start, content_range.len()),
+      Some(content_range) => (0, content_range.len()),
+      None => (0, u64::MAX),
+    };
+    Ok(Some((
+      metadata,
+      content_range,
+      ObjectStorageBodyStream {
+        response,
+        skip,
+        remaining,
+      },
+    )))
+  }
+
+  fn get_request(&self, key: &str, range: Option<ObjectByteRange>) -> StorageHttpRequest {
+    let action = GetObject::new(&self.bucket, Some(&self.credentials), key);
+    let mut headers = HashMap::new();
+    if let Some(range) = range {
+      headers.insert(RANGE.to_string(), range.header_value());
+    }
+    StorageHttpRequest {
+      method: Method::GET,
+      url: action.sign(expires_in(self.presign_expires_in_seconds)),
+      headers,
+      body: None,
+      max_response_body_bytes: MAX_RESPONSE_BODY_BYTES,
+    }
+  }
+
   pub(crate) async fn list(&self, prefix: Option<String>) -> ObjectStorageResult<Vec<ObjectListEntry>> {
     let mut entries = Vec::new();
     let mut token = None;
@@ -695,6 +804,67 @@ fn ensure_success_status(response: &StorageHttpResponse, context: &str) -> Objec
   })
 }
 
+/// Reads object metadata from a `GET` response and works out which bytes of
+/// the object the body covers. `content_length` always reports the full size.
+fn ranged_response_metadata(
+  response: &StorageHttpResponse,
+  range: Option<ObjectByteRange>,
+  key: &str,
+) -> ObjectStorageResult<(ObjectMetadata, Option<ObjectContentRange>)> {
+  if !response.status.is_success() {
+    return Err(get_status_error(response, key));
+  }
+  let mut metadata = metadata_from_headers(&response.headers);
+  let Some(range) = range else {
+    return Ok((metadata, None));
+  };
+  if response.status == StatusCode::PARTIAL_CONTENT {
+    let content_range = response_header(&response.headers, CONTENT_RANGE)
+      .and_then(|value| ObjectContentRange::parse(&value))
+      .ok_or_else(|| ObjectStorageError::InvalidHeader(format!("ObjectStorage get for {key} missing Content-Range")))?;
+    metadata.content_length = content_range.total as i64;
+    return Ok((metadata, Some(content_range)));
+  }
+  let content_range =
+    range
+      .resolve(metadata.content_length.max(0) as u64)
+      .ok_or(ObjectStorageError::RangeNotSatisfiable {
+        length: metadata.content_length,
+      })?;
+  Ok((metadata, Some(content_range)))
+}
+
+/// Cuts the requested range out of a full-object response. The range was
+/// resolved against `Content-Length`, so a short body is an error rather than
+/// a panic.
+fn slice_full_body(body: &[u8], range: ObjectContentRange) -> ObjectStorageResult<Vec<u8>> {
+  body
+    .get(range.start as usize..=range.end as usize)
+    .map(<[u8]>::to_vec)
+    .ok_or(ObjectStorageError::TruncatedBody {
+      expected: range.end + 1,
+      actual: body.len(),
+    })
+}
+
+fn get_status_error(response: &StorageHttpResponse, key: &str) -> ObjectStorageError {
+  if response.status == StatusCode::RANGE_NOT_SATISFIABLE {
+    let length = response_header(&response.headers, CONTENT_RANGE)
+      .and_then(|value| {
+        value
+          .strip_prefix("bytes */")
+          .and_then(|total| total.parse::<i64>().ok())
+      })
+      .unwrap_or(0);
+    return ObjectStorageError::RangeNotSatisfiable { length };
+  }
+  ObjectStorageError::HttpStatus {
+    context: format!("ObjectStorage get failed for {key}"),
+    status: response.status,
+    body: String::from_utf8_lossy(&response.body).to_string(),
+  }
+}
+
 fn is_not_found_body(body: &[u8]) -> bool {
   let body = String::from_utf8_lossy(body);
   body.contains("<Code>NoSuchKey</Code>")
@@ -829,6 +999,74 @@ mod tests {
     ReqwestStorageHttpClient::new(Some(1_000)).unwrap();
   }
 
+  #[test]
+  fn slice_full_body_rejects_bodies_shorter_than_the_range() {
+    let range = ObjectContentRange {
+      start: 7,
+      end: 9,
+      total: 10,
+    };
+    assert_eq!(slice_full_body(b"0123456789", range).unwrap(), b"789");
+    assert!(matches!(
+      slice_full_body(b"01234", range),
+      Err(ObjectStorageError::TruncatedBody {
+        expected: 10,
+        actual: 5
+      })
+    ));
+  }
+
+  #[test]
+  fn ranged_response_metadata_reports_full_length_and_served_range() {
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_LENGTH, HeaderValue::from_static("4"));
+    headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes 2-5/10"));
+    let partial = StorageHttpResponse {
+      status: StatusCode::PARTIAL_CONTENT,
+      headers,
+      body: b"2345".to_vec(),
+    };
+    let (metadata, range) = ranged_response_metadata(&partial, ObjectByteRange::parse("bytes=2-5").ok(), "k").unwrap();
+    assert_eq!(metadata.content_length, 10);
+    assert_eq!(
+      range,
+      Some(ObjectContentRange {
+        start: 2,
+        end: 5,
+        total: 10
+      })
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_LENGTH, HeaderValue::from_static("10"));
+    let ignored = StorageHttpResponse {
+      status: StatusCode::OK,
+      headers,
+      body: b"0123456789".to_vec(),
+    };
+    let (_, range) = ranged_response_metadata(&ignored, ObjectByteRange::parse("bytes=-3").ok(), "k").unwrap();
+    assert_eq!(
+      range,
+      Some(ObjectContentRange {
+        start: 7,
+        end: 9,
+        total: 10
+      })
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(CONTENT_RANGE, HeaderValue::from_static("bytes */10"));
+    let unsatisfiable = StorageHttpResponse {
+      status: StatusCode::RANGE_NOT_SATISFIABLE,
+      headers,
+      body: Vec::new(),
+    };
+    assert!(matches!(
+      ranged_response_metadata(&unsatisfiable, ObjectByteRange::parse("bytes=20-").ok(), "k"),
+      Err(ObjectStorageError::RangeNotSatisfiable { length: 10 })
+    ));
+  }
+
   #[test]
   fn metadata_from_headers_uses_s3_defaults_and_checksum() {
     let mut headers = HeaderMap::new();
@@ -38,6 +38,10 @@ pub(crate) enum ObjectStorageError {
   },
   #[error("ObjectStorage invalid input: {0}")]
   InvalidInput(String),
+  #[error("ObjectStorage requested range is not satisfiable for object of {length} bytes")]
+  RangeNotSatisfiable { length: i64 },
+  #[error("ObjectStorage response body has {actual} bytes, expected at least {expected}")]
+  TruncatedBody { expected: u64, actual: usize },
 }
 
 impl ObjectStorageError {
@@ -0,0 +1,720 @@
+//! In-process S3 stand-in for tests and local development.
+//!
+//! Speaks just enough of the S3 REST API for `ObjectStorageClient`: object
+//! put/get/head/delete with byte ranges, ListObjectsV2, DeleteObjects and
+//! multipart uploads. Requests must use path-style URLs; signatures are not
+//! verified and buckets are created on first use.
+
+use std::{
+  collections::{BTreeMap, HashMap},
+  io::{self, BufRead, BufReader, Read, Write},
+  net::{SocketAddr, TcpListener, TcpStream},
+  sync::{
+    Arc, Mutex,
+    atomic::{AtomicBool, Ordering},
+  },
+  thread::{self, JoinHandle},
+  time::Duration,
+};
+
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{DateTime, SecondsFormat, Utc};
+use reqwest::StatusCode;
+use sha2::{Digest, Sha256};
+
+use super::{
+  ObjectStorageConfig,
+  error::{ObjectStorageError, ObjectStorageResult},
+  types::ObjectByteRange,
+};
+
+const MOCK_REGION: &str = "us-east-1";
+const MOCK_CREDENTIAL: &str = "mock";
+const DEFAULT_MAX_KEYS: usize = 1000;
+const CONNECTION_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Bodies are buffered whole, so a client-supplied length above this is
+/// refused before anything is allocated.
+const MAX_REQUEST_BODY: usize = 64 * 1024 * 1024;
+
+struct MockObject {
+  body: Vec<u8>,
+  content_type: String,
+  checksum_crc32: Option<String>,
+  etag: String,
+  last_modified: DateTime<Utc>,
+}
+
+struct MockUpload {
+  bucket: String,
+  key: String,
+  content_type: String,
+  parts: BTreeMap<u16, (String, Vec<u8>)>,
+}
+
+#[derive(Default)]
+struct MockState {
+  buckets: HashMap<String, BTreeMap<String, MockObject>>,
+  uploads: HashMap<String, MockUpload>,
+  next_upload_id: u64,
+}
+
+struct MockRequest {
+  method: String,
+  bucket: String,
+  key: String,
+  query: HashMap<String, String>,
+  headers: HashMap<String, String>,
+  body: Vec<u8>,
+}
+
+impl MockRequest {
+  fn header(&self, name: &str) -> Option<&str> {
+    self.headers.get(name).map(String::as_str)
+  }
+}
+
+struct MockResponse {
+  status: StatusCode,
+  headers: Vec<(&'static str, String)>,
+  body: Vec<u8>,
+}
+
+impl MockResponse {
+  fn new(status: StatusCode) -> Self {
+    Self {
+      status,
+      headers: Vec::new(),
+      body: Vec::new(),
+    }
+  }
+
+  fn xml(status: StatusCode, body: String) -> Self {
+    Self {
+      status,
+      headers: vec![("content-type", "application/xml".to_string())],
+      body: format!(r#"<?xml version="1.0" encoding="UTF-8"?>{body}"#).into_bytes(),
+    }
+  }
+
+  fn error(status: StatusCode, code: &str, message: &str) -> Self {
+    Self::xml(
+      status,
+      format!(
+        "<Error><Code>{code}</Code><Message>{}</Message></Error>",
+        xml_escape(message)
+      ),
+    )
+  }
+
+  fn header(mut self, name: &'static str, value: impl Into<String>) -> Self {
+    self.headers.push((name, value.into()));
+    self
+  }
+}
+
+/// A running mock server. Dropping it stops accepting connections.
+pub(crate) struct MockObjectStorageServer {
+  addr: SocketAddr,
+  shutdown: Arc<AtomicBool>,
+  accept: Option<JoinHandle<()>>,
+}
+
+impl MockObjectStorageServer {
+  /// Binds a random localhost port and serves requests on background threads.
+  pub(crate) fn start() -> ObjectStorageResult<Self> {
+    let listener = TcpListener::bind(("127.0.0.1", 0))
+      .map_err(|err| ObjectStorageError::Config(format!("ObjectStorage mock server bind failed: {err}")))?;
+    let addr = listener
+      .local_addr()
+      .map_err(|err| ObjectStorageError::Config(format!("ObjectStorage mock server address failed: {err}")))?;
+    let shutdown = Arc::new(AtomicBool::new(false));
+    let state = Arc::new(Mutex::new(MockState::default()));
+    let accept_shutdown = shutdown.clone();
+    let accept = thread::spawn(move || {
+      for stream in listener.incoming() {
+        if accept_shutdown.load(Ordering::SeqCst) {
+          break;
+        }
+        let Ok(stream) = stream else {
+          continue;
+        };
+        let state = state.clone();
+        thread::spawn(move || {
+          let _ = serve_connection(stream, &state);
+        });
+      }
+    });
+    Ok(Self {
+      addr,
+      shutdown,
+      accept: Some(accept),
+    })
+  }
+
+  pub(crate) fn endpoint(&self) -> String {
+    format!("http://{}", self.addr)
+  }
+
+  /// `storages.*` entry that points the `aws-s3` provider at this server.
+  pub(crate) fn provider_config(&self, bucket: &str) -> serde_json::Value {
+    serde_json::json!({
+      "provider": "aws-s3",
+      "bucket": bucket,
+      "config": {
+        "endpoint": self.endpoint(),
+        "region": MOCK_REGION,
+        "forcePathStyle": true,
+        "credentials": {
+          "accessKeyId": MOCK_CREDENTIAL,
+          "secretAccessKey": MOCK_CREDENTIAL,
+        },
+      },
+    })
+  }
+
+  pub(crate) fn storage_config(&self, bucket: &str) -> ObjectStorageConfig {
+    ObjectStorageConfig {
+      provider: "aws-s3".to_string(),
+      bucket: bucket.to_string(),
+      endpoint: Some(self.endpoint()),
+      region: Some(MOCK_REGION.to_string()),
+      access_key_id: Some(MOCK_CREDENTIAL.to_string()),
+      secret_access_key: Some(MOCK_CREDENTIAL.to_string()),
+      session_token: None,
+      force_path_style: true,
+      request_timeout_ms: None,
+      min_part_size: None,
+      presign_expires_in_seconds: None,
+      presign_sign_content_type_for_put: None,
+      use_presigned_url: false,
+      proxy_upload: false,
+    }
+  }
+
+  pub(crate) fn stop(&mut self) {
+    let Some(accept) = self.accept.take() else {
+      return;
+    };
+    self.shutdown.store(true, Ordering::SeqCst);
+    // Wake the blocking accept loop so it observes the shutdown flag.
+    let _ = TcpStream::connect(self.addr);
+    let _ = accept.join();
+  }
+}
+
+impl Drop for MockObjectStorageServer {
+  fn drop(&mut self) {
+    self.stop();
+  }
+}
+
+fn serve_connection(stream: TcpStream, state: &Mutex<MockState>) -> io::Result<()> {
+  stream.set_read_timeout(Some(CONNECTION_IDLE_TIMEOUT))?;
+  let mut writer = stream.try_clone()?;
+  let mut reader = BufReader::new(stream);
+  while let Some(request) = read_request(&mut reader)? {
+    let request = match request {
+      Ok(request) => request,
+      // The unread body would be parsed as the next request, so close.
+      Err(response) => return write_response(&mut writer, response.header("connection", "close"), false),
+    };
+    let head_only = request.method == "HEAD";
+    let response = match state.lock() {
+      Ok(mut state) => handle(&mut state, request),
+      Err(_) => MockResponse::error(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "InternalError",
+        "state lock poisoned",
+      ),
+    };
+    write_response(&mut writer, response, head_only)?;
+  }
+  Ok(())
+}
+
+/// Resolves to the response to send instead when the request is refused
+/// before its body is read.
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Result<MockRequest, MockResponse>>> {
+  let mut line = String::new();
+  if reader.read_line(&mut line)? == 0 {
+    return Ok(None);
+  }
+  let mut parts = line.split_whitespace();
+  let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+    return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed request line"));
+  };
+  let method = method.to_string();
+  let (path, query) = target.split_once('?').unwrap_or((target, ""));
+  let path = path.trim_start_matches('/');
+  let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+  let (bucket, key) = (percent_decode(bucket), percent_decode(key));
+  let query = url::form_urlencoded::parse(query.as_bytes())
+    .map(|(name, value)| (name.into_owned(), value.into_owned()))
+    .collect();
+
+  let mut headers = HashMap::new();
+  loop {
+    line.clear();
+    if reader.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let header = line.trim_end();
+    if header.is_empty() {
+      break;
+    }
+    if let Some((name, value)) = header.split_once(':') {
+      headers.insert(name.trim().to_ascii_lowercase(), value.trim().to_string());
+    }
+  }
+  let content_length = headers
+    .get("content-length")
+    .and_then(|value| value.parse::<usize>().ok())
+    .unwrap_or(0);
+  if content_length > MAX_REQUEST_BODY {
+    return Ok(Some(Err(MockResponse::error(
+      StatusCode::PAYLOAD_TOO_LARGE,
+      "EntityTooLarge",
+      "Your proposed upload exceeds the maximum allowed size.",
+    ))));
+  }
+  let mut body = vec![0; content_length];
+  reader.read_exact(&mut body)?;
+
+  Ok(Some(Ok(MockRequest {
+    method,
+    bucket,
+    key,
+    query,
+    headers,
+    body,
+  })))
+}
+
+fn write_response(writer: &mut impl Write, response: MockResponse, head_only: bool) -> io::Result<()> {
+  let mut head = format!(
+    "HTTP/1.1 {} {}\r\n",
+    response.status.as_u16(),
+    response.status.canonical_reason().unwrap_or("")
+  );
+  let mut has_content_length = false;
+  for (name, value) in &response.headers {
+    has_content_length |= *name == "content-length";
+    head.push_str(&format!("{name}: {value}\r\n"));
+  }
+  if !has_content_length {
+    head.push_str(&format!("content-length: {}\r\n", response.body.len()));
+  }
+  head.push_str("\r\n");
+  writer.write_all(head.as_bytes())?;
+  if !head_only {
+    writer.write_all(&response.body)?;
+  }
+  writer.flush()
+}
+
+fn handle(state: &mut MockState, request: MockRequest) -> MockResponse {
+  if request.bucket.is_empty() {
+    return MockResponse::error(
+      StatusCode::BAD_REQUEST,
+      "InvalidRequest",
+      "path-style bucket is required",
+    );
+  }
+  let has = |name: &str| request.query.contains_key(name);
+  match (request.method.as_str(), request.key.is_empty()) {
+    ("GET", true) if request.query.get("list-type").map(String::as_str) == Some("2") => list_objects(state, &request),
+    ("POST", true) if has("delete") => delete_objects(state, &request),
+    ("PUT", false) if has("uploadId") => upload_part(state, &request),
+    ("PUT", false) => put_object(state, request),
+    ("GET", false) if has("uploadId") => list_parts(state, &request),
+    ("GET" | "HEAD", false) => get_object(state, &request),
+    ("DELETE", false) if has("uploadId") => abort_upload(state, &request),
+    ("DELETE", false) => {
+      state.buckets.entry(request.bucket).or_default().remove(&request.key);
+      MockResponse::new(StatusCode::NO_CONTENT)
+    }
+    ("POST", false) if has("uploads") => create_upload(state, &request),
+    ("POST", false) if has("uploadId") => complete_upload(state, &request),
+    _ => MockResponse::error(
+      StatusCode::METHOD_NOT_ALLOWED,
+      "MethodNotAllowed",
+      "unsupported mock object storage request",
+    ),
+  }
+}
+
+fn put_object(state: &mut MockState, request: MockRequest) -> MockResponse {
+  let object = MockObject {
+    etag: etag(&request.body),
+    content_type: request
+      .header("content-type")
+      .unwrap_or("application/octet-stream")
+      .to_string(),
+    checksum_crc32: request.header("x-amz-checksum-crc32").map(ToString::to_string),
+    last_modified: Utc::now(),
+    body: request.body,
+  };
+  let etag = object.etag.clone();
+  state
+    .buckets
+    .entry(request.bucket)
+    .or_default()
+    .insert(request.key, object);
+  MockResponse::new(StatusCode::OK).header("etag", etag)
+}
+
+fn get_object(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let Some(object) = state
+    .buckets
+    .get(&request.bucket)
+    .and_then(|objects| objects.get(&request.key))
+  else {
+    return MockResponse::error(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist.");
+  };
+  let total = object.body.len() as u64;
+  let mut response = MockResponse::new(StatusCode::OK)
+    .header("content-type", object.content_type.clone())
+    .header("etag", object.etag.clone())
+    .header("last-modified", object.last_modified.to_rfc2822())
+    .header("accept-ranges", "bytes");
+  if let Some(checksum) = &object.checksum_crc32 {
+    response = response.header("x-amz-checksum-crc32", checksum.clone());
+  }
+  // Like S3, an unparseable `Range` is ignored and the whole object is sent.
+  let range = request
+    .header("range")
+    .and_then(|value| ObjectByteRange::parse(value).ok());
+  let Some(range) = range else {
+    response.body = object.body.clone();
+    return response;
+  };
+  let Some(content_range) = range.resolve(total) else {
+    return MockResponse::error(
+      StatusCode::RANGE_NOT_SATISFIABLE,
+      "InvalidRange",
+      "The requested range is not satisfiable",
+    )
+    .header("content-range", format!("bytes */{total}"));
+  };
+  response.status = StatusCode::PARTIAL_CONTENT;
+  response.body = object.body[content_range.start as usize..=content_range.end as usize].to_vec();
+  response.header("content-range", content_range.header_value())
+}
+
+fn list_objects(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let prefix = request.query.get("prefix").cloned().unwrap_or_default();
+  let max_keys = request
+    .query
+    .get("max-keys")
+    .and_then(|value| value.parse::<usize>().ok())
+    .unwrap_or(DEFAULT_MAX_KEYS);
+  let marker = match request.query.get("continuation-token") {
+    Some(token) => match URL_SAFE_NO_PAD
+      .decode(token)
+      .ok()
+      .and_then(|key| String::from_utf8(key).ok())
+    {
+      Some(key) => Some(key),
+      None => {
+        return MockResponse::error(
+          StatusCode::BAD_REQUEST,
+          "InvalidArgument",
+          "The continuation token provided is incorrect",
+        );
+      }
+    },
+    None => request.query.get("start-after").cloned(),
+  };
+
+  let objects = state.buckets.entry(request.bucket.clone()).or_default();
+  let mut page = objects
+    .iter()
+    .filter(|(key, _)| key.starts_with(&prefix) && marker.as_ref().is_none_or(|marker| *key > marker))
+    .take(max_keys + 1)
+    .collect::<Vec<_>>();
+  let truncated = page.len() > max_keys;
+  page.truncate(max_keys);
+
+  let mut body = format!(
+    "<ListBucketResult><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{max_keys}</MaxKeys><IsTruncated>{truncated}</IsTruncated>",
+    xml_escape(&request.bucket),
+    xml_escape(&prefix),
+    page.len(),
+  );
+  if truncated && let Some((last_key, _)) = page.last() {
+    body.push_str(&format!(
+      "<NextContinuationToken>{}</NextContinuationToken>",
+      URL_SAFE_NO_PAD.encode(last_key)
+    ));
+  }
+  for (key, object) in page {
+    body.push_str(&format!(
+      "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+      xml_escape(key),
+      object.last_modified.to_rfc3339_opts(SecondsFormat::Millis, true),
+      xml_escape(&object.etag),
+      object.body.len(),
+    ));
+  }
+  body.push_str("</ListBucketResult>");
+  MockResponse::xml(StatusCode::OK, body)
+}
+
+fn delete_objects(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let request_body = String::from_utf8_lossy(&request.body);
+  let objects = state.buckets.entry(request.bucket.clone()).or_default();
+  let mut body = String::from("<DeleteResult>");
+  for key in xml_elements(&request_body, "Key") {
+    let key = xml_unescape(key);
+    objects.remove(&key);
+    body.push_str(&format!("<Deleted><Key>{}</Key></Deleted>", xml_escape(&key)));
+  }
+  body.push_str("</DeleteResult>");
+  MockResponse::xml(StatusCode::OK, body)
+}
+
+fn create_upload(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  state.next_upload_id += 1;
+  let upload_id = format!("mock-upload-{}", state.next_upload_id);
+  state.uploads.insert(
+    upload_id.clone(),
+    MockUpload {
+      bucket: request.bucket.clone(),
+      key: request.key.clone(),
+      content_type: request
+        .header("content-type")
+        .unwrap_or("application/octet-stream")
+        .to_string(),
+      parts: BTreeMap::new(),
+    },
+  );
+  MockResponse::xml(
+    StatusCode::OK,
+    format!(
+      "<InitiateMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{upload_id}</UploadId></InitiateMultipartUploadResult>",
+      xml_escape(&request.bucket),
+      xml_escape(&request.key),
+    ),
+  )
+}
+
+fn upload_for<'a>(state: &'a mut MockState, request: &MockRequest) -> Option<(String, &'a mut MockUpload)> {
+  let upload_id = request.query.get("uploadId")?;
+  state
+    .uploads
+    .get_mut(upload_id)
+    .filter(|upload| upload.bucket == request.bucket && upload.key == request.key)
+    .map(|upload| (upload_id.clone(), upload))
+}
+
+fn no_such_upload() -> MockResponse {
+  MockResponse::error(
+    StatusCode::NOT_FOUND,
+    "NoSuchUpload",
+    "The specified multipart upload does not exist.",
+  )
+}
+
+fn upload_part(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let Some(part_number) = request
+    .query
+    .get("partNumber")
+    .and_then(|value| value.parse::<u16>().ok())
+    .filter(|part_number| (1..=10_000).contains(part_number))
+  else {
+    return MockResponse::error(StatusCode::BAD_REQUEST, "InvalidArgument", "invalid part number");
+  };
+  let Some((_, upload)) = upload_for(state, request) else {
+    return no_such_upload();
+  };
+  let etag = etag(&request.body);
+  upload.parts.insert(part_number, (etag.clone(), request.body.clone()));
+  MockResponse::new(StatusCode::OK).header("etag", etag)
+}
+
+fn list_parts(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let Some((upload_id, upload)) = upload_for(state, request) else {
+    return no_such_upload();
+  };
+  let mut body = format!(
+    "<ListPartsResult><Bucket>{}</Bucket><Key>{}</Key><UploadId>{upload_id}</UploadId><PartNumberMarker>0</PartNumberMarker><MaxParts>10000</MaxParts><IsTruncated>false</IsTruncated>",
+    xml_escape(&upload.bucket),
+    xml_escape(&upload.key),
+  );
+  let last_modified = Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true);
+  for (part_number, (etag, part)) in &upload.parts {
+    body.push_str(&format!(
+      "<Part><PartNumber>{part_number}</PartNumber><LastModified>{last_modified}</LastModified><ETag>{}</ETag><Size>{}</Size></Part>",
+      xml_escape(etag),
+      part.len(),
+    ));
+  }
+  body.push_str("</ListPartsResult>");
+  MockResponse::xml(StatusCode::OK, body)
+}
+
+fn complete_upload(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let Some((upload_id, upload)) = upload_for(state, request) else {
+    return no_such_upload();
+  };
+  let request_body = String::from_utf8_lossy(&request.body);
+  let mut body = Vec::new();
+  let mut previous = 0;
+  for part in xml_elements(&request_body, "Part") {
+    let part_number = xml_elements(part, "PartNumber")
+      .first()
+      .and_then(|value| value.trim().parse::<u16>().ok());
+    let etag = xml_elements(part, "ETag").first().map(|value| xml_unescape(value));
+    let Some((part_number, etag)) = part_number.zip(etag) else {
+      return MockResponse::error(StatusCode::BAD_REQUEST, "MalformedXML", "invalid part list");
+    };
+    if part_number <= previous {
+      return MockResponse::error(StatusCode::BAD_REQUEST, "InvalidPartOrder", "parts must be ascending");
+    }
+    previous = part_number;
+    match upload.parts.get(&part_number) {
+      Some((stored, part)) if stored.trim_matches('"') == etag.trim_matches('"') => body.extend_from_slice(part),
+      _ => {
+        return MockResponse::error(
+          StatusCode::BAD_REQUEST,
+          "InvalidPart",
+          "part is missing or etag differs",
+        );
+      }
+    }
+  }
+  if previous == 0 {
+    return MockResponse::error(StatusCode::BAD_REQUEST, "MalformedXML", "no parts to complete");
+  }
+
+  let Some(upload) = state.uploads.remove(&upload_id) else {
+    return no_such_upload();
+  };
+  let etag = etag(&body);
+  state.buckets.entry(upload.bucket.clone()).or_default().insert(
+    upload.key.clone(),
+    MockObject {
+      body,
+      content_type: upload.content_type,
+      checksum_crc32: None,
+      etag: etag.clone(),
+      last_modified: Utc::now(),
+    },
+  );
+  MockResponse::xml(
+    StatusCode::OK,
+    format!(
+      "<CompleteMultipartUploadResult><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>",
+      xml_escape(&upload.bucket),
+      xml_escape(&upload.key),
+      xml_escape(&etag),
+    ),
+  )
+}
+
+fn abort_upload(state: &mut MockState, request: &MockRequest) -> MockResponse {
+  let Some((upload_id, _)) = upload_for(state, request) else {
+    return no_such_upload();
+  };
+  state.uploads.remove(&upload_id);
+  MockResponse::new(StatusCode::NO_CONTENT)
+}
+
+fn etag(body: &[u8]) -> String {
+  format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+/// Inner text of every non-nested `<tag>…</tag>` in document order.
+fn xml_elements<'a>(xml: &'a str, tag: &str) -> Vec<&'a str> {
+  let (open, close) = (format!("<{tag}>"), format!("</{tag}>"));
+  let mut elements = Vec::new();
+  let mut rest = xml;
+  while let Some(start) = rest.find(&open) {
+    rest = &rest[start + open.len()..];
+    let Some(end) = rest.find(&close) else {
+      break;
+    };
+    elements.push(&rest[..end]);
+    rest = &rest[end + close.len()..];
+  }
+  elements
+}
+
+fn xml_escape(value: &str) -> String {
+  value
+    .replace('&', "&amp;")
+    .replace('<', "&lt;")
+    .replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn xml_unescape(value: &str) -> String {
+  value
+    .replace("&lt;", "<")
+    .replace("&gt;", ">")
+    .replace("&quot;", "\"")
+    .replace("&apos;", "'")
+    .replace("&amp;", "&")
+}
+
+fn percent_decode(value: &str) -> String {
+  let bytes = value.as_bytes();
+  let mut decoded = Vec::with_capacity(bytes.len());
+  let mut index = 0;
+  while index < bytes.len() {
+    if bytes[index] == b'%'
+      && let Some(byte) = value
+        .get(index + 1..index + 3)
+        .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+    {
+      decoded.push(byte);
+      index += 3;
+      continue;
+    }
+    decoded.push(bytes[index]);
+    index += 1;
+  }
+  String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn mock_server_decodes_path_style_targets() {
+    let raw = b"PUT /bucket/workspace/a%20b%2Bc.txt?partNumber=2&uploadId=u%2B1 HTTP/1.1\r\ncontent-length: 3\r\nContent-Type: text/plain\r\n\r\nabc";
+    let Ok(request) = read_request(&mut &raw[..]).unwrap().unwrap() else {
+      panic!("request should be accepted");
+    };
+    assert_eq!(request.bucket, "bucket");
+    assert_eq!(request.key, "workspace/a b+c.txt");
+    assert_eq!(request.query.get("uploadId").map(String::as_str), Some("u+1"));
+    assert_eq!(request.header("content-type"), Some("text/plain"));
+    assert_eq!(request.body, b"abc");
+  }
+
+  #[test]
+  fn mock_server_refuses_bodies_over_the_limit() {
+    let raw = format!(
+      "PUT /bucket/key HTTP/1.1\r\ncontent-length: {}\r\n\r\n",
+      MAX_REQUEST_BODY + 1
+    );
+    let Err(response) = read_request(&mut raw.as_bytes()).unwrap().unwrap() else {
+      panic!("request should be refused");
+    };
+    assert_eq!(response.status, StatusCode::PAYLOAD_TOO_LARGE);
+  }
+
+  #[test]
+  fn mock_server_extracts_xml_elements_in_order() {
+    let xml = "<Delete><Object><Key>a&amp;b</Key></Object><Object><Key>c</Key></Object></Delete>";
+    assert_eq!(
+      xml_elements(xml, "Key")
+        .into_iter()
+        .map(xml_unescape)
+        .collect::<Vec<_>>(),
+      ["a&b", "c"]
+    );
+  }
+}
@@ -1,6 +1,7 @@
 pub(crate) mod client;
 pub(crate) mod config;
 pub(crate) mod error;
+pub(crate) mod mock_server;
 #[cfg(test)]
 mod tests;
 pub(crate) mod types;
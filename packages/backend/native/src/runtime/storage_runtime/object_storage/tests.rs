@@ -3,9 +3,10 @@ use reqwest::StatusCode;
 use super::{
   config::ObjectStorageConfig,
   error::ObjectStorageError,
+  mock_server::MockObjectStorageServer,
   types::{
-    MultipartUploadPart, ObjectPutMetadata, StorageProviderConfig, checksum_crc32_base64, completed_multipart_parts,
-    trim_etag,
+    MultipartUploadPart, ObjectByteRange, ObjectContentRange, ObjectPutMetadata, StorageProviderConfig,
+    checksum_crc32_base64, completed_multipart_parts, trim_etag,
   },
 };
 
@@ -374,3 +375,181 @@ fn object_storage_crc32_checksum_uses_s3_base64_format() {
   assert_eq!(checksum_crc32_base64(b"hello"), "NhCmhg==");
   assert_ne!(checksum_crc32_base64(b"hello"), "3610a686");
 }
+
+#[test]
+fn byte_ranges_parse_and_resolve_like_http_range_requests() {
+  assert_eq!(
+    ObjectByteRange::parse("bytes=0-99").unwrap(),
+    ObjectByteRange::Bounded { start: 0, end: 99 }
+  );
+  assert_eq!(
+    ObjectByteRange::parse("bytes=100-").unwrap(),
+    ObjectByteRange::From { start: 100 }
+  );
+  assert_eq!(
+    ObjectByteRange::parse("bytes=-20").unwrap(),
+    ObjectByteRange::Suffix { length: 20 }
+  );
+  for invalid in ["0-99", "bytes=", "bytes=-", "bytes=9-1", "bytes=a-b", "bytes=0-1,4-5"] {
+    assert!(ObjectByteRange::parse(invalid).is_err(), "{invalid}");
+  }
+
+  let resolve = |value: &str, total| ObjectByteRange::parse(value).unwrap().resolve(total);
+  assert_eq!(
+    resolve("bytes=0-99", 50),
+    Some(ObjectContentRange {
+      start: 0,
+      end: 49,
+      total: 50
+    })
+  );
+  assert_eq!(
+    resolve("bytes=10-", 50),
+    Some(ObjectContentRange {
+      start: 10,
+      end: 49,
+      total: 50
+    })
+  );
+  assert_eq!(
+    resolve("bytes=-20", 50),
+    Some(ObjectContentRange {
+      start: 30,
+      end: 49,
+      total: 50
+    })
+  );
+  assert_eq!(
+    resolve("bytes=-80", 50),
+    Some(ObjectContentRange {
+      start: 0,
+      end: 49,
+      total: 50
+    })
+  );
+  assert_eq!(resolve("bytes=50-", 50), None);
+  assert_eq!(resolve("bytes=-0", 50), None);
+  assert_eq!(resolve("bytes=0-", 0), None);
+
+  let range = ObjectContentRange {
+    start: 30,
+    end: 49,
+    total: 50,
+  };
+  assert_eq!(range.len(), 20);
+  assert_eq!(range.header_value(), "bytes 30-49/50");
+  assert_eq!(ObjectContentRange::parse("bytes 30-49/50"), Some(range));
+  assert_eq!(ObjectContentRange::parse("bytes */50"), None);
+}
+
+#[tokio::test]
+async fn client_round_trips_objects_through_mock_server() {
+  let server = MockObjectStorageServer::start().unwrap();
+  let client = server.storage_config("test-bucket").build_client().unwrap();
+  let body = b"0123456789".to_vec();
+
+  client
+    .put(
+      "workspace/a b+c",
+      body.clone(),
+      ObjectPutMetadata {
+        content_type: Some("text/plain".to_string()),
+        content_length: None,
+        checksum_crc32: Some(checksum_crc32_base64(&body)),
+      },
+    )
+    .await
+    .unwrap();
+  let head = client.head("workspace/a b+c").await.unwrap().unwrap();
+  assert_eq!(head.content_type, "text/plain");
+  assert_eq!(head.content_length, 10);
+  assert_eq!(head.checksum_crc32, Some(checksum_crc32_base64(&body)));
+  assert!(client.head("workspace/missing").await.unwrap().is_none());
+  assert!(client.get("workspace/missing").await.unwrap().is_none());
+
+  let object = client
+    .get_range("workspace/a b+c", Some(ObjectByteRange::Suffix { length: 3 }))
+    .await
+    .unwrap()
+    .unwrap();
+  assert_eq!(object.body, b"789");
+  assert_eq!(object.metadata.content_length, 10);
+  assert!(matches!(
+    client
+      .get_range("workspace/a b+c", Some(ObjectByteRange::From { start: 10 }))
+      .await,
+    Err(ObjectStorageError::RangeNotSatisfiable { length: 10 })
+  ));
+
+  for key in ["workspace/b", "workspace/c", "other/d"] {
+    client
+      .put(key, b"x".to_vec(), ObjectPutMetadata::default())
+      .await
+      .unwrap();
+  }
+  let page = client
+    .list_page(Some("workspace/".to_string()), None, None, 2)
+    .await
+    .unwrap();
+  assert_eq!(
+    page.entries.iter().map(|entry| entry.key.as_str()).collect::<Vec<_>>(),
+    ["workspace/a b+c", "workspace/b"]
+  );
+  let page = client
+    .list_page(Some("workspace/".to_string()), page.next_continuation_token, None, 2)
+    .await
+    .unwrap();
+  assert_eq!(page.entries.len(), 1);
+  assert!(page.next_continuation_token.is_none());
+  assert_eq!(client.list(None).await.unwrap().len(), 4);
+
+  let init = client
+    .create_multipart_upload("workspace/large", ObjectPutMetadata::default())
+    .await
+    .unwrap()
+    .unwrap();
+  for (part_number, part) in [(2, b"world".to_vec()), (1, b"hello ".to_vec())] {
+    client
+      .upload_part("workspace/large", &init.upload_id, part_number, part, None)
+      .await
+      .unwrap()
+      .unwrap();
+  }
+  let parts = client
+    .list_multipart_upload_parts("workspace/large", &init.upload_id)
+    .await
+    .unwrap();
+  assert_eq!(parts.len(), 2);
+  client
+    .complete_multipart_upload("workspace/large", &init.upload_id, parts)
+    .await
+    .unwrap();
+  assert_eq!(
+    client.get("workspace/large").await.unwrap().unwrap().body,
+    b"hello world"
+  );
+  assert!(
+    client
+      .list_multipart_upload_parts("workspace/large", &init.upload_id)
+      .await
+      .unwrap()
+      .is_empty()
+  );
+
+  let outcomes = client
+    .delete_many(vec!["workspace/b".to_string(), "workspace/missing".to_string()])
+    .await
+    .unwrap();
+  assert!(outcomes.iter().all(|outcome| outcome.error.is_none()));
+  client.delete("workspace/c").await.unwrap();
+  assert_eq!(
+    client
+      .list(Some("workspace/".to_string()))
+      .await
+      .unwrap()
+      .iter()
+      .map(|entry| entry.key.as_str())
+      .collect::<Vec<_>>(),
+    ["workspace/a b+c", "workspace/large"]
+  );
+}
@@ -4,8 +4,9 @@ use base64::{Engine as _, engine::general_purpose::STANDARD};
 use serde::Deserialize;
 
 use super::super::{
-  RuntimeError, RuntimeMultipartUploadInit, RuntimeMultipartUploadPart, RuntimeObjectGetResult, RuntimeObjectListEntry,
-  RuntimeObjectMetadata, RuntimeObjectStoragePutOptions, RuntimePresignedObjectRequest, RuntimeResult,
+  RuntimeError, RuntimeMultipartUploadInit, RuntimeMultipartUploadPart, RuntimeObjectContentRange,
+  RuntimeObjectGetResult, RuntimeObjectListEntry, RuntimeObjectMetadata, RuntimeObjectStoragePutOptions,
+  RuntimePresignedObjectRequest, RuntimeResult,
 };
 
 #[derive(Clone, Debug, Default)]
@@ -46,6 +47,27 @@ pub(crate) struct ObjectDeleteOutcome {
 pub(crate) struct ObjectGetResult {
   pub(crate) body: Vec<u8>,
   pub(crate) metadata: ObjectMetadata,
+  /// Set when the body is a byte range of the object rather than all of it.
+  pub(crate) content_range: Option<ObjectContentRange>,
+}
+
+/// A single `Range: bytes=...` request. Multi-range requests are rejected.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ObjectByteRange {
+  /// `bytes=start-end`, `end` inclusive.
+  Bounded { start: u64, end: u64 },
+  /// `bytes=start-`
+  From { start: u64 },
+  /// `bytes=-length`, the last `length` bytes.
+  Suffix { length: u64 },
+}
+
+/// The resolved byte range served for an object, `end` inclusive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct ObjectContentRange {
+  pub(crate) start: u64,
+  pub(crate) end: u64,
+  pub(crate) total: u64,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -75,6 +97,75 @@ pub(crate) struct StorageProviderConfig {
   pub(crate) config: serde_json::Value,
 }
 
+impl ObjectByteRange {
+  pub(crate) fn parse(value: &str) -> RuntimeResult<Self> {
+    let invalid = || RuntimeError::invalid_input(format!("StorageRuntime invalid byte range: {value}"));
+    let spec = value.trim().strip_prefix("bytes=").ok_or_else(invalid)?.trim();
+    if spec.contains(',') {
+      return Err(RuntimeError::invalid_input(
+        "StorageRuntime multiple byte ranges are not supported",
+      ));
+    }
+    let (start, end) = spec.split_once('-').ok_or_else(invalid)?;
+    let parse = |value: &str| value.trim().parse::<u64>().map_err(|_| invalid());
+    match (start.trim().is_empty(), end.trim().is_empty()) {
+      (true, true) => Err(invalid()),
+      (true, false) => Ok(Self::Suffix { length: parse(end)? }),
+      (false, true) => Ok(Self::From { start: parse(start)? }),
+      (false, false) => {
+        let (start, end) = (parse(start)?, parse(end)?);
+        if end < start {
+          return Err(invalid());
+        }
+        Ok(Self::Bounded { start, end })
+      }
+    }
+  }
+
+  /// Clamps the range to an object of `total` bytes, or returns `None` when
+  /// no byte of the object falls inside it.
+  pub(crate) fn resolve(self, total: u64) -> Option<ObjectContentRange> {
+    let last = total.checked_sub(1)?;
+    let (start, end) = match self {
+      Self::Bounded { start, end } => (start, end.min(last)),
+      Self::From { start } => (start, last),
+      Self::Suffix { length: 0 } => return None,
+      Self::Suffix { length } => (total - length.min(total), last),
+    };
+    (start <= end).then_some(ObjectContentRange { start, end, total })
+  }
+
+  pub(crate) fn header_value(self) -> String {
+    match self {
+      Self::Bounded { start, end } => format!("bytes={start}-{end}"),
+      Self::From { start } => format!("bytes={start}-"),
+      Self::Suffix { length } => format!("bytes=-{length}"),
+    }
+  }
+}
+
+impl ObjectContentRange {
+  pub(crate) fn len(&self) -> u64 {
+    self.end - self.start + 1
+  }
+
+  /// Parses a `Content-Range: bytes start-end/total` response header.
+  pub(crate) fn parse(value: &str) -> Option<Self> {
+    let (range, total) = value.trim().strip_prefix("bytes ")?.split_once('/')?;
+    let (start, end) = range.split_once('-')?;
+    let range = Self {
+      start: start.parse().ok()?,
+      end: end.parse().ok()?,
+      total: total.parse().ok()?,
+    };
+    (range.start <= range.end && range.end < range.total).then_some(range)
+  }
+
+  pub(crate) fn header_value(&self) -> String {
+    format!("bytes {}-{}/{}", self.start, self.end, self.total)
+  }
+}
+
 pub(crate) fn trim_etag(etag: &str) -> String {
   etag.trim_matches('"').to_string()
 }
@@ -159,6 +250,18 @@ impl From<ObjectGetResult> for RuntimeObjectGetResult {
     Self {
       body: result.body.into(),
       metadata: result.metadata.into(),
+      content_range: result.content_range.map(Into::into),
+    }
+  }
+}
+
+impl From<ObjectContentRange> for RuntimeObjectContentRange {
+  fn from(range: ObjectContentRange) -> Self {
+    Self {
+      start: range.start as i64,
+      end: range.end as i64,
+      total: range.total as i64,
+      header: range.header_value(),
     }
   }
 }
@@ -0,0 +1,163 @@
+use std::{
+  fs,
+  io::{self, Read},
+};
+
+use napi::bindgen_prelude::Buffer;
+use tokio::sync::Mutex;
+
+use super::{
+  ObjectContentRange, ObjectGetResult, ObjectMetadata, RuntimeError, RuntimeObjectContentRange, RuntimeObjectMetadata,
+  RuntimeResult, object_storage::client::ObjectStorageBodyStream, to_napi_error,
+};
+
+const OBJECT_STREAM_CHUNK_SIZE: usize = 256 * 1024;
+
+pub(super) enum ObjectBody {
+  File(io::Take<fs::File>),
+  Buffered { body: Vec<u8>, offset: usize },
+  S3(ObjectStorageBodyStream),
+}
+
+/// Body of an fs object: a bounded file handle, or the decrypted bytes of an
+/// encrypted object.
+pub(super) enum FsObjectBody {
+  File(io::Take<fs::File>),
+  Buffered(Vec<u8>),
+}
+
+impl From<FsObjectBody> for ObjectBody {
+  fn from(body: FsObjectBody) -> Self {
+    match body {
+      FsObjectBody::File(file) => Self::File(file),
+      FsObjectBody::Buffered(body) => Self::Buffered { body, offset: 0 },
+    }
+  }
+}
+
+impl ObjectBody {
+  async fn next_chunk(&mut self) -> RuntimeResult<Option<Vec<u8>>> {
+    match self {
+      Self::File(file) => {
+        let mut chunk = Vec::with_capacity(OBJECT_STREAM_CHUNK_SIZE);
+        file
+          .by_ref()
+          .take(OBJECT_STREAM_CHUNK_SIZE as u64)
+          .read_to_end(&mut chunk)
+          .map_err(|err| RuntimeError::io("StorageRuntime fs stream read failed", err))?;
+        Ok((!chunk.is_empty()).then_some(chunk))
+      }
+      Self::Buffered { body, offset } => {
+        if *offset >= body.len() {
+          return Ok(None);
+        }
+        let end = body.len().min(*offset + OBJECT_STREAM_CHUNK_SIZE);
+        let chunk = body[*offset..end].to_vec();
+        *offset = end;
+        Ok(Some(chunk))
+      }
+      Self::S3(stream) => stream.next_chunk().await.map_err(RuntimeError::from),
+    }
+  }
+}
+
+/// Pull-based object body returned by `StorageRuntime.openObjectStream`.
+#[napi_derive::napi]
+pub struct StorageObjectStream {
+  metadata: ObjectMetadata,
+  content_range: Option<ObjectContentRange>,
+  body: Mutex<Option<ObjectBody>>,
+}
+
+impl StorageObjectStream {
+  pub(super) fn new(metadata: ObjectMetadata, content_range: Option<ObjectContentRange>, body: ObjectBody) -> Self {
+    Self {
+      metadata,
+      content_range,
+      body: Mutex::new(Some(body)),
+    }
+  }
+
+  pub(super) fn buffered(object: ObjectGetResult) -> Self {
+    Self::new(
+      object.metadata,
+      object.content_range,
+      ObjectBody::Buffered {
+        body: object.body,
+        offset: 0,
+      },
+    )
+  }
+}
+
+#[napi_derive::napi]
+impl StorageObjectStream {
+  #[napi(getter)]
+  pub fn metadata(&self) -> RuntimeObjectMetadata {
+    self.metadata.clone().into()
+  }
+
+  #[napi(getter)]
+  pub fn content_range(&self) -> Option<RuntimeObjectContentRange> {
+    self.content_range.map(Into::into)
+  }
+
+  /// Resolves to the next chunk, or `null` once the body is exhausted.
+  #[napi]
+  pub async fn read(&self) -> napi::Result<Option<Buffer>> {
+    let mut guard = self.body.lock().await;
+    let Some(body) = guard.as_mut() else {
+      return Ok(None);
+    };
+    match body.next_chunk().await {
+      Ok(Some(chunk)) => Ok(Some(chunk.into())),
+      Ok(None) => {
+        guard.take();
+        Ok(None)
+      }
+      Err(err) => {
+        guard.take();
+        Err(to_napi_error(err))
+      }
+    }
+  }
+
+  /// Releases the underlying file or connection before the body is drained.
+  #[napi]
+  pub async fn close(&self) {
+    self.body.lock().await.take();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn buffered_stream_yields_bounded_chunks_then_ends() {
+    let body = (0..OBJECT_STREAM_CHUNK_SIZE * 2 + 10)
+      .map(|index| index as u8)
+      .collect::<Vec<_>>();
+    let stream = StorageObjectStream::buffered(ObjectGetResult {
+      body: body.clone(),
+      metadata: ObjectMetadata {
+        content_type: "application/octet-stream".to_string(),
+        content_length: body.len() as i64,
+        last_modified_ms: 0,
+        checksum_crc32: None,
+      },
+      content_range: None,
+    });
+
+    let mut chunks = Vec::new();
+    while let Some(chunk) = stream.read().await.unwrap() {
+      chunks.push(chunk.to_vec());
+    }
+    assert_eq!(
+      chunks.iter().map(Vec::len).collect::<Vec<_>>(),
+      vec![OBJECT_STREAM_CHUNK_SIZE, OBJECT_STREAM_CHUNK_SIZE, 10]
+    );
+    assert_eq!(chunks.concat(), body);
+    assert!(stream.read().await.unwrap().is_none());
+  }
+}
@@ -0,0 +1,545 @@
+//! Soft-delete retention for storage scopes.
+//!
+//! When a storage config sets `softDelete.retentionSeconds`, deleting an
+//! object moves it to `.tombstones/{key}/{deletedAtMs}` instead of removing
+//! it. Every delete of the same key keeps its own tombstone, so earlier
+//! versions stay restorable until `purgeExpiredTombstones` drops the ones
+//! older than the retention window.
+
+use std::time::SystemTime;
+
+use serde::Deserialize;
+
+use super::{
+  ObjectDeleteOutcome, ObjectListEntry, ObjectMetadata, ObjectPutMetadata, RuntimeError, RuntimeObjectMetadata,
+  RuntimeObjectTombstone, RuntimeResult, RuntimeTombstonePurgeResult, StorageBackendConfig, StorageRuntime, assetpack,
+  checksum_crc32_base64, fs_head, fs_list, key_checkpoint::KeyCheckpointKind, napi_error, system_time_ms,
+};
+
+pub(super) const TOMBSTONE_PREFIX: &str = ".tombstones/";
+/// Zero-padded so tombstones of one key list in deletion order.
+const DELETED_AT_WIDTH: usize = 13;
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct SoftDeleteConfig {
+  retention_seconds: u64,
+}
+
+impl SoftDeleteConfig {
+  fn expires_at_ms(&self, deleted_at_ms: i64) -> i64 {
+    let retention_ms = i64::try_from(self.retention_seconds.saturating_mul(1000)).unwrap_or(i64::MAX);
+    deleted_at_ms.saturating_add(retention_ms)
+  }
+
+  fn is_expired(&self, deleted_at_ms: i64, now_ms: i64) -> bool {
+    self.expires_at_ms(deleted_at_ms) <= now_ms
+  }
+}
+
+/// Tombstones are hidden from regular listings.
+pub(super) fn is_tombstone_key(key: &str) -> bool {
+  key.starts_with(TOMBSTONE_PREFIX)
+}
+
+fn tombstone_key(key: &str, deleted_at_ms: i64) -> String {
+  format!(
+    "{TOMBSTONE_PREFIX}{key}/{deleted_at_ms:0width$}",
+    width = DELETED_AT_WIDTH
+  )
+}
+
+fn parse_tombstone_key(tombstone_key: &str) -> Option<(&str, i64)> {
+  let (key, deleted_at) = tombstone_key.strip_prefix(TOMBSTONE_PREFIX)?.rsplit_once('/')?;
+  if key.is_empty() || deleted_at.len() != DELETED_AT_WIDTH || !deleted_at.bytes().all(|byte| byte.is_ascii_digit()) {
+    return None;
+  }
+  Some((key, deleted_at.parse().ok()?))
+}
+
+fn put_metadata(metadata: &ObjectMetadata, body: &[u8]) -> ObjectPutMetadata {
+  ObjectPutMetadata {
+    content_type: Some(metadata.content_type.clone()),
+    content_length: Some(metadata.content_length),
+    checksum_crc32: Some(checksum_crc32_base64(body)),
+  }
+}
+
+async fn head(backend: &StorageBackendConfig, scope: &str, key: &str) -> RuntimeResult<Option<ObjectMetadata>> {
+  match backend {
+    StorageBackendConfig::Fs(config) => fs_head(config, key),
+    StorageBackendConfig::Assetpack(config) => assetpack::head(config, scope, key).await,
+    StorageBackendConfig::S3(config) => config.build_client()?.head(key).await.map_err(Into::into),
+  }
+}
+
+/// Tombstones of `key`, oldest first.
+async fn list_tombstones(
+  backend: &StorageBackendConfig,
+  scope: &str,
+  key: &str,
+) -> RuntimeResult<Vec<(i64, ObjectListEntry)>> {
+  let prefix = format!("{TOMBSTONE_PREFIX}{key}/");
+  let entries = match backend {
+    StorageBackendConfig::Fs(config) => fs_list(config, Some(prefix))?,
+    StorageBackendConfig::Assetpack(config) => assetpack::list(config, scope, Some(prefix)).await?,
+    StorageBackendConfig::S3(config) => config.build_client()?.list(Some(prefix)).await?,
+  };
+  let mut tombstones = entries
+    .into_iter()
+    .filter_map(|entry| match parse_tombstone_key(&entry.key) {
+      // The prefix also matches tombstones of keys nested below `key`.
+      Some((tombstoned, deleted_at_ms)) if tombstoned == key => Some((deleted_at_ms, entry)),
+      _ => None,
+    })
+    .collect::<Vec<_>>();
+  tombstones.sort_by_key(|(deleted_at_ms, _)| *deleted_at_ms);
+  Ok(tombstones)
+}
+
+/// Drops tombstones past retention, which purge deletes and restore ignores,
+/// so they stay unlisted even before the purge gets to them.
+fn unexpired(
+  tombstones: Vec<(i64, ObjectListEntry)>,
+  soft_delete: Option<SoftDeleteConfig>,
+  now_ms: i64,
+) -> Vec<(i64, ObjectListEntry)> {
+  tombstones
+    .into_iter()
+    .filter(|(deleted_at_ms, _)| !soft_delete.is_some_and(|soft_delete| soft_delete.is_expired(*deleted_at_ms, now_ms)))
+    .collect()
+}
+
+/// Writes the tombstone before removing the live object, so a failure in
+/// between leaves a restorable duplicate rather than nothing.
+async fn soft_delete(backend: &StorageBackendConfig, scope: &str, key: &str, deleted_at_ms: i64) -> RuntimeResult<()> {
+  let Some(object) = backend.get_object(scope, key).await? else {
+    return Ok(());
+  };
+  let metadata = put_metadata(&object.metadata, &object.body);
+  backend
+    .put_object(scope, &tombstone_key(key, deleted_at_ms), object.body, metadata)
+    .await?;
+  backend.delete_object(scope, key).await
+}
+
+async fn restore(
+  backend: &StorageBackendConfig,
+  scope: &str,
+  key: &str,
+  deleted_at_ms: Option<i64>,
+  soft_delete: Option<SoftDeleteConfig>,
+  now_ms: i64,
+) -> RuntimeResult<Option<ObjectMetadata>> {
+  if is_tombstone_key(key) {
+    return Err(RuntimeError::invalid_input(format!(
+      "StorageRuntime cannot restore tombstone key {key}"
+    )));
+  }
+  if head(backend, scope, key).await?.is_some() {
+    return Err(RuntimeError::invalid_input(format!(
+      "StorageRuntime cannot restore {key} over an existing object"
+    )));
+  }
+  let tombstones = unexpired(list_tombstones(backend, scope, key).await?, soft_delete, now_ms);
+  let tombstone = match deleted_at_ms {
+    Some(deleted_at_ms) => tombstones.iter().find(|(candidate, _)| *candidate == deleted_at_ms),
+    None => tombstones.last(),
+  };
+  let Some((_, tombstone)) = tombstone else {
+    return Ok(None);
+  };
+  let Some(object) = backend.get_object(scope, &tombstone.key).await? else {
+    return Ok(None);
+  };
+  let metadata = put_metadata(&object.metadata, &object.body);
+  let restored = backend.put_object(scope, key, object.body, metadata).await?;
+  backend.delete_object(scope, &tombstone.key).await?;
+  Ok(Some(restored))
+}
+
+/// Deletes expired tombstones among the next `limit` keys after `start_after`.
+async fn purge_tombstones(
+  backend: &StorageBackendConfig,
+  scope: &str,
+  soft_delete: SoftDeleteConfig,
+  now_ms: i64,
+  start_after: Option<String>,
+  limit: usize,
+) -> RuntimeResult<RuntimeTombstonePurgeResult> {
+  let (entries, has_more) = backend
+    .list_after(scope, TOMBSTONE_PREFIX.to_string(), start_after, limit)
+    .await?;
+  let mut result = RuntimeTombstonePurgeResult {
+    scanned: 0,
+    purged: 0,
+    retained: 0,
+    failed: 0,
+    next_cursor: None,
+  };
+  for entry in &entries {
+    result.scanned += 1;
+    let expired =
+      parse_tombstone_key(&entry.key).is_some_and(|(_, deleted_at_ms)| soft_delete.is_expired(deleted_at_ms, now_ms));
+    if !expired {
+      result.retained += 1;
+      continue;
+    }
+    match backend.delete_object(scope, &entry.key).await {
+      Ok(()) => result.purged += 1,
+      Err(_) => result.failed += 1,
+    }
+  }
+  if has_more {
+    result.next_cursor = entries.last().map(|entry| entry.key.clone());
+  }
+  Ok(result)
+}
+
+/// Unlike the one-off migration checkpoints, a completed pass clears
+/// `last_key` so the next housekeeping run starts from the beginning.
+const PURGE_CHECKPOINT: KeyCheckpointKind = KeyCheckpointKind {
+  kind: "tombstone_purge",
+  label: "Tombstone purge",
+  restart_when_completed: true,
+};
+
+impl StorageRuntime {
+  /// Follows the same `blob` fallback as `backend_for_scope`.
+  fn soft_delete_for_scope(&self, scope: &str) -> RuntimeResult<Option<SoftDeleteConfig>> {
+    let config = self.config()?;
+    let scope = if config.backends.contains_key(scope) {
+      scope
+    } else {
+      "blob"
+    };
+    Ok(config.soft_delete.get(scope).copied())
+  }
+
+  /// Moves `key` into a tombstone when the scope retains deleted objects.
+  /// Returns `false` when the caller should delete permanently instead,
+  /// including for keys that already are tombstones.
+  pub(super) async fn soft_delete_object(&self, scope: &str, key: &str) -> RuntimeResult<bool> {
+    if is_tombstone_key(key) || self.soft_delete_for_scope(scope)?.is_none() {
+      return Ok(false);
+    }
+    let backend = self.backend_for_scope(scope)?;
+    soft_delete(&backend, scope, key, system_time_ms(SystemTime::now())?).await?;
+    Ok(true)
+  }
+
+  /// `None` when the scope deletes permanently.
+  pub(super) async fn soft_delete_many(
+    &self,
+    scope: &str,
+    keys: Vec<String>,
+  ) -> RuntimeResult<Option<Vec<ObjectDeleteOutcome>>> {
+    if self.soft_delete_for_scope(scope)?.is_none() {
+      return Ok(None);
+    }
+    let backend = self.backend_for_scope(scope)?;
+    let deleted_at_ms = system_time_ms(SystemTime::now())?;
+    let mut outcomes = Vec::with_capacity(keys.len());
+    for key in keys {
+      let result = if is_tombstone_key(&key) {
+        backend.delete_object(scope, &key).await
+      } else {
+        soft_delete(&backend, scope, &key, deleted_at_ms).await
+      };
+      let error = result.err().map(|err| err.to_string());
+      outcomes.push(ObjectDeleteOutcome { key, error });
+    }
+    Ok(Some(outcomes))
+  }
+}
+
+#[napi_derive::napi]
+impl StorageRuntime {
+  /// Deleted versions of `key` still within retention, oldest first.
+  #[napi]
+  pub async fn list_object_versions(&self, scope: String, key: String) -> napi::Result<Vec<RuntimeObjectTombstone>> {
+    let backend = self.backend_for_scope(&scope)?;
+    let soft_delete = self.soft_delete_for_scope(&scope)?;
+    let tombstones = unexpired(
+      list_tombstones(&backend, &scope, &key).await?,
+      soft_delete,
+      system_time_ms(SystemTime::now())?,
+    );
+    Ok(
+      tombstones
+        .into_iter()
+        .map(|(deleted_at_ms, entry)| RuntimeObjectTombstone {
+          key: key.clone(),
+          deleted_at_ms,
+          expires_at_ms: soft_delete.map(|soft_delete| soft_delete.expires_at_ms(deleted_at_ms)),
+          content_length: entry.content_length,
+        })
+        .collect(),
+    )
+  }
+
+  /// Restores the latest deleted version of `key` still within retention, or
+  /// the one deleted at `deletedAtMs`. Resolves to `null` when no such
+  /// tombstone exists; fails if `key` currently holds an object.
+  #[napi]
+  pub async fn restore_object(
+    &self,
+    scope: String,
+    key: String,
+    deleted_at_ms: Option<i64>,
+  ) -> napi::Result<Option<RuntimeObjectMetadata>> {
+    let backend = self.backend_for_scope(&scope)?;
+    let soft_delete = self.soft_delete_for_scope(&scope)?;
+    let now_ms = system_time_ms(SystemTime::now())?;
+    let restored = restore(&backend, &scope, &key, deleted_at_ms, soft_delete, now_ms).await?;
+    Ok(restored.map(Into::into))
+  }
+
+  /// Permanently deletes tombstones older than the scope's retention window,
+  /// scanning `limit` tombstones per call. Call until `nextCursor` is `null`;
+  /// the following call starts a new pass.
+  #[napi]
+  pub async fn purge_expired_tombstones(&self, scope: String, limit: i64) -> napi::Result<RuntimeTombstonePurgeResult> {
+    if limit <= 0 {
+      return Err(napi_error("tombstone purge limit must be positive"));
+    }
+    let Some(soft_delete) = self.soft_delete_for_scope(&scope)? else {
+      return Err(napi_error(format!("soft delete is not configured for scope {scope}")));
+    };
+    let backend = self.backend_for_scope(&scope)?;
+
+    let pool = self.pool().await?;
+    let last_key = PURGE_CHECKPOINT.load(&pool, &scope).await?.last_key;
+    let result = purge_tombstones(
+      &backend,
+      &scope,
+      soft_delete,
+      system_time_ms(SystemTime::now())?,
+      last_key,
+      limit as usize,
+    )
+    .await?;
+    PURGE_CHECKPOINT
+      .save(
+        &pool,
+        &scope,
+        result.next_cursor.as_deref(),
+        &[],
+        result.next_cursor.is_none(),
+      )
+      .await?;
+
+    PURGE_CHECKPOINT
+      .record_run(
+        &pool,
+        None,
+        (result.scanned, result.purged, result.failed),
+        serde_json::json!({
+          "scope": scope,
+          "retained": result.retained,
+          "nextCursor": result.next_cursor,
+        }),
+      )
+      .await?;
+
+    Ok(result)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::RwLock;
+
+  use tokio::sync::Mutex;
+
+  use super::*;
+  use crate::runtime::storage_runtime::{
+    FsStorageConfig, StorageRuntimeConfig, object_storage::mock_server::MockObjectStorageServer,
+  };
+
+  fn fs_backend(provider: &str, root: &std::path::Path) -> StorageBackendConfig {
+    let config = FsStorageConfig {
+      provider: provider.to_string(),
+      root: root.to_string_lossy().to_string(),
+      bucket: "bucket".to_string(),
+      encryption: None,
+    };
+    match provider {
+      "assetpack" => StorageBackendConfig::Assetpack(config),
+      _ => StorageBackendConfig::Fs(config),
+    }
+  }
+
+  #[test]
+  fn tombstone_keys_round_trip_and_sort_by_deletion_time() {
+    let key = tombstone_key("workspace/blob", 42);
+    assert_eq!(key, ".tombstones/workspace/blob/0000000000042");
+    assert_eq!(parse_tombstone_key(&key), Some(("workspace/blob", 42)));
+    assert!(tombstone_key("a", 9) < tombstone_key("a", 10));
+    assert_eq!(parse_tombstone_key(".tombstones/workspace/blob"), None);
+    assert_eq!(parse_tombstone_key("workspace/blob/0000000000042"), None);
+  }
+
+  #[tokio::test]
+  async fn soft_deleted_objects_restore_and_purge_on_every_backend() -> anyhow::Result<()> {
+    let temp = tempfile::tempdir()?;
+    let server = MockObjectStorageServer::start()?;
+    let retention = SoftDeleteConfig { retention_seconds: 60 };
+    let scope = format!("test_{}", uuid::Uuid::new_v4().simple());
+    let backends = [
+      fs_backend("fs", &temp.path().join("fs")),
+      fs_backend("assetpack", &temp.path().join("assetpack")),
+      StorageBackendConfig::S3(server.storage_config("tombstones")),
+    ];
+
+    for backend in &backends {
+      let key = "workspace/blob";
+      for (deleted_at_ms, body) in [(1_000, b"first".to_vec()), (2_000, b"second".to_vec())] {
+        backend
+          .put_object(
+            &scope,
+            key,
+            body,
+            ObjectPutMetadata {
+              content_type: Some("text/plain".to_string()),
+              ..Default::default()
+            },
+          )
+          .await?;
+        soft_delete(backend, &scope, key, deleted_at_ms).await?;
+        assert!(backend.get_object(&scope, key).await?.is_none());
+      }
+      backend
+        .put_object(
+          &scope,
+          "workspace/other",
+          b"other".to_vec(),
+          ObjectPutMetadata::default(),
+        )
+        .await?;
+      soft_delete(backend, &scope, "workspace/other", 1_000).await?;
+      assert_eq!(
+        list_tombstones(backend, &scope, key)
+          .await?
+          .iter()
+          .map(|(deleted_at_ms, _)| *deleted_at_ms)
+          .collect::<Vec<_>>(),
+        [1_000, 2_000]
+      );
+
+      let now_ms = retention.expires_at_ms(1_500);
+      assert_eq!(
+        unexpired(list_tombstones(backend, &scope, key).await?, Some(retention), now_ms)
+          .iter()
+          .map(|(deleted_at_ms, _)| *deleted_at_ms)
+          .collect::<Vec<_>>(),
+        [2_000]
+      );
+      assert!(
+        restore(backend, &scope, key, Some(1_000), Some(retention), now_ms)
+          .await?
+          .is_none()
+      );
+
+      let restored = restore(backend, &scope, key, Some(1_000), Some(retention), 0)
+        .await?
+        .unwrap();
+      assert_eq!(restored.content_type, "text/plain");
+      assert_eq!(backend.get_object(&scope, key).await?.unwrap().body, b"first");
+      assert!(restore(backend, &scope, key, None, Some(retention), 0).await.is_err());
+      assert!(
+        restore(backend, &scope, key, Some(3_000), Some(retention), 0)
+          .await?
+          .is_none()
+      );
+
+      let first = purge_tombstones(backend, &scope, retention, now_ms, None, 1).await?;
+      assert_eq!((first.scanned, first.purged, first.retained), (1, 0, 1));
+      let rest = purge_tombstones(backend, &scope, retention, now_ms, first.next_cursor, 10).await?;
+      assert_eq!((rest.scanned, rest.purged, rest.retained), (1, 1, 0));
+      assert!(rest.next_cursor.is_none());
+      assert!(list_tombstones(backend, &scope, "workspace/other").await?.is_empty());
+
+      // Tombstones of nested keys share the prefix but are not versions of `key`.
+      backend.delete_object(&scope, key).await?;
+      backend
+        .put_object(
+          &scope,
+          "workspace/blob/nested",
+          b"nested".to_vec(),
+          ObjectPutMetadata::default(),
+        )
+        .await?;
+      soft_delete(backend, &scope, "workspace/blob/nested", 500).await?;
+      assert_eq!(list_tombstones(backend, &scope, key).await?.len(), 1);
+    }
+    Ok(())
+  }
+
+  #[tokio::test]
+  async fn runtime_deletes_through_tombstones_when_soft_delete_is_configured() {
+    let temp = tempfile::tempdir().unwrap();
+    let app_config = serde_json::from_value(serde_json::json!({
+      "storages": {
+        "blob.storage": {
+          "provider": "fs",
+          "bucket": "blobs",
+          "config": { "path": temp.path() },
+          "softDelete": { "retentionSeconds": 3600 },
+        },
+      },
+    }))
+    .unwrap();
+    let runtime = StorageRuntime {
+      config: RwLock::new(StorageRuntimeConfig::from_app_config_file(app_config).unwrap()),
+      pool: Mutex::new(None),
+    };
+    let key = "workspace/blob".to_string();
+    runtime
+      .put_object("blob".to_string(), key.clone(), b"body".to_vec().into(), None)
+      .await
+      .unwrap();
+
+    // `avatar` is not configured and falls back to the `blob` storage.
+    runtime.delete_object("avatar".to_string(), key.clone()).await.unwrap();
+    assert!(runtime.list_objects("blob".to_string(), None).await.unwrap().is_empty());
+    let versions = runtime
+      .list_object_versions("blob".to_string(), key.clone())
+      .await
+      .unwrap();
+    assert_eq!(versions.len(), 1);
+    assert_eq!(versions[0].expires_at_ms, Some(versions[0].deleted_at_ms + 3_600_000));
+
+    let restored = runtime
+      .restore_object("blob".to_string(), key.clone(), None)
+      .await
+      .unwrap()
+      .unwrap();
+    assert_eq!(restored.content_length, 4);
+    assert!(
+      runtime
+        .list_object_versions("blob".to_string(), key.clone())
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    let outcomes = runtime.object_storage_delete_many(vec![key.clone()]).await.unwrap();
+    assert!(outcomes.iter().all(|outcome| outcome.error.is_none()));
+
+    // A tombstone past retention is not listed even before a purge drops it.
+    let backend = runtime.backend_for_scope("blob").unwrap();
+    backend
+      .put_object("blob", &key, b"expired".to_vec(), ObjectPutMetadata::default())
+      .await
+      .unwrap();
+    soft_delete(&backend, "blob", &key, 1_000).await.unwrap();
+    assert_eq!(
+      runtime
+        .list_object_versions("blob".to_string(), key)
+        .await
+        .unwrap()
+        .len(),
+      1
+    );
+  }
+}
@@ -14,6 +14,22 @@ pub struct BackendRuntimeHealth {
   pub database_connected: bool,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeMigrationStatus {
+  pub version: i64,
+  pub description: String,
+  /// One of `applied`, `pending`, `failed` or `checksum_mismatch`.
+  pub state: String,
+  pub reversible: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeMigrationPlan {
+  pub current_version: i64,
+  pub pending: i64,
+  pub migrations: Vec<RuntimeMigrationStatus>,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeQuotaTargetDomainInput {
   pub domain: String,
@@ -116,6 +132,7 @@ pub struct RuntimeMailDeliveryQuotaDecision {
 }
 
 #[napi_derive::napi(object)]
+#[derive(Clone)]
 pub struct CoordinationLeaseGrant {
   pub key: String,
   pub owner: String,
@@ -145,6 +162,44 @@ pub struct RuntimeByokLocalLeaseRecord {
   pub expires_at_ms: i64,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeRefreshTokenFamilyRecord {
+  pub token: String,
+  pub family_id: String,
+  pub generation: i64,
+  pub user_id: String,
+  pub auth_session_id: String,
+  pub expires_at_ms: i64,
+  pub family_expires_at_ms: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeRefreshTokenRotateResult {
+  pub ok: bool,
+  /// One of `invalid`, `expired`, `revoked` or `reused` when `ok` is false.
+  pub reason: Option<String>,
+  pub family_id: Option<String>,
+  pub user_id: Option<String>,
+  pub auth_session_id: Option<String>,
+  pub refresh_token: Option<RuntimeRefreshTokenFamilyRecord>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeChallengeVerifyResult {
+  pub ok: bool,
+  /// One of `invalid`, `expired`, `future` or `replayed` when `ok` is false.
+  pub reason: Option<String>,
+  /// When the accepted stamp stops being valid.
+  pub expires_at_ms: Option<i64>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeDocHistoryDeltaMigrationResult {
+  pub scanned_docs: i64,
+  pub converted_rows: i64,
+  pub next_cursor: Option<String>,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeDocHistoryInput {
   pub workspace_id: String,
@@ -179,10 +234,21 @@ pub struct RuntimeObjectListEntry {
   pub last_modified_ms: i64,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeObjectContentRange {
+  pub start: i64,
+  /// Inclusive, like the `Content-Range` header.
+  pub end: i64,
+  pub total: i64,
+  /// Ready-to-send `Content-Range` header value.
+  pub header: String,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeObjectGetResult {
   pub body: Buffer,
   pub metadata: RuntimeObjectMetadata,
+  pub content_range: Option<RuntimeObjectContentRange>,
 }
 
 #[napi_derive::napi(object)]
@@ -233,6 +299,45 @@ pub struct RuntimeBlobMetadataBackfillResult {
   pub workspace_ids: Vec<String>,
 }
 
+#[napi_derive::napi(object)]
+pub struct RuntimeStorageEncryptionRotationResult {
+  pub scanned: i64,
+  pub rewrapped: i64,
+  pub encrypted: i64,
+  pub skipped: i64,
+  pub failed: i64,
+  pub next_cursor: Option<String>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeBlobMigrationResult {
+  pub scanned: i64,
+  pub copied: i64,
+  pub skipped_existing: i64,
+  pub skipped_missing: i64,
+  pub deleted_source: i64,
+  pub failed: i64,
+  pub next_cursor: Option<String>,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeObjectTombstone {
+  pub key: String,
+  pub deleted_at_ms: i64,
+  /// `None` once soft delete is no longer configured for the scope.
+  pub expires_at_ms: Option<i64>,
+  pub content_length: i64,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeTombstonePurgeResult {
+  pub scanned: i64,
+  pub purged: i64,
+  pub retained: i64,
+  pub failed: i64,
+  pub next_cursor: Option<String>,
+}
+
 #[napi_derive::napi(object)]
 pub struct RuntimeDocBlobRefsResult {
   pub scanned_docs: i64,
@@ -272,6 +377,30 @@ pub struct RuntimeDocCompactionResult {
   pub doc_id: String,
   pub updates_merged: i64,
   pub history_created: bool,
+  pub diverged: bool,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeDocCompactionFailure {
+  pub doc_id: String,
+  pub error: String,
+}
+
+#[napi_derive::napi(object)]
+pub struct RuntimeDocCompactionScheduleResult {
+  pub workspace_id: String,
+  pub scanned_docs: i64,
+  pub compacted_docs: i64,
+  pub skipped_docs: i64,
+  pub failed_docs: i64,
+  /// Why each of the `failed_docs` failed.
+  pub failures: Vec<RuntimeDocCompactionFailure>,
+  pub diverged_docs: i64,
+  pub updates_merged: i64,
+  pub histories_created: i64,
+  pub backlog_docs: i64,
+  pub backlog_updates: i64,
+  pub oldest_update_age_ms: Option<i64>,
 }
 
 #[napi_derive::napi(object)]
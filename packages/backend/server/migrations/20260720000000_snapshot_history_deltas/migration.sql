@@ -0,0 +1,5 @@
+-- AlterTable
+ALTER TABLE "snapshot_histories" ADD COLUMN     "base_timestamp" TIMESTAMPTZ(3);
+
+-- CreateIndex
+CREATE INDEX "snapshot_histories_workspace_id_guid_base_timestamp_idx" ON "snapshot_histories"("workspace_id", "guid", "base_timestamp");
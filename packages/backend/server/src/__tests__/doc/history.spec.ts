@@ -231,7 +231,7 @@ test('should be able to get history data', async t => {
   );
 
   t.truthy(history);
-  t.deepEqual(history?.bin, snapshot.blob);
+  t.deepEqual(Buffer.from(history!.bin), Buffer.from(snapshot.blob));
 });
 
 test('should be able to get last history record', async t => {
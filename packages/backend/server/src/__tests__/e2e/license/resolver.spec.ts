@@ -4,6 +4,7 @@ import { join } from 'node:path';
 import { installLicenseMutation, SubscriptionVariant } from '@affine/graphql';
 import { PrismaClient } from '@prisma/client';
 
+import { Config } from '../../../base';
 import { Workspace, WorkspaceRole } from '../../../models';
 import { LicenseService } from '../../../plugins/license/service';
 import {
@@ -131,6 +132,51 @@ e2e('should install file license', async t => {
   t.true((entitlement.validatedAt?.getTime() ?? 0) > staleAt.getTime());
 });
 
+e2e('should revalidate license files offline', async t => {
+  const config = app.get(Config);
+  config.entitlement.license.offline = true;
+  t.teardown(() => {
+    config.entitlement.license.offline = false;
+  });
+
+  await app.gql({
+    query: installLicenseMutation,
+    variables: {
+      workspaceId: workspace.id,
+      license: licenses.valid,
+    },
+  });
+  const db = app.get(PrismaClient);
+  const installed = await db.installedLicense.findUniqueOrThrow({
+    where: { workspaceId: workspace.id },
+  });
+  const staleAt = new Date(0);
+  await db.installedLicense.update({
+    where: { key: installed.key },
+    data: { validatedAt: staleAt },
+  });
+
+  await app.get(LicenseService).licensesHealthCheck();
+
+  const revalidated = await db.installedLicense.findUniqueOrThrow({
+    where: { key: installed.key },
+  });
+  t.true(revalidated.validatedAt.getTime() > staleAt.getTime());
+  const entitlement = await db.entitlement.findFirstOrThrow({
+    where: {
+      source: 'selfhost_license',
+      targetType: 'workspace',
+      targetId: workspace.id,
+    },
+  });
+  t.is(entitlement.status, 'active');
+
+  await t.throwsAsync(
+    app.get(LicenseService).activateTeamLicense(workspace.id, 'license-key'),
+    { message: /offline license mode/ }
+  );
+});
+
 e2e('should not allow to install license if not owner', async t => {
   const user = await app.signup();
   await app.create(Mockers.WorkspaceUser, {
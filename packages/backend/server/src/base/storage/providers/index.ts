@@ -8,6 +8,10 @@ export type StorageProviderName =
 
 export interface FsStorageConfig {
   path: string;
+  encryption?: {
+    activeKeyId: string;
+    keys: Record<string, string>;
+  };
 }
 
 export type AssetpackStorageConfig = FsStorageConfig;
@@ -41,7 +45,14 @@ export interface R2StorageConfig extends Omit<S3StorageConfig, 'endpoint'> {
   };
 }
 
-export type StorageProviderConfig = { bucket: string } & (
+export interface StorageSoftDeleteConfig {
+  retentionSeconds: number;
+}
+
+export type StorageProviderConfig = {
+  bucket: string;
+  softDelete?: StorageSoftDeleteConfig;
+} & (
   | {
       provider: 'fs';
       config: FsStorageConfig;
@@ -129,6 +140,35 @@ const S3ConfigPropertiesWithoutEndpoint = Object.fromEntries(
   ).filter(([key]) => key !== 'endpoint')
 ) as Record<string, JSONSchema>;
 
+const FsEncryptionSchema: JSONSchema = {
+  type: 'object',
+  description:
+    'Encrypt stored blobs at rest with AES-256-GCM. New blobs use `activeKeyId`; other keys stay readable until rotated.',
+  properties: {
+    activeKeyId: {
+      type: 'string',
+    },
+    keys: {
+      type: 'object',
+      description: 'Key id to key, e.g. {"2026-01": "<64 hex chars>"}.',
+    },
+  },
+  required: ['activeKeyId', 'keys'],
+};
+
+const SoftDeleteSchema: JSONSchema = {
+  type: 'object',
+  description:
+    'Keep deleted blobs restorable under `.tombstones/` until the retention window passes.',
+  properties: {
+    retentionSeconds: {
+      type: 'number',
+      description: 'How long deleted blobs stay restorable.',
+    },
+  },
+  required: ['retentionSeconds'],
+};
+
 export const StorageJSONSchema: JSONSchema = {
   oneOf: [
     {
@@ -141,12 +181,14 @@ export const StorageJSONSchema: JSONSchema = {
         bucket: {
           type: 'string',
         },
+        softDelete: SoftDeleteSchema,
         config: {
           type: 'object',
           properties: {
             path: {
               type: 'string',
             },
+            encryption: FsEncryptionSchema,
           },
         },
       },
@@ -161,6 +203,7 @@ export const StorageJSONSchema: JSONSchema = {
         bucket: {
           type: 'string',
         },
+        softDelete: SoftDeleteSchema,
         config: S3ConfigSchema,
       },
     },
@@ -174,6 +217,7 @@ export const StorageJSONSchema: JSONSchema = {
         bucket: {
           type: 'string',
         },
+        softDelete: SoftDeleteSchema,
         config: {
           ...S3ConfigSchema,
           properties: {
@@ -225,12 +269,14 @@ export const StorageJSONSchema: JSONSchema = {
         bucket: {
           type: 'string',
         },
+        softDelete: SoftDeleteSchema,
         config: {
           type: 'object',
           properties: {
             path: {
               type: 'string',
             },
+            encryption: FsEncryptionSchema,
           },
           required: ['path'],
         },
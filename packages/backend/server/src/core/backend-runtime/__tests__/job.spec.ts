@@ -16,6 +16,7 @@ interface Context {
     cleanupExpiredRuntimeStates: Sinon.SinonStub;
     cleanupExpiredRuntimeGates: Sinon.SinonStub;
     cleanupExpiredRollingQuota: Sinon.SinonStub;
+    cleanupExpiredDocCompactionDiagnostics: Sinon.SinonStub;
   };
 }
 
@@ -26,6 +27,7 @@ test.before(async t => {
     cleanupExpiredRuntimeStates: Sinon.stub(),
     cleanupExpiredRuntimeGates: Sinon.stub(),
     cleanupExpiredRollingQuota: Sinon.stub(),
+    cleanupExpiredDocCompactionDiagnostics: Sinon.stub(),
   };
   t.context.module = await createTestingModule({
     imports: [ScheduleModule.forRoot(), BackendRuntimeModule],
@@ -42,6 +44,7 @@ test.beforeEach(t => {
   t.context.runtime.cleanupExpiredRuntimeStates.reset();
   t.context.runtime.cleanupExpiredRuntimeGates.reset();
   t.context.runtime.cleanupExpiredRollingQuota.reset();
+  t.context.runtime.cleanupExpiredDocCompactionDiagnostics.reset();
 });
 
 test.after.always(async t => {
@@ -53,10 +56,15 @@ test('backend-runtime housekeeping cleans runtime state and gate batches', async
   t.context.runtime.cleanupExpiredRuntimeStates.onCall(1).resolves(2);
   t.context.runtime.cleanupExpiredRuntimeGates.resolves(1);
   t.context.runtime.cleanupExpiredRollingQuota.resolves(1);
+  t.context.runtime.cleanupExpiredDocCompactionDiagnostics.resolves(3);
 
   await t.context.job.cleanExpiredRuntimeHousekeeping();
 
   t.is(t.context.runtime.cleanupExpiredRuntimeStates.callCount, 2);
   t.is(t.context.runtime.cleanupExpiredRuntimeGates.callCount, 1);
   t.is(t.context.runtime.cleanupExpiredRollingQuota.callCount, 1);
+  t.deepEqual(
+    t.context.runtime.cleanupExpiredDocCompactionDiagnostics.firstCall.args,
+    [1000]
+  );
 });
@@ -41,9 +41,12 @@ export class BackendRuntimeHousekeepingJob {
     const rollingQuota = await this.cleanBatches(() =>
       this.rt.cleanupExpiredRollingQuota(1000)
     );
+    const compactionDiagnostics = await this.cleanBatches(() =>
+      this.rt.cleanupExpiredDocCompactionDiagnostics(1000)
+    );
 
     this.logger.log(
-      `cleaned runtime housekeeping states=${states} gates=${gates} rollingQuota=${rollingQuota}`
+      `cleaned runtime housekeeping states=${states} gates=${gates} rollingQuota=${rollingQuota} compactionDiagnostics=${compactionDiagnostics}`
     );
   }
 
@@ -6,7 +6,11 @@ import {
 } from '@nestjs/common';
 
 import { wrapCallMetric } from '../../base/metrics';
-import { BackendRuntime, type BackendRuntimeHealth } from '../../native';
+import {
+  BackendRuntime,
+  type BackendRuntimeHealth,
+  type RuntimeDocHistoryInput,
+} from '../../native';
 
 type RuntimeInstance = InstanceType<typeof BackendRuntime>;
 
@@ -229,6 +233,30 @@ export class BackendRuntimeProvider
     );
   }
 
+  async cleanupExpiredDocCompactionDiagnostics(limit: number) {
+    return await this.measured('cleanupExpiredDocCompactionDiagnostics', rt =>
+      rt.cleanupExpiredDocCompactionDiagnostics(limit)
+    );
+  }
+
+  async createDocHistory(input: RuntimeDocHistoryInput) {
+    return await this.measured('createDocHistory', rt =>
+      rt.createDocHistory(input)
+    );
+  }
+
+  async getDocHistory(workspaceId: string, docId: string, timestamp: number) {
+    return await this.measured('getDocHistory', rt =>
+      rt.getDocHistory(workspaceId, docId, timestamp)
+    );
+  }
+
+  async migrateDocHistoryDeltas(cursor: string | null, docLimit: number) {
+    return await this.measured('migrateDocHistoryDeltas', rt =>
+      rt.migrateDocHistoryDeltas(cursor, docLimit)
+    );
+  }
+
   async cleanupExpiredUserSessions(limit: number) {
     return await this.measured('cleanupExpiredUserSessions', rt =>
       rt.cleanupExpiredUserSessions(limit)
@@ -13,6 +13,7 @@ import {
 } from '../../../base';
 import { retryable } from '../../../base/utils/promise';
 import { Models } from '../../../models';
+import { BackendRuntimeProvider } from '../../backend-runtime';
 import { DocStorageOptions } from '../options';
 import {
   DocRecord,
@@ -46,7 +47,8 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
     private readonly mutex: Mutex,
     private readonly event: EventBus,
     protected override readonly options: DocStorageOptions,
-    private readonly queue: JobQueue
+    private readonly queue: JobQueue,
+    private readonly runtime: BackendRuntimeProvider
   ) {
     super(options);
   }
@@ -186,10 +188,20 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
       return null;
     }
 
+    // history rows may be deltas against a keyframe, the runtime merges them
+    const bin = await this.runtime.getDocHistory(
+      workspaceId,
+      docId,
+      timestamp
+    );
+    if (!bin) {
+      return null;
+    }
+
     return {
       spaceId: workspaceId,
       docId,
-      bin: history.blob,
+      bin,
       timestamp: history.timestamp,
       editor: history.editor?.id,
     };
@@ -279,16 +291,19 @@ export class PgWorkspaceDocStorageAdapter extends DocStorageAdapter {
         return false;
       }
 
-      await this.models.history.create(
-        {
-          spaceId: snapshot.spaceId,
-          docId: snapshot.docId,
-          timestamp: snapshot.timestamp,
-          blob: Buffer.from(snapshot.bin),
-          editorId: snapshot.editor,
-        },
-        historyMaxAge
-      );
+      const created = await this.runtime.createDocHistory({
+        workspaceId: snapshot.spaceId,
+        docId: snapshot.docId,
+        blob: Buffer.from(snapshot.bin),
+        timestampMs: snapshot.timestamp,
+        editorId: snapshot.editor,
+        force,
+        historyMinIntervalMs: this.options.historyMinInterval(snapshot.spaceId),
+        historyMaxAgeMs: historyMaxAge,
+      });
+      if (!created) {
+        return false;
+      }
 
       metrics.doc
         .counter('history_created_counter', {
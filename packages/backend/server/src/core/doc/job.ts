@@ -1,4 +1,4 @@
-import { Injectable } from '@nestjs/common';
+import { Injectable, Logger } from '@nestjs/common';
 import { Cron, CronExpression } from '@nestjs/schedule';
 
 import { JobQueue, OnJob } from '../../base';
@@ -7,11 +7,17 @@ import { BackendRuntimeProvider } from '../backend-runtime';
 declare global {
   interface Jobs {
     'nightly.cleanExpiredHistories': {};
+    'doc.migrateHistoryDeltas': {
+      cursor?: string;
+      docLimit?: number;
+    };
   }
 }
 
 @Injectable()
 export class DocStorageCronJob {
+  private readonly logger = new Logger(DocStorageCronJob.name);
+
   constructor(
     private readonly rt: BackendRuntimeProvider,
     private readonly queue: JobQueue
@@ -35,4 +41,32 @@ export class DocStorageCronJob {
       if (count < 1000) break;
     }
   }
+
+  async enqueueHistoryDeltaMigration(cursor?: string, docLimit = 100) {
+    await this.queue.add('doc.migrateHistoryDeltas', { cursor, docLimit });
+  }
+
+  /**
+   * Re-encodes full history rows written before delta encoding, one batch of
+   * docs per job, enqueueing the next batch until every doc is scanned.
+   */
+  @OnJob('doc.migrateHistoryDeltas')
+  async migrateHistoryDeltas({
+    cursor,
+    docLimit = 100,
+  }: Jobs['doc.migrateHistoryDeltas']) {
+    const result = await this.rt.migrateDocHistoryDeltas(
+      cursor ?? null,
+      docLimit
+    );
+    this.logger.verbose(
+      `doc history delta migration scanned=${result.scannedDocs} converted=${result.convertedRows}`
+    );
+
+    if (result.nextCursor) {
+      await this.enqueueHistoryDeltaMigration(result.nextCursor, docLimit);
+    } else {
+      this.logger.log('doc history delta migration finished');
+    }
+  }
 }
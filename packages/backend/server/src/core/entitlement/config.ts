@@ -0,0 +1,54 @@
+import { z } from 'zod';
+
+import { defineModuleConfig } from '../../base';
+
+export interface EntitlementPlanConfig {
+  blobLimit: number;
+  storageQuota: number;
+  historyPeriod: number;
+  memberLimit?: number;
+  seatQuota?: number;
+  copilotActionLimit?: number;
+  flags?: Record<string, boolean>;
+}
+
+declare global {
+  interface AppConfigSchema {
+    entitlement: {
+      plans: ConfigItem<Record<string, EntitlementPlanConfig>>;
+      license: {
+        offline: boolean;
+        gracePeriodDays: number;
+      };
+    };
+  }
+}
+
+defineModuleConfig('entitlement', {
+  plans: {
+    desc: 'Custom plans keyed by plan name. A plan named like a built-in plan replaces its quotas; sizes are in bytes and historyPeriod in seconds.',
+    default: {},
+    shape: z.record(
+      z
+        .object({
+          blobLimit: z.number().int().positive(),
+          storageQuota: z.number().int().nonnegative(),
+          historyPeriod: z.number().int().positive(),
+          memberLimit: z.number().int().positive().optional(),
+          seatQuota: z.number().int().positive().optional(),
+          copilotActionLimit: z.number().int().nonnegative().optional(),
+          flags: z.record(z.boolean()).optional(),
+        })
+        .strict()
+    ),
+  },
+  'license.offline': {
+    desc: 'Verify installed license files locally and never contact app.affine.pro. Licenses must be imported as files.',
+    default: false,
+  },
+  'license.gracePeriodDays': {
+    desc: 'Days an expired license file stays valid in offline license mode.',
+    default: 14,
+    shape: z.number().int().nonnegative(),
+  },
+});
@@ -1,3 +1,5 @@
+import './config';
+
 import { Module } from '@nestjs/common';
 
 import { EntitlementService } from './service';
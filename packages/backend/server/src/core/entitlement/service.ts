@@ -1,7 +1,7 @@
 import { Injectable } from '@nestjs/common';
 import { Entitlement, Prisma, PrismaClient } from '@prisma/client';
 
-import { BadRequest, CryptoHelper, EventBus } from '../../base';
+import { BadRequest, Config, CryptoHelper, EventBus } from '../../base';
 import { checkLicenseHealth, resolveEntitlementV1 } from '../../native';
 import {
   SubscriptionPlan,
@@ -71,6 +71,7 @@ export class EntitlementService {
 
   constructor(
     private readonly db: PrismaClient,
+    private readonly config: Config,
     private readonly crypto: CryptoHelper,
     private readonly event: EventBus
   ) {}
@@ -224,6 +225,7 @@ export class EntitlementService {
       targetId: input.targetId,
       plan: input.plan,
       quantity,
+      plans: this.config.entitlement.plans,
       now: new Date().toISOString(),
     });
 
@@ -300,6 +302,8 @@ export class EntitlementService {
           signedPayload: input.license,
           publicKey: this.crypto.AFFiNEProPublicKey?.toString(),
           licenseAesKey: this.crypto.AFFiNEProLicenseAESKey?.toString('hex'),
+          plans: this.config.entitlement.plans,
+          licenseGracePeriodDays: this.licenseGracePeriodDays(),
           now: new Date().toISOString(),
         })
       : null;
@@ -315,7 +319,11 @@ export class EntitlementService {
       targetId: input.workspaceId,
       source: 'selfhost_license',
       plan: 'selfhost_team',
-      status: valid ? 'active' : ('needs_reupload' as EntitlementStatus),
+      status: valid
+        ? resolved.status === 'grace'
+          ? 'grace'
+          : 'active'
+        : ('needs_reupload' as EntitlementStatus),
       subjectId,
       quantity: valid ? resolved.quantity : undefined,
       signedPayload: input.license ?? undefined,
@@ -331,6 +339,7 @@ export class EntitlementService {
       expiresAt:
         input.expiresAt ??
         (resolved?.expiresAt ? new Date(resolved.expiresAt) : undefined),
+      graceUntil: resolved?.graceUntil ? new Date(resolved.graceUntil) : null,
       validatedAt: input.validatedAt ?? new Date(),
     };
 
@@ -483,6 +492,7 @@ export class EntitlementService {
     return resolveEntitlementV1({
       deploymentType: env.selfhosted ? 'selfhosted' : 'cloud',
       targetType,
+      plans: this.config.entitlement.plans,
       now: new Date().toISOString(),
     });
   }
@@ -518,6 +528,8 @@ export class EntitlementService {
           : undefined,
         publicKey: this.crypto.AFFiNEProPublicKey?.toString(),
         licenseAesKey: this.crypto.AFFiNEProLicenseAESKey?.toString('hex'),
+        plans: this.config.entitlement.plans,
+        licenseGracePeriodDays: this.licenseGracePeriodDays(),
         now: new Date().toISOString(),
       });
     } catch (e) {
@@ -537,6 +549,15 @@ export class EntitlementService {
     }
   }
 
+  /**
+   * Expired license files only get a grace period in offline license mode;
+   * online licenses are renewed through app.affine.pro instead.
+   */
+  private licenseGracePeriodDays() {
+    const license = this.config.entitlement.license;
+    return license.offline ? license.gracePeriodDays : undefined;
+  }
+
   private findBySubject(source: string, subjectId: string) {
     return this.db.entitlement.findFirst({
       where: { source, subjectId },
@@ -697,6 +718,14 @@ export class EntitlementService {
       return null;
     }
 
+    if (this.config.entitlement.license.offline) {
+      await this.markRemoteSelfhostLicenseNeedsReupload(
+        entitlement,
+        'Offline license mode requires an imported license file.'
+      );
+      return null;
+    }
+
     const metadata = entitlement.metadata as {
       validateKey?: string | null;
       variant?: string | null;
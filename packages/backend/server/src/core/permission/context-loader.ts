@@ -113,6 +113,7 @@ export class PermissionContextLoader {
         readonlyReason: runtime.readonlyReasons[0],
         sharingEnabled,
         urlPreviewEnabled,
+        now: Date.now(),
       },
       workspace: {
         role: member?.role,
@@ -0,0 +1,15 @@
+import { ModuleRef } from '@nestjs/core';
+import { PrismaClient } from '@prisma/client';
+
+import { DocStorageCronJob } from '../../core/doc/job';
+
+export class MigrateDocHistoryDeltas1765700000000 {
+  // history rows are converted in the background, batch by batch
+  static async up(_db: PrismaClient, ref: ModuleRef) {
+    await ref
+      .get(DocStorageCronJob, { strict: false })
+      .enqueueHistoryDeltaMigration();
+  }
+
+  static async down(_db: PrismaClient) {}
+}
@@ -68,10 +68,7 @@ test('should create a history record', async t => {
     snapshot.docId,
     snapshot.timestamp
   );
-  t.deepEqual(history, {
-    ...created,
-    blob: snapshot.blob,
-  });
+  t.deepEqual(history, created);
 });
 
 test('should not fail on duplicated history record', async t => {
@@ -8,12 +8,6 @@ export interface DocHistorySimple {
   editor: DocEditor | null;
 }
 
-export interface DocHistory {
-  blob: Uint8Array;
-  timestamp: number;
-  editor: DocEditor | null;
-}
-
 export interface DocHistoryFilter {
   /**
    * timestamp to filter histories before.
@@ -31,6 +25,9 @@ export interface DocHistoryFilter {
 export class HistoryModel extends BaseModel {
   /**
    * Create a doc history with a max age.
+   *
+   * The blob is stored in full. Doc storage records histories through the
+   * backend runtime instead, which stores them as deltas against keyframes.
    */
   async create(snapshot: Doc, maxAge: number): Promise<DocHistorySimple> {
     const timestamp = new Date(snapshot.timestamp);
@@ -103,13 +100,14 @@ export class HistoryModel extends BaseModel {
   /**
    * Get the history of a doc at a specific timestamp.
    *
-   * Including blob and createdByUser
+   * Only including timestamp, createdByUser. Use the backend runtime to read
+   * the blob, which may be stored as a delta.
    */
   async get(
     workspaceId: string,
     docId: string,
     timestamp: number
-  ): Promise<DocHistory | null> {
+  ): Promise<DocHistorySimple | null> {
     const row = await this.db.snapshotHistory.findUnique({
       where: {
         workspaceId_id_timestamp: {
@@ -118,7 +116,8 @@ export class HistoryModel extends BaseModel {
           timestamp: new Date(timestamp),
         },
       },
-      include: {
+      select: {
+        timestamp: true,
         createdByUser: { select: publicUserSelect },
       },
     });
@@ -126,7 +125,6 @@ export class HistoryModel extends BaseModel {
       return null;
     }
     return {
-      blob: row.blob,
       timestamp: row.timestamp.getTime(),
       editor: row.createdByUser,
     };
@@ -31,6 +31,8 @@ import serverNativeModule, {
   type ModelConditionsContract,
   type ModelRegistryMatchResponse,
   type ModelRegistryResolveResponse,
+  type OfflineLicenseInput,
+  type OfflineLicenseStatus,
   type PortalResponse,
   type PromptMessageContract,
   type PromptMetadataContract,
@@ -54,8 +56,12 @@ import serverNativeModule, {
   type RuntimeBlobCompleteResult,
   type RuntimeBlobMetadataBackfillResult,
   type RuntimeByokLocalLeaseRecord,
+  type RuntimeChallengeVerifyResult,
   type RuntimeDocBlobRefsResult,
   type RuntimeDocCompactionResult,
+  type RuntimeDocCompactionScheduleResult,
+  type RuntimeDocHistoryDeltaMigrationResult,
+  type RuntimeDocHistoryInput,
   type RuntimeMagicLinkOtpConsumeResult,
   type RuntimeMultipartUploadInit,
   type RuntimeMultipartUploadPart,
@@ -64,6 +70,8 @@ import serverNativeModule, {
   type RuntimeObjectMetadata,
   type RuntimeObjectStoragePutOptions,
   type RuntimePresignedObjectRequest,
+  type RuntimeRefreshTokenFamilyRecord,
+  type RuntimeRefreshTokenRotateResult,
   type RuntimeVerificationTokenRecord,
   type RuntimeWorkspaceInviteLinkRecord,
   type RuntimeWorkspaceStatsDailyRecalibrationResult,
@@ -92,6 +100,8 @@ export type {
   LicenseResponse,
   LicenseSeatsRequest,
   ModelConditionsContract,
+  OfflineLicenseInput,
+  OfflineLicenseStatus,
   PortalResponse,
   PromptMessageContract,
   PromptStructuredResponseContract,
@@ -106,8 +116,12 @@ export type {
   RuntimeBlobCompleteResult,
   RuntimeBlobMetadataBackfillResult,
   RuntimeByokLocalLeaseRecord,
+  RuntimeChallengeVerifyResult,
   RuntimeDocBlobRefsResult,
   RuntimeDocCompactionResult,
+  RuntimeDocCompactionScheduleResult,
+  RuntimeDocHistoryDeltaMigrationResult,
+  RuntimeDocHistoryInput,
   RuntimeMagicLinkOtpConsumeResult,
   RuntimeMultipartUploadInit,
   RuntimeMultipartUploadPart,
@@ -116,6 +130,8 @@ export type {
   RuntimeObjectMetadata,
   RuntimeObjectStoragePutOptions,
   RuntimePresignedObjectRequest,
+  RuntimeRefreshTokenFamilyRecord,
+  RuntimeRefreshTokenRotateResult,
   RuntimeVerificationTokenRecord,
   RuntimeWorkspaceInviteLinkRecord,
   RuntimeWorkspaceStatsDailyRecalibrationResult,
@@ -167,6 +183,8 @@ import type {
 } from './plugins/copilot/runtime/contracts/tool-contract';
 
 export const mergeUpdatesInApplyWay = serverNativeModule.mergeUpdatesInApplyWay;
+export const authSessionAccessTokenJwks =
+  serverNativeModule.authSessionAccessTokenJwks;
 export const authSessionAccessTokenKeyId =
   serverNativeModule.authSessionAccessTokenKeyId;
 export const createAuthSessionRefreshToken =
@@ -175,8 +193,12 @@ export const parseAuthSessionRefreshToken =
   serverNativeModule.parseAuthSessionRefreshToken;
 export const signAuthSessionAccessToken =
   serverNativeModule.signAuthSessionAccessToken;
+export const signAuthSessionAccessTokenWithKey =
+  serverNativeModule.signAuthSessionAccessTokenWithKey;
 export const verifyAuthSessionAccessToken =
   serverNativeModule.verifyAuthSessionAccessToken;
+export const verifyAuthSessionAccessTokenWithJwks =
+  serverNativeModule.verifyAuthSessionAccessTokenWithJwks;
 
 export async function validateDocUpdate(
   update: Buffer,
@@ -287,6 +309,8 @@ export const parsePageDocFromBinary = serverNativeModule.parsePageDoc;
 export const parseWorkspaceDocFromBinary = serverNativeModule.parseWorkspaceDoc;
 export const readAllDocIdsFromRootDoc =
   serverNativeModule.readAllDocIdsFromRootDoc;
+export const diffDocVersions = serverNativeModule.diffDocVersions;
+export const buildDocRestoreUpdate = serverNativeModule.buildDocRestoreUpdate;
 export const AFFINE_PRO_PUBLIC_KEY = serverNativeModule.AFFINE_PRO_PUBLIC_KEY;
 export const AFFINE_PRO_LICENSE_AES_KEY =
   serverNativeModule.AFFINE_PRO_LICENSE_AES_KEY;
@@ -303,6 +327,35 @@ export type PermissionDocRole =
   | 'manager'
   | 'owner';
 
+export type PermissionGrantWindowV1 = {
+  notBefore?: number;
+  expiresAt?: number;
+};
+
+/** Built-in role names or the name of an entry in `customRoles`. */
+export type PermissionWorkspaceRoleInput =
+  | PermissionWorkspaceRole
+  | (string & {});
+export type PermissionDocRoleInput = PermissionDocRole | (string & {});
+
+export type PermissionCustomRoleV1 = {
+  name: string;
+  workspaceActions?: string[];
+  docActions?: string[];
+};
+
+export type PermissionGroupGrantV1 = {
+  groupId: string;
+  role: PermissionDocRoleInput;
+} & PermissionGrantWindowV1;
+
+export type PermissionDenyV1 = {
+  userId?: string;
+  groupId?: string;
+  actions: string[];
+  reason?: string;
+};
+
 export type PermissionEvaluationInputV1 = {
   version: 1;
   legacyCompatMode?: boolean;
@@ -318,28 +371,46 @@ export type PermissionEvaluationInputV1 = {
     readonlyReason?: string;
     sharingEnabled?: boolean;
     urlPreviewEnabled?: boolean;
+    /** Unix epoch milliseconds used to check grant validity windows. */
+    now?: number;
   };
   workspace?: {
-    role?: PermissionWorkspaceRole;
+    role?: PermissionWorkspaceRoleInput;
     memberState?: 'active' | 'pending' | 'waiting_review' | 'waiting_seat';
     public?: boolean;
     sharingEnabled?: boolean;
     urlPreviewEnabled?: boolean;
     local?: boolean;
+    denies?: PermissionDenyV1[];
   };
   workspaceActions?: string[];
   docs?: Array<{
     docId: string;
     actions?: string[];
-    explicitUserRole?: PermissionDocRole;
-    groupGrants?: Array<{ groupId: string; role: PermissionDocRole }>;
+    explicitUserRole?: PermissionDocRoleInput;
+    explicitUserRoleNotBefore?: number;
+    explicitUserRoleExpiresAt?: number;
+    groupGrants?: Array<PermissionGroupGrantV1>;
     groupGrantsEnabled?: boolean;
-    memberDefaultRole?: PermissionDocRole;
+    /** Folders, collections or parent docs, nearest first. */
+    ancestors?: Array<{
+      id: string;
+      explicitUserRole?: PermissionDocRoleInput;
+      explicitUserRoleNotBefore?: number;
+      explicitUserRoleExpiresAt?: number;
+      groupGrants?: Array<PermissionGroupGrantV1>;
+      denies?: PermissionDenyV1[];
+    }>;
+    denies?: PermissionDenyV1[];
+    memberDefaultRole?: PermissionDocRoleInput;
     publicRole?: 'external';
     visibility?: 'private' | 'public';
     sharingEnabled?: boolean;
     previewEnabled?: boolean;
   }>;
+  /** Attach a resolution trace to every decision. */
+  explain?: boolean;
+  customRoles?: PermissionCustomRoleV1[];
 };
 
 export type PermissionDecisionV1 = {
@@ -356,13 +427,39 @@ export type PermissionDecisionV1 = {
       | 'group-grant'
       | 'member-default-policy'
       | 'public-policy'
+      | 'inherited'
       | 'doc-preview-policy';
     role?: string;
+    ancestorId?: string;
   }>;
   restrictions: Array<{
-    type: 'runtime_unknown' | 'runtime_stale' | 'readonly' | 'sharing-disabled';
+    type:
+      | 'runtime_unknown'
+      | 'runtime_stale'
+      | 'readonly'
+      | 'sharing-disabled'
+      | 'grant-expired'
+      | 'grant-not-yet-valid'
+      | 'grant-window-unknown'
+      | 'explicit-deny';
     reason?: string;
   }>;
+  trace?: {
+    candidates: Array<{
+      type: PermissionDecisionV1['sources'][number]['type'];
+      role: string;
+      ancestorId?: string;
+      outcome: 'granted' | 'blocked' | 'inactive' | 'not-granted';
+      inactiveReason?: string;
+    }>;
+    denies: Array<{
+      type: 'workspace-deny' | 'inherited-deny' | 'doc-deny';
+      ancestorId?: string;
+      userId?: string;
+      groupId?: string;
+      reason?: string;
+    }>;
+  };
 };
 
 export type PermissionEvaluationOutputV1 = {
@@ -385,6 +482,61 @@ export const evaluatePermissionV1 = (
 ): PermissionEvaluationOutputV1 =>
   serverNativeModule.evaluatePermissionV1(input);
 
+export type PermissionBatchEvaluationInputV1 = {
+  version: 1;
+  legacyCompatMode?: boolean;
+  subjects: Array<{
+    key: string;
+    userId?: string;
+    groupIds?: string[];
+    allowLocal?: boolean;
+    role?: PermissionWorkspaceRoleInput;
+    memberState?: NonNullable<
+      PermissionEvaluationInputV1['workspace']
+    >['memberState'];
+    docRoles?: Record<
+      string,
+      | PermissionDocRoleInput
+      | ({ role: PermissionDocRoleInput } & PermissionGrantWindowV1)
+    >;
+  }>;
+  runtime?: PermissionEvaluationInputV1['runtime'];
+  workspace?: Omit<
+    NonNullable<PermissionEvaluationInputV1['workspace']>,
+    'role' | 'memberState'
+  >;
+  workspaceActions?: string[];
+  docActions?: string[];
+  customRoles?: PermissionCustomRoleV1[];
+  docs?: Array<
+    Omit<
+      NonNullable<PermissionEvaluationInputV1['docs']>[number],
+      | 'actions'
+      | 'explicitUserRole'
+      | 'explicitUserRoleNotBefore'
+      | 'explicitUserRoleExpiresAt'
+    >
+  >;
+};
+
+export type PermissionBatchEvaluationOutputV1 = {
+  version: 1;
+  workspaceActions: string[];
+  docActions: string[];
+  docIds: string[];
+  subjects: Array<{
+    key: string;
+    /** Bit `i % 32` of word `i / 32` is set when action `i` is allowed. */
+    workspace: number[];
+    docs: number[][];
+  }>;
+};
+
+export const evaluatePermissionBatchV1 = (
+  input: PermissionBatchEvaluationInputV1
+): PermissionBatchEvaluationOutputV1 =>
+  serverNativeModule.evaluatePermissionBatchV1(input);
+
 export const permissionActionRoleMatrixV1 = (): unknown =>
   serverNativeModule.permissionActionRoleMatrixV1();
 
@@ -394,6 +546,9 @@ export const permissionActionRoleMatrixV1Json =
 export const resolveEntitlementV1 = (
   input: ResolveEntitlementInput
 ): ResolvedEntitlement => serverNativeModule.resolveEntitlementV1(input);
+export const verifyOfflineLicenseV1 = (
+  input: OfflineLicenseInput
+): OfflineLicenseStatus => serverNativeModule.verifyOfflineLicenseV1(input);
 
 // MCP write tools exports
 export const createDocWithMarkdown = serverNativeModule.createDocWithMarkdown;
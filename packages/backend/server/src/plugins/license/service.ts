@@ -4,6 +4,8 @@ import { Transactional } from '@nestjs-cls/transactional';
 import { InstalledLicense, PrismaClient } from '@prisma/client';
 
 import {
+  BadRequest,
+  Config,
   CryptoHelper,
   EventBus,
   InternalServerError,
@@ -26,11 +28,13 @@ import {
   deactivateLicense,
   type LicenseError,
   type LicenseResponse,
+  type OfflineLicenseStatus,
   type PortalResponse,
   type ResolvedEntitlement,
   resolveEntitlementV1,
   updateLicenseRecurring,
   updateLicenseSeats,
+  verifyOfflineLicenseV1,
 } from '../../native';
 import {
   SubscriptionPlan,
@@ -67,6 +71,7 @@ export class LicenseService {
 
   constructor(
     private readonly db: PrismaClient,
+    private readonly config: Config,
     private readonly event: EventBus,
     private readonly models: Models,
     private readonly crypto: CryptoHelper,
@@ -189,6 +194,7 @@ export class LicenseService {
 
   @Transactional()
   async activateTeamLicense(workspaceId: string, licenseKey: string) {
+    this.assertOnlineLicenseMode('Activating a license key');
     const installedLicense = await this.getLicense(workspaceId);
 
     if (installedLicense) {
@@ -257,7 +263,10 @@ export class LicenseService {
     });
     await this.entitlement.revokeBySubject('selfhost_license', license.key);
 
-    if (license.variant !== SubscriptionVariant.Onetime) {
+    if (
+      license.variant !== SubscriptionVariant.Onetime &&
+      !this.config.entitlement.license.offline
+    ) {
       await this.deactivateTeamLicense(license);
     }
 
@@ -277,6 +286,7 @@ export class LicenseService {
   }
 
   async updateTeamRecurring(key: string, recurring: SubscriptionRecurring) {
+    this.assertOnlineLicenseMode('Changing the license recurring');
     this.remoteCommand(
       await licenseClient.updateRecurring({
         licenseKey: key,
@@ -286,6 +296,7 @@ export class LicenseService {
   }
 
   async createCustomerPortal(workspaceId: string) {
+    this.assertOnlineLicenseMode('The customer portal');
     const license = await this.db.installedLicense.findUnique({
       where: {
         workspaceId,
@@ -318,7 +329,11 @@ export class LicenseService {
       return;
     }
 
-    if (license.variant === SubscriptionVariant.Onetime) {
+    // seats of a license file are fixed, offline verification reports overuse
+    if (
+      license.variant === SubscriptionVariant.Onetime ||
+      this.config.entitlement.license.offline
+    ) {
       const state =
         await this.quotaState.reconcileWorkspaceQuotaState(workspaceId);
       this.event.emit('workspace.members.allocateSeats', {
@@ -375,7 +390,9 @@ export class LicenseService {
     });
 
     for (const license of licenses) {
-      if (license.variant === SubscriptionVariant.Onetime) {
+      if (this.config.entitlement.license.offline) {
+        await this.revalidateOfflineLicense(license);
+      } else if (license.variant === SubscriptionVariant.Onetime) {
         await this.revalidateOnetimeLicense(license);
       } else {
         await this.revalidateRecurringLicense(license);
@@ -552,6 +569,91 @@ export class LicenseService {
     }
   }
 
+  private async revalidateOfflineLicense(license: InstalledLicense) {
+    if (!license.license) {
+      await this.entitlement.markSelfhostLicenseNeedsReupload({
+        workspaceId: license.workspaceId,
+        licenseKey: license.key,
+        reason: 'Offline license mode requires an imported license file.',
+      });
+      return;
+    }
+
+    let status: OfflineLicenseStatus | null = null;
+    try {
+      status = verifyOfflineLicenseV1({
+        signedPayload: Buffer.from(license.license),
+        workspaceId: license.workspaceId,
+        publicKey: this.crypto.AFFiNEProPublicKey?.toString(),
+        licenseAesKey: this.crypto.AFFiNEProLicenseAESKey?.toString('hex'),
+        gracePeriodDays: this.config.entitlement.license.gracePeriodDays,
+        seatsUsed: await this.models.workspaceUser.chargedCount(
+          license.workspaceId
+        ),
+        lastCheckedAt: license.validatedAt.toISOString(),
+        plans: this.config.entitlement.plans,
+        now: new Date().toISOString(),
+      });
+    } catch (e) {
+      this.logger.error('Failed to verify offline license', e);
+    }
+
+    if (!status?.valid) {
+      this.event.emit('workspace.subscription.canceled', {
+        workspaceId: license.workspaceId,
+        plan: SubscriptionPlan.SelfHostedTeam,
+        recurring: SubscriptionRecurring.Monthly,
+      });
+      await this.entitlement.revokeBySubject('selfhost_license', license.key);
+      return;
+    }
+
+    if (status.status === 'grace') {
+      this.logger.warn(
+        `License of workspace ${license.workspaceId} expired, grace period ends at ${status.graceEndsAt}`
+      );
+    }
+    if (status.seatOveruse > 0) {
+      this.logger.warn(
+        `Workspace ${license.workspaceId} uses ${status.seatsUsed} seats, ${status.seatOveruse} over the licensed ${status.seatLimit}`
+      );
+    }
+
+    const resolved = status.entitlement;
+    const recurring = this.licenseRecurring(resolved);
+    const quantity = this.licenseQuantity(resolved);
+    const expiresAt = this.licenseExpiresAt(resolved);
+    const validatedAt = new Date(status.checkedAt);
+
+    await this.db.installedLicense.update({
+      where: { key: license.key },
+      data: { recurring, quantity, expiredAt: expiresAt, validatedAt },
+    });
+    this.event.emit('workspace.subscription.activated', {
+      workspaceId: license.workspaceId,
+      plan: SubscriptionPlan.SelfHostedTeam,
+      recurring,
+      quantity,
+    });
+    await this.entitlement.upsertFromSelfhostLicense({
+      workspaceId: license.workspaceId,
+      recurring,
+      quantity,
+      expiresAt,
+      validatedAt,
+      variant: license.variant,
+      license: Buffer.from(license.license),
+    });
+  }
+
+  private assertOnlineLicenseMode(action: string) {
+    if (this.config.entitlement.license.offline) {
+      throw new BadRequest(
+        `${action} is unavailable in offline license mode. Import a license file instead.`
+      );
+    }
+  }
+
   private resolveWorkspaceTeamLicense(workspaceId: string | null, buf: Buffer) {
     if (!this.crypto.AFFiNEProPublicKey) {
       throw new InternalServerError(
@@ -572,6 +674,7 @@ export class LicenseService {
       signedPayload: buf,
       publicKey: this.crypto.AFFiNEProPublicKey.toString(),
       licenseAesKey: this.crypto.AFFiNEProLicenseAESKey.toString('hex'),
+      plans: this.config.entitlement.plans,
       now: new Date().toISOString(),
     });
 
@@ -1,13 +1,69 @@
-use std::convert::TryFrom;
+use std::{
+  convert::TryFrom,
+  hint::black_box,
+  sync::{
+    Arc,
+    atomic::{AtomicBool, AtomicU64, Ordering},
+  },
+  time::Instant,
+};
 
 use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use rand::{
   distr::{Alphanumeric, Distribution},
   rng,
 };
+use rayon::prelude::*;
 use sha3::{Digest, Sha3_256};
 
 const SALT_LENGTH: usize = 16;
+/// Bits in a SHA3-256 digest, the most leading zeros a stamp can claim.
+const DIGEST_BITS: u32 = 256;
+/// Hashes each worker tries between progress reports and abort checks.
+const PROGRESS_BATCH: u64 = 1 << 14;
+const ESTIMATE_SAMPLE_HASHES: u64 = 1 << 12;
+
+/// Cancels an in-flight [`Stamp::mint_parallel`] from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct MintAbort(Arc<AtomicBool>);
+
+impl MintAbort {
+  pub fn abort(&self) {
+    self.0.store(true, Ordering::Relaxed);
+  }
+
+  pub fn is_aborted(&self) -> bool {
+    self.0.load(Ordering::Relaxed)
+  }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MintProgress {
+  /// Hashes tried so far across all workers.
+  pub attempts: u64,
+  /// Average number of hashes needed to find a stamp at this difficulty.
+  pub expected_attempts: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StampRejection {
+  /// Wrong version, resource, difficulty or proof of work.
+  Invalid,
+  /// Well formed, but older than the validity window.
+  Expired,
+  /// Dated further ahead than [`Stamp::MAX_CLOCK_SKEW`].
+  Future,
+}
+
+impl StampRejection {
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Invalid => "invalid",
+      Self::Expired => "expired",
+      Self::Future => "future",
+    }
+  }
+}
 
 #[derive(Debug)]
 pub struct Stamp {
@@ -21,30 +77,56 @@ pub struct Stamp {
 }
 
 impl Stamp {
-  fn check_expiration(&self) -> bool {
+  /// How long a stamp stays valid when callers don't configure a window.
+  pub const DEFAULT_VALIDITY: Duration = Duration::minutes(5);
+  /// How far ahead of the verifier's clock a stamp may be dated.
+  pub const MAX_CLOCK_SKEW: Duration = Duration::seconds(30);
+
+  fn issued_at(&self) -> Option<DateTime<Utc>> {
     NaiveDateTime::parse_from_str(&self.ts, "%Y%m%d%H%M%S")
       .ok()
       .map(|ts| DateTime::<Utc>::from_naive_utc_and_offset(ts, Utc))
-      .and_then(|utc| {
-        utc
-          .checked_add_signed(Duration::minutes(5))
-          .map(|utc| Utc::now() <= utc)
-      })
-      .unwrap_or(false)
   }
 
   pub fn check<S: AsRef<str>>(&self, bits: u32, resource: S) -> bool {
-    if self.version == "1" && bits <= self.claim && self.check_expiration() && self.resource == resource.as_ref() {
-      let hex_digits = ((self.claim as f32) / 4.).floor() as usize;
+    self.verify(bits, resource, Self::DEFAULT_VALIDITY, Utc::now()).is_ok()
+  }
 
-      // check challenge
-      let mut hasher = Sha3_256::new();
-      hasher.update(self.format().as_bytes());
-      let result = hex::encode(hasher.finalize());
-      result[..hex_digits] == String::from_utf8(vec![b'0'; hex_digits]).unwrap()
-    } else {
-      false
+  /// Checks the stamp against `bits` and `resource`, accepting it only while
+  /// `now` is within `validity` of its timestamp. Returns when it stops being
+  /// valid, which is how long a replay record has to be kept.
+  ///
+  /// The proof of work is checked before the timestamp, so only stamps that
+  /// cost their minter the full work are told apart as expired or future.
+  pub fn verify<S: AsRef<str>>(
+    &self,
+    bits: u32,
+    resource: S,
+    validity: Duration,
+    now: DateTime<Utc>,
+  ) -> Result<DateTime<Utc>, StampRejection> {
+    // The claim comes from the client and must fit in the digest.
+    if self.version != "1" || bits > self.claim || self.claim > DIGEST_BITS || self.resource != resource.as_ref() {
+      return Err(StampRejection::Invalid);
     }
+
+    let hex_digits = ((self.claim as f32) / 4.).floor() as usize;
+    let mut hasher = Sha3_256::new();
+    hasher.update(self.format().as_bytes());
+    let result = hex::encode(hasher.finalize());
+    if !result.as_bytes()[..hex_digits].iter().all(|digit| *digit == b'0') {
+      return Err(StampRejection::Invalid);
+    }
+
+    let issued_at = self.issued_at().ok_or(StampRejection::Invalid)?;
+    if issued_at > now + Self::MAX_CLOCK_SKEW {
+      return Err(StampRejection::Future);
+    }
+    let expires_at = issued_at.checked_add_signed(validity).ok_or(StampRejection::Invalid)?;
+    if now > expires_at {
+      return Err(StampRejection::Expired);
+    }
+    Ok(expires_at)
   }
 
   pub fn format(&self) -> String {
@@ -56,36 +138,127 @@ impl Stamp {
 
   /// Mint a new hashcash stamp.
   pub fn mint(resource: String, bits: Option<u32>) -> Self {
-    let version = "1";
-    let now = Utc::now();
-    let ts = now.format("%Y%m%d%H%M%S");
-    let bits = bits.unwrap_or(20);
-    let rand = String::from_iter(Alphanumeric.sample_iter(rng()).take(SALT_LENGTH).map(char::from));
-    let challenge = format!("{}:{}:{}:{}:{}:{}", version, bits, ts, resource, "", rand);
+    let mut stamp = Self::unsolved(resource, bits);
+    let challenge = stamp.challenge();
+    let hex_digits = mint_hex_digits(stamp.claim);
+    let mut hasher = Sha3_256::new();
+    let counter = (0_u64..)
+      .find(|counter| solves(&mut hasher, &challenge, *counter, hex_digits))
+      .unwrap_or_default();
+    stamp.counter = format!("{counter:x}");
+    stamp
+  }
+
+  /// Mint a new hashcash stamp on up to `threads` rayon workers (all of them
+  /// when `None`), reporting progress roughly every [`PROGRESS_BATCH`] hashes
+  /// per worker and once more with the total when the search ends. Returns
+  /// `None` once `abort` fires.
+  pub fn mint_parallel<F>(
+    resource: String,
+    bits: Option<u32>,
+    threads: Option<usize>,
+    abort: &MintAbort,
+    progress: F,
+  ) -> Option<Self>
+  where
+    F: Fn(MintProgress) + Sync,
+  {
+    let mut stamp = Self::unsolved(resource, bits);
+    let challenge = stamp.challenge();
+    let hex_digits = mint_hex_digits(stamp.claim);
+    let expected_attempts = Self::expected_attempts(stamp.claim);
+    let workers = worker_count(threads) as u64;
+    let attempts = AtomicU64::new(0);
+    let found = AtomicBool::new(false);
+
+    // Worker `n` tries counters n, n + workers, n + 2 * workers, ... so the
+    // search space is split without any coordination beyond the stop flags.
+    let counter = (0..workers).into_par_iter().find_map_any(|worker| {
+      let mut hasher = Sha3_256::new();
+      let mut counter = worker;
+      loop {
+        for tried in 1..=PROGRESS_BATCH {
+          if solves(&mut hasher, &challenge, counter, hex_digits) {
+            attempts.fetch_add(tried, Ordering::Relaxed);
+            found.store(true, Ordering::Relaxed);
+            return Some(counter);
+          }
+          counter += workers;
+        }
+        let total = attempts.fetch_add(PROGRESS_BATCH, Ordering::Relaxed) + PROGRESS_BATCH;
+        if found.load(Ordering::Relaxed) || abort.is_aborted() {
+          return None;
+        }
+        progress(MintProgress {
+          attempts: total,
+          expected_attempts,
+        });
+      }
+    });
+    // Every worker has stopped by now, so this is the exact number of hashes.
+    progress(MintProgress {
+      attempts: attempts.load(Ordering::Relaxed),
+      expected_attempts,
+    });
+    let counter = counter?;
+
+    stamp.counter = format!("{counter:x}");
+    Some(stamp)
+  }
+
+  /// Average number of hashes [`Stamp::mint`] needs for `bits`.
+  pub fn expected_attempts(bits: u32) -> u64 {
+    16_u64.saturating_pow(mint_hex_digits(bits) as u32)
+  }
 
+  /// Estimate how long [`Stamp::mint_parallel`] takes on this device by timing
+  /// a short hashing sample.
+  pub fn estimate_mint_duration(bits: Option<u32>, threads: Option<usize>) -> std::time::Duration {
+    let stamp = Self::unsolved(String::new(), bits);
+    let challenge = stamp.challenge();
+    let mut hasher = Sha3_256::new();
+    let started = Instant::now();
+    for counter in 0..ESTIMATE_SAMPLE_HASHES {
+      black_box(solves(&mut hasher, &challenge, counter, 0));
+    }
+    let per_hash = started.elapsed().as_secs_f64() / ESTIMATE_SAMPLE_HASHES as f64;
+    let expected = Self::expected_attempts(stamp.claim) as f64 / worker_count(threads) as f64;
+    std::time::Duration::try_from_secs_f64(per_hash * expected).unwrap_or(std::time::Duration::MAX)
+  }
+
+  fn unsolved(resource: String, bits: Option<u32>) -> Self {
     Stamp {
-      version: version.to_string(),
-      claim: bits,
-      ts: ts.to_string(),
+      version: "1".to_string(),
+      claim: bits.unwrap_or(20),
+      ts: Utc::now().format("%Y%m%d%H%M%S").to_string(),
       resource,
       ext: "".to_string(),
-      rand,
-      counter: {
-        let mut hasher = Sha3_256::new();
-        let mut counter = 0;
-        let hex_digits = ((bits as f32) / 4.).ceil() as usize;
-        let zeros = String::from_utf8(vec![b'0'; hex_digits]).unwrap();
-        loop {
-          hasher.update(format!("{challenge}:{counter:x}").as_bytes());
-          let result = hex::encode(hasher.finalize_reset());
-          if result[..hex_digits] == zeros {
-            break format!("{counter:x}");
-          };
-          counter += 1
-        }
-      },
+      rand: String::from_iter(Alphanumeric.sample_iter(rng()).take(SALT_LENGTH).map(char::from)),
+      counter: "".to_string(),
     }
   }
+
+  fn challenge(&self) -> String {
+    format!(
+      "{}:{}:{}:{}:{}:{}",
+      self.version, self.claim, self.ts, self.resource, self.ext, self.rand
+    )
+  }
+}
+
+fn mint_hex_digits(bits: u32) -> usize {
+  ((bits as f32) / 4.).ceil() as usize
+}
+
+fn worker_count(threads: Option<usize>) -> usize {
+  let available = rayon::current_num_threads();
+  threads.unwrap_or(available).clamp(1, available)
+}
+
+fn solves(hasher: &mut Sha3_256, challenge: &str, counter: u64, hex_digits: usize) -> bool {
+  hasher.update(format!("{challenge}:{counter:x}").as_bytes());
+  let result = hex::encode(hasher.finalize_reset());
+  result.as_bytes()[..hex_digits].iter().all(|digit| *digit == b'0')
 }
 
 impl TryFrom<&str> for Stamp {
@@ -113,7 +286,7 @@ mod tests {
   use rand::{Rng, distr::Alphanumeric};
   use rayon::prelude::*;
 
-  use super::Stamp;
+  use super::*;
 
   #[test]
   fn test_mint() {
@@ -144,7 +317,53 @@ mod tests {
   #[test]
   fn test_check_expiration() {
     let response = Stamp::mint("test".into(), Some(20));
-    assert!(response.check_expiration());
+    let now = Utc::now();
+    assert!(response.verify(20, "test", Stamp::DEFAULT_VALIDITY, now).is_ok());
+    assert_eq!(
+      response.verify(20, "test", Duration::seconds(30), now + Duration::minutes(1)),
+      Err(StampRejection::Expired)
+    );
+    assert_eq!(
+      response.verify(20, "test", Duration::minutes(10), now + Duration::minutes(6)),
+      Ok(response.issued_at().unwrap() + Duration::minutes(10))
+    );
+    assert_eq!(
+      response.verify(20, "other", Stamp::DEFAULT_VALIDITY, now),
+      Err(StampRejection::Invalid)
+    );
+    assert_eq!(
+      response.verify(20, "test", Stamp::DEFAULT_VALIDITY, now - Duration::minutes(2)),
+      Err(StampRejection::Future)
+    );
+    assert!(
+      response
+        .verify(20, "test", Stamp::DEFAULT_VALIDITY, now - Duration::seconds(10))
+        .is_ok(),
+      "small clock skew is tolerated"
+    );
+  }
+
+  #[test]
+  fn test_verify_checks_work_before_time() {
+    let mut response = Stamp::mint("test".into(), Some(20));
+    response.ts = "20000101000000".to_string();
+    assert_eq!(
+      response.verify(20, "test", Stamp::DEFAULT_VALIDITY, Utc::now()),
+      Err(StampRejection::Invalid),
+      "changing the timestamp invalidates the work"
+    );
+  }
+
+  #[test]
+  fn test_verify_rejects_claims_beyond_the_digest() {
+    let mut response = Stamp::mint("test".into(), Some(20));
+    for claim in [257, 1024, u32::MAX] {
+      response.claim = claim;
+      assert_eq!(
+        response.verify(20, "test", Stamp::DEFAULT_VALIDITY, Utc::now()),
+        Err(StampRejection::Invalid)
+      );
+    }
   }
 
   #[test]
@@ -156,6 +375,45 @@ mod tests {
     );
   }
 
+  #[test]
+  fn test_mint_parallel() {
+    let progress = AtomicU64::new(0);
+    let stamp = Stamp::mint_parallel("test".into(), Some(16), Some(4), &MintAbort::default(), |update| {
+      assert_eq!(update.expected_attempts, Stamp::expected_attempts(16));
+      progress.fetch_max(update.attempts, Ordering::Relaxed);
+    })
+    .unwrap();
+    assert!(Stamp::try_from(stamp.format().as_str()).unwrap().check(16, "test"));
+    // Worker `n` only tries counters congruent to `n`, so finding this counter
+    // took at least `counter / workers + 1` hashes.
+    let counter = u64::from_str_radix(&stamp.counter, 16).unwrap();
+    assert!(progress.load(Ordering::Relaxed) > counter / worker_count(Some(4)) as u64);
+
+    let abort = MintAbort::default();
+    abort.abort();
+    let updates = AtomicU64::new(0);
+    let attempts = AtomicU64::new(0);
+    let stamp = Stamp::mint_parallel("test".into(), Some(64), Some(2), &abort, |update| {
+      updates.fetch_add(1, Ordering::Relaxed);
+      attempts.store(update.attempts, Ordering::Relaxed);
+    });
+    assert!(stamp.is_none());
+    // Each worker finishes its first batch before noticing the abort, and only
+    // the final total is reported.
+    assert_eq!(updates.load(Ordering::Relaxed), 1);
+    assert_eq!(
+      attempts.load(Ordering::Relaxed),
+      PROGRESS_BATCH * worker_count(Some(2)) as u64
+    );
+  }
+
+  #[test]
+  fn test_estimate_mint_duration() {
+    assert_eq!(Stamp::expected_attempts(20), 1 << 20);
+    assert_eq!(Stamp::expected_attempts(18), 1 << 20);
+    assert!(Stamp::estimate_mint_duration(Some(24), Some(1)) > Stamp::estimate_mint_duration(Some(8), Some(1)));
+  }
+
   #[test]
   fn test_fuzz() {
     (0..1000).into_par_iter().for_each(|_| {
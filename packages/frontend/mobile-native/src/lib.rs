@@ -10,7 +10,7 @@ mod tests;
 #[cfg(any(target_os = "android", target_os = "ios", test))]
 #[cfg_attr(all(test, not(any(target_os = "android", target_os = "ios"))), allow(dead_code))]
 pub(crate) mod cache;
-use affine_common::hashcash::Stamp;
+use affine_common::hashcash::{MintAbort, Stamp};
 pub(crate) use error::Result;
 pub use error::UniffiError;
 pub use ffi_types::{
@@ -26,3 +26,54 @@ uniffi::setup_scaffolding!("affine_mobile_native");
 pub fn hashcash_mint(resource: String, bits: u32) -> String {
   Stamp::mint(resource, Some(bits)).format()
 }
+
+#[uniffi::export]
+pub fn hashcash_estimate_mint_ms(bits: u32, threads: Option<u32>) -> u64 {
+  Stamp::estimate_mint_duration(Some(bits), threads.map(|threads| threads as usize)).as_millis() as u64
+}
+
+#[uniffi::export(callback_interface)]
+pub trait HashcashMintObserver: Send + Sync {
+  fn on_progress(&self, attempts: u64, expected_attempts: u64);
+}
+
+/// Multi-threaded hashcash minting that can be aborted from another thread.
+#[derive(uniffi::Object, Default)]
+pub struct HashcashMinter {
+  abort: MintAbort,
+}
+
+#[uniffi::export]
+impl HashcashMinter {
+  #[uniffi::constructor]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  pub fn abort(&self) {
+    self.abort.abort();
+  }
+
+  /// Returns `None` when [`HashcashMinter::abort`] is called before a stamp is
+  /// found.
+  pub fn mint(
+    &self,
+    resource: String,
+    bits: u32,
+    threads: Option<u32>,
+    observer: Option<Box<dyn HashcashMintObserver>>,
+  ) -> Option<String> {
+    Stamp::mint_parallel(
+      resource,
+      Some(bits),
+      threads.map(|threads| threads as usize),
+      &self.abort,
+      |progress| {
+        if let Some(observer) = &observer {
+          observer.on_progress(progress.attempts, progress.expected_attempts);
+        }
+      },
+    )
+    .map(|stamp| stamp.format())
+  }
+}
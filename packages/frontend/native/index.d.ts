@@ -66,8 +66,19 @@ export interface RecordingStartOptions {
 export declare function startRecording(opts: RecordingStartOptions): Promise<RecordingSessionMeta>
 
 export declare function stopRecording(id: string): Promise<RecordingArtifact>
+export declare class ChallengeMintHandle {
+  abort(): void
+}
 export declare function cancelImportSession(sessionId: string): void
 
+export interface ChallengeMintEvent {
+  /** One of `progress`, `done` or `aborted`. */
+  kind: string
+  attempts: number
+  expectedAttempts: number
+  stamp?: string
+}
+
 export interface CreateImportBatchLimits {
   maxDocs?: number
   maxBlobs?: number
@@ -89,6 +100,8 @@ export interface CreateImportSessionSource {
 
 export declare function disposeImportSession(sessionId: string): void
 
+export declare function estimateChallengeMintMs(bits?: number | undefined | null, threads?: number | undefined | null): number
+
 export interface MermaidRenderOptions {
   theme?: string
   fontFamily?: string
@@ -106,6 +119,12 @@ export interface MermaidRenderResult {
 
 export declare function mintChallengeResponse(resource: string, bits?: number | undefined | null): Promise<string>
 
+/**
+ * Mint on a background thread pool, streaming `progress` events to
+ * `callback` and finishing with a single `done` or `aborted` event.
+ */
+export declare function mintChallengeResponseStream(resource: string, bits: number | undefined | null, threads: number | undefined | null, callback: ((err: Error | null, arg: ChallengeMintEvent) => void)): ChallengeMintHandle
+
 export declare function nextImportBatch(sessionId: string): string | null
 
 export declare function renderMermaidSvg(request: MermaidRenderRequest): MermaidRenderResult
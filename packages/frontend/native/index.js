@@ -581,10 +581,13 @@ module.exports.decodeAudio = nativeBinding.decodeAudio
 module.exports.decodeAudioSync = nativeBinding.decodeAudioSync
 module.exports.startRecording = nativeBinding.startRecording
 module.exports.stopRecording = nativeBinding.stopRecording
+module.exports.ChallengeMintHandle = nativeBinding.ChallengeMintHandle
 module.exports.cancelImportSession = nativeBinding.cancelImportSession
 module.exports.createImportSession = nativeBinding.createImportSession
 module.exports.disposeImportSession = nativeBinding.disposeImportSession
+module.exports.estimateChallengeMintMs = nativeBinding.estimateChallengeMintMs
 module.exports.mintChallengeResponse = nativeBinding.mintChallengeResponse
+module.exports.mintChallengeResponseStream = nativeBinding.mintChallengeResponseStream
 module.exports.nextImportBatch = nativeBinding.nextImportBatch
 module.exports.renderMermaidSvg = nativeBinding.renderMermaidSvg
 module.exports.renderTypstSvg = nativeBinding.renderTypstSvg
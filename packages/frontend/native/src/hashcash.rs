@@ -1,7 +1,14 @@
-use std::convert::TryFrom;
+use std::{
+  convert::TryFrom,
+  sync::atomic::{AtomicU64, Ordering},
+};
 
-use affine_common::hashcash::Stamp;
-use napi::{Env, Result, Task, bindgen_prelude::AsyncTask};
+use affine_common::hashcash::{MintAbort, Stamp};
+use napi::{
+  Env, Result, Task,
+  bindgen_prelude::AsyncTask,
+  threadsafe_function::{ThreadsafeFunction, ThreadsafeFunctionCallMode},
+};
 use napi_derive::napi;
 
 pub struct AsyncVerifyChallengeResponse {
@@ -65,6 +72,83 @@ pub fn mint_challenge_response(resource: String, bits: Option<u32>) -> AsyncTask
   AsyncTask::new(AsyncMintChallengeResponse { bits, resource })
 }
 
+#[napi(object)]
+pub struct ChallengeMintEvent {
+  /// One of `progress`, `done` or `aborted`.
+  pub kind: String,
+  pub attempts: f64,
+  pub expected_attempts: f64,
+  pub stamp: Option<String>,
+}
+
+#[napi]
+pub struct ChallengeMintHandle {
+  abort: MintAbort,
+}
+
+#[napi]
+impl ChallengeMintHandle {
+  #[napi]
+  pub fn abort(&self) {
+    self.abort.abort();
+  }
+}
+
+/// Mint on a background thread pool, streaming `progress` events to
+/// `callback` and finishing with a single `done` or `aborted` event.
+#[napi]
+pub fn mint_challenge_response_stream(
+  resource: String,
+  bits: Option<u32>,
+  threads: Option<u32>,
+  callback: ThreadsafeFunction<ChallengeMintEvent, ()>,
+) -> ChallengeMintHandle {
+  let abort = MintAbort::default();
+  let abort_in_worker = abort.clone();
+
+  std::thread::spawn(move || {
+    mint_stream(resource, bits, threads, &abort_in_worker, |event| {
+      let _ = callback.call(Ok(event), ThreadsafeFunctionCallMode::NonBlocking);
+    });
+  });
+
+  ChallengeMintHandle { abort }
+}
+
+fn mint_stream<F>(resource: String, bits: Option<u32>, threads: Option<u32>, abort: &MintAbort, emit: F)
+where
+  F: Fn(ChallengeMintEvent) + Sync,
+{
+  let expected_attempts = Stamp::expected_attempts(bits.unwrap_or(20)) as f64;
+  let attempts = AtomicU64::new(0);
+  let stamp = Stamp::mint_parallel(
+    resource,
+    bits,
+    threads.map(|threads| threads as usize),
+    abort,
+    |progress| {
+      attempts.fetch_max(progress.attempts, Ordering::Relaxed);
+      emit(ChallengeMintEvent {
+        kind: "progress".to_string(),
+        attempts: progress.attempts as f64,
+        expected_attempts,
+        stamp: None,
+      });
+    },
+  );
+  emit(ChallengeMintEvent {
+    kind: if stamp.is_some() { "done" } else { "aborted" }.to_string(),
+    attempts: attempts.load(Ordering::Relaxed) as f64,
+    expected_attempts,
+    stamp: stamp.map(|stamp| stamp.format()),
+  });
+}
+
+#[napi]
+pub fn estimate_challenge_mint_ms(bits: Option<u32>, threads: Option<u32>) -> f64 {
+  Stamp::estimate_mint_duration(bits, threads.map(|threads| threads as usize)).as_secs_f64() * 1000.
+}
+
 #[cfg(test)]
 mod tests {
   use napi::Task;
@@ -87,4 +171,55 @@ mod tests {
     };
     assert!(verify.compute().unwrap());
   }
+
+  #[test]
+  fn mint_stream_finishes_with_total_attempts() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    mint_stream(
+      "test-resource".to_string(),
+      Some(8),
+      Some(2),
+      &MintAbort::default(),
+      |event| {
+        tx.send(event).unwrap();
+      },
+    );
+    drop(tx);
+
+    let done = rx.iter().last().unwrap();
+    assert_eq!(done.kind, "done");
+    assert!(done.attempts >= 1.);
+    assert_eq!(done.expected_attempts, 256.);
+    let mut verify = AsyncVerifyChallengeResponse {
+      response: done.stamp.unwrap(),
+      bits: 8,
+      resource: "test-resource".to_string(),
+    };
+    assert!(verify.compute().unwrap());
+  }
+
+  #[test]
+  fn mint_handle_aborts_running_stream() {
+    let handle = ChallengeMintHandle {
+      abort: MintAbort::default(),
+    };
+    let abort = handle.abort.clone();
+    let (tx, rx) = std::sync::mpsc::channel();
+    let worker = std::thread::spawn(move || {
+      mint_stream("test-resource".to_string(), Some(64), Some(1), &abort, |event| {
+        let _ = tx.send(event);
+      });
+    });
+
+    let first = rx.recv().unwrap();
+    assert_eq!(first.kind, "progress");
+    handle.abort();
+    worker.join().unwrap();
+
+    let aborted = rx.iter().last().unwrap();
+    assert_eq!(aborted.kind, "aborted");
+    assert_eq!(aborted.stamp, None);
+    assert!(aborted.attempts > first.attempts);
+    assert!(estimate_challenge_mint_ms(Some(8), Some(1)) >= 0.);
+  }
 }